@@ -1,4 +1,7 @@
 pub mod minecraft;
+pub mod rcon;
+pub mod server_log;
+pub mod trace;
 pub mod websocket;
 
 #[cfg(test)]
@@ -0,0 +1,330 @@
+// Getting brand new turtles going.
+// See `turtle.md`. New turtles boot off a floppy in a disk drive next to them. The floppy only holds
+// a tiny `startup.lua` that copies itself onto the turtle, then asks the control server for every
+// other file. That way the floppy never has stale copies of the libraries.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Value, json};
+
+use crate::minecraft::computercraft::turtle::lua::{
+    LUA_ASSETS, lexer::LexError, lua_string, minify::minify,
+};
+
+/// The file the bootstrapper stores the installed library version in.
+pub const VERSION_FILE: &str = ".meshpit_version";
+
+/// The file the bootstrapper stores the floppy's version in, once it's done with that floppy.
+///
+/// Not the same as `VERSION_FILE`, the server always sends its newest libraries, and updates change
+/// them later anyways.
+pub const BOOTSTRAPPED_FILE: &str = ".meshpit_bootstrapped";
+
+// ==
+// Library bundle
+// ==
+
+/// Every library file a turtle needs, and a hash of all of them together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryBundle {
+    /// A hash of every file, as hex.
+    version: String,
+    /// File name -> contents. Sorted so the hash doesn't depend on read order.
+    files: BTreeMap<String, String>,
+}
+
+impl LibraryBundle {
+    /// Make a bundle out of some files.
+    pub fn new(files: BTreeMap<String, String>) -> Self {
+        let mut hasher = Fnv1a::new();
+        for (name, contents) in &files {
+            hasher.write(name.as_bytes());
+            // Separate the name from the contents so moving bytes between them changes the hash.
+            hasher.write(&[0]);
+            hasher.write(contents.as_bytes());
+            hasher.write(&[0]);
+        }
+        Self {
+            version: format!("{:016x}", hasher.finish()),
+            files,
+        }
+    }
+
+    /// Every library that's baked into meshpit, pointed at `server_url`. See `bootstrap_script`.
+    pub fn turtle_libraries(server_url: &str) -> Self {
+        Self::new(
+            LUA_ASSETS
+                .iter()
+                .map(|asset| (asset.name.to_string(), asset.with_server_url(server_url)))
+                .collect(),
+        )
+    }
+
+    /// The same bundle, but with every file minified. The version changes too, since the files did.
+    pub fn minified(&self) -> Result<Self, LexError> {
+        let files = self
+            .files
+            .iter()
+            .map(|(name, contents)| Ok((name.clone(), minify(contents)?)))
+            .collect::<Result<_, LexError>>()?;
+        Ok(Self::new(files))
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn files(&self) -> &BTreeMap<String, String> {
+        &self.files
+    }
+
+    /// The message we send back to a bootstrapping turtle.
+    pub fn to_reply(&self) -> String {
+        json!({
+            "bootstrap": {
+                "version": self.version,
+                "files": self.files,
+            }
+        })
+        .to_string()
+    }
+}
+
+/// FNV-1a. We only need the hash to change when the files do, it doesn't need to be secure.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// ==
+// Bootstrap script
+// ==
+
+/// Make the floppy's `startup.lua`.
+///
+/// `server_url` is the websocket url without the scheme, IE `localhost:4816/meshpit`, same as
+/// `networking.lua`. `version` is the bundle version the floppy was written for. If the turtle
+/// was already bootstrapped from a floppy with that version, we skip the download.
+pub fn bootstrap_script(server_url: &str, version: &str) -> String {
+    let template = r#"-- Meshpit bootstrap. This file is generated, edits will be overwritten.
+local SERVER_URL = __SERVER_URL__
+local VERSION = __VERSION__
+local VERSION_FILE = __VERSION_FILE__
+local BOOTSTRAPPED_FILE = __BOOTSTRAPPED_FILE__
+
+-- Copy ourselves onto the turtle if we're running off of the floppy.
+local running = shell.getRunningProgram()
+if running ~= "startup.lua" then
+    if fs.exists("startup.lua") then
+        fs.delete("startup.lua")
+    end
+    fs.copy(running, "startup.lua")
+end
+
+local function readVersion(path)
+    if not fs.exists(path) then
+        return nil
+    end
+    local file = fs.open(path, "r")
+    local version = file.readAll()
+    file.close()
+    return version
+end
+
+local function writeVersion(path, version)
+    local file = fs.open(path, "w")
+    file.write(version)
+    file.close()
+end
+
+-- The installed version can be newer than the floppy after updates, that's fine.
+if readVersion(BOOTSTRAPPED_FILE) ~= VERSION or not readVersion(VERSION_FILE) then
+    print("Fetching libraries...")
+    local socket, error_string = http.websocket(SERVER_URL, {
+        ["Computer-ID"] = tostring(os.getComputerID())
+    }, 10)
+    if not socket then
+        print("Failed to connect! " .. tostring(error_string))
+        os.sleep(5)
+        os.reboot()
+    end
+
+    -- Same packet layout as networking.lua
+    socket.send(textutils.serializeJSON({
+        id = os.getComputerID(),
+        uuid = "BOOTSTRP",
+        timestamp = os.epoch("utc"),
+        data = textutils.serializeJSON({ bootstrap = VERSION })
+    }))
+
+    local reply = socket.receive(30)
+    socket.close()
+    local bundle = reply and textutils.unserializeJSON(reply)
+    if not bundle or not bundle.bootstrap then
+        print("Bad bootstrap reply!")
+        os.sleep(5)
+        os.reboot()
+    end
+
+    for name, contents in pairs(bundle.bootstrap.files) do
+        local file = fs.open(name, "w")
+        file.write(contents)
+        file.close()
+    end
+    -- What we actually got, which is what control and updates compare against.
+    writeVersion(VERSION_FILE, bundle.bootstrap.version)
+    writeVersion(BOOTSTRAPPED_FILE, VERSION)
+    print("Installed libraries " .. bundle.bootstrap.version)
+end
+
+-- Finish off an update that got cut short, before loading anything it was replacing.
+if fs.exists("update.lua") then
+    require("update").resume()
+end
+
+-- Say hello, so we get tied back to whoever made us.
+local networking = require("networking")
+networking.sendToControl("hello")
+-- And tell control what we're running, so it knows if an update worked.
+require("update").reportVersion()
+"#;
+    template
+        .replace("__SERVER_URL__", &lua_string(server_url))
+        .replace("__VERSION__", &lua_string(version))
+        .replace("__VERSION_FILE__", &lua_string(VERSION_FILE))
+        .replace("__BOOTSTRAPPED_FILE__", &lua_string(BOOTSTRAPPED_FILE))
+}
+
+/// Write the bootstrap script onto a floppy. Computercraft keeps floppy contents in
+/// `world/computercraft/disk/<id>`, so this only works with the server's files on hand.
+///
+/// Returns the path of the written file.
+pub fn write_bootstrap_disk(
+    server_folder: &Path,
+    disk_id: u16,
+    script: &str,
+) -> Result<PathBuf, std::io::Error> {
+    let disk_folder = server_folder
+        .join("world/computercraft/disk")
+        .join(disk_id.to_string());
+    std::fs::create_dir_all(&disk_folder)?;
+    let path = disk_folder.join("startup.lua");
+    std::fs::write(&path, script)?;
+    Ok(path)
+}
+
+// ==
+// Serving
+// ==
+
+/// If this incoming packet is a bootstrap request, get the version the floppy asked for.
+///
+/// Packets are in the `networking.lua` format, where `data` is itself a json string.
+pub fn bootstrap_request(packet: &str) -> Option<String> {
+    let packet: Value = serde_json::from_str(packet).ok()?;
+    let data = packet.get("data")?;
+    // Strings get wrapped again, tables don't.
+    let data: Value = match data {
+        Value::String(inner) => serde_json::from_str(inner).ok()?,
+        other => other.clone(),
+    };
+    data.get("bootstrap")?.as_str().map(str::to_string)
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+use crate::minecraft::computercraft::turtle::lua::DEFAULT_SERVER_URL;
+
+#[cfg(test)]
+fn test_bundle(networking: &str) -> LibraryBundle {
+    let mut files = BTreeMap::new();
+    files.insert("networking.lua".to_string(), networking.to_string());
+    files.insert("helpers.lua".to_string(), "return {}".to_string());
+    LibraryBundle::new(files)
+}
+
+#[test]
+/// The version should only change when the files do.
+fn bundle_version_follows_contents() {
+    let first = test_bundle("return {}");
+    assert_eq!(first.version(), test_bundle("return {}").version());
+    assert_ne!(first.version(), test_bundle("return { }").version());
+    assert_eq!(first.version().len(), 16);
+}
+
+#[test]
+/// The repo's libraries should all make it into the bundle.
+fn bundle_reads_turtle_libraries() {
+    let bundle = LibraryBundle::turtle_libraries(DEFAULT_SERVER_URL);
+    for name in ["helpers.lua", "networking.lua", "panic.lua", "walkback.lua"] {
+        assert!(bundle.files().contains_key(name), "{name}");
+    }
+    let minified = bundle.minified().unwrap();
+    assert_ne!(minified.version(), bundle.version());
+    assert_eq!(minified.files().len(), bundle.files().len());
+
+    // Turtles set up for another server have to connect to it after bootstrapping too.
+    let elsewhere = LibraryBundle::turtle_libraries("example.com:1234/meshpit");
+    assert!(elsewhere.files()["networking.lua"].contains(r#""example.com:1234/meshpit""#));
+    assert!(!elsewhere.files()["networking.lua"].contains(DEFAULT_SERVER_URL));
+    assert_ne!(elsewhere.version(), bundle.version());
+}
+
+#[test]
+/// Quotes in the url shouldn't break out of the lua string.
+fn bootstrap_script_escapes() {
+    let script = bootstrap_script("evil\"url\\", "abc");
+    assert!(script.contains(r#"local SERVER_URL = "evil\"url\\""#));
+    assert!(script.contains(r#"local VERSION = "abc""#));
+    assert!(!script.contains("__"));
+}
+
+#[test]
+/// A request from the bootstrap script gets picked out, and the reply has every file.
+fn bootstrap_request_and_reply() {
+    let packet = json!({
+        "id": 5,
+        "uuid": "BOOTSTRP",
+        "timestamp": 0,
+        "data": json!({ "bootstrap": "abc" }).to_string(),
+    })
+    .to_string();
+    assert_eq!(bootstrap_request(&packet), Some("abc".to_string()));
+    let not_bootstrap = json!({ "id": 5, "data": "\"ping\"" }).to_string();
+    assert_eq!(bootstrap_request(&not_bootstrap), None);
+
+    let bundle = test_bundle("return {}");
+    let reply: Value = serde_json::from_str(&bundle.to_reply()).unwrap();
+    assert_eq!(reply["bootstrap"]["version"], bundle.version());
+    assert_eq!(reply["bootstrap"]["files"]["helpers.lua"], "return {}");
+}
+
+#[test]
+/// The script ends up where computercraft looks for floppy files.
+fn bootstrap_disk_path() {
+    let server = std::env::temp_dir().join(format!("meshpit_bootstrap_{}", std::process::id()));
+    let path = write_bootstrap_disk(&server, 7, "print(1)").unwrap();
+    assert_eq!(path, server.join("world/computercraft/disk/7/startup.lua"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "print(1)");
+    std::fs::remove_dir_all(server).unwrap();
+}
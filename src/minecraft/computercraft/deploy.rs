@@ -0,0 +1,390 @@
+// Over the air library updates.
+// Rather than copying files into computer folders by hand, we push a whole library bundle over the
+// websocket. `update.lua` checks every file's hash, stages them, swaps them in, and reboots.
+//
+// Bundles get rolled out in stages, so a broken bundle only takes out a few turtles. If too many of
+// those panic, we stop and push the previous bundle back out.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::{Value, json};
+
+use crate::minecraft::computercraft::{bootstrap::LibraryBundle, lua_types::table::unpack_json};
+
+/// The ID of any computer, turtle or not.
+pub type ComputerId = u16;
+
+/// Hash a single file. This is FNV-1a 32, since lua numbers are doubles and can't do 64 bit math.
+/// `update.lua` has the same function, keep them in sync!
+pub fn file_hash(contents: &str) -> String {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in contents.as_bytes() {
+        hash ^= u32::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    format!("{hash:08x}")
+}
+
+/// The message that tells a computer to install a bundle.
+pub fn update_message(bundle: &LibraryBundle) -> String {
+    let hashes: BTreeMap<&String, String> = bundle
+        .files()
+        .iter()
+        .map(|(name, contents)| (name, file_hash(contents)))
+        .collect();
+    json!({
+        "update": {
+            "version": bundle.version(),
+            "files": bundle.files(),
+            "hashes": hashes,
+        }
+    })
+    .to_string()
+}
+
+/// Pull the `data` out of an incoming packet.
+///
+/// Packets from `networking.lua` are packed, and their data is packed json inside of a string.
+/// Plain json works too, since the bootstrap script doesn't have our serializer yet.
+pub fn packet_data(packet: &str) -> Option<Value> {
+    let packet = unpack_json(serde_json::from_str(packet).ok()?);
+    let data = match packet.get("data")? {
+        Value::String(inner) => serde_json::from_str(inner).ok()?,
+        other => other.clone(),
+    };
+    Some(unpack_json(data))
+}
+
+/// If this packet is a computer telling us what library version it's running, get the version.
+pub fn version_report(packet: &str) -> Option<String> {
+    packet_data(packet)?
+        .get("version")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Is this packet a panic from `panic.lua`?
+pub fn is_panic(packet: &str) -> bool {
+    packet_data(packet).is_some_and(|data| data.get("stack_trace").is_some())
+}
+
+// ==
+// Deployment
+// ==
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeployError {
+    /// Stages have to go up, and end at 100%.
+    BadStages,
+}
+
+/// Where a single computer is in the rollout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UpdateState {
+    /// Hasn't been sent the bundle yet.
+    Waiting,
+    /// Has the bundle, but hasn't told us it's running it yet.
+    Sent,
+    /// Running the new bundle.
+    Updated,
+    /// Panicked while running the new bundle.
+    Panicked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeploymentStatus {
+    /// Still rolling out, on this stage.
+    InProgress { stage: usize },
+    /// Every computer is running the new bundle.
+    Finished,
+    /// Too many panics, the previous bundle should go back out.
+    RolledBack,
+}
+
+/// Rolls a bundle out to every computer we know about, a stage at a time.
+#[derive(Debug)]
+pub struct Deployment {
+    bundle: LibraryBundle,
+    /// What to go back to if this goes badly.
+    previous: Option<LibraryBundle>,
+    /// How much of the fleet gets the bundle by the end of each stage, in percent.
+    stages: Vec<u8>,
+    stage: usize,
+    computers: BTreeMap<ComputerId, UpdateState>,
+    /// How many panics we put up with before rolling back.
+    panic_limit: usize,
+    rolled_back: bool,
+}
+
+impl Deployment {
+    /// Start a deployment. `stages` are percentages of the fleet, IE `[10, 50, 100]`.
+    pub fn new(
+        bundle: LibraryBundle,
+        previous: Option<LibraryBundle>,
+        stages: &[u8],
+    ) -> Result<Self, DeployError> {
+        let ascending = stages.windows(2).all(|pair| pair[0] < pair[1]);
+        if !ascending || stages.last() != Some(&100) || stages.first() == Some(&0) {
+            return Err(DeployError::BadStages);
+        }
+        Ok(Self {
+            bundle,
+            previous,
+            stages: stages.to_vec(),
+            stage: 0,
+            computers: BTreeMap::new(),
+            panic_limit: 0,
+            rolled_back: false,
+        })
+    }
+
+    /// Allow this many panics before rolling back. Defaults to none at all.
+    pub fn with_panic_limit(mut self, limit: usize) -> Self {
+        self.panic_limit = limit;
+        self
+    }
+
+    pub fn bundle(&self) -> &LibraryBundle {
+        &self.bundle
+    }
+
+    /// Add a computer to the rollout, along with what it's running right now.
+    pub fn add_computer(&mut self, id: ComputerId, installed: &str) {
+        let state = if installed == self.bundle.version() {
+            UpdateState::Updated
+        } else {
+            UpdateState::Waiting
+        };
+        self.computers.insert(id, state);
+    }
+
+    pub fn state(&self, id: ComputerId) -> Option<UpdateState> {
+        self.computers.get(&id).copied()
+    }
+
+    /// Which computers should be sent the bundle now.
+    pub fn to_send(&self) -> Vec<ComputerId> {
+        if self.rolled_back {
+            return vec![];
+        }
+        let allowed = (self.computers.len() * usize::from(self.stages[self.stage])).div_ceil(100);
+        let started = self
+            .computers
+            .values()
+            .filter(|state| **state != UpdateState::Waiting)
+            .count();
+        self.computers
+            .iter()
+            .filter(|(_, state)| **state == UpdateState::Waiting)
+            .map(|(id, _)| *id)
+            .take(allowed.saturating_sub(started))
+            .collect()
+    }
+
+    /// We sent the bundle to this computer.
+    pub fn mark_sent(&mut self, id: ComputerId) {
+        if let Some(state) = self.computers.get_mut(&id)
+            && *state == UpdateState::Waiting
+        {
+            *state = UpdateState::Sent;
+        }
+    }
+
+    /// A computer told us what version it's on, usually right after it rebooted.
+    pub fn reported_version(&mut self, id: ComputerId, version: &str) {
+        if version != self.bundle.version() {
+            return;
+        }
+        if let Some(state) = self.computers.get_mut(&id)
+            && *state != UpdateState::Panicked
+        {
+            *state = UpdateState::Updated;
+        }
+    }
+
+    /// A computer panicked. Only counts against the bundle if the computer was running it.
+    ///
+    /// Returns true if this made us roll back.
+    pub fn report_panic(&mut self, id: ComputerId) -> bool {
+        let Some(state) = self.computers.get_mut(&id) else {
+            return false;
+        };
+        if !matches!(state, UpdateState::Sent | UpdateState::Updated) {
+            return false;
+        }
+        *state = UpdateState::Panicked;
+        let panics = self
+            .computers
+            .values()
+            .filter(|state| **state == UpdateState::Panicked)
+            .count();
+        if panics > self.panic_limit && !self.rolled_back {
+            self.rolled_back = true;
+            return true;
+        }
+        false
+    }
+
+    /// Move on to the next stage if everyone in this one is updated.
+    ///
+    /// Returns whether we moved on.
+    pub fn advance(&mut self) -> bool {
+        if self.rolled_back || self.stage + 1 >= self.stages.len() {
+            return false;
+        }
+        let settled = self
+            .computers
+            .values()
+            .all(|state| matches!(state, UpdateState::Waiting | UpdateState::Updated));
+        if !settled || !self.to_send().is_empty() {
+            return false;
+        }
+        self.stage += 1;
+        true
+    }
+
+    pub fn status(&self) -> DeploymentStatus {
+        if self.rolled_back {
+            return DeploymentStatus::RolledBack;
+        }
+        if self
+            .computers
+            .values()
+            .all(|state| *state == UpdateState::Updated)
+        {
+            return DeploymentStatus::Finished;
+        }
+        DeploymentStatus::InProgress { stage: self.stage }
+    }
+
+    /// After a rollback, every computer that got the new bundle, and what to send them instead.
+    ///
+    /// Empty if we haven't rolled back, or there's nothing to roll back to.
+    pub fn rollback(&self) -> Option<(&LibraryBundle, Vec<ComputerId>)> {
+        if !self.rolled_back {
+            return None;
+        }
+        let previous = self.previous.as_ref()?;
+        let touched = self
+            .computers
+            .iter()
+            .filter(|(_, state)| **state != UpdateState::Waiting)
+            .map(|(id, _)| *id)
+            .collect();
+        Some((previous, touched))
+    }
+
+    /// How many computers are in each state.
+    pub fn summary(&self) -> HashMap<UpdateState, usize> {
+        let mut summary = HashMap::new();
+        for state in self.computers.values() {
+            *summary.entry(*state).or_default() += 1;
+        }
+        summary
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+fn test_bundle(contents: &str) -> LibraryBundle {
+    let mut files = BTreeMap::new();
+    files.insert("networking.lua".to_string(), contents.to_string());
+    LibraryBundle::new(files)
+}
+
+#[test]
+/// Known FNV-1a 32 values, so the lua side has something to check against.
+fn file_hash_vectors() {
+    assert_eq!(file_hash(""), "811c9dc5");
+    assert_eq!(file_hash("a"), "e40c292c");
+    assert_eq!(file_hash("foobar"), "bf9cf968");
+}
+
+#[test]
+/// The update message has every file and its hash.
+fn update_message_has_hashes() {
+    let bundle = test_bundle("return {}");
+    let message: Value = serde_json::from_str(&update_message(&bundle)).unwrap();
+    assert_eq!(message["update"]["version"], bundle.version());
+    assert_eq!(message["update"]["files"]["networking.lua"], "return {}");
+    assert_eq!(
+        message["update"]["hashes"]["networking.lua"],
+        file_hash("return {}")
+    );
+
+    let report = json!({ "id": 1, "data": json!({ "version": "abc" }).to_string() }).to_string();
+    assert_eq!(version_report(&report), Some("abc".to_string()));
+    let panic = json!({ "id": 1, "data": json!({ "pairs": [{ "key": "stack_trace", "value": "oops" }] }).to_string() }).to_string();
+    assert!(is_panic(&panic));
+    assert!(!is_panic(&report));
+
+    // What `networking.lua` actually sends, with the whole packet packed too.
+    let packed = json!({ "pairs": [
+        { "key": "id", "value": 1 },
+        { "key": "data", "value": json!({ "pairs": [{ "key": "version", "value": "abc" }] }).to_string() },
+    ]})
+    .to_string();
+    assert_eq!(version_report(&packed), Some("abc".to_string()));
+}
+
+#[test]
+/// Stages only let a slice of the fleet update at a time.
+fn deployment_stages() {
+    assert!(Deployment::new(test_bundle("new"), None, &[50, 10, 100]).is_err());
+    assert!(Deployment::new(test_bundle("new"), None, &[10, 50]).is_err());
+
+    let bundle = test_bundle("new");
+    let version = bundle.version().to_string();
+    let mut deployment = Deployment::new(bundle, None, &[10, 100]).unwrap();
+    for id in 0..10 {
+        deployment.add_computer(id, "old");
+    }
+    // Already running it.
+    deployment.add_computer(10, &version);
+
+    assert_eq!(deployment.to_send(), vec![0]);
+    deployment.mark_sent(0);
+    assert!(deployment.to_send().is_empty());
+    // Can't move on until the first one is back up.
+    assert!(!deployment.advance());
+    deployment.reported_version(0, &version);
+    assert!(deployment.advance());
+
+    let rest = deployment.to_send();
+    assert_eq!(rest.len(), 9);
+    for id in rest {
+        deployment.mark_sent(id);
+        deployment.reported_version(id, &version);
+    }
+    assert_eq!(deployment.status(), DeploymentStatus::Finished);
+}
+
+#[test]
+/// A panic on the new bundle stops the rollout and sends the old one back out.
+fn deployment_rollback() {
+    let old = test_bundle("old");
+    let mut deployment = Deployment::new(test_bundle("new"), Some(old.clone()), &[50, 100])
+        .unwrap()
+        .with_panic_limit(1);
+    for id in 0..4 {
+        deployment.add_computer(id, old.version());
+    }
+    let first = deployment.to_send();
+    assert_eq!(first, vec![0, 1]);
+    deployment.mark_sent(0);
+    deployment.mark_sent(1);
+
+    // Computers that never got the bundle don't count.
+    assert!(!deployment.report_panic(3));
+    assert!(!deployment.report_panic(0));
+    assert!(deployment.report_panic(1));
+    assert_eq!(deployment.status(), DeploymentStatus::RolledBack);
+    assert!(deployment.to_send().is_empty());
+
+    let (bundle, targets) = deployment.rollback().unwrap();
+    assert_eq!(bundle, &old);
+    assert_eq!(targets, vec![0, 1]);
+}
@@ -17,3 +17,97 @@ pub struct LuaKeyValuePair {
     pub key: Value,
     pub value: Value,
 }
+
+impl PairedLuaTable {
+    /// Get the value for a string key, if there is one.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.pairs
+            .iter()
+            .find(|pair| pair.key.as_str() == Some(key))
+            .map(|pair| &pair.value)
+    }
+}
+
+/// Turn our packed format back into plain json, all the way down. Anything that isn't a packed
+/// table is left alone.
+///
+/// Lua keys can be anything, but json keys can only be strings, so other keys get stringified.
+pub fn unpack_json(value: Value) -> Value {
+    let Value::Object(object) = value else {
+        return value;
+    };
+    if object.len() != 1 {
+        return Value::Object(object);
+    }
+    let table = match object.get("pairs") {
+        Some(Value::Array(_)) => {
+            match serde_json::from_value::<PairedLuaTable>(Value::Object(object.clone())) {
+                Ok(table) => table,
+                Err(_) => return Value::Object(object),
+            }
+        }
+        // Empty lua tables come out as an empty object, not an array.
+        Some(Value::Object(inner)) if inner.is_empty() => return Value::Object(Default::default()),
+        _ => return Value::Object(object),
+    };
+    let unpacked = table
+        .pairs
+        .into_iter()
+        .map(|pair| {
+            let key = match unpack_json(pair.key) {
+                Value::String(key) => key,
+                other => other.to_string(),
+            };
+            (key, unpack_json(pair.value))
+        })
+        .collect();
+    Value::Object(unpacked)
+}
+
+/// The other way around, turn plain json into our packed format like `helpers.serializeJSON` does.
+///
+/// Arrays become tables with keys starting at 1, since that's what they were in lua.
+pub fn pack_json(value: Value) -> Value {
+    let pairs: Vec<LuaKeyValuePair> = match value {
+        Value::Object(object) => object
+            .into_iter()
+            .map(|(key, value)| LuaKeyValuePair {
+                key: Value::String(key),
+                value: pack_json(value),
+            })
+            .collect(),
+        Value::Array(array) => array
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| LuaKeyValuePair {
+                key: Value::from(index + 1),
+                value: pack_json(value),
+            })
+            .collect(),
+        other => return other,
+    };
+    serde_json::to_value(PairedLuaTable { pairs }).expect("Pairs always serialize")
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Nested packed tables come back out as plain json.
+fn unpack_nested() {
+    let packed = serde_json::json!({
+        "pairs": [
+            { "key": "id", "value": 5 },
+            { "key": 1, "value": { "pairs": [{ "key": "stack_trace", "value": "oops" }] } },
+            { "key": "empty", "value": { "pairs": {} } },
+        ]
+    });
+    assert_eq!(
+        unpack_json(packed),
+        serde_json::json!({ "id": 5, "1": { "stack_trace": "oops" }, "empty": {} })
+    );
+    let plain = serde_json::json!({ "pairs": 5, "other": true });
+    assert_eq!(unpack_json(plain.clone()), plain);
+    assert_eq!(unpack_json(pack_json(plain.clone())), plain);
+}
@@ -1,4 +1,6 @@
+pub mod bootstrap;
 pub mod computer_types;
+pub mod deploy;
 pub mod lua_types;
 pub mod modded_blocks;
 pub mod modded_data;
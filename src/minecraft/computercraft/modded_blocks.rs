@@ -5,6 +5,8 @@ use std::collections::HashMap;
 use mcdata_rs::Block;
 use once_cell::sync::Lazy;
 
+use crate::minecraft::vanilla::block_state::StateProperty;
+
 pub(super) static MODDED_BLOCKS: Lazy<HashMap<String, Block>> = Lazy::new(|| {
     let mut m = HashMap::new();
 
@@ -37,7 +39,7 @@ pub(super) static MODDED_BLOCKS: Lazy<HashMap<String, Block>> = Lazy::new(|| {
             emit_light: 0,
             filter_light: 0,
             transparent: true, // i mean i guess?
-            states: vec![],    // TODO: Do we need this?
+            states: vec![],    // See MODDED_BLOCK_STATES
             min_state_id: 0,   // these defaults to 0 in mcdata_rs
             max_state_id: 0,
             default_state: 0,
@@ -455,6 +457,105 @@ pub(super) static MODDED_BLOCKS: Lazy<HashMap<String, Block>> = Lazy::new(|| {
     m
 });
 
+/// Block state properties for the modded blocks, keyed by block name.
+///
+/// These are pulled from the CC:Tweaked source, since there is no data dump for them.
+pub(crate) static MODDED_BLOCK_STATES: Lazy<HashMap<&'static str, Vec<StateProperty>>> =
+    Lazy::new(|| {
+        let mut m = HashMap::new();
+
+        let horizontal = || StateProperty::new("facing", &["north", "south", "west", "east"]);
+        let any_direction =
+            || StateProperty::new("facing", &["down", "up", "north", "south", "west", "east"]);
+        let waterlogged = || StateProperty::boolean("waterlogged");
+
+        for turtle in ["turtle_normal", "turtle_advanced"] {
+            m.insert(turtle, vec![horizontal(), waterlogged()]);
+        }
+        for computer in ["computer_normal", "computer_advanced"] {
+            m.insert(
+                computer,
+                vec![
+                    horizontal(),
+                    StateProperty::new("state", &["off", "on", "blinking"]),
+                ],
+            );
+        }
+        for monitor in ["monitor_normal", "monitor_advanced"] {
+            m.insert(
+                monitor,
+                vec![
+                    horizontal(),
+                    StateProperty::new("orientation", &["down", "up", "north"]),
+                    // Which sides connect to other monitors.
+                    StateProperty::new(
+                        "state",
+                        &[
+                            "none", "l", "r", "lr", "u", "d", "ud", "rd", "ld", "ru", "lu", "lrd",
+                            "rud", "lud", "lru", "lrud",
+                        ],
+                    ),
+                ],
+            );
+        }
+        m.insert(
+            "printer",
+            vec![
+                horizontal(),
+                StateProperty::boolean("top"),
+                StateProperty::boolean("bottom"),
+            ],
+        );
+        m.insert(
+            "disk_drive",
+            vec![
+                horizontal(),
+                StateProperty::new("state", &["empty", "full", "invalid"]),
+            ],
+        );
+        m.insert("speaker", vec![horizontal()]);
+        m.insert("redstone_relay", vec![horizontal()]);
+        for modem in ["wireless_modem_normal", "wireless_modem_advanced"] {
+            m.insert(
+                modem,
+                vec![any_direction(), StateProperty::boolean("on"), waterlogged()],
+            );
+        }
+        m.insert(
+            "wired_modem",
+            vec![any_direction(), StateProperty::boolean("on"), waterlogged()],
+        );
+        m.insert(
+            "wired_modem_full",
+            vec![
+                StateProperty::boolean("modem"),
+                StateProperty::boolean("peripheral"),
+            ],
+        );
+
+        // Cables have a modem variant for every direction, and every on/off/peripheral combo.
+        let mut modem_variants: Vec<String> = vec!["none".to_string()];
+        for suffix in ["off", "on", "off_peripheral", "on_peripheral"] {
+            for direction in ["down", "up", "north", "south", "west", "east"] {
+                modem_variants.push(format!("{direction}_{suffix}"));
+            }
+        }
+        let mut cable = vec![
+            StateProperty {
+                name: "modem".to_string(),
+                values: modem_variants,
+            },
+            StateProperty::boolean("cable"),
+            waterlogged(),
+        ];
+        for direction in ["down", "up", "north", "south", "west", "east"] {
+            cable.push(StateProperty::boolean(direction));
+        }
+        m.insert("cable", cable);
+
+        m
+    });
+
 // ===
 // Tests
 // ===
@@ -503,3 +604,11 @@ fn check_modded_blocks() {
         assert_eq!(key, &value.name)
     }
 }
+
+#[test]
+/// Every modded block state should belong to an actual modded block.
+fn modded_block_states_exist() {
+    for name in MODDED_BLOCK_STATES.keys() {
+        assert!(MODDED_BLOCKS.contains_key(*name), "{name} is not a block!");
+    }
+}
@@ -0,0 +1,336 @@
+// Fuel estimation and planning.
+// Turtles can only really be stranded one way, and thats running out of fuel. So before we send a
+// turtle anywhere, we want to know if it can actually make it there and back.
+
+use std::collections::HashMap;
+
+use crate::minecraft::{
+    types::MinecraftPosition,
+    vanilla::{fuel::turtle_fuel_value, item_type::MinecraftItem},
+};
+
+/// How much fuel a normal turtle can hold.
+pub const NORMAL_TURTLE_FUEL_LIMIT: u32 = 20_000;
+
+/// How much fuel an advanced turtle can hold.
+pub const ADVANCED_TURTLE_FUEL_LIMIT: u32 = 100_000;
+
+/// Every movement costs a single unit of fuel. Turning is free.
+pub const FUEL_PER_MOVE: u32 = 1;
+
+// ==
+// Path costs
+// ==
+
+/// How much fuel it costs to walk a path, in order.
+///
+/// Paths do not need to be made of adjacent positions, if there is a gap between two positions we
+/// assume the turtle takes the shortest route between them.
+pub fn path_cost(path: &[MinecraftPosition]) -> u32 {
+    path.windows(2)
+        .map(|step| step[0].manhattan_distance(&step[1]) * FUEL_PER_MOVE)
+        .sum()
+}
+
+/// How much fuel `walkback.rewind()` would cost after walking this path.
+///
+/// This mimics the walkback pruning in `walkback.md`, where returning to a position we have already
+/// visited removes every step after it, thus loops in a path are free to walk back.
+pub fn walkback_cost(path: &[MinecraftPosition]) -> u32 {
+    // The chain of positions we would rewind through, and where each position lives in that chain.
+    let mut chain: Vec<MinecraftPosition> = Vec::with_capacity(path.len());
+    let mut seen: HashMap<(i64, i64, i64), usize> = HashMap::new();
+
+    for position in path {
+        if let Some(&index) = seen.get(&position.xyz()) {
+            // Been here before, toss everything after it.
+            for pruned in chain.drain(index + 1..) {
+                seen.remove(&pruned.xyz());
+            }
+            continue;
+        }
+        seen.insert(position.xyz(), chain.len());
+        chain.push(*position);
+    }
+
+    path_cost(&chain)
+}
+
+// ==
+// Fuel model
+// ==
+
+/// Everything we need to know about a turtle's fuel to plan around it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuelModel {
+    /// How much fuel the turtle currently has.
+    pub fuel_level: u32,
+    /// How much fuel the turtle can hold at most.
+    pub fuel_limit: u32,
+}
+
+/// The predicted fuel usage of a trip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuelEstimate {
+    /// The fuel used getting to the end of the path.
+    pub outbound: u32,
+    /// The fuel used getting back to the start afterwards.
+    pub return_trip: u32,
+}
+
+impl FuelEstimate {
+    /// The whole trip, there and back again.
+    pub fn total(&self) -> u32 {
+        self.outbound.saturating_add(self.return_trip)
+    }
+}
+
+/// A place a turtle can go to top itself back up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefuelStation {
+    /// Where the turtle needs to stand to refuel.
+    pub position: MinecraftPosition,
+}
+
+/// A suggested detour to a refuel station while walking a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefuelStop {
+    /// The index into the path where the turtle should leave to go refuel. The turtle comes back to
+    /// this same position before continuing.
+    pub path_index: usize,
+    /// Which station to go to.
+    pub station: RefuelStation,
+    /// How much fuel the turtle should have when it gets to the station.
+    pub fuel_on_arrival: u32,
+    /// How much fuel the turtle should load at the station.
+    pub fuel_to_load: u32,
+}
+
+/// Reasons a trip cannot be planned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuelPlanError {
+    /// There are no refuel stations, and we don't have enough fuel.
+    NoStations,
+    /// At this index of the path, the turtle can no longer reach any refuel station.
+    Stranded(usize),
+    /// The trip needs more fuel than the turtle can even hold, refueling or not.
+    OverFuelLimit,
+}
+
+impl FuelModel {
+    /// Make a fuel model for a turtle.
+    pub fn new(fuel_level: u32, fuel_limit: u32) -> Self {
+        Self {
+            fuel_level: fuel_level.min(fuel_limit),
+            fuel_limit,
+        }
+    }
+
+    /// Estimate the fuel usage of walking a path, then walking back to the start of it.
+    pub fn estimate(&self, path: &[MinecraftPosition]) -> FuelEstimate {
+        FuelEstimate {
+            outbound: path_cost(path),
+            return_trip: walkback_cost(path),
+        }
+    }
+
+    /// Check if we can walk a path and still make it home afterwards, without refueling.
+    pub fn is_feasible(&self, path: &[MinecraftPosition]) -> bool {
+        self.estimate(path).total() <= self.fuel_level
+    }
+
+    /// How much fuel is missing to be able to do this trip. Zero if we already have enough.
+    pub fn deficit(&self, path: &[MinecraftPosition]) -> u32 {
+        self.estimate(path).total().saturating_sub(self.fuel_level)
+    }
+
+    /// How many of an item we would need to burn to cover a fuel deficit.
+    ///
+    /// Returns `None` if the item is not a fuel. Burning this many items may overflow the fuel
+    /// limit of the turtle, in which case the extra fuel is wasted.
+    pub fn items_needed(deficit: u32, fuel_item: &MinecraftItem) -> Option<u32> {
+        let value = turtle_fuel_value(fuel_item)?;
+        Some(deficit.div_ceil(value))
+    }
+
+    /// The mining task gives up when this is true. See `mining.md`
+    ///
+    /// `walkback.cost() > fuel_level / 2`
+    pub fn mining_should_stop(&self, walkback_cost: u32) -> bool {
+        walkback_cost > self.fuel_level / 2
+    }
+
+    /// The dig task gives up when this is true. See `dig.md`
+    ///
+    /// `max(number of blocks remaining in volume, walkback.cost() * 2) > fuel_level`
+    pub fn dig_should_stop(&self, blocks_remaining: u32, walkback_cost: u32) -> bool {
+        blocks_remaining.max(walkback_cost.saturating_mul(2)) > self.fuel_level
+    }
+
+    /// Plan refuel detours to walk a path and make it back to the start.
+    ///
+    /// This is greedy, we walk the path for as long as we could still reach a station afterwards,
+    /// and only then go fill up. This is not optimal, but its pretty good when stations are sparse.
+    pub fn plan_refuel_stops(
+        &self,
+        path: &[MinecraftPosition],
+        stations: &[RefuelStation],
+    ) -> Result<Vec<RefuelStop>, FuelPlanError> {
+        if self.is_feasible(path) {
+            // easy.
+            return Ok(vec![]);
+        }
+        if stations.is_empty() {
+            return Err(FuelPlanError::NoStations);
+        }
+
+        // Find the nearest station to a position, and how far it is.
+        let nearest = |position: &MinecraftPosition| -> (RefuelStation, u32) {
+            stations
+                .iter()
+                .map(|station| (*station, station.position.manhattan_distance(position)))
+                .min_by_key(|(_, distance)| *distance)
+                .expect("Stations are not empty")
+        };
+
+        let mut stops: Vec<RefuelStop> = vec![];
+        let mut fuel = self.fuel_level;
+
+        for (index, here) in path.iter().enumerate() {
+            // How much we need to finish the trip from here without stopping again.
+            let rest_of_trip = path_cost(&path[index..]) + walkback_cost(path);
+            if rest_of_trip <= fuel {
+                return Ok(stops);
+            }
+
+            // Can we still reach a station after taking the next step? If not, stop now.
+            let must_stop = match path.get(index + 1) {
+                Some(next) => {
+                    let step = here.manhattan_distance(next);
+                    let (_, distance) = nearest(next);
+                    step + distance > fuel
+                }
+                // We're at the end of the path, but can't make it home.
+                None => true,
+            };
+
+            if must_stop {
+                let (station, distance) = nearest(here);
+                if distance > fuel {
+                    return Err(FuelPlanError::Stranded(index));
+                }
+                let fuel_on_arrival = fuel - distance;
+
+                // Fill the tank, since we're already here.
+                let fuel_to_load = self.fuel_limit - fuel_on_arrival;
+
+                // Now we need to make it back to where we left off, then make sure that is
+                // actually enough to get further than we were.
+                fuel = self.fuel_limit - distance;
+                if let Some(next) = path.get(index + 1) {
+                    let step = here.manhattan_distance(next);
+                    if step + nearest(next).1 > fuel {
+                        return Err(FuelPlanError::OverFuelLimit);
+                    }
+                } else if walkback_cost(path) > fuel {
+                    return Err(FuelPlanError::OverFuelLimit);
+                }
+
+                stops.push(RefuelStop {
+                    path_index: index,
+                    station,
+                    fuel_on_arrival,
+                    fuel_to_load,
+                });
+            }
+
+            // Take the step.
+            if let Some(next) = path.get(index + 1) {
+                fuel -= here.manhattan_distance(next);
+            }
+        }
+
+        // Walked off the end of the path with enough fuel to get home, otherwise we would have
+        // stopped on the last position.
+        Ok(stops)
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+fn pos(x: i64, y: i64, z: i64) -> MinecraftPosition {
+    MinecraftPosition {
+        x,
+        y,
+        z,
+        facing: None,
+    }
+}
+
+#[test]
+/// Walking out and back along the same line should prune down to nothing.
+fn walkback_prunes_loops() {
+    let path = vec![pos(0, 0, 0), pos(1, 0, 0), pos(2, 0, 0), pos(1, 0, 0)];
+    assert_eq!(path_cost(&path), 3);
+    assert_eq!(walkback_cost(&path), 1);
+
+    // A little square, ending where we started. Free to walk back.
+    let square = vec![
+        pos(0, 0, 0),
+        pos(1, 0, 0),
+        pos(1, 0, 1),
+        pos(0, 0, 1),
+        pos(0, 0, 0),
+    ];
+    assert_eq!(path_cost(&square), 4);
+    assert_eq!(walkback_cost(&square), 0);
+}
+
+#[test]
+/// The task cutoffs from the docs.
+fn task_cutoffs() {
+    let model = FuelModel::new(100, NORMAL_TURTLE_FUEL_LIMIT);
+    assert!(!model.mining_should_stop(50));
+    assert!(model.mining_should_stop(51));
+    assert!(!model.dig_should_stop(100, 50));
+    assert!(model.dig_should_stop(101, 0));
+    assert!(model.dig_should_stop(0, 51));
+}
+
+#[test]
+/// A long straight line with a station partway along it.
+fn refuel_stop_planning() {
+    let path: Vec<MinecraftPosition> = (0..=50).map(|x| pos(x, 0, 0)).collect();
+    let station = RefuelStation {
+        position: pos(20, 0, 2),
+    };
+
+    // 50 out, 50 back.
+    let model = FuelModel::new(40, 200);
+    assert!(!model.is_feasible(&path));
+    assert_eq!(model.deficit(&path), 60);
+
+    let stops = model.plan_refuel_stops(&path, &[station]).unwrap();
+    assert_eq!(stops.len(), 1);
+    // We should have gone as far as we could before refueling.
+    assert!(stops[0].path_index >= 20);
+    assert_eq!(stops[0].fuel_to_load + stops[0].fuel_on_arrival, 200);
+
+    // Nowhere to refuel
+    assert_eq!(
+        model.plan_refuel_stops(&path, &[]),
+        Err(FuelPlanError::NoStations)
+    );
+
+    // Plenty of fuel, no stops.
+    let model = FuelModel::new(100, 200);
+    assert!(
+        model
+            .plan_refuel_stops(&path, &[station])
+            .unwrap()
+            .is_empty()
+    );
+}
@@ -1,5 +1,6 @@
 -- Weirdly, lua doesn't have some methods it really should.
 local helpers = {}
+local panic = require("panic")
 print("Setting up helpers...")
 
 --- Deep-copy a table (or anything), a lot of the time we do NOT want to take tables
@@ -110,7 +111,7 @@ end
 --- Unpack our custom json table format back into a normal lua table.
 ---@param packed string
 ---@return any unpacked
-function unpackJSON(packed)
+local function unpackJSON(packed)
     -- Only need special logic for tables
     if type(packed) ~= "table" then
         return packed
@@ -128,7 +129,7 @@ function unpackJSON(packed)
     -- Unpack our table!
     -- We do not care about the keys from the originating table, as
     -- the keys we want are packed into the pair.
-    for _, pair in ipairs(packed) do
+    for _, pair in ipairs(packed.pairs) do
         -- If anything is nil (which it should never be) we skip the pair.
         if pair.key == nil or pair.value == nil then
             goto continue
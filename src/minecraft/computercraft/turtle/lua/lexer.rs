@@ -0,0 +1,235 @@
+// Splitting lua source into tokens.
+// Used by the linter and the minifier. This covers lua 5.2, which is what CC:Tweaked runs, plus the
+// 5.3 operators since Cobalt accepts some of them.
+
+/// What sort of token this is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Name,
+    Keyword,
+    Number,
+    /// Quoted or long bracket strings, with the quotes still on.
+    String,
+    Symbol,
+}
+
+/// A single token, pointing back into the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    /// Exactly as it was written.
+    pub text: &'a str,
+    /// The line the token starts on, starting at 1.
+    pub line: usize,
+}
+
+impl Token<'_> {
+    /// Is this the keyword or symbol `text`?
+    pub fn is(&self, text: &str) -> bool {
+        matches!(self.kind, TokenKind::Keyword | TokenKind::Symbol) && self.text == text
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub line: usize,
+    pub message: String,
+}
+
+pub const KEYWORDS: [&str; 22] = [
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Longest first, so `...` wins over `..`.
+const SYMBOLS: [&str; 33] = [
+    "...", "..", "==", "~=", "<=", ">=", "::", "//", "<<", ">>", "+", "-", "*", "/", "%", "^", "#",
+    "&", "~", "|", "<", ">", "=", "(", ")", "{", "}", "[", "]", ";", ":", ",", ".",
+];
+
+/// Split up some lua. Comments and whitespace are dropped.
+pub fn tokenize(source: &str) -> Result<Vec<Token<'_>>, LexError> {
+    let bytes = source.as_bytes();
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let byte = bytes[index];
+        let start = index;
+        let start_line = line;
+
+        // Whitespace
+        if byte.is_ascii_whitespace() {
+            if byte == b'\n' {
+                line += 1;
+            }
+            index += 1;
+            continue;
+        }
+
+        // Comments
+        if source[index..].starts_with("--") {
+            index += 2;
+            if let Some(level) = long_bracket_level(&bytes[index..]) {
+                index = skip_long_bracket(source, index, level, &mut line).ok_or(LexError {
+                    line: start_line,
+                    message: "Unfinished long comment".to_string(),
+                })?;
+            } else {
+                while index < bytes.len() && bytes[index] != b'\n' {
+                    index += 1;
+                }
+            }
+            continue;
+        }
+
+        let kind = if byte.is_ascii_alphabetic() || byte == b'_' {
+            while index < bytes.len()
+                && (bytes[index].is_ascii_alphanumeric() || bytes[index] == b'_')
+            {
+                index += 1;
+            }
+            if KEYWORDS.contains(&&source[start..index]) {
+                TokenKind::Keyword
+            } else {
+                TokenKind::Name
+            }
+        } else if byte.is_ascii_digit()
+            || (byte == b'.' && bytes.get(index + 1).is_some_and(u8::is_ascii_digit))
+        {
+            index = read_number(bytes, index);
+            TokenKind::Number
+        } else if byte == b'"' || byte == b'\'' {
+            index = read_quoted(bytes, index, &mut line).ok_or(LexError {
+                line: start_line,
+                message: "Unfinished string".to_string(),
+            })?;
+            TokenKind::String
+        } else if let Some(level) = long_bracket_level(&bytes[index..]) {
+            index = skip_long_bracket(source, index, level, &mut line).ok_or(LexError {
+                line: start_line,
+                message: "Unfinished long string".to_string(),
+            })?;
+            TokenKind::String
+        } else if let Some(symbol) = SYMBOLS
+            .iter()
+            .find(|symbol| source[index..].starts_with(**symbol))
+        {
+            index += symbol.len();
+            TokenKind::Symbol
+        } else {
+            return Err(LexError {
+                line,
+                message: format!(
+                    "Unexpected character `{}`",
+                    source[index..].chars().next().unwrap_or('?')
+                ),
+            });
+        };
+
+        tokens.push(Token {
+            kind,
+            text: &source[start..index],
+            line: start_line,
+        });
+    }
+    Ok(tokens)
+}
+
+/// If this is the start of a long bracket, IE `[[` or `[==[`, how many `=` it has.
+fn long_bracket_level(bytes: &[u8]) -> Option<usize> {
+    if bytes.first() != Some(&b'[') {
+        return None;
+    }
+    let level = bytes[1..].iter().take_while(|byte| **byte == b'=').count();
+    (bytes.get(1 + level) == Some(&b'[')).then_some(level)
+}
+
+/// Skip past a long bracket starting at `index`. Returns the index just past the closing bracket.
+fn skip_long_bracket(source: &str, index: usize, level: usize, line: &mut usize) -> Option<usize> {
+    let body_start = index + level + 2;
+    let closing = format!("]{}]", "=".repeat(level));
+    let length = source[body_start..].find(&closing)?;
+    *line += source[index..body_start + length].matches('\n').count();
+    Some(body_start + length + closing.len())
+}
+
+/// Skip a quoted string. Returns the index just past the closing quote.
+fn read_quoted(bytes: &[u8], mut index: usize, line: &mut usize) -> Option<usize> {
+    let quote = bytes[index];
+    index += 1;
+    loop {
+        match *bytes.get(index)? {
+            byte if byte == quote => return Some(index + 1),
+            // Escaped newlines are allowed, but raw ones aren't.
+            b'\\' => {
+                if *bytes.get(index + 1)? == b'\n' {
+                    *line += 1;
+                }
+                index += 2;
+            }
+            b'\n' => return None,
+            _ => index += 1,
+        }
+    }
+}
+
+/// Read a number, hex or decimal. Returns the index just past it.
+fn read_number(bytes: &[u8], mut index: usize) -> usize {
+    let hex = bytes[index] == b'0' && matches!(bytes.get(index + 1), Some(b'x' | b'X'));
+    if hex {
+        index += 2;
+    }
+    let exponents: &[u8] = if hex { b"pP" } else { b"eE" };
+    while let Some(byte) = bytes.get(index) {
+        if exponents.contains(byte) {
+            index += 1;
+            if matches!(bytes.get(index), Some(b'+' | b'-')) {
+                index += 1;
+            }
+        } else if byte.is_ascii_alphanumeric() || *byte == b'.' {
+            // Let lua deal with malformed numbers, we just need to know where it ends.
+            index += 1;
+        } else {
+            break;
+        }
+    }
+    index
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Comments go away, strings and long brackets stay whole.
+fn tokenize_basics() {
+    let source =
+        "local x = 0x1F -- hi\n--[[ long\ncomment ]] print(\"a\\\"b\", [==[\nraw]==], x..1.5e-3)";
+    let tokens = tokenize(source).unwrap();
+    let texts: Vec<&str> = tokens.iter().map(|token| token.text).collect();
+    assert_eq!(
+        texts,
+        vec![
+            "local",
+            "x",
+            "=",
+            "0x1F",
+            "print",
+            "(",
+            "\"a\\\"b\"",
+            ",",
+            "[==[\nraw]==]",
+            ",",
+            "x",
+            "..",
+            "1.5e-3",
+            ")"
+        ]
+    );
+    assert_eq!(tokens[0].kind, TokenKind::Keyword);
+    assert_eq!(tokens[4].line, 3);
+    assert_eq!(tokens.last().unwrap().line, 4);
+    assert!(tokenize("print(\"oops)").is_err());
+}
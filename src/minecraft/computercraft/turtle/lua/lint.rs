@@ -0,0 +1,748 @@
+// Static checks for our lua.
+// See `banned_functions.md`. Nothing stopped anyone from calling `turtle.forward()` directly, or from
+// forgetting a `local` and leaking a global, so we parse every library and check.
+//
+// This isn't a full lua compiler, it just walks the grammar far enough to know which names are
+// locals, which are globals, and what's being called.
+
+use std::collections::HashSet;
+
+use crate::minecraft::computercraft::turtle::lua::{
+    LuaAsset,
+    lexer::{LexError, Token, TokenKind, tokenize},
+};
+
+/// The only file allowed to touch `turtle` directly.
+pub const WALKBACK_FILE: &str = "walkback.lua";
+
+/// Globals that lua and CC:Tweaked give us.
+const KNOWN_GLOBALS: &[&str] = &[
+    // Lua
+    "_G",
+    "_ENV",
+    "_HOST",
+    "assert",
+    "bit32",
+    "collectgarbage",
+    "coroutine",
+    "debug",
+    "error",
+    "getmetatable",
+    "ipairs",
+    "load",
+    "loadstring",
+    "math",
+    "next",
+    "os",
+    "package",
+    "pairs",
+    "pcall",
+    "print",
+    "rawequal",
+    "rawget",
+    "rawlen",
+    "rawset",
+    "require",
+    "select",
+    "setmetatable",
+    "string",
+    "table",
+    "tonumber",
+    "tostring",
+    "type",
+    "unpack",
+    "utf8",
+    "xpcall",
+    // CC:Tweaked
+    "colors",
+    "colours",
+    "commands",
+    "disk",
+    "fs",
+    "gps",
+    "help",
+    "http",
+    "io",
+    "keys",
+    "multishell",
+    "paintutils",
+    "parallel",
+    "peripheral",
+    "pocket",
+    "printError",
+    "read",
+    "rednet",
+    "redstone",
+    "rs",
+    "settings",
+    "shell",
+    "sleep",
+    "term",
+    "textutils",
+    "turtle",
+    "vector",
+    "window",
+    "write",
+];
+
+/// Something wrong with a lua file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintIssue {
+    /// Couldn't even parse it.
+    Syntax { line: usize, message: String },
+    /// Used `turtle` outside of walkback. `name` is what was used, IE `turtle.forward`.
+    BannedCall { line: usize, name: String },
+    /// Read a global that nobody declared.
+    UndeclaredGlobal { line: usize, name: String },
+    /// Assigned to a global, probably a missing `local`.
+    GlobalAssignment { line: usize, name: String },
+}
+
+impl From<LexError> for LintIssue {
+    fn from(value: LexError) -> Self {
+        LintIssue::Syntax {
+            line: value.line,
+            message: value.message,
+        }
+    }
+}
+
+/// Check a single file.
+pub fn lint(file_name: &str, source: &str) -> Vec<LintIssue> {
+    let tokens = match tokenize(source) {
+        Ok(tokens) => tokens,
+        Err(error) => return vec![error.into()],
+    };
+    let mut linter = Linter {
+        tokens,
+        index: 0,
+        scopes: vec![HashSet::new()],
+        allow_turtle: file_name == WALKBACK_FILE,
+        issues: vec![],
+    };
+    if let Err(issue) = linter.chunk() {
+        linter.issues.push(issue);
+    }
+    linter.issues
+}
+
+/// Check every embedded library.
+pub fn lint_assets<'a>(
+    assets: impl IntoIterator<Item = &'a LuaAsset>,
+) -> Vec<(&'static str, LintIssue)> {
+    assets
+        .into_iter()
+        .flat_map(|asset| {
+            lint(asset.name, &asset.contents)
+                .into_iter()
+                .map(|issue| (asset.name, issue))
+        })
+        .collect()
+}
+
+// ==
+// The walker
+// ==
+
+type Parse<T> = Result<T, LintIssue>;
+
+/// What a suffixed expression turned out to be.
+enum Expression {
+    /// Just a name, we don't know yet if it's being read or written.
+    Name(String, usize),
+    /// A function call, which is allowed as a statement.
+    Call,
+    /// Anything else.
+    Other,
+}
+
+struct Linter<'a> {
+    tokens: Vec<Token<'a>>,
+    index: usize,
+    /// Locals in each block, innermost last.
+    scopes: Vec<HashSet<String>>,
+    allow_turtle: bool,
+    issues: Vec<LintIssue>,
+}
+
+impl<'a> Linter<'a> {
+    // Token helpers
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.index).copied()
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.peek().is_some_and(|token| token.is(text))
+    }
+
+    fn line(&self) -> usize {
+        self.peek()
+            .or_else(|| self.tokens.last().copied())
+            .map_or(1, |token| token.line)
+    }
+
+    fn next(&mut self) -> Parse<Token<'a>> {
+        let token = self
+            .peek()
+            .ok_or_else(|| self.error("Unexpected end of file"))?;
+        self.index += 1;
+        Ok(token)
+    }
+
+    fn accept(&mut self, text: &str) -> bool {
+        if self.peek_is(text) {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Parse<()> {
+        if self.accept(text) {
+            Ok(())
+        } else {
+            let found = self.peek().map_or("end of file", |token| token.text);
+            Err(self.error(&format!("Expected `{text}`, found `{found}`")))
+        }
+    }
+
+    fn name(&mut self) -> Parse<Token<'a>> {
+        let token = self.next()?;
+        if token.kind != TokenKind::Name {
+            return Err(LintIssue::Syntax {
+                line: token.line,
+                message: format!("Expected a name, found `{}`", token.text),
+            });
+        }
+        Ok(token)
+    }
+
+    fn error(&self, message: &str) -> LintIssue {
+        LintIssue::Syntax {
+            line: self.line(),
+            message: message.to_string(),
+        }
+    }
+
+    // Scopes
+
+    fn declare(&mut self, name: &str) {
+        self.scopes
+            .last_mut()
+            .expect("There's always a scope")
+            .insert(name.to_string());
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    fn in_scope<T>(&mut self, inner: impl FnOnce(&mut Self) -> Parse<T>) -> Parse<T> {
+        self.scopes.push(HashSet::new());
+        let result = inner(self);
+        self.scopes.pop();
+        result
+    }
+
+    /// A name is being read.
+    fn read(&mut self, name: &str, line: usize) {
+        if self.is_local(name) {
+            return;
+        }
+        if name == "turtle" && !self.allow_turtle {
+            // Grab the method name if there is one, it makes the error nicer.
+            let method = match (self.peek(), self.tokens.get(self.index + 1)) {
+                (Some(access), Some(field))
+                    if (access.is(".") || access.is(":")) && field.kind == TokenKind::Name =>
+                {
+                    format!("turtle.{}", field.text)
+                }
+                _ => "turtle".to_string(),
+            };
+            self.issues
+                .push(LintIssue::BannedCall { line, name: method });
+            return;
+        }
+        if !KNOWN_GLOBALS.contains(&name) {
+            self.issues.push(LintIssue::UndeclaredGlobal {
+                line,
+                name: name.to_string(),
+            });
+        }
+    }
+
+    /// A name is being assigned to.
+    fn write(&mut self, name: &str, line: usize) {
+        if !self.is_local(name) {
+            self.issues.push(LintIssue::GlobalAssignment {
+                line,
+                name: name.to_string(),
+            });
+        }
+    }
+
+    // Grammar
+
+    fn chunk(&mut self) -> Parse<()> {
+        self.block()?;
+        if let Some(token) = self.peek() {
+            return Err(LintIssue::Syntax {
+                line: token.line,
+                message: format!("Unexpected `{}`", token.text),
+            });
+        }
+        Ok(())
+    }
+
+    fn block_ends(&self) -> bool {
+        self.peek().is_none_or(|token| {
+            ["end", "else", "elseif", "until"]
+                .iter()
+                .any(|keyword| token.is(keyword))
+        })
+    }
+
+    fn block(&mut self) -> Parse<()> {
+        while !self.block_ends() {
+            if self.accept("return") {
+                if !self.block_ends() && !self.peek_is(";") {
+                    self.expression_list()?;
+                }
+                self.accept(";");
+                break;
+            }
+            self.statement()?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Parse<()> {
+        let token = self
+            .peek()
+            .ok_or_else(|| self.error("Unexpected end of file"))?;
+        match token.text {
+            ";" if token.kind == TokenKind::Symbol => {
+                self.index += 1;
+            }
+            "::" if token.kind == TokenKind::Symbol => {
+                self.index += 1;
+                self.name()?;
+                self.expect("::")?;
+            }
+            "break" if token.kind == TokenKind::Keyword => {
+                self.index += 1;
+            }
+            "goto" if token.kind == TokenKind::Keyword => {
+                self.index += 1;
+                self.name()?;
+            }
+            "do" if token.kind == TokenKind::Keyword => {
+                self.index += 1;
+                self.in_scope(Self::block)?;
+                self.expect("end")?;
+            }
+            "while" if token.kind == TokenKind::Keyword => {
+                self.index += 1;
+                self.expression()?;
+                self.expect("do")?;
+                self.in_scope(Self::block)?;
+                self.expect("end")?;
+            }
+            "repeat" if token.kind == TokenKind::Keyword => {
+                self.index += 1;
+                // The condition can see the block's locals.
+                self.in_scope(|linter| {
+                    linter.block()?;
+                    linter.expect("until")?;
+                    linter.expression()
+                })?;
+            }
+            "if" if token.kind == TokenKind::Keyword => {
+                self.index += 1;
+                self.expression()?;
+                self.expect("then")?;
+                self.in_scope(Self::block)?;
+                while self.accept("elseif") {
+                    self.expression()?;
+                    self.expect("then")?;
+                    self.in_scope(Self::block)?;
+                }
+                if self.accept("else") {
+                    self.in_scope(Self::block)?;
+                }
+                self.expect("end")?;
+            }
+            "for" if token.kind == TokenKind::Keyword => {
+                self.index += 1;
+                self.for_loop()?;
+            }
+            "function" if token.kind == TokenKind::Keyword => {
+                self.index += 1;
+                self.function_statement()?;
+            }
+            "local" if token.kind == TokenKind::Keyword => {
+                self.index += 1;
+                self.local()?;
+            }
+            _ => self.expression_statement()?,
+        }
+        Ok(())
+    }
+
+    fn for_loop(&mut self) -> Parse<()> {
+        let first = self.name()?;
+        let mut names = vec![first.text];
+        if self.accept("=") {
+            self.expression()?;
+            self.expect(",")?;
+            self.expression()?;
+            if self.accept(",") {
+                self.expression()?;
+            }
+        } else {
+            while self.accept(",") {
+                names.push(self.name()?.text);
+            }
+            self.expect("in")?;
+            self.expression_list()?;
+        }
+        self.expect("do")?;
+        self.in_scope(|linter| {
+            for name in &names {
+                linter.declare(name);
+            }
+            linter.block()
+        })?;
+        self.expect("end")
+    }
+
+    fn function_statement(&mut self) -> Parse<()> {
+        // function a.b.c:d() end
+        let base = self.name()?;
+        let mut method = false;
+        let mut dotted = false;
+        while self.peek_is(".") || self.peek_is(":") {
+            method = self.peek_is(":");
+            dotted = true;
+            self.index += 1;
+            self.name()?;
+            if method {
+                break;
+            }
+        }
+        if dotted {
+            self.read(base.text, base.line);
+        } else {
+            self.write(base.text, base.line);
+        }
+        self.function_body(method)
+    }
+
+    fn local(&mut self) -> Parse<()> {
+        if self.accept("function") {
+            let name = self.name()?;
+            // Declared before the body, so it can call itself.
+            self.declare(name.text);
+            return self.function_body(false);
+        }
+        let mut names = vec![self.name()?.text];
+        while self.accept(",") {
+            names.push(self.name()?.text);
+        }
+        // `local x = x` reads the outer `x`, so the values go first.
+        if self.accept("=") {
+            self.expression_list()?;
+        }
+        for name in names {
+            self.declare(name);
+        }
+        Ok(())
+    }
+
+    fn function_body(&mut self, method: bool) -> Parse<()> {
+        self.expect("(")?;
+        self.in_scope(|linter| {
+            if method {
+                linter.declare("self");
+            }
+            if !linter.accept(")") {
+                loop {
+                    if linter.accept("...") {
+                        break;
+                    }
+                    let parameter = linter.name()?;
+                    linter.declare(parameter.text);
+                    if !linter.accept(",") {
+                        break;
+                    }
+                }
+                linter.expect(")")?;
+            }
+            linter.block()?;
+            linter.expect("end")
+        })
+    }
+
+    fn expression_statement(&mut self) -> Parse<()> {
+        let line = self.line();
+        let first = self.suffixed_expression()?;
+        if !self.peek_is("=") && !self.peek_is(",") {
+            return match first {
+                Expression::Call => Ok(()),
+                _ => Err(LintIssue::Syntax {
+                    line,
+                    message: "Expected an assignment or a function call".to_string(),
+                }),
+            };
+        }
+        let mut targets = vec![first];
+        while self.accept(",") {
+            targets.push(self.suffixed_expression()?);
+        }
+        self.expect("=")?;
+        self.expression_list()?;
+        for target in targets {
+            match target {
+                Expression::Name(name, line) => self.write(&name, line),
+                Expression::Call => {
+                    return Err(LintIssue::Syntax {
+                        line,
+                        message: "Can't assign to a function call".to_string(),
+                    });
+                }
+                Expression::Other => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn expression_list(&mut self) -> Parse<()> {
+        self.expression()?;
+        while self.accept(",") {
+            self.expression()?;
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self) -> Parse<()> {
+        const UNARY: [&str; 4] = ["not", "-", "#", "~"];
+        const BINARY: [&str; 21] = [
+            "+", "-", "*", "/", "//", "%", "^", "..", "==", "~=", "<", "<=", ">", ">=", "and",
+            "or", "&", "|", "~", "<<", ">>",
+        ];
+        while UNARY.iter().any(|operator| self.peek_is(operator)) {
+            self.index += 1;
+        }
+        self.simple_expression()?;
+        while BINARY.iter().any(|operator| self.peek_is(operator)) {
+            self.index += 1;
+            while UNARY.iter().any(|operator| self.peek_is(operator)) {
+                self.index += 1;
+            }
+            self.simple_expression()?;
+        }
+        Ok(())
+    }
+
+    fn simple_expression(&mut self) -> Parse<()> {
+        let token = self
+            .peek()
+            .ok_or_else(|| self.error("Expected an expression"))?;
+        match token.kind {
+            TokenKind::Number | TokenKind::String => {
+                self.index += 1;
+            }
+            TokenKind::Keyword if ["nil", "true", "false"].contains(&token.text) => {
+                self.index += 1;
+            }
+            TokenKind::Keyword if token.text == "function" => {
+                self.index += 1;
+                self.function_body(false)?;
+            }
+            TokenKind::Symbol if token.text == "..." => {
+                self.index += 1;
+            }
+            TokenKind::Symbol if token.text == "{" => self.table()?,
+            _ => {
+                if let Expression::Name(name, line) = self.suffixed_expression()? {
+                    self.read(&name, line);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn table(&mut self) -> Parse<()> {
+        self.expect("{")?;
+        while !self.accept("}") {
+            if self.accept("[") {
+                self.expression()?;
+                self.expect("]")?;
+                self.expect("=")?;
+                self.expression()?;
+            } else if self
+                .peek()
+                .is_some_and(|token| token.kind == TokenKind::Name)
+                && self
+                    .tokens
+                    .get(self.index + 1)
+                    .is_some_and(|token| token.is("="))
+            {
+                // `name = value`, the name is just a key.
+                self.index += 2;
+                self.expression()?;
+            } else {
+                self.expression()?;
+            }
+            if !self.accept(",") && !self.accept(";") {
+                self.expect("}")?;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn suffixed_expression(&mut self) -> Parse<Expression> {
+        // The base.
+        let mut expression = if self.accept("(") {
+            self.expression()?;
+            self.expect(")")?;
+            Expression::Other
+        } else {
+            let name = self.name()?;
+            Expression::Name(name.text.to_string(), name.line)
+        };
+
+        while let Some(token) = self.peek() {
+            let suffix = token.is(".")
+                || token.is("[")
+                || token.is(":")
+                || token.is("(")
+                || token.is("{")
+                || token.kind == TokenKind::String;
+            if !suffix {
+                break;
+            }
+            // Anything after a name means the name was read.
+            if let Expression::Name(name, line) = &expression {
+                let (name, line) = (name.clone(), *line);
+                self.read(&name, line);
+            }
+            if self.accept(".") {
+                self.name()?;
+                expression = Expression::Other;
+            } else if self.accept("[") {
+                self.expression()?;
+                self.expect("]")?;
+                expression = Expression::Other;
+            } else if self.accept(":") {
+                self.name()?;
+                self.arguments()?;
+                expression = Expression::Call;
+            } else {
+                self.arguments()?;
+                expression = Expression::Call;
+            }
+        }
+        Ok(expression)
+    }
+
+    fn arguments(&mut self) -> Parse<()> {
+        let token = self
+            .peek()
+            .ok_or_else(|| self.error("Expected arguments"))?;
+        if token.kind == TokenKind::String {
+            self.index += 1;
+            return Ok(());
+        }
+        if token.is("{") {
+            return self.table();
+        }
+        self.expect("(")?;
+        if !self.accept(")") {
+            self.expression_list()?;
+            self.expect(")")?;
+        }
+        Ok(())
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+use crate::minecraft::computercraft::turtle::lua::LUA_ASSETS;
+
+#[test]
+/// Our own libraries should pass.
+fn lint_embedded_libraries() {
+    let issues = lint_assets(LUA_ASSETS.iter());
+    assert!(issues.is_empty(), "{issues:#?}");
+}
+
+#[test]
+/// Turtle calls are only allowed in walkback.
+fn lint_banned_calls() {
+    let source = "local function go()\n    turtle.forward()\nend\nlocal t = turtle";
+    assert_eq!(
+        lint("mining.lua", source),
+        vec![
+            LintIssue::BannedCall {
+                line: 2,
+                name: "turtle.forward".to_string()
+            },
+            LintIssue::BannedCall {
+                line: 4,
+                name: "turtle".to_string()
+            },
+        ]
+    );
+    assert!(lint(WALKBACK_FILE, source).is_empty());
+}
+
+#[test]
+/// Locals are fine, forgetting `local` isn't.
+fn lint_globals() {
+    let source = r#"
+local helpers = {}
+function helpers.thing(a, ...)
+    local t = { key = a, [a] = 1 }
+    for i, v in pairs(t) do print(i, v, self) end
+    oops = 5
+    return unknownThing(t.key)
+end
+function leaked() end
+local function recurse(n) if n > 0 then return recurse(n - 1) end end
+::continue::
+goto continue
+return helpers
+"#;
+    assert_eq!(
+        lint("helpers.lua", source),
+        vec![
+            LintIssue::UndeclaredGlobal {
+                line: 5,
+                name: "self".to_string()
+            },
+            LintIssue::GlobalAssignment {
+                line: 6,
+                name: "oops".to_string()
+            },
+            LintIssue::UndeclaredGlobal {
+                line: 7,
+                name: "unknownThing".to_string()
+            },
+            LintIssue::GlobalAssignment {
+                line: 9,
+                name: "leaked".to_string()
+            },
+        ]
+    );
+    assert!(matches!(
+        lint("broken.lua", "local = 5")[0],
+        LintIssue::Syntax { line: 1, .. }
+    ));
+}
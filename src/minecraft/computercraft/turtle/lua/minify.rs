@@ -0,0 +1,81 @@
+// Shrinking lua so it takes up less of a turtle's disk.
+// We drop comments and indentation, and squash the spaces between tokens. Line breaks stay where
+// they were, so line numbers in panics still match the real files.
+
+use crate::minecraft::computercraft::turtle::lua::lexer::{LexError, Token, tokenize};
+
+/// Minify some lua.
+pub fn minify(source: &str) -> Result<String, LexError> {
+    let tokens = tokenize(source)?;
+    let mut output = String::with_capacity(source.len());
+    let mut line = 1;
+    let mut previous: Option<Token> = None;
+
+    for token in tokens {
+        if token.line > line {
+            output.push_str(&"\n".repeat(token.line - line));
+            line = token.line;
+        } else if let Some(previous) = previous
+            && needs_space(previous, token)
+        {
+            output.push(' ');
+        }
+        output.push_str(token.text);
+        // Long strings can span lines.
+        line += token.text.matches('\n').count();
+        previous = Some(token);
+    }
+    output.push('\n');
+    Ok(output)
+}
+
+/// Would gluing these two tokens together turn them into something else? IE `local x` into
+/// `localx`, or `- -1` into a comment.
+fn needs_space(left: Token, right: Token) -> bool {
+    let glued = format!("{}{}", left.text, right.text);
+    match tokenize(&glued) {
+        Ok(tokens) => {
+            tokens.len() != 2 || tokens[0].text != left.text || tokens[1].text != right.text
+        }
+        Err(_) => true,
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+use crate::minecraft::computercraft::turtle::lua::{LUA_ASSETS, lint::lint};
+
+#[test]
+/// Minifying shouldn't change a single token, or what line it's on.
+fn minify_keeps_tokens() {
+    for asset in LUA_ASSETS.iter() {
+        let minified = minify(&asset.contents).unwrap();
+        assert!(minified.len() < asset.contents.len(), "{}", asset.name);
+
+        let before: Vec<(&str, usize)> = tokenize(&asset.contents)
+            .unwrap()
+            .iter()
+            .map(|token| (token.text, token.line))
+            .collect();
+        let after: Vec<(&str, usize)> = tokenize(&minified)
+            .unwrap()
+            .iter()
+            .map(|token| (token.text, token.line))
+            .collect();
+        assert_eq!(before, after, "{}", asset.name);
+        assert!(lint(asset.name, &minified).is_empty(), "{}", asset.name);
+    }
+}
+
+#[test]
+/// Spaces only stay where they have to.
+fn minify_spacing() {
+    let source = "local x = - -1 -- comment\nlocal y = x .. 1 .. \"a\"  ..  [[b]]";
+    assert_eq!(
+        minify(source).unwrap(),
+        "local x=- -1\nlocal y=x..1 ..\"a\"..[[b]]\n"
+    );
+}
@@ -1,2 +1,132 @@
+// The turtle's lua libraries.
+// These get baked into the binary, so the control server doesn't need the repo around to hand
+// them out. The bootstrapper, updates, and the test harness all get their files from here.
+
+use once_cell::sync::Lazy;
+
+use crate::minecraft::computercraft::deploy::file_hash;
+
+pub mod lexer;
+pub mod lint;
+pub mod minify;
 #[cfg(test)]
 mod tests;
+#[cfg(test)]
+pub mod vm;
+
+/// Where `networking.lua` connects to unless told otherwise. No scheme, CC:Tweaked adds that.
+pub const DEFAULT_SERVER_URL: &str = "localhost:4816/meshpit";
+
+/// Stands in for the server url in the library files.
+const SERVER_URL_PLACEHOLDER: &str = "__SERVER_URL__";
+
+/// A lua file that ships with meshpit.
+#[derive(Debug)]
+pub struct LuaAsset {
+    /// The file name, IE `networking.lua`. Also what it's saved as on the computer.
+    pub name: &'static str,
+    /// The file, pointed at `DEFAULT_SERVER_URL`.
+    pub contents: String,
+    /// The file as written, with the placeholders still in it.
+    template: &'static str,
+    /// See `file_hash`.
+    pub hash: String,
+}
+
+impl LuaAsset {
+    fn new(name: &'static str, template: &'static str) -> Self {
+        let contents = fill_server_url(template, DEFAULT_SERVER_URL);
+        Self {
+            name,
+            hash: file_hash(&contents),
+            contents,
+            template,
+        }
+    }
+
+    /// The file, pointed at some other server. `server_url` is like `DEFAULT_SERVER_URL`.
+    pub fn with_server_url(&self, server_url: &str) -> String {
+        fill_server_url(self.template, server_url)
+    }
+
+    /// The name used to `require` this file, IE `networking`.
+    pub fn module_name(&self) -> &'static str {
+        self.name.trim_end_matches(".lua")
+    }
+}
+
+/// Every lua library, sorted by name.
+pub static LUA_ASSETS: Lazy<Vec<LuaAsset>> = Lazy::new(|| {
+    vec![
+        LuaAsset::new("helpers.lua", include_str!("helpers.lua")),
+        LuaAsset::new("networking.lua", include_str!("networking.lua")),
+        LuaAsset::new("panic.lua", include_str!("panic.lua")),
+        LuaAsset::new("update.lua", include_str!("update.lua")),
+        LuaAsset::new("walkback.lua", include_str!("walkback.lua")),
+    ]
+});
+
+fn fill_server_url(template: &str, server_url: &str) -> String {
+    template.replace(SERVER_URL_PLACEHOLDER, &lua_string(server_url))
+}
+
+/// Quote a string for lua.
+pub fn lua_string(string: &str) -> String {
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push('"');
+    for character in string.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            other => quoted.push(other),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Get a library by its file name.
+pub fn get_asset(name: &str) -> Option<&'static LuaAsset> {
+    LUA_ASSETS.iter().find(|asset| asset.name == name)
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Every lua file in this folder should be embedded, and nothing else.
+fn lua_assets_match_folder() {
+    let folder = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/minecraft/computercraft/turtle/lua");
+    let mut on_disk: Vec<String> = std::fs::read_dir(folder)
+        .unwrap()
+        .filter_map(|entry| {
+            let name = entry.unwrap().file_name().into_string().unwrap();
+            name.ends_with(".lua").then_some(name)
+        })
+        .collect();
+    on_disk.sort();
+    let embedded: Vec<&str> = LUA_ASSETS.iter().map(|asset| asset.name).collect();
+    assert_eq!(on_disk, embedded);
+    assert_eq!(get_asset("panic.lua").unwrap().module_name(), "panic");
+}
+
+#[test]
+/// The server url gets filled in, and nothing ships with the placeholder still in it.
+fn lua_assets_server_url() {
+    let networking = get_asset("networking.lua").unwrap();
+    assert!(networking.contents.contains("\"localhost:4816/meshpit\""));
+    let pointed = networking.with_server_url("10.0.0.2:5000/meshpit");
+    assert!(pointed.contains("local SERVER_URL = \"10.0.0.2:5000/meshpit\""));
+    assert!(!pointed.contains("localhost:4816"));
+    for asset in LUA_ASSETS.iter() {
+        assert!(
+            !asset.contents.contains(SERVER_URL_PLACEHOLDER),
+            "{}",
+            asset.name
+        );
+    }
+}
@@ -12,8 +12,8 @@ local networking = {}
 
 -- All compuers communicate over the same websocket, only differentiated by their computer ID
 -- via sending it in the initial handshake.
--- TODO: This is currently pinned to localhost. Should we do this another way?
-local SERVER_URL = "localhost:4816/meshpit"
+-- Filled in when the file is handed out, see `LuaAsset` in `mod.rs`.
+local SERVER_URL = __SERVER_URL__
 local websocket = nil
 local HEADERS = {
     ["Computer-ID"] = tostring(os.getComputerID())
@@ -161,6 +161,14 @@ local function receive(timeout)
         panic.force_reboot("Failed to unpack received packet! : " .. tostring(second_result))
     end
 
+    -- Updates get handled here, so they land no matter what program is listening.
+    -- Applying one reboots, so we only come back if it was refused.
+    if type(second_result) == "table" and second_result.update then
+        local _, reason = require("update").apply(second_result)
+        networking.sendToControl({ update_failed = reason })
+        return false, reason
+    end
+
     return true, second_result
 end
 
@@ -2,7 +2,10 @@
 
 -- Table that is used to call the panic handler.
 -- Empty, as it is just for calling the method.
-panic = {}
+local panic = {}
+
+-- Defined at the bottom, but panic needs them.
+local panicLocals, panicUpValues
 
 --- The panic method. Takes in a panic message.
 --- 
@@ -33,7 +36,7 @@ function panic.panic(message, messageOnly)
 
     ---@alias PanicData {stack_trace: string, locals: table, up_values: table} 
     ---@type PanicData
-    panic_data = {
+    local panic_data = {
         -- A stack trace of where the panic was called.
         stack_trace = trace,
 
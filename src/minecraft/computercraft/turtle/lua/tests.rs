@@ -3,7 +3,19 @@
 
 use log::info;
 
-use crate::minecraft::computercraft::lua_types::table::PairedLuaTable;
+use serde_json::json;
+
+use crate::minecraft::computercraft::{
+    bootstrap::{
+        BOOTSTRAPPED_FILE, LibraryBundle, VERSION_FILE, bootstrap_request, bootstrap_script,
+    },
+    deploy::{is_panic, packet_data, update_message, version_report},
+    lua_types::table::{PairedLuaTable, unpack_json},
+    turtle::lua::{
+        DEFAULT_SERVER_URL,
+        vm::{LuaVm, REBOOT_ERROR},
+    },
+};
 use crate::tests::prelude::*;
 
 #[tokio::test]
@@ -12,6 +24,7 @@ async fn basic_networking_test() {
     // create a test to run a 'puter in
     let area = TestArea {
         size_x: 3,
+        size_y: 3,
         size_z: 3,
     };
     let mut test = MinecraftTestHandle::new(area).await;
@@ -114,3 +127,299 @@ async fn basic_networking_test() {
     test.stop(pass_fail).await;
     assert!(pass_fail);
 }
+
+// ==
+// Embedded VM
+// ==
+// These don't need minecraft at all, see `vm.rs`.
+
+#[test]
+/// Our serializer should survive a round trip, and the rust side should agree on what it means.
+fn vm_serializer_round_trip() {
+    let vm = LuaVm::new(1).unwrap();
+    vm.exec(
+        r#"
+        helpers = require("helpers")
+        input = { a = 1, list = { 1, 2, 3 }, [5] = "five", nested = { deep = true } }
+        "#,
+    )
+    .unwrap();
+
+    let serialized: String = vm.eval("helpers.serializeJSON(input)").unwrap();
+    assert_eq!(
+        unpack_json(serde_json::from_str(&serialized).unwrap()),
+        json!({
+            "a": 1,
+            "list": { "1": 1, "2": 2, "3": 3 },
+            "5": "five",
+            "nested": { "deep": true },
+        })
+    );
+
+    let survived: bool = vm
+        .eval(
+            r#"
+            local output = helpers.deserializeJSON(helpers.serializeJSON(input))
+            return output.a == 1 and output.list[3] == 3 and output[5] == "five"
+                and output.nested.deep == true
+            "#,
+        )
+        .unwrap();
+    assert!(survived);
+}
+
+#[test]
+/// Packets go out in the same format the control server expects, and come back in.
+fn vm_networking() {
+    let vm = LuaVm::new(7).unwrap();
+    vm.exec(r#"networking = require("networking")"#).unwrap();
+    assert_eq!(
+        vm.websocket_url(),
+        Some("localhost:4816/meshpit".to_string())
+    );
+
+    vm.exec(r#"networking.sendToControl({ hello = "world" })"#)
+        .unwrap();
+    let sent = vm.take_sent();
+    // Health check first.
+    assert_eq!(sent.len(), 2);
+    assert_eq!(packet_data(&sent[0]), Some(json!("health")));
+    assert_eq!(packet_data(&sent[1]), Some(json!({ "hello": "world" })));
+    let packet = unpack_json(serde_json::from_str(&sent[1]).unwrap());
+    assert_eq!(packet["id"], 7);
+
+    vm.push_incoming(json!({ "reply": true }).to_string());
+    let reply: bool = vm
+        .eval("select(2, networking.waitForPacket(1)).reply")
+        .unwrap();
+    assert!(reply);
+}
+
+#[test]
+/// Panics send out everything they can, then reboot.
+fn vm_panic_capture() {
+    let vm = LuaVm::new(1).unwrap();
+    let error = vm.exec(r#"require("panic").panic("oh no")"#).unwrap_err();
+    assert!(error.to_string().contains(REBOOT_ERROR));
+    assert!(vm.rebooted());
+
+    let sent = vm.take_sent();
+    let panic = sent.last().unwrap();
+    assert!(is_panic(panic));
+    let data = packet_data(panic).unwrap();
+    assert!(data["stack_trace"].as_str().unwrap().contains("oh no"));
+    assert_eq!(data["locals"]["message"], "oh no");
+    assert!(vm.output().iter().any(|line| line == "Panic! : oh no"));
+}
+
+#[test]
+/// Walkback keeps track of where it is, and turtle calls get recorded.
+fn vm_walkback() {
+    let vm = LuaVm::new(1).unwrap();
+    let position: (i64, i64, i64, String) = vm
+        .eval(
+            r#"
+            local walkback = require("walkback")
+            walkback:setup(1, 2, 3, "e")
+            local position = walkback.position
+            return position.x, position.y, position.z, position.facing
+            "#,
+        )
+        .unwrap();
+    assert_eq!(position, (1, 2, 3, "e".to_string()));
+
+    vm.exec("turtle.forward() turtle.digUp()").unwrap();
+    assert_eq!(vm.turtle_calls(), vec!["forward", "digUp"]);
+}
+
+#[cfg(test)]
+/// Install an update on a VM. Updates always end in a reboot.
+fn apply_update(vm: &LuaVm, bundle: &LibraryBundle) {
+    vm.lua()
+        .globals()
+        .set("message", update_message(bundle))
+        .unwrap();
+    let error = vm
+        .exec(r#"require("update").apply(textutils.unserializeJSON(message))"#)
+        .unwrap_err();
+    assert!(error.to_string().contains(REBOOT_ERROR));
+    vm.reboot().unwrap();
+}
+
+#[test]
+/// Updates swap every file in, and the new version gets reported once we're back up.
+fn vm_update_reports_version() {
+    let vm = LuaVm::new(3).unwrap();
+    vm.write_file("a.lua", "old a");
+    vm.write_file(VERSION_FILE, "old");
+
+    let mut files = std::collections::BTreeMap::new();
+    files.insert("a.lua".to_string(), "new a".to_string());
+    let bundle = LibraryBundle::new(files);
+    apply_update(&vm, &bundle);
+    assert_eq!(vm.read_file("a.lua").as_deref(), Some("new a"));
+    assert_eq!(
+        vm.read_file(VERSION_FILE).as_deref(),
+        Some(bundle.version())
+    );
+
+    vm.exec(r#"require("update").reportVersion()"#).unwrap();
+    let sent = vm.take_sent();
+    assert_eq!(
+        version_report(sent.last().unwrap()).as_deref(),
+        Some(bundle.version())
+    );
+}
+
+#[test]
+/// Updates pushed over the websocket get installed by whatever is waiting for a packet, and bad
+/// ones get refused and reported.
+fn vm_update_over_networking() {
+    let vm = LuaVm::new(3).unwrap();
+    vm.write_file("a.lua", "old a");
+    vm.exec(r#"networking = require("networking")"#).unwrap();
+
+    let mut files = std::collections::BTreeMap::new();
+    files.insert("a.lua".to_string(), "new a".to_string());
+    let bundle = LibraryBundle::new(files);
+    let mut bad: serde_json::Value = serde_json::from_str(&update_message(&bundle)).unwrap();
+    bad["update"]["files"]["a.lua"] = json!("tampered");
+    vm.push_incoming(bad.to_string());
+    let (got, reason): (bool, String) = vm.eval("networking.waitForPacket(1)").unwrap();
+    assert!(!got);
+    assert!(reason.contains("a.lua"));
+    let sent = vm.take_sent();
+    let failed = packet_data(sent.last().unwrap()).unwrap();
+    assert_eq!(failed["update_failed"], reason);
+    assert_eq!(vm.read_file("a.lua").as_deref(), Some("old a"));
+
+    vm.push_incoming(update_message(&bundle));
+    let error = vm.exec("networking.waitForPacket(1)").unwrap_err();
+    assert!(error.to_string().contains(REBOOT_ERROR));
+    assert_eq!(vm.read_file("a.lua").as_deref(), Some("new a"));
+    assert_eq!(
+        vm.read_file(VERSION_FILE).as_deref(),
+        Some(bundle.version())
+    );
+}
+
+#[test]
+/// A swap that got cut off halfway gets finished on the next boot.
+fn vm_update_resume() {
+    let vm = LuaVm::new(3).unwrap();
+    // Cut off after swapping `a.lua`, but before `b.lua`.
+    vm.write_file("a.lua", "new a");
+    vm.write_file("b.lua", "old b");
+    vm.write_file(".previous/a.lua", "old a");
+    vm.write_file(".previous/.added", "");
+    vm.write_file(".update/b.lua", "new b");
+    vm.write_file(".update/.meshpit_version", "new");
+    vm.write_file(VERSION_FILE, "old");
+    vm.write_file(".swapping", "apply");
+
+    assert!(
+        vm.eval::<bool>(r#"return require("update").resume()"#)
+            .unwrap()
+    );
+    assert_eq!(vm.read_file("a.lua").as_deref(), Some("new a"));
+    assert_eq!(vm.read_file("b.lua").as_deref(), Some("new b"));
+    assert_eq!(vm.read_file(VERSION_FILE).as_deref(), Some("new"));
+    assert_eq!(vm.read_file(".previous/b.lua").as_deref(), Some("old b"));
+    assert!(
+        !vm.file_names()
+            .iter()
+            .any(|name| name.starts_with(".update"))
+    );
+
+    // Nothing left to do, and a rollback still has everything it needs.
+    assert!(
+        !vm.eval::<bool>(r#"return require("update").resume()"#)
+            .unwrap()
+    );
+    let error = vm.exec(r#"require("update").rollback()"#).unwrap_err();
+    assert!(error.to_string().contains(REBOOT_ERROR));
+    assert_eq!(vm.read_file("a.lua").as_deref(), Some("old a"));
+    assert_eq!(vm.read_file("b.lua").as_deref(), Some("old b"));
+    assert_eq!(vm.read_file(VERSION_FILE).as_deref(), Some("old"));
+    assert!(!vm.file_names().iter().any(|name| name == ".swapping"));
+}
+
+#[test]
+/// Rolling back puts the old files back, and gets rid of ones the update added.
+fn vm_update_rollback() {
+    let vm = LuaVm::new(3).unwrap();
+    vm.write_file("a.lua", "old a");
+    vm.write_file(VERSION_FILE, "old");
+    let before = vm.file_names();
+
+    let mut files = std::collections::BTreeMap::new();
+    files.insert("a.lua".to_string(), "new a".to_string());
+    files.insert("b.lua".to_string(), "new b".to_string());
+    apply_update(&vm, &LibraryBundle::new(files));
+    assert_eq!(vm.read_file("b.lua").as_deref(), Some("new b"));
+
+    let error = vm.exec(r#"require("update").rollback()"#).unwrap_err();
+    assert!(error.to_string().contains(REBOOT_ERROR));
+    assert_eq!(vm.file_names(), before);
+    assert_eq!(vm.read_file("a.lua").as_deref(), Some("old a"));
+    assert_eq!(vm.read_file(VERSION_FILE).as_deref(), Some("old"));
+
+    // Nothing left to roll back to.
+    vm.reboot().unwrap();
+    assert!(
+        !vm.eval::<bool>(r#"return require("update").rollback()"#)
+            .unwrap()
+    );
+}
+
+#[test]
+/// The floppy installs everything on the first boot, and leaves it alone after that.
+fn vm_bootstrap_twice() {
+    let bundle = LibraryBundle::turtle_libraries(DEFAULT_SERVER_URL);
+    // The floppy is out of date, the server sends what it has.
+    let script = bootstrap_script(DEFAULT_SERVER_URL, "floppy");
+    let vm = LuaVm::new(4).unwrap();
+    vm.write_file("disk/startup.lua", &script);
+    vm.set_running_program("disk/startup.lua");
+
+    vm.push_incoming(bundle.to_reply());
+    vm.exec(&script).unwrap();
+    let sent = vm.take_sent();
+    assert_eq!(bootstrap_request(&sent[0]).as_deref(), Some("floppy"));
+    assert_eq!(
+        vm.read_file("startup.lua").as_deref(),
+        Some(script.as_str())
+    );
+    assert_eq!(
+        vm.read_file("networking.lua").as_deref(),
+        Some(bundle.files()["networking.lua"].as_str())
+    );
+    assert_eq!(
+        vm.read_file(VERSION_FILE).as_deref(),
+        Some(bundle.version())
+    );
+    assert_eq!(vm.read_file(BOOTSTRAPPED_FILE).as_deref(), Some("floppy"));
+    assert_eq!(
+        version_report(sent.last().unwrap()).as_deref(),
+        Some(bundle.version())
+    );
+
+    // Second boot, off the turtle's own copy this time.
+    vm.reboot().unwrap();
+    vm.set_running_program("startup.lua");
+    vm.exec(&script).unwrap();
+    let sent = vm.take_sent();
+    assert!(
+        sent.iter()
+            .all(|packet| bootstrap_request(packet).is_none())
+    );
+    let fetches = vm.output();
+    let fetches = fetches
+        .iter()
+        .filter(|line| *line == "Fetching libraries...");
+    assert_eq!(fetches.count(), 1);
+    assert_eq!(
+        version_report(sent.last().unwrap()).as_deref(),
+        Some(bundle.version())
+    );
+}
@@ -0,0 +1,197 @@
+---@diagnostic disable: undefined-global, undefined-field
+-- Over the air updates. See `deploy.rs` for the other side of this.
+--
+-- Updates come in as a whole bundle of files with a hash for each one. We check every hash before
+-- touching anything, stage the files, then swap them in one by one and reboot. The old files are
+-- kept around so we can go back if the new ones are broken.
+--
+-- Swapping isn't atomic, so a marker is written first. If we get cut off halfway, `update.resume`
+-- finishes the job on the next boot.
+
+local update = {}
+
+-- Where the installed bundle version is kept. Shared with the bootstrap script.
+local VERSION_FILE = ".meshpit_version"
+-- New files are written here before being swapped in.
+local STAGING = ".update"
+-- The files we replaced, in case we need to roll back.
+local BACKUP = ".previous"
+-- Inside the backup, the files the update added that weren't there before. One per line.
+local ADDED = ".added"
+-- Exists while a swap is happening, and says which one. Either "apply" or "rollback".
+local SWAPPING = ".swapping"
+
+--- FNV-1a 32. Must match `file_hash` in `deploy.rs`.
+---
+--- Lua numbers are doubles, so we can't just multiply by the prime. The prime is 2^24 + 403, so
+--- we shift and add instead, which keeps everything under 2^53.
+---@param contents string
+---@return string hash the hash as 8 hex characters.
+function update.hash(contents)
+    local hash = 2166136261
+    for i = 1, #contents do
+        hash = bit32.bxor(hash, string.byte(contents, i))
+        hash = (bit32.lshift(hash, 24) + hash * 403) % 4294967296
+    end
+    return string.format("%08x", hash)
+end
+
+--- What bundle version we're running, if we know.
+---@return string|nil
+function update.installed()
+    if not fs.exists(VERSION_FILE) then
+        return nil
+    end
+    local file = fs.open(VERSION_FILE, "r")
+    local version = file.readAll()
+    file.close()
+    return version
+end
+
+--- Tell control what version we're on. The bootstrapped `startup.lua` does this every boot,
+--- which includes the one right after an update.
+function update.reportVersion()
+    local networking = require("networking")
+    networking.sendToControl({ version = update.installed() })
+end
+
+local function writeFile(path, contents)
+    local file = fs.open(path, "w")
+    file.write(contents)
+    file.close()
+end
+
+local function readFile(path)
+    local file = fs.open(path, "r")
+    local contents = file.readAll()
+    file.close()
+    return contents
+end
+
+--- Swap every file in `from` into the root, moving whatever was there into `backup`.
+---
+--- Each file leaves `from` once it's in place, so running this again after getting cut off picks
+--- up where it stopped.
+local function swapIn(from, backup)
+    if not fs.exists(from) then
+        return
+    end
+    for _, name in ipairs(fs.list(from)) do
+        if fs.exists(name) then
+            if backup then
+                fs.move(name, fs.combine(backup, name))
+            else
+                fs.delete(name)
+            end
+        end
+        fs.move(fs.combine(from, name), name)
+    end
+    fs.delete(from)
+end
+
+--- The last half of an update, from once everything is staged.
+local function finishApply()
+    swapIn(STAGING, BACKUP)
+    fs.delete(SWAPPING)
+end
+
+--- Everything a rollback does. Safe to run again if it got cut off.
+local function finishRollback()
+    local manifest = fs.combine(BACKUP, ADDED)
+    if fs.exists(manifest) then
+        for name in string.gmatch(readFile(manifest), "[^\n]+") do
+            if fs.exists(name) then
+                fs.delete(name)
+            end
+        end
+        fs.delete(manifest)
+    end
+    swapIn(BACKUP, nil)
+    fs.delete(SWAPPING)
+end
+
+--- Install an update packet, then reboot.
+---
+--- Returns false and a reason if any of the hashes don't match. Nothing is touched in that case.
+---@param packet {update: {version: string, files: table<string, string>, hashes: table<string, string>}}
+---@return boolean, string|nil
+function update.apply(packet)
+    local bundle = packet.update
+    if not bundle or not bundle.files or not bundle.hashes then
+        return false, "Not an update packet!"
+    end
+
+    -- Check everything before we write anything.
+    for name, contents in pairs(bundle.files) do
+        local got = update.hash(contents)
+        if got ~= bundle.hashes[name] then
+            return false, "Hash mismatch on " .. name .. ": " .. got
+        end
+    end
+
+    -- Stage it all.
+    if fs.exists(STAGING) then
+        fs.delete(STAGING)
+    end
+    fs.makeDir(STAGING)
+    for name, contents in pairs(bundle.files) do
+        writeFile(fs.combine(STAGING, name), contents)
+    end
+    writeFile(fs.combine(STAGING, VERSION_FILE), bundle.version)
+
+    -- Anything new has nothing to go back to, so it has to be deleted on rollback instead.
+    local added = {}
+    for _, name in ipairs(fs.list(STAGING)) do
+        if not fs.exists(name) then
+            table.insert(added, name)
+        end
+    end
+    if fs.exists(BACKUP) then
+        fs.delete(BACKUP)
+    end
+    fs.makeDir(BACKUP)
+    writeFile(fs.combine(BACKUP, ADDED), table.concat(added, "\n"))
+
+    -- And swap it in.
+    writeFile(SWAPPING, "apply")
+    finishApply()
+    print("Updated to " .. bundle.version .. ", rebooting...")
+    os.reboot()
+    return true -- never reached
+end
+
+--- Put back whatever the last update replaced, then reboot.
+---
+--- For when the new files are so broken that control can't send us the old ones.
+---@return boolean
+function update.rollback()
+    if not fs.exists(BACKUP) then
+        return false
+    end
+    writeFile(SWAPPING, "rollback")
+    finishRollback()
+    print("Rolled back, rebooting...")
+    os.reboot()
+    return true -- never reached
+end
+
+--- Finish a swap that got cut off, if there is one. The bootstrapped `startup.lua` calls this
+--- before loading any other library, since they might be half swapped.
+---
+--- Returns whether there was anything to finish.
+---@return boolean
+function update.resume()
+    if not fs.exists(SWAPPING) then
+        return false
+    end
+    local kind = readFile(SWAPPING)
+    print("Finishing an interrupted " .. kind .. "...")
+    if kind == "rollback" then
+        finishRollback()
+    else
+        finishApply()
+    end
+    return true
+end
+
+return update
@@ -0,0 +1,666 @@
+// An embedded lua VM for testing the turtle libraries without booting minecraft.
+// CC:Tweaked's lua is lua 5.2 (well, Cobalt), so that's what we embed. Everything from CC:Tweaked
+// that our libraries touch is stubbed out here, just enough for them to run. If a library starts
+// using something new, it needs a stub here too.
+//
+// Nothing in here actually waits. `os.sleep` just moves the clock forwards. The file system only
+// lives in memory.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashSet, VecDeque},
+    ffi::c_void,
+    rc::Rc,
+};
+
+use mlua::{FromLuaMulti, Lua, Table, Value as LuaValue, Variadic};
+use serde_json::{Map, Number, Value};
+
+use crate::minecraft::computercraft::turtle::lua::LUA_ASSETS;
+
+/// What `os.reboot()` and `os.shutdown()` error with, since the VM can't actually turn off.
+pub const REBOOT_ERROR: &str = "vm rebooted";
+
+/// Where `os.epoch("utc")` starts.
+const START_EPOCH: u64 = 1_700_000_000_000;
+
+/// Everything the stubs keep track of.
+#[derive(Debug, Default)]
+struct VmState {
+    /// Milliseconds since `START_EPOCH`.
+    clock: Cell<u64>,
+    /// Where `http.websocket` was told to connect.
+    websocket_url: RefCell<Option<String>>,
+    /// Everything sent out the websocket.
+    sent: RefCell<Vec<String>>,
+    /// Waiting to be received from the websocket.
+    incoming: RefCell<VecDeque<String>>,
+    /// Every line printed.
+    output: RefCell<Vec<String>>,
+    /// The names of every `turtle.*` function called, in order.
+    turtle_calls: RefCell<Vec<String>>,
+    label: RefCell<Option<String>>,
+    rebooted: Cell<bool>,
+    /// Path -> contents, or `None` for folders. Paths never start with a slash.
+    files: RefCell<BTreeMap<String, Option<String>>>,
+    /// What `shell.getRunningProgram()` says.
+    running_program: RefCell<String>,
+}
+
+/// A lua VM with our libraries, pretending to be a computer.
+pub struct LuaVm {
+    lua: Lua,
+    state: Rc<VmState>,
+}
+
+impl LuaVm {
+    /// Make a new VM for a computer with this ID. Nothing is loaded until it's `require`d.
+    pub fn new(computer_id: u16) -> mlua::Result<Self> {
+        // Panics need the debug library, which mlua only hands out to unsafe VMs. We trust our own
+        // lua, so thats fine.
+        let lua = unsafe { Lua::unsafe_new() };
+        let vm = Self {
+            lua,
+            state: Rc::new(VmState::default()),
+        };
+        vm.install_require()?;
+        vm.install_print()?;
+        vm.install_os(computer_id)?;
+        vm.install_textutils()?;
+        vm.install_http()?;
+        vm.install_turtle()?;
+        vm.install_fs()?;
+        vm.install_shell()?;
+        vm.lua
+            .load(
+                r#"
+                peripheral = {
+                    find = function() return nil end,
+                    wrap = function() return nil end,
+                    isPresent = function() return false end,
+                    getType = function() return nil end,
+                    getNames = function() return {} end,
+                }
+                "#,
+            )
+            .set_name("=stubs")
+            .exec()?;
+        Ok(vm)
+    }
+
+    pub fn lua(&self) -> &Lua {
+        &self.lua
+    }
+
+    /// Run some lua.
+    pub fn exec(&self, source: &str) -> mlua::Result<()> {
+        self.lua.load(source).set_name("=test").exec()
+    }
+
+    /// Run some lua and get what it returns.
+    pub fn eval<'lua, T: FromLuaMulti<'lua>>(&'lua self, source: &str) -> mlua::Result<T> {
+        self.lua.load(source).set_name("=test").eval()
+    }
+
+    /// Queue up a message for the computer to receive from the websocket.
+    pub fn push_incoming(&self, message: impl Into<String>) {
+        self.state.incoming.borrow_mut().push_back(message.into());
+    }
+
+    /// Take everything sent out the websocket so far.
+    pub fn take_sent(&self) -> Vec<String> {
+        self.state.sent.take()
+    }
+
+    pub fn websocket_url(&self) -> Option<String> {
+        self.state.websocket_url.borrow().clone()
+    }
+
+    /// Every line printed so far.
+    pub fn output(&self) -> Vec<String> {
+        self.state.output.borrow().clone()
+    }
+
+    /// Every `turtle.*` function called so far.
+    pub fn turtle_calls(&self) -> Vec<String> {
+        self.state.turtle_calls.borrow().clone()
+    }
+
+    pub fn label(&self) -> Option<String> {
+        self.state.label.borrow().clone()
+    }
+
+    /// Has the computer tried to reboot or shut down?
+    pub fn rebooted(&self) -> bool {
+        self.state.rebooted.get()
+    }
+
+    /// Turn the computer back on after a reboot. Files stay, everything loaded is forgotten.
+    pub fn reboot(&self) -> mlua::Result<()> {
+        self.state.rebooted.set(false);
+        let loaded: Table = self
+            .lua
+            .globals()
+            .get::<_, Table>("package")?
+            .get("loaded")?;
+        for asset in LUA_ASSETS.iter() {
+            loaded.set(asset.module_name(), LuaValue::Nil)?;
+        }
+        Ok(())
+    }
+
+    /// Put a file on the computer, making folders as needed.
+    pub fn write_file(&self, path: &str, contents: &str) {
+        let path = normalize(path);
+        let mut files = self.state.files.borrow_mut();
+        for folder in parents(&path) {
+            files.insert(folder, None);
+        }
+        files.insert(path, Some(contents.to_string()));
+    }
+
+    /// Read a file off the computer. `None` for folders and missing files.
+    pub fn read_file(&self, path: &str) -> Option<String> {
+        self.state.files.borrow().get(&normalize(path)).cloned()?
+    }
+
+    /// Every file on the computer, not including folders.
+    pub fn file_names(&self) -> Vec<String> {
+        let files = self.state.files.borrow();
+        files
+            .iter()
+            .filter(|(_, contents)| contents.is_some())
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// Pretend the running program is this path, IE a floppy's `disk/startup.lua`.
+    pub fn set_running_program(&self, path: &str) {
+        self.state.running_program.replace(path.to_string());
+    }
+
+    // ==
+    // Stubs
+    // ==
+
+    /// `require` only ever finds our libraries, never anything on disk.
+    fn install_require(&self) -> mlua::Result<()> {
+        let package: Table = self.lua.globals().get("package")?;
+        package.set("path", "")?;
+        package.set("cpath", "")?;
+        let preload: Table = package.get("preload")?;
+        for asset in LUA_ASSETS.iter() {
+            let loader = self
+                .lua
+                .load(asset.contents.as_str())
+                .set_name(format!("@{}", asset.name))
+                .into_function()?;
+            preload.set(asset.module_name(), loader)?;
+        }
+        Ok(())
+    }
+
+    fn install_print(&self) -> mlua::Result<()> {
+        let globals = self.lua.globals();
+        for name in ["print", "printError"] {
+            let state = self.state.clone();
+            let print = self
+                .lua
+                .create_function(move |lua, values: Variadic<LuaValue>| {
+                    let tostring: mlua::Function = lua.globals().get("tostring")?;
+                    let line = values
+                        .into_iter()
+                        .map(|value| tostring.call::<_, String>(value))
+                        .collect::<mlua::Result<Vec<String>>>()?
+                        .join("\t");
+                    state.output.borrow_mut().push(line);
+                    Ok(())
+                })?;
+            globals.set(name, print)?;
+        }
+        Ok(())
+    }
+
+    fn install_os(&self, computer_id: u16) -> mlua::Result<()> {
+        let os: Table = self.lua.globals().get("os")?;
+        os.set(
+            "getComputerID",
+            self.lua.create_function(move |_, ()| Ok(computer_id))?,
+        )?;
+
+        let state = self.state.clone();
+        os.set(
+            "epoch",
+            self.lua
+                .create_function(move |_, _: Option<String>| Ok(START_EPOCH + state.clock.get()))?,
+        )?;
+
+        let state = self.state.clone();
+        let sleep = self.lua.create_function(move |_, seconds: Option<f64>| {
+            let millis = (seconds.unwrap_or(0.0).max(0.0) * 1000.0) as u64;
+            state.clock.set(state.clock.get() + millis);
+            Ok(())
+        })?;
+        os.set("sleep", sleep.clone())?;
+        self.lua.globals().set("sleep", sleep)?;
+
+        for name in ["reboot", "shutdown"] {
+            let state = self.state.clone();
+            os.set(
+                name,
+                self.lua.create_function(move |_, ()| -> mlua::Result<()> {
+                    state.rebooted.set(true);
+                    Err(mlua::Error::RuntimeError(REBOOT_ERROR.to_string()))
+                })?,
+            )?;
+        }
+
+        let state = self.state.clone();
+        os.set(
+            "setComputerLabel",
+            self.lua.create_function(move |_, label: Option<String>| {
+                state.label.replace(label);
+                Ok(())
+            })?,
+        )?;
+        let state = self.state.clone();
+        os.set(
+            "getComputerLabel",
+            self.lua
+                .create_function(move |_, ()| Ok(state.label.borrow().clone()))?,
+        )
+    }
+
+    fn install_textutils(&self) -> mlua::Result<()> {
+        let textutils = self.lua.create_table()?;
+        // A unique table, so it can't be confused with anything else.
+        let null = self.lua.create_table()?;
+        self.lua
+            .set_named_registry_value("json_null", null.clone())?;
+        textutils.set("json_null", null)?;
+
+        let serialize = self.lua.create_function(|lua, value: LuaValue| {
+            let null: Table = lua.named_registry_value("json_null")?;
+            let json = lua_to_json(&value, null.to_pointer(), &mut HashSet::new())?;
+            Ok(json.to_string())
+        })?;
+        textutils.set("serializeJSON", serialize.clone())?;
+        textutils.set("serialiseJSON", serialize)?;
+
+        let unserialize =
+            self.lua
+                .create_function(|lua, (json, options): (String, Option<Table>)| {
+                    let parse_null = match options {
+                        Some(options) => options.get::<_, Option<bool>>("parse_null")?,
+                        None => None,
+                    };
+                    let null = if parse_null == Some(true) {
+                        Some(lua.named_registry_value::<Table>("json_null")?)
+                    } else {
+                        None
+                    };
+                    match serde_json::from_str::<Value>(&json) {
+                        Ok(value) => Ok((json_to_lua(lua, &value, null.as_ref())?, None)),
+                        Err(error) => Ok((LuaValue::Nil, Some(error.to_string()))),
+                    }
+                })?;
+        textutils.set("unserializeJSON", unserialize.clone())?;
+        textutils.set("unserialiseJSON", unserialize)?;
+
+        self.lua.globals().set("textutils", textutils)
+    }
+
+    fn install_http(&self) -> mlua::Result<()> {
+        let http = self.lua.create_table()?;
+        let state = self.state.clone();
+        let websocket = self.lua.create_function(
+            move |lua, (url, _headers, _timeout): (String, Option<Table>, Option<f64>)| {
+                state.websocket_url.replace(Some(url));
+                let socket = lua.create_table()?;
+
+                let sending = state.clone();
+                socket.set(
+                    "send",
+                    lua.create_function(move |_, message: String| {
+                        sending.sent.borrow_mut().push(message);
+                        Ok(())
+                    })?,
+                )?;
+                // Like the real thing, this is nil when nothing comes in before the timeout.
+                let receiving = state.clone();
+                socket.set(
+                    "receive",
+                    lua.create_function(move |_, _timeout: Option<f64>| {
+                        Ok(receiving.incoming.borrow_mut().pop_front())
+                    })?,
+                )?;
+                socket.set("close", lua.create_function(|_, ()| Ok(()))?)?;
+                Ok(socket)
+            },
+        )?;
+        http.set("websocket", websocket)?;
+        self.lua.globals().set("http", http)
+    }
+
+    /// Every `turtle.*` function exists, does nothing, and works.
+    fn install_turtle(&self) -> mlua::Result<()> {
+        let turtle = self.lua.create_table()?;
+        let metatable = self.lua.create_table()?;
+        let state = self.state.clone();
+        metatable.set(
+            "__index",
+            self.lua
+                .create_function(move |lua, (_, name): (Table, String)| {
+                    let state = state.clone();
+                    lua.create_function(move |_, _: Variadic<LuaValue>| {
+                        state.turtle_calls.borrow_mut().push(name.clone());
+                        Ok(true)
+                    })
+                })?,
+        )?;
+        turtle.set_metatable(Some(metatable));
+        self.lua.globals().set("turtle", turtle)
+    }
+
+    /// Just enough of `fs` for our libraries, on top of `VmState::files`.
+    fn install_fs(&self) -> mlua::Result<()> {
+        let fs = self.lua.create_table()?;
+        fs.set(
+            "combine",
+            self.lua
+                .create_function(|_, parts: Variadic<String>| Ok(normalize(&parts.join("/"))))?,
+        )?;
+
+        let state = self.state.clone();
+        fs.set(
+            "exists",
+            self.lua.create_function(move |_, path: String| {
+                let path = normalize(&path);
+                Ok(path.is_empty() || state.files.borrow().contains_key(&path))
+            })?,
+        )?;
+
+        let state = self.state.clone();
+        fs.set(
+            "isDir",
+            self.lua.create_function(move |_, path: String| {
+                let path = normalize(&path);
+                Ok(path.is_empty() || matches!(state.files.borrow().get(&path), Some(None)))
+            })?,
+        )?;
+
+        let state = self.state.clone();
+        fs.set(
+            "list",
+            self.lua.create_function(move |_, path: String| {
+                let path = normalize(&path);
+                let files = state.files.borrow();
+                if !path.is_empty() && !matches!(files.get(&path), Some(None)) {
+                    return Err(runtime_error(&format!("/{path}: Not a directory")));
+                }
+                Ok(files
+                    .keys()
+                    .filter_map(|name| match name.rsplit_once('/') {
+                        Some((parent, name)) if parent == path => Some(name.to_string()),
+                        None if path.is_empty() => Some(name.clone()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>())
+            })?,
+        )?;
+
+        let state = self.state.clone();
+        fs.set(
+            "makeDir",
+            self.lua.create_function(move |_, path: String| {
+                let path = normalize(&path);
+                let mut files = state.files.borrow_mut();
+                for folder in parents(&path).into_iter().chain([path]) {
+                    files.insert(folder, None);
+                }
+                Ok(())
+            })?,
+        )?;
+
+        let state = self.state.clone();
+        fs.set(
+            "delete",
+            self.lua.create_function(move |_, path: String| {
+                let path = normalize(&path);
+                let inside = format!("{path}/");
+                state
+                    .files
+                    .borrow_mut()
+                    .retain(|name, _| *name != path && !name.starts_with(&inside));
+                Ok(())
+            })?,
+        )?;
+
+        for (name, keep) in [("move", false), ("copy", true)] {
+            let state = self.state.clone();
+            fs.set(
+                name,
+                self.lua
+                    .create_function(move |_, (from, to): (String, String)| {
+                        let (from, to) = (normalize(&from), normalize(&to));
+                        let mut files = state.files.borrow_mut();
+                        if !files.contains_key(&from) {
+                            return Err(runtime_error(&format!("/{from}: No such file")));
+                        }
+                        if files.contains_key(&to) {
+                            return Err(runtime_error(&format!("/{to}: File exists")));
+                        }
+                        let inside = format!("{from}/");
+                        let moving: Vec<_> = files
+                            .iter()
+                            .filter(|(name, _)| **name == from || name.starts_with(&inside))
+                            .map(|(name, contents)| (name.clone(), contents.clone()))
+                            .collect();
+                        for folder in parents(&to) {
+                            files.insert(folder, None);
+                        }
+                        for (name, contents) in moving {
+                            if !keep {
+                                files.remove(&name);
+                            }
+                            files.insert(format!("{to}{}", &name[from.len()..]), contents);
+                        }
+                        Ok(())
+                    })?,
+            )?;
+        }
+
+        let state = self.state.clone();
+        fs.set(
+            "open",
+            self.lua
+                .create_function(move |lua, (path, mode): (String, String)| {
+                    let path = normalize(&path);
+                    let handle = lua.create_table()?;
+                    handle.set("close", lua.create_function(|_, ()| Ok(()))?)?;
+                    if mode.starts_with('r') {
+                        let Some(Some(contents)) = state.files.borrow().get(&path).cloned() else {
+                            return Ok((LuaValue::Nil, Some(format!("/{path}: No such file"))));
+                        };
+                        handle.set(
+                            "readAll",
+                            lua.create_function(move |_, ()| Ok(contents.clone()))?,
+                        )?;
+                        return Ok((LuaValue::Table(handle), None));
+                    }
+
+                    // Writes land straight away, there's no buffering to worry about.
+                    let mut files = state.files.borrow_mut();
+                    if matches!(files.get(&path), Some(None)) {
+                        return Ok((LuaValue::Nil, Some(format!("/{path}: Is a directory"))));
+                    }
+                    for folder in parents(&path) {
+                        files.insert(folder, None);
+                    }
+                    let existing = files.entry(path.clone()).or_insert(Some(String::new()));
+                    if !mode.starts_with('a') {
+                        *existing = Some(String::new());
+                    }
+                    for (name, newline) in [("write", ""), ("writeLine", "\n")] {
+                        let state = state.clone();
+                        let path = path.clone();
+                        handle.set(
+                            name,
+                            lua.create_function(move |_, text: LuaValue| {
+                                let text = match text {
+                                    LuaValue::String(text) => text.to_str()?.to_string(),
+                                    LuaValue::Integer(number) => number.to_string(),
+                                    LuaValue::Number(number) => number.to_string(),
+                                    other => {
+                                        return Err(runtime_error(&format!(
+                                            "Can't write a {}",
+                                            other.type_name()
+                                        )));
+                                    }
+                                };
+                                if let Some(Some(contents)) =
+                                    state.files.borrow_mut().get_mut(&path)
+                                {
+                                    contents.push_str(&text);
+                                    contents.push_str(newline);
+                                }
+                                Ok(())
+                            })?,
+                        )?;
+                    }
+                    Ok((LuaValue::Table(handle), None))
+                })?,
+        )?;
+
+        self.lua.globals().set("fs", fs)
+    }
+
+    fn install_shell(&self) -> mlua::Result<()> {
+        let shell = self.lua.create_table()?;
+        let state = self.state.clone();
+        shell.set(
+            "getRunningProgram",
+            self.lua
+                .create_function(move |_, ()| Ok(state.running_program.borrow().clone()))?,
+        )?;
+        self.lua.globals().set("shell", shell)
+    }
+}
+
+// ==
+// Files
+// ==
+
+/// Paths like CC:Tweaked sees them, no leading slash and no empty parts.
+fn normalize(path: &str) -> String {
+    path.split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Every folder a path is inside of, outermost first.
+fn parents(path: &str) -> Vec<String> {
+    path.match_indices('/')
+        .map(|(index, _)| path[..index].to_string())
+        .collect()
+}
+
+// ==
+// Json conversion
+// ==
+
+fn runtime_error(message: &str) -> mlua::Error {
+    mlua::Error::RuntimeError(message.to_string())
+}
+
+/// Turn a lua value into json, like `textutils.serializeJSON`.
+fn lua_to_json(
+    value: &LuaValue,
+    null: *const c_void,
+    seen: &mut HashSet<*const c_void>,
+) -> mlua::Result<Value> {
+    let table = match value {
+        LuaValue::Nil => return Ok(Value::Null),
+        LuaValue::Boolean(boolean) => return Ok(Value::Bool(*boolean)),
+        LuaValue::Integer(integer) => return Ok(Value::from(*integer)),
+        LuaValue::Number(number) => {
+            if number.fract() == 0.0 && number.abs() < 2f64.powi(53) {
+                return Ok(Value::from(*number as i64));
+            }
+            // CC:Tweaked writes these out as `inf` and `nan`, which isn't json anyways.
+            return Ok(Number::from_f64(*number).map_or(Value::Null, Value::Number));
+        }
+        LuaValue::String(string) => return Ok(Value::String(string.to_str()?.to_string())),
+        LuaValue::Table(table) => table,
+        other => {
+            return Err(runtime_error(&format!(
+                "Cannot serialize type {}",
+                other.type_name()
+            )));
+        }
+    };
+
+    let pointer = table.to_pointer();
+    if pointer == null {
+        return Ok(Value::Null);
+    }
+    if !seen.insert(pointer) {
+        return Err(runtime_error(
+            "Cannot serialize table with recursive entries",
+        ));
+    }
+
+    let entries = table
+        .clone()
+        .pairs::<LuaValue, LuaValue>()
+        .collect::<mlua::Result<Vec<_>>>()?;
+    let length = table.raw_len();
+    let json = if length > 0 && entries.len() == length {
+        let mut array = Vec::with_capacity(length);
+        for index in 1..=length {
+            array.push(lua_to_json(&table.raw_get(index)?, null, seen)?);
+        }
+        Value::Array(array)
+    } else {
+        let mut object = Map::new();
+        for (key, value) in entries {
+            let LuaValue::String(key) = key else {
+                return Err(runtime_error("Cannot serialize table with non-string keys"));
+            };
+            object.insert(key.to_str()?.to_string(), lua_to_json(&value, null, seen)?);
+        }
+        Value::Object(object)
+    };
+    seen.remove(&pointer);
+    Ok(json)
+}
+
+/// Turn json into a lua value, like `textutils.unserializeJSON`. Nulls become `nil`, unless we're
+/// given a `json_null` to use instead.
+fn json_to_lua<'lua>(
+    lua: &'lua Lua,
+    value: &Value,
+    null: Option<&Table<'lua>>,
+) -> mlua::Result<LuaValue<'lua>> {
+    Ok(match value {
+        Value::Null => null.map_or(LuaValue::Nil, |null| LuaValue::Table(null.clone())),
+        Value::Bool(boolean) => LuaValue::Boolean(*boolean),
+        Value::Number(number) => LuaValue::Number(number.as_f64().unwrap_or_default()),
+        Value::String(string) => LuaValue::String(lua.create_string(string)?),
+        Value::Array(array) => {
+            let table = lua.create_table()?;
+            for (index, value) in array.iter().enumerate() {
+                table.raw_set(index + 1, json_to_lua(lua, value, null)?)?;
+            }
+            LuaValue::Table(table)
+        }
+        Value::Object(object) => {
+            let table = lua.create_table()?;
+            for (key, value) in object {
+                table.raw_set(key.as_str(), json_to_lua(lua, value, null)?)?;
+            }
+            LuaValue::Table(table)
+        }
+    })
+}
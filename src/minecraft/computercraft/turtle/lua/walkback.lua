@@ -177,9 +177,25 @@ function walkback.inspectDown() end
 -- ============
 -- these should update our internal state.
 
-function walkback.dig() end
-function walkback.digUp() end
-function walkback.digDown() end
+-- These are the same as the normal `turtle` calls for now, see walkback.md.
+
+--- @param side string? Which tool to dig with.
+--- @return boolean dug, string|nil reason
+function walkback.dig(side)
+    return turtle.dig(side)
+end
+
+--- @param side string? Which tool to dig with.
+--- @return boolean dug, string|nil reason
+function walkback.digUp(side)
+    return turtle.digUp(side)
+end
+
+--- @param side string? Which tool to dig with.
+--- @return boolean dug, string|nil reason
+function walkback.digDown(side)
+    return turtle.digDown(side)
+end
 
 -- ============
 -- Placing
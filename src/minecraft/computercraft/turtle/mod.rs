@@ -1,3 +1,8 @@
+pub mod fuel;
 pub mod implementations;
 pub mod lua;
+pub mod replication;
+pub mod reservations;
+pub mod sim;
+pub mod tasks;
 pub mod turtle_type;
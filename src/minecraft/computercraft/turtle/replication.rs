@@ -0,0 +1,560 @@
+// Turtles making more turtles.
+// See `turtle.md`. A crafty turtle gathers, smelts, and crafts a new turtle, a disk drive, and a
+// floppy, then places the new turtle next to the drive. The floppy's `startup.lua` copies itself
+// onto the newborn, which then phones home so we can tie it back to its parent.
+
+use std::collections::HashMap;
+
+use crate::minecraft::{
+    computercraft::turtle::reservations::TurtleId,
+    types::MinecraftPosition,
+    vanilla::{
+        fuel::{TICKS_PER_SECOND, fuel_burn_ticks},
+        item_type::MinecraftItem,
+        recipe::{CraftItem, RecipeDatabase},
+        resolver::{ItemLedger, Resolution, ResolveStep, StepKind, resolve},
+    },
+};
+
+/// Everything that goes into a new turtle, in the order we make it.
+pub const REPLICATION_ITEMS: [&str; 3] = ["turtle_normal", "disk_drive", "disk"];
+
+// ==
+// Errors
+// ==
+
+/// Why a replication can't be planned or carried out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicationError {
+    /// The new turtle has to be right next to the disk drive, or it won't see the floppy.
+    NotAdjacent {
+        drive: MinecraftPosition,
+        newborn: MinecraftPosition,
+    },
+    /// A recipe came back from the resolver that we can't lay out in a grid.
+    Uncraftable(MinecraftItem),
+    /// We weren't waiting on a newborn to phone home.
+    NotWaiting,
+    /// This turtle already has a parent.
+    AlreadyRegistered { child: TurtleId, parent: TurtleId },
+}
+
+// ==
+// Planning
+// ==
+
+/// Where the new turtle goes. Both positions are in world space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplicationSite {
+    /// Where the disk drive gets placed.
+    pub drive: MinecraftPosition,
+    /// Where the new turtle gets placed.
+    pub newborn: MinecraftPosition,
+}
+
+impl ReplicationSite {
+    /// Make a site, making sure the turtle will actually be touching the drive.
+    pub fn new(
+        drive: MinecraftPosition,
+        newborn: MinecraftPosition,
+    ) -> Result<Self, ReplicationError> {
+        if drive.manhattan_distance(&newborn) != 1 {
+            return Err(ReplicationError::NotAdjacent { drive, newborn });
+        }
+        Ok(Self { drive, newborn })
+    }
+}
+
+/// A single thing the parent turtle has to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicationStep {
+    /// Go out and get some items.
+    Gather { item: MinecraftItem, count: u32 },
+    /// Put items through a furnace.
+    Smelt {
+        input: MinecraftItem,
+        output: MinecraftItem,
+        count: u32,
+    },
+    /// Craft something, with the grid the craft task expects.
+    Craft {
+        item: MinecraftItem,
+        crafts: u32,
+        grid: Box<[Option<CraftItem>; 9]>,
+    },
+    /// Put the disk drive down.
+    PlaceDiskDrive(MinecraftPosition),
+    /// Put the floppy in the drive.
+    InsertFloppy(MinecraftPosition),
+    /// Put the new turtle down next to the drive.
+    PlaceTurtle(MinecraftPosition),
+    /// Right click the new turtle to boot it.
+    TurnOn(MinecraftPosition),
+    /// Wait for the newborn to copy the startup file and say hello.
+    AwaitPhoneHome,
+}
+
+/// Every step to go from whatever we have on hand to a running turtle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicationPlan {
+    pub site: ReplicationSite,
+    pub steps: Vec<ReplicationStep>,
+    /// Items we need to go gather, including fuel for the furnaces.
+    pub raw_materials: HashMap<MinecraftItem, u32>,
+    /// How long the furnaces need to burn for, in seconds.
+    pub fuel_seconds: u32,
+}
+
+impl ReplicationPlan {
+    /// Work out how to make a new turtle.
+    ///
+    /// The parent is assumed to already be a crafty turtle. Disk drives and floppies get used from
+    /// the ledger if we have them, so a drive that gets picked back up is only ever made once.
+    pub fn new(
+        site: ReplicationSite,
+        ledger: &ItemLedger,
+        fuel: MinecraftItem,
+        recipes: &RecipeDatabase,
+    ) -> Result<Self, ReplicationError> {
+        let mut ledger = ledger.clone();
+        let mut resolutions: Vec<Resolution> = vec![];
+        for name in REPLICATION_ITEMS {
+            let item = MinecraftItem::from_string(name).expect("Replication items should exist");
+            let resolution = resolve(item, 1, &ledger, recipes);
+            ledger = resolution.remaining.clone();
+            resolutions.push(resolution);
+        }
+
+        let mut steps = vec![];
+        let mut raw_materials: HashMap<MinecraftItem, u32> = HashMap::new();
+        let mut fuel_seconds = 0;
+        for resolution in &resolutions {
+            flatten(&resolution.root, &mut steps)?;
+            for (item, count) in &resolution.raw_materials {
+                *raw_materials.entry(*item).or_default() += count;
+            }
+            fuel_seconds += resolution.fuel_seconds;
+        }
+
+        // Furnaces don't run on nothing. Gathering the fuel goes first, so it's on hand by the
+        // time we get to smelting.
+        let fuel_items = fuel_burn_ticks(&fuel)
+            .map(|ticks| (fuel_seconds * TICKS_PER_SECOND).div_ceil(ticks))
+            .unwrap_or(0)
+            .saturating_sub(ledger.count(&fuel));
+        if fuel_items > 0 {
+            *raw_materials.entry(fuel).or_default() += fuel_items;
+            steps.insert(
+                0,
+                ReplicationStep::Gather {
+                    item: fuel,
+                    count: fuel_items,
+                },
+            );
+        }
+
+        steps.extend([
+            ReplicationStep::PlaceDiskDrive(site.drive),
+            ReplicationStep::InsertFloppy(site.drive),
+            ReplicationStep::PlaceTurtle(site.newborn),
+            ReplicationStep::TurnOn(site.newborn),
+            ReplicationStep::AwaitPhoneHome,
+        ]);
+
+        Ok(Self {
+            site,
+            steps,
+            raw_materials,
+            fuel_seconds,
+        })
+    }
+
+    /// Do we already have everything, IE nothing needs gathering?
+    pub fn has_materials(&self) -> bool {
+        self.raw_materials.is_empty()
+    }
+}
+
+/// Turn a resolution tree into steps, inputs before the things made from them.
+fn flatten(step: &ResolveStep, out: &mut Vec<ReplicationStep>) -> Result<(), ReplicationError> {
+    for child in &step.children {
+        flatten(child, out)?;
+    }
+    let count = step.count - step.from_stock;
+    match &step.kind {
+        StepKind::InStock => {}
+        StepKind::Gather => out.push(ReplicationStep::Gather {
+            item: step.item,
+            count,
+        }),
+        StepKind::Smelt { input } => out.push(ReplicationStep::Smelt {
+            input: *input,
+            output: step.item,
+            count,
+        }),
+        StepKind::Craft { recipe, crafts } => {
+            // The resolver already picked which items fill each ingredient, IE which planks.
+            let grid = recipe
+                .craft_grid(*crafts, |ingredient| {
+                    step.children
+                        .iter()
+                        .map(|child| child.item)
+                        .find(|item| ingredient.matches(item))
+                })
+                .ok_or(ReplicationError::Uncraftable(step.item))?;
+            out.push(ReplicationStep::Craft {
+                item: step.item,
+                crafts: *crafts,
+                grid: Box::new(grid),
+            });
+        }
+    }
+    Ok(())
+}
+
+// ==
+// Supervisor
+// ==
+
+/// Who made who.
+#[derive(Debug, Default)]
+pub struct ReplicationRegistry {
+    /// Child -> parent.
+    parents: HashMap<TurtleId, TurtleId>,
+}
+
+impl ReplicationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `parent` made `child`.
+    pub fn register(&mut self, child: TurtleId, parent: TurtleId) -> Result<(), ReplicationError> {
+        if let Some(existing) = self.parents.get(&child) {
+            return Err(ReplicationError::AlreadyRegistered {
+                child,
+                parent: *existing,
+            });
+        }
+        self.parents.insert(child, parent);
+        Ok(())
+    }
+
+    /// Who made this turtle, if it was made by a turtle at all.
+    pub fn parent_of(&self, child: TurtleId) -> Option<TurtleId> {
+        self.parents.get(&child).copied()
+    }
+
+    /// Every turtle this one has made.
+    pub fn children_of(&self, parent: TurtleId) -> Vec<TurtleId> {
+        let mut children: Vec<TurtleId> = self
+            .parents
+            .iter()
+            .filter(|(_, p)| **p == parent)
+            .map(|(child, _)| *child)
+            .collect();
+        children.sort_unstable();
+        children
+    }
+}
+
+/// Walks a parent turtle through a replication plan, one step at a time.
+///
+/// Whatever is handing out tasks asks for the current step, and tells us when it's done.
+#[derive(Debug)]
+pub struct ReplicationSupervisor {
+    parent: TurtleId,
+    plan: ReplicationPlan,
+    /// Index of the step we're on.
+    next: usize,
+    /// The newborn, once it has phoned home.
+    child: Option<TurtleId>,
+}
+
+impl ReplicationSupervisor {
+    pub fn new(parent: TurtleId, plan: ReplicationPlan) -> Self {
+        Self {
+            parent,
+            plan,
+            next: 0,
+            child: None,
+        }
+    }
+
+    /// The turtle doing the replicating.
+    pub fn parent(&self) -> TurtleId {
+        self.parent
+    }
+
+    pub fn plan(&self) -> &ReplicationPlan {
+        &self.plan
+    }
+
+    /// What the parent should be doing right now. `None` once we're done.
+    pub fn current(&self) -> Option<&ReplicationStep> {
+        self.plan.steps.get(self.next)
+    }
+
+    /// Mark the current step as done and move on.
+    ///
+    /// Phoning home can't be skipped this way, use `newborn_phoned_home`.
+    pub fn step_done(&mut self) {
+        if matches!(self.current(), Some(ReplicationStep::AwaitPhoneHome) | None) {
+            return;
+        }
+        self.next += 1;
+    }
+
+    /// The newborn said hello. Registers it with its parent and finishes the replication.
+    pub fn newborn_phoned_home(
+        &mut self,
+        child: TurtleId,
+        registry: &mut ReplicationRegistry,
+    ) -> Result<(), ReplicationError> {
+        if self.current() != Some(&ReplicationStep::AwaitPhoneHome) {
+            return Err(ReplicationError::NotWaiting);
+        }
+        registry.register(child, self.parent)?;
+        self.child = Some(child);
+        self.next += 1;
+        Ok(())
+    }
+
+    /// The new turtle, if it's been born yet.
+    pub fn child(&self) -> Option<TurtleId> {
+        self.child
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current().is_none()
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+use crate::minecraft::vanilla::recipe::get_recipes;
+
+#[cfg(test)]
+fn item(name: &str) -> MinecraftItem {
+    MinecraftItem::from_string(name).unwrap()
+}
+
+#[cfg(test)]
+fn test_site() -> ReplicationSite {
+    let drive = MinecraftPosition {
+        x: 0,
+        y: 0,
+        z: 0,
+        facing: None,
+    };
+    let newborn = MinecraftPosition { x: 1, ..drive };
+    ReplicationSite::new(drive, newborn).unwrap()
+}
+
+#[test]
+/// The turtle and drive can't be placed apart.
+fn replication_site_adjacent() {
+    let drive = MinecraftPosition {
+        x: 0,
+        y: 0,
+        z: 0,
+        facing: None,
+    };
+    let far = MinecraftPosition { x: 2, ..drive };
+    assert!(ReplicationSite::new(drive, far).is_err());
+    assert!(ReplicationSite::new(drive, drive).is_err());
+}
+
+#[test]
+/// From nothing, we have to gather everything, and things get made after their inputs.
+fn replication_from_nothing() {
+    let plan =
+        ReplicationPlan::new(test_site(), &ItemLedger::new(), item("coal"), get_recipes()).unwrap();
+    assert!(!plan.has_materials());
+
+    // 7 for the turtle, 7 for the drive.
+    assert_eq!(plan.raw_materials.get(&item("cobblestone")), Some(&14));
+    // 1 for the computer, 2 for the drive, 1 for the floppy.
+    assert_eq!(plan.raw_materials.get(&item("redstone")), Some(&4));
+    assert!(plan.raw_materials.contains_key(&item("coal")));
+
+    let position = |wanted: &ReplicationStep| plan.steps.iter().position(|step| step == wanted);
+    let made = |name: &str| {
+        plan.steps.iter().position(
+            |step| matches!(step, ReplicationStep::Craft { item: made, .. } if *made == item(name)),
+        )
+    };
+    assert!(made("computer_normal").unwrap() < made("turtle_normal").unwrap());
+    assert!(
+        made("disk").unwrap()
+            < position(&ReplicationStep::InsertFloppy(test_site().drive)).unwrap()
+    );
+    assert_eq!(plan.steps.last(), Some(&ReplicationStep::AwaitPhoneHome));
+}
+
+#[test]
+/// With everything on hand we go straight to placing things, and the newborn gets registered.
+fn replication_supervisor_registers_child() {
+    let mut ledger = ItemLedger::new();
+    for name in REPLICATION_ITEMS {
+        ledger.add(item(name), 1);
+    }
+    let plan = ReplicationPlan::new(test_site(), &ledger, item("coal"), get_recipes()).unwrap();
+    assert!(plan.has_materials());
+    assert_eq!(plan.steps.len(), 5);
+
+    let mut registry = ReplicationRegistry::new();
+    let mut supervisor = ReplicationSupervisor::new(3, plan);
+    assert_eq!(
+        supervisor.newborn_phoned_home(9, &mut registry),
+        Err(ReplicationError::NotWaiting)
+    );
+    while supervisor.current() != Some(&ReplicationStep::AwaitPhoneHome) {
+        supervisor.step_done();
+    }
+    // Can't skip the phone home.
+    supervisor.step_done();
+    assert!(!supervisor.is_finished());
+
+    supervisor.newborn_phoned_home(9, &mut registry).unwrap();
+    assert!(supervisor.is_finished());
+    assert_eq!(supervisor.child(), Some(9));
+    assert_eq!(registry.parent_of(9), Some(3));
+    assert_eq!(registry.children_of(3), vec![9]);
+    assert!(registry.register(9, 4).is_err());
+}
+
+#[tokio::test]
+/// Go through replication in game from the drive onwards, and check the supervisor registers the
+/// newborn once it bootstraps off the floppy and phones home. The harness stands in for the parent
+/// turtle, and for the control server handing out libraries.
+async fn replication_placement_in_game() {
+    use crate::{
+        minecraft::computercraft::bootstrap::{
+            LibraryBundle, bootstrap_request, bootstrap_script, write_bootstrap_disk,
+        },
+        tests::prelude::*,
+    };
+
+    let area = TestArea {
+        size_x: 3,
+        size_y: 3,
+        size_z: 3,
+    };
+    let mut test = MinecraftTestHandle::new(area).await;
+    let drive = MinecraftPosition {
+        x: 1,
+        y: 1,
+        z: 1,
+        facing: None,
+    };
+    let newborn = MinecraftPosition { x: 2, ..drive };
+    let site = ReplicationSite::new(drive, newborn).unwrap();
+
+    // Already have everything, so there's nothing to gather.
+    let mut ledger = ItemLedger::new();
+    for name in REPLICATION_ITEMS {
+        ledger.add(item(name), 1);
+    }
+    let plan = ReplicationPlan::new(site, &ledger, item("coal"), get_recipes()).unwrap();
+    let mut registry = ReplicationRegistry::new();
+    // Nobody actually has this ID, the harness is the parent.
+    let mut supervisor = ReplicationSupervisor::new(u16::MAX, plan);
+
+    let bundle = LibraryBundle::turtle_libraries(test.websocket_url());
+    let script = bootstrap_script(test.websocket_url(), bundle.version());
+
+    let mut floppy_drive = None;
+    let mut computer = None;
+    let mut socket = None;
+    let mut passed = true;
+    while let Some(step) = supervisor.current().cloned() {
+        match step {
+            ReplicationStep::PlaceDiskDrive(position) => {
+                let block = MinecraftBlock::from_string("disk_drive").unwrap();
+                passed &= test
+                    .command(TestCommand::SetBlock(position, block.into()))
+                    .await
+                    .success();
+            }
+            // Building the turtle boots it once to get its ID, which would run the floppy early.
+            // So the floppy goes in right before the turtle gets turned on for real.
+            ReplicationStep::InsertFloppy(position) => floppy_drive = Some(position),
+            ReplicationStep::PlaceTurtle(position) => {
+                // No startup of its own, or it would run that instead of the floppy.
+                #[allow(deprecated)]
+                let setup = ComputerSetup::new(ComputerKind::Turtle(None), ComputerConfigs::Empty);
+                let built = test.build_computer(&position, setup).await;
+                socket = Some(TestWebsocket::new(built.id()).await);
+                computer = Some(built);
+            }
+            ReplicationStep::TurnOn(_) => {
+                let computer = computer.as_ref().expect("Turtle should be placed by now");
+                // The turtle's ID doubles as the disk ID, so tests don't share floppies.
+                let disk_id = computer.id();
+                write_bootstrap_disk(test.server_folder(), disk_id, &script)
+                    .expect("Should be able to write the floppy");
+                let drive = floppy_drive.expect("The floppy step comes before turning on");
+                passed &= test
+                    .command(TestCommand::InsertFloppy(drive, disk_id))
+                    .await
+                    .success();
+                computer.turn_on(&mut test).await;
+            }
+            ReplicationStep::AwaitPhoneHome => {
+                let id = computer
+                    .as_ref()
+                    .expect("Turtle should be placed by now")
+                    .id();
+                let mut bootstrapping = socket.take().expect("Turtle should be placed by now");
+                let request = bootstrapping
+                    .receiver
+                    .recv()
+                    .await
+                    .expect("Channel should be open.");
+                passed &= bootstrap_request(&request).as_deref() == Some(bundle.version());
+                bootstrapping
+                    .sender
+                    .send(bundle.to_reply())
+                    .await
+                    .expect("Channel should be open.");
+                // The bootstrap script hangs up, and `networking.lua` connects again.
+                drop(bootstrapping);
+                let mut phoning = TestWebsocket::new(id).await;
+                let hello = phoning
+                    .receiver
+                    .recv()
+                    .await
+                    .expect("Channel should be open.");
+                passed &= hello.contains("hello");
+                passed &= supervisor.newborn_phoned_home(id, &mut registry).is_ok();
+                passed &= registry.parent_of(id) == Some(u16::MAX);
+                // Phoning home moves the supervisor along by itself.
+                if !passed {
+                    break;
+                }
+                continue;
+            }
+            ReplicationStep::Gather { .. }
+            | ReplicationStep::Smelt { .. }
+            | ReplicationStep::Craft { .. } => {
+                panic!("Everything should already be in stock!")
+            }
+        }
+        supervisor.step_done();
+    }
+
+    if let Some(computer) = &computer {
+        let disk = test
+            .server_folder()
+            .join("world/computercraft/disk")
+            .join(computer.id().to_string());
+        let _ = std::fs::remove_dir_all(disk);
+    }
+    passed &= supervisor.is_finished();
+    test.stop(passed).await;
+    assert!(passed);
+}
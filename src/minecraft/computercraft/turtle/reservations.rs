@@ -0,0 +1,487 @@
+// Keeping turtles out of each other's way.
+// Every turtle that is about to move somewhere reserves the blocks it will be in, and when. Anyone
+// else that wants to move through the same spot at the same time has to wait or go around.
+// See `mining.md` and `build.md` for why we care.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::minecraft::types::MinecraftPosition;
+
+/// The computer ID of a turtle.
+pub type TurtleId = u16;
+
+/// Reservations are made in discrete time slots. A slot is roughly how long it takes a turtle to
+/// make a single move, so walking a path of `n` positions takes `n` slots.
+pub type TimeSlot = u64;
+
+/// How many ticks a single turtle movement takes. Used to convert between slots and game time.
+pub const TICKS_PER_SLOT: u64 = 8;
+
+/// A reservation on an entire box of space for a while, such as a mining area, or the area a build
+/// is happening in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CorridorReservation {
+    /// Who owns this space.
+    pub owner: TurtleId,
+    /// One corner of the box.
+    pub pos1: MinecraftPosition,
+    /// The opposite corner of the box.
+    pub pos2: MinecraftPosition,
+    /// The first slot that this reservation is active in.
+    pub from: TimeSlot,
+    /// The slot that this reservation ends on, exclusive. `None` means forever, or until released.
+    pub until: Option<TimeSlot>,
+}
+
+impl CorridorReservation {
+    /// Does this corridor contain a position at a given time?
+    pub fn contains(&self, position: &MinecraftPosition, slot: TimeSlot) -> bool {
+        let in_time = slot >= self.from && self.until.is_none_or(|until| slot < until);
+        let in_axis = |value: i64, a: i64, b: i64| value >= a.min(b) && value <= a.max(b);
+        in_time
+            && in_axis(position.x, self.pos1.x, self.pos2.x)
+            && in_axis(position.y, self.pos1.y, self.pos2.y)
+            && in_axis(position.z, self.pos1.z, self.pos2.z)
+    }
+}
+
+/// Why a reservation could not be made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReservationConflict {
+    /// Someone else is already going to be in this spot at this time.
+    Occupied {
+        position: MinecraftPosition,
+        slot: TimeSlot,
+        by: TurtleId,
+    },
+    /// Someone else is going to be walking through us in the opposite direction at this time. We
+    /// would swap places, which is impossible.
+    HeadOn {
+        position: MinecraftPosition,
+        slot: TimeSlot,
+        by: TurtleId,
+    },
+    /// This spot is inside of someone else's corridor.
+    Corridor {
+        position: MinecraftPosition,
+        slot: TimeSlot,
+        by: TurtleId,
+    },
+}
+
+/// The reservation table. The scheduler should check paths against this before handing out a
+/// `move_to` or any other task that moves a turtle.
+#[derive(Debug, Default)]
+pub struct ReservationTable {
+    /// Every reserved voxel, and who has it at what time.
+    voxels: HashMap<(i64, i64, i64), BTreeMap<TimeSlot, TurtleId>>,
+    /// Every reserved corridor.
+    corridors: Vec<CorridorReservation>,
+}
+
+impl ReservationTable {
+    /// Make an empty reservation table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Who, if anyone, has this position at this time.
+    pub fn owner_at(&self, position: &MinecraftPosition, slot: TimeSlot) -> Option<TurtleId> {
+        if let Some(owner) = self
+            .voxels
+            .get(&position.xyz())
+            .and_then(|slots| slots.get(&slot))
+        {
+            return Some(*owner);
+        }
+        self.corridors
+            .iter()
+            .find(|corridor| corridor.contains(position, slot))
+            .map(|corridor| corridor.owner)
+    }
+
+    /// Check if a turtle is allowed to be at a position at a time.
+    pub fn is_free(&self, position: &MinecraftPosition, slot: TimeSlot, turtle: TurtleId) -> bool {
+        self.owner_at(position, slot)
+            .is_none_or(|owner| owner == turtle)
+    }
+
+    /// Check if a path can be walked starting at a slot, without actually reserving it.
+    ///
+    /// The turtle is at `path[0]` at `start`, `path[1]` at `start + 1`, and so on.
+    pub fn check_path(
+        &self,
+        turtle: TurtleId,
+        path: &[MinecraftPosition],
+        start: TimeSlot,
+    ) -> Result<(), ReservationConflict> {
+        for (offset, position) in path.iter().enumerate() {
+            let slot = start + offset as u64;
+
+            // Corridors first, they're the bigger hammer.
+            if let Some(corridor) = self
+                .corridors
+                .iter()
+                .find(|corridor| corridor.owner != turtle && corridor.contains(position, slot))
+            {
+                return Err(ReservationConflict::Corridor {
+                    position: *position,
+                    slot,
+                    by: corridor.owner,
+                });
+            }
+
+            if let Some(by) = self.voxel_owner(position, slot)
+                && by != turtle
+            {
+                return Err(ReservationConflict::Occupied {
+                    position: *position,
+                    slot,
+                    by,
+                });
+            }
+
+            // Swapping places with someone is also a collision, even though at no single slot are
+            // we in the same spot. IE we go A -> B while they go B -> A.
+            if offset > 0 {
+                let previous = &path[offset - 1];
+                if let (Some(there_now), Some(here_before)) = (
+                    self.voxel_owner(previous, slot),
+                    self.voxel_owner(position, slot - 1),
+                ) && there_now == here_before
+                    && there_now != turtle
+                {
+                    return Err(ReservationConflict::HeadOn {
+                        position: *position,
+                        slot,
+                        by: there_now,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reserve a path for a turtle. See `check_path()`. Nothing is reserved if this fails.
+    pub fn reserve_path(
+        &mut self,
+        turtle: TurtleId,
+        path: &[MinecraftPosition],
+        start: TimeSlot,
+    ) -> Result<(), ReservationConflict> {
+        self.check_path(turtle, path, start)?;
+        for (offset, position) in path.iter().enumerate() {
+            self.voxels
+                .entry(position.xyz())
+                .or_default()
+                .insert(start + offset as u64, turtle);
+        }
+        Ok(())
+    }
+
+    /// Reserve a box of space for a turtle. Fails if any voxel reservation from another turtle is
+    /// inside of it during that time, or if it overlaps another corridor.
+    pub fn reserve_corridor(
+        &mut self,
+        corridor: CorridorReservation,
+    ) -> Result<(), ReservationConflict> {
+        for (xyz, slots) in &self.voxels {
+            let position = MinecraftPosition {
+                x: xyz.0,
+                y: xyz.1,
+                z: xyz.2,
+                facing: None,
+            };
+            for (slot, owner) in slots {
+                if *owner != corridor.owner && corridor.contains(&position, *slot) {
+                    return Err(ReservationConflict::Occupied {
+                        position,
+                        slot: *slot,
+                        by: *owner,
+                    });
+                }
+            }
+        }
+        if let Some(other) = self
+            .corridors
+            .iter()
+            .find(|other| other.owner != corridor.owner && corridors_overlap(other, &corridor))
+        {
+            return Err(ReservationConflict::Corridor {
+                position: corridor.pos1,
+                slot: corridor.from,
+                by: other.owner,
+            });
+        }
+        self.corridors.push(corridor);
+        Ok(())
+    }
+
+    /// Drop every reservation a turtle holds. Used when a task finishes or is revoked.
+    pub fn release(&mut self, turtle: TurtleId) {
+        for slots in self.voxels.values_mut() {
+            slots.retain(|_, owner| *owner != turtle);
+        }
+        self.voxels.retain(|_, slots| !slots.is_empty());
+        self.corridors.retain(|corridor| corridor.owner != turtle);
+    }
+
+    /// Forget about everything that happened before a slot, since it's in the past now.
+    pub fn prune_before(&mut self, slot: TimeSlot) {
+        for slots in self.voxels.values_mut() {
+            *slots = slots.split_off(&slot);
+        }
+        self.voxels.retain(|_, slots| !slots.is_empty());
+        self.corridors
+            .retain(|corridor| corridor.until.is_none_or(|until| until > slot));
+    }
+
+    /// Find the first slot at or after `earliest` where this whole path could be walked.
+    ///
+    /// Gives up after `horizon` slots of waiting.
+    pub fn earliest_start(
+        &self,
+        turtle: TurtleId,
+        path: &[MinecraftPosition],
+        earliest: TimeSlot,
+        horizon: TimeSlot,
+    ) -> Option<TimeSlot> {
+        (earliest..=earliest + horizon).find(|start| self.check_path(turtle, path, *start).is_ok())
+    }
+
+    fn voxel_owner(&self, position: &MinecraftPosition, slot: TimeSlot) -> Option<TurtleId> {
+        self.voxels
+            .get(&position.xyz())
+            .and_then(|slots| slots.get(&slot))
+            .copied()
+    }
+}
+
+fn corridors_overlap(a: &CorridorReservation, b: &CorridorReservation) -> bool {
+    let range = |p: i64, q: i64| (p.min(q), p.max(q));
+    let axis_overlap = |a: (i64, i64), b: (i64, i64)| a.0 <= b.1 && b.0 <= a.1;
+    let time_overlap =
+        a.until.is_none_or(|until| b.from < until) && b.until.is_none_or(|until| a.from < until);
+    time_overlap
+        && axis_overlap(range(a.pos1.x, a.pos2.x), range(b.pos1.x, b.pos2.x))
+        && axis_overlap(range(a.pos1.y, a.pos2.y), range(b.pos1.y, b.pos2.y))
+        && axis_overlap(range(a.pos1.z, a.pos2.z), range(b.pos1.z, b.pos2.z))
+}
+
+// ==
+// Deadlocks
+// ==
+
+/// What to do about a deadlock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadlockResolution {
+    /// Every turtle that is stuck waiting on another in the cycle.
+    pub cycle: Vec<TurtleId>,
+    /// The turtle that should back off with `walkback.step_back()`.
+    pub back_off: TurtleId,
+    /// How many times it should step back.
+    pub steps: u32,
+}
+
+/// Keeps track of which turtles are waiting on which, so we can notice when they're waiting on
+/// each other forever.
+#[derive(Debug, Default)]
+pub struct WaitGraph {
+    /// turtle -> the turtle it is waiting on.
+    waiting_on: HashMap<TurtleId, TurtleId>,
+}
+
+impl WaitGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark that a turtle is blocked by another turtle.
+    pub fn blocked(&mut self, turtle: TurtleId, by: TurtleId) {
+        self.waiting_on.insert(turtle, by);
+    }
+
+    /// Mark that a turtle is no longer waiting on anyone.
+    pub fn unblocked(&mut self, turtle: TurtleId) {
+        self.waiting_on.remove(&turtle);
+    }
+
+    /// Look for a cycle of turtles waiting on each other.
+    ///
+    /// Since each turtle only ever waits on one other turtle, this is just following the chain
+    /// until we either run out, or end up somewhere we've already been.
+    pub fn find_cycle(&self) -> Option<Vec<TurtleId>> {
+        let mut checked: HashSet<TurtleId> = HashSet::new();
+        // Sorted so the result does not depend on hashmap ordering.
+        let mut starts: Vec<&TurtleId> = self.waiting_on.keys().collect();
+        starts.sort_unstable();
+
+        for start in starts {
+            if checked.contains(start) {
+                continue;
+            }
+            let mut chain: Vec<TurtleId> = vec![*start];
+            let mut current = *start;
+            while let Some(next) = self.waiting_on.get(&current) {
+                if let Some(index) = chain.iter().position(|turtle| turtle == next) {
+                    return Some(chain[index..].to_vec());
+                }
+                if checked.contains(next) {
+                    break;
+                }
+                chain.push(*next);
+                current = *next;
+            }
+            checked.extend(chain);
+        }
+        None
+    }
+
+    /// Find a deadlock and decide who has to back off.
+    ///
+    /// The turtle with the least walkback history (so the cheapest to back off) loses. Ties go to
+    /// the highest ID, so the result is at least consistent. `walkback_costs` should contain the
+    /// result of `walkback.cost()` for each turtle, turtles missing from it are treated as having
+    /// nowhere to back off to.
+    pub fn resolve(&self, walkback_costs: &HashMap<TurtleId, u32>) -> Option<DeadlockResolution> {
+        let cycle = self.find_cycle()?;
+        let back_off = *cycle
+            .iter()
+            .filter(|turtle| walkback_costs.get(turtle).is_some_and(|cost| *cost > 0))
+            .min_by_key(|turtle| (walkback_costs[turtle], std::cmp::Reverse(**turtle)))?;
+        // One step is enough to let a single other turtle past us, but every extra turtle in the
+        // cycle needs another step of room. Can't go back further than we've been though.
+        let steps = (cycle.len() as u32 - 1).min(walkback_costs[&back_off]);
+        Some(DeadlockResolution {
+            cycle,
+            back_off,
+            steps,
+        })
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+fn pos(x: i64, y: i64, z: i64) -> MinecraftPosition {
+    MinecraftPosition {
+        x,
+        y,
+        z,
+        facing: None,
+    }
+}
+
+#[test]
+/// Two turtles walking the same line in opposite directions should collide.
+fn reservations_collide() {
+    let mut table = ReservationTable::new();
+    let east: Vec<MinecraftPosition> = (0..5).map(|x| pos(x, 0, 0)).collect();
+    let west: Vec<MinecraftPosition> = (0..5).rev().map(|x| pos(x, 0, 0)).collect();
+
+    table.reserve_path(1, &east, 0).unwrap();
+    assert!(table.check_path(2, &west, 0).is_err());
+    // Our own reservations never block us.
+    assert!(table.check_path(1, &east, 0).is_ok());
+
+    // Once the first turtle is done, the second can go.
+    let start = table.earliest_start(2, &west, 0, 100).unwrap();
+    assert_eq!(start, 5);
+    table.reserve_path(2, &west, start).unwrap();
+
+    table.release(1);
+    assert!(table.check_path(3, &east, 0).is_ok());
+}
+
+#[test]
+/// Swapping places is not allowed.
+fn head_on_swap() {
+    let mut table = ReservationTable::new();
+    table
+        .reserve_path(1, &[pos(0, 0, 0), pos(1, 0, 0)], 0)
+        .unwrap();
+    // Walking into a spot someone is about to be in.
+    let result = table.check_path(2, &[pos(1, 0, 1), pos(1, 0, 0)], 0);
+    assert!(matches!(result, Err(ReservationConflict::Occupied { .. })));
+
+    // Starting where they end up, and going where they came from. We never share a spot in the
+    // same slot, but we would have to pass through each other.
+    let result = table.check_path(2, &[pos(1, 0, 0), pos(0, 0, 0)], 0);
+    assert!(matches!(result, Err(ReservationConflict::HeadOn { .. })));
+}
+
+#[test]
+/// Corridors keep everyone else out.
+fn corridors_block() {
+    let mut table = ReservationTable::new();
+    table
+        .reserve_corridor(CorridorReservation {
+            owner: 1,
+            pos1: pos(0, 0, 0),
+            pos2: pos(10, 10, 10),
+            from: 0,
+            until: Some(50),
+        })
+        .unwrap();
+    assert!(!table.is_free(&pos(5, 5, 5), 10, 2));
+    assert!(table.is_free(&pos(5, 5, 5), 10, 1));
+    assert!(table.is_free(&pos(5, 5, 5), 50, 2));
+
+    // Overlapping corridor
+    assert!(
+        table
+            .reserve_corridor(CorridorReservation {
+                owner: 2,
+                pos1: pos(10, 0, 0),
+                pos2: pos(20, 0, 0),
+                from: 0,
+                until: None,
+            })
+            .is_err()
+    );
+}
+
+#[test]
+/// Two turtles stuck facing each other.
+fn deadlock_detection() {
+    let mut graph = WaitGraph::new();
+    graph.blocked(1, 2);
+    graph.blocked(3, 1);
+    assert!(graph.find_cycle().is_none());
+
+    graph.blocked(2, 1);
+    let mut cycle = graph.find_cycle().unwrap();
+    cycle.sort();
+    assert_eq!(cycle, vec![1, 2]);
+
+    let costs = HashMap::from([(1, 10), (2, 3), (3, 1)]);
+    let resolution = graph.resolve(&costs).unwrap();
+    assert_eq!(resolution.back_off, 2);
+    assert_eq!(resolution.steps, 1);
+
+    graph.unblocked(2);
+    assert!(graph.find_cycle().is_none());
+}
+
+#[test]
+/// Three turtles in a loop, each waiting on the next. Whoever backs off has to make room for two.
+fn deadlock_three_turtles() {
+    let mut graph = WaitGraph::new();
+    graph.blocked(1, 2);
+    graph.blocked(2, 3);
+    graph.blocked(3, 1);
+    let mut cycle = graph.find_cycle().unwrap();
+    cycle.sort();
+    assert_eq!(cycle, vec![1, 2, 3]);
+
+    let costs = HashMap::from([(1, 10), (2, 5), (3, 8)]);
+    let resolution = graph.resolve(&costs).unwrap();
+    assert_eq!(resolution.back_off, 2);
+    assert_eq!(resolution.steps, 2);
+
+    // Only one step of history, so that's all it can do.
+    let costs = HashMap::from([(1, 10), (2, 1), (3, 8)]);
+    assert_eq!(graph.resolve(&costs).unwrap().steps, 1);
+}
@@ -0,0 +1,10 @@
+// An offline turtle simulator.
+// Booting a real server takes minutes and a JVM, so for testing logic that only cares about what
+// turtles do (not how minecraft does it) we fake the world instead. See `world.rs` for the grid and
+// `socket.rs` for how sim turtles talk to control.
+//
+// This is nowhere near a full minecraft, there's no gravity, no entities, and blocks don't have
+// states. It only needs to be good enough to give the same answers a real turtle would.
+
+pub mod socket;
+pub mod world;
@@ -0,0 +1,161 @@
+// Hooking simulated turtles up to control.
+// Each turtle gets its own websocket, exactly like `networking.lua` makes. Commands come in as
+// `{"turtle": "forward", "args": []}`, and every command gets a reply packet with either the result
+// or CC:Tweaked's error string.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures_util::{SinkExt, StreamExt};
+use once_cell::sync::Lazy;
+use rand::Rng;
+use serde_json::{Value, json};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{Error, Message, client::IntoClientRequest, http::HeaderValue},
+};
+
+use crate::minecraft::computercraft::{
+    deploy::ComputerId,
+    lua_types::table::{pack_json, unpack_json},
+    turtle::{
+        lua,
+        sim::world::{SimWorld, TurtleAction},
+    },
+};
+
+/// Where real turtles connect to, see `networking.lua`. Turtles leave the scheme off, we can't.
+pub static DEFAULT_SERVER_URL: Lazy<String> =
+    Lazy::new(|| format!("ws://{}", lua::DEFAULT_SERVER_URL));
+
+/// Build a packet the same way `networking.lua` does. The message is packed and turned into a
+/// string, then that goes in a packed packet.
+pub fn format_packet(id: ComputerId, message: Value) -> String {
+    let mut rng = rand::rng();
+    let uuid: String = (0..8)
+        .map(|_| char::from(rng.random_range(b'A'..=b'Z')))
+        .collect();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis() as u64);
+    let packet = json!({
+        "id": id,
+        "uuid": uuid,
+        "timestamp": timestamp,
+        "data": pack_json(message).to_string(),
+    });
+    pack_json(packet).to_string()
+}
+
+/// Work out the reply to a single incoming message.
+fn handle(world: &Mutex<SimWorld>, id: ComputerId, incoming: &str) -> Value {
+    let result = serde_json::from_str(incoming)
+        .map_err(|error| format!("Failed to unpack received packet! : {error}"))
+        .map(unpack_json)
+        .and_then(|command| TurtleAction::from_json(&command))
+        .and_then(|action| world.lock().expect("Sim world poisoned").run(id, action));
+    match result {
+        Ok(result) => json!({ "ok": true, "result": result }),
+        Err(error) => json!({ "ok": false, "error": error }),
+    }
+}
+
+/// Connect a turtle in the world to control, and answer commands until the socket closes.
+///
+/// The turtle has to already be in the world.
+pub async fn connect_turtle(
+    world: Arc<Mutex<SimWorld>>,
+    id: ComputerId,
+    url: &str,
+) -> Result<JoinHandle<()>, Error> {
+    let mut request = url.into_client_request()?;
+    request
+        .headers_mut()
+        .insert("Computer-ID", HeaderValue::from(id));
+    let (socket, _) = connect_async(request).await?;
+    let (mut sender, mut receiver) = socket.split();
+
+    Ok(tokio::spawn(async move {
+        while let Some(Ok(incoming)) = receiver.next().await {
+            let Message::Text(incoming) = incoming else {
+                continue;
+            };
+            let reply = handle(&world, id, &incoming);
+            // Real turtles check their health before every send, so we do too.
+            for message in [json!("health"), reply] {
+                if sender
+                    .send(Message::Text(format_packet(id, message).into()))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }))
+}
+
+// ===
+// Tests
+// ===
+
+#[tokio::test]
+/// A sim turtle answers commands over a real websocket.
+async fn sim_turtle_over_websocket() {
+    use tokio::net::TcpListener;
+
+    use crate::{
+        minecraft::{
+            computercraft::{deploy::packet_data, turtle::sim::world::SimTurtle},
+            types::MinecraftFacingDirection,
+        },
+        websocket::CCWebsocket,
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}/meshpit", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        CCWebsocket::new(stream).await
+    });
+
+    let world = Arc::new(Mutex::new(SimWorld::new()));
+    world.lock().unwrap().add_turtle(
+        7,
+        SimTurtle::new((0, 0, 0), MinecraftFacingDirection::North),
+    );
+    let _turtle = connect_turtle(world.clone(), 7, &url).await.unwrap();
+    let (control, mut incoming) = server.await.unwrap();
+
+    // Skips the health packets, same as the test server.
+    let mut reply = async |command: Value| {
+        control.send(command.to_string()).unwrap();
+        loop {
+            let packet = incoming.recv().await.unwrap();
+            let data = packet_data(&packet).unwrap();
+            if data != json!("health") {
+                return data;
+            }
+        }
+    };
+
+    assert_eq!(
+        reply(json!({ "turtle": "turnRight", "args": [] })).await,
+        json!({ "ok": true, "result": true })
+    );
+    assert_eq!(
+        reply(json!({ "turtle": "forward" })).await,
+        json!({ "ok": false, "error": "Out of fuel" })
+    );
+    assert_eq!(
+        reply(json!({ "turtle": "explode" })).await,
+        json!({ "ok": false, "error": "No such function `turtle.explode`" })
+    );
+    assert_eq!(
+        world.lock().unwrap().turtle(7).unwrap().facing,
+        MinecraftFacingDirection::East
+    );
+}
@@ -0,0 +1,789 @@
+// The simulated world itself.
+// A sparse grid of blocks, some of which are containers, and the turtles walking around in it.
+// Anything that isn't in the grid is air.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::{Value, json};
+
+use crate::minecraft::{
+    computercraft::{deploy::ComputerId, turtle::fuel::NORMAL_TURTLE_FUEL_LIMIT},
+    peripherals::inventory::{GenericInventory, GenericInventorySlot, Inventory, MAX_STACK_SIZE},
+    types::MinecraftFacingDirection,
+    vanilla::{block_type::MinecraftBlock, fuel::turtle_fuel_value, item_type::MinecraftItem},
+};
+
+/// A block position, without any facing.
+pub type Xyz = (i64, i64, i64);
+
+/// Turtles can't go below this.
+pub const MIN_Y: i64 = -64;
+
+/// Or at or above this.
+pub const MAX_Y: i64 = 320;
+
+/// How many slots a turtle has.
+pub const TURTLE_SLOTS: u16 = 16;
+
+/// Blocks that are really just nothing.
+const AIR: [&str; 3] = ["air", "cave_air", "void_air"];
+
+/// Blocks that turtles can move through. Fluids still show up when inspected.
+const PASSABLE: [&str; 5] = ["air", "cave_air", "void_air", "water", "lava"];
+
+/// Blocks that `turtle.dig()` refuses to break.
+const UNBREAKABLE: [&str; 8] = [
+    "bedrock",
+    "barrier",
+    "command_block",
+    "structure_block",
+    "jigsaw",
+    "end_portal",
+    "end_portal_frame",
+    "reinforced_deepslate",
+];
+
+/// Blocks that drop something other than themselves. Anything not in here drops itself, assuming
+/// there's an item for it.
+const DROPS: [(&str, &str); 10] = [
+    ("stone", "cobblestone"),
+    ("deepslate", "cobbled_deepslate"),
+    ("grass_block", "dirt"),
+    ("coal_ore", "coal"),
+    ("iron_ore", "raw_iron"),
+    ("copper_ore", "raw_copper"),
+    ("gold_ore", "raw_gold"),
+    ("diamond_ore", "diamond"),
+    ("redstone_ore", "redstone"),
+    ("lapis_ore", "lapis_lazuli"),
+];
+
+/// Which way a turtle is acting, relative to itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Front,
+    Up,
+    Down,
+}
+
+/// Everything a simulated turtle knows how to do. These are named after, and act like, the
+/// matching functions in CC:Tweaked's `turtle` API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurtleAction {
+    Forward,
+    Back,
+    Up,
+    Down,
+    TurnLeft,
+    TurnRight,
+    Dig(Side),
+    Place(Side),
+    Detect(Side),
+    Inspect(Side),
+    /// How many to take, defaults to a stack.
+    Suck(Side, Option<u8>),
+    /// How many to drop, defaults to the whole slot.
+    Drop(Side, Option<u8>),
+    Select(u16),
+    GetSelectedSlot,
+    /// Defaults to the selected slot.
+    GetItemCount(Option<u16>),
+    /// Defaults to the selected slot.
+    GetItemDetail(Option<u16>),
+    GetFuelLevel,
+    GetFuelLimit,
+    /// How many items to burn, defaults to the whole slot.
+    Refuel(Option<u8>),
+}
+
+impl TurtleAction {
+    /// Read an action out of a command, IE `{"turtle": "digUp", "args": []}`.
+    pub fn from_json(command: &Value) -> Result<Self, String> {
+        let name = command
+            .get("turtle")
+            .and_then(Value::as_str)
+            .ok_or("Not a turtle command")?;
+        let args = command
+            .get("args")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        // Every argument we take is a small number.
+        let number = |index: usize| -> Result<Option<u64>, String> {
+            match args.get(index) {
+                None | Some(Value::Null) => Ok(None),
+                Some(value) => value
+                    .as_u64()
+                    .map(Some)
+                    .ok_or(format!("bad argument #{} (number expected)", index + 1)),
+            }
+        };
+        let count = |index: usize| -> Result<Option<u8>, String> {
+            number(index)?
+                .map(|count| {
+                    u8::try_from(count)
+                        .ok()
+                        .filter(|count| *count <= MAX_STACK_SIZE)
+                        .ok_or(format!("bad argument #{} (count out of range)", index + 1))
+                })
+                .transpose()
+        };
+        let slot = |index: usize| -> Result<Option<u16>, String> {
+            number(index)?
+                .map(|slot| {
+                    u16::try_from(slot)
+                        .ok()
+                        .filter(|slot| (1..=TURTLE_SLOTS).contains(slot))
+                        .ok_or(format!("bad argument #{} (slot out of range)", index + 1))
+                })
+                .transpose()
+        };
+
+        let action = match name {
+            "forward" => Self::Forward,
+            "back" => Self::Back,
+            "up" => Self::Up,
+            "down" => Self::Down,
+            "turnLeft" => Self::TurnLeft,
+            "turnRight" => Self::TurnRight,
+            "dig" => Self::Dig(Side::Front),
+            "digUp" => Self::Dig(Side::Up),
+            "digDown" => Self::Dig(Side::Down),
+            "place" => Self::Place(Side::Front),
+            "placeUp" => Self::Place(Side::Up),
+            "placeDown" => Self::Place(Side::Down),
+            "detect" => Self::Detect(Side::Front),
+            "detectUp" => Self::Detect(Side::Up),
+            "detectDown" => Self::Detect(Side::Down),
+            "inspect" => Self::Inspect(Side::Front),
+            "inspectUp" => Self::Inspect(Side::Up),
+            "inspectDown" => Self::Inspect(Side::Down),
+            "suck" => Self::Suck(Side::Front, count(0)?),
+            "suckUp" => Self::Suck(Side::Up, count(0)?),
+            "suckDown" => Self::Suck(Side::Down, count(0)?),
+            "drop" => Self::Drop(Side::Front, count(0)?),
+            "dropUp" => Self::Drop(Side::Up, count(0)?),
+            "dropDown" => Self::Drop(Side::Down, count(0)?),
+            "select" => Self::Select(slot(0)?.ok_or("bad argument #1 (number expected)")?),
+            "getSelectedSlot" => Self::GetSelectedSlot,
+            "getItemCount" => Self::GetItemCount(slot(0)?),
+            "getItemDetail" => Self::GetItemDetail(slot(0)?),
+            "getFuelLevel" => Self::GetFuelLevel,
+            "getFuelLimit" => Self::GetFuelLimit,
+            "refuel" => Self::Refuel(count(0)?),
+            other => return Err(format!("No such function `turtle.{other}`")),
+        };
+        Ok(action)
+    }
+}
+
+// ==
+// Turtles
+// ==
+
+/// A single simulated turtle.
+#[derive(Debug, Clone)]
+pub struct SimTurtle {
+    pub position: Xyz,
+    /// Only ever north, east, south or west.
+    pub facing: MinecraftFacingDirection,
+    pub fuel: u32,
+    pub fuel_limit: u32,
+    pub inventory: GenericInventory,
+    /// Starts at 1, like in game.
+    pub selected: u16,
+}
+
+impl SimTurtle {
+    /// A fresh turtle with no fuel and nothing in it.
+    pub fn new(position: Xyz, facing: MinecraftFacingDirection) -> Self {
+        Self {
+            position,
+            facing,
+            fuel: 0,
+            fuel_limit: NORMAL_TURTLE_FUEL_LIMIT,
+            inventory: GenericInventory::new(TURTLE_SLOTS),
+            selected: 1,
+        }
+    }
+
+    /// Put items in, starting at the selected slot and wrapping around, like a turtle picking
+    /// things up does.
+    ///
+    /// Returns how many didn't fit.
+    pub fn store(&mut self, item: MinecraftItem, count: u32) -> u32 {
+        FromSelected {
+            inventory: &mut self.inventory,
+            selected: self.selected,
+        }
+        .insert(item, count)
+    }
+
+    /// Take up to `count` items out of a slot.
+    fn take(&mut self, slot: u16, count: u8) -> Option<GenericInventorySlot> {
+        let contents = self.inventory.get_slot(slot)?;
+        let taken = count.min(contents.count);
+        self.inventory.set_slot(
+            slot,
+            Some(GenericInventorySlot {
+                item: contents.item,
+                count: contents.count - taken,
+            }),
+        );
+        Some(GenericInventorySlot {
+            item: contents.item,
+            count: taken,
+        })
+    }
+}
+
+/// A turtle's inventory, renumbered so slot 1 is the selected slot. Lets `Inventory::insert` fill
+/// slots in the same order a turtle does.
+struct FromSelected<'a> {
+    inventory: &'a mut GenericInventory,
+    selected: u16,
+}
+
+impl FromSelected<'_> {
+    fn real_slot(&self, slot: u16) -> u16 {
+        (slot + self.selected - 2) % TURTLE_SLOTS + 1
+    }
+}
+
+impl Inventory for FromSelected<'_> {
+    fn size(&self) -> u16 {
+        TURTLE_SLOTS
+    }
+
+    fn get_slot(&self, slot: u16) -> Option<GenericInventorySlot> {
+        self.inventory.get_slot(self.real_slot(slot))
+    }
+
+    fn set_slot(&mut self, slot: u16, contents: Option<GenericInventorySlot>) {
+        let slot = self.real_slot(slot);
+        self.inventory.set_slot(slot, contents);
+    }
+}
+
+// ==
+// World
+// ==
+
+/// A tiny minecraft world, with turtles in it.
+#[derive(Debug, Default)]
+pub struct SimWorld {
+    blocks: HashMap<Xyz, MinecraftBlock>,
+    /// Blocks that hold items, IE chests.
+    containers: HashMap<Xyz, GenericInventory>,
+    /// Items lying around as entities, from dropping into the world or digging with a full
+    /// inventory.
+    ground: HashMap<Xyz, Vec<GenericInventorySlot>>,
+    turtles: BTreeMap<ComputerId, SimTurtle>,
+}
+
+impl SimWorld {
+    /// An empty world, all air.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Put a block down, replacing whatever was there. Containers there are lost.
+    pub fn set_block(&mut self, position: Xyz, block: MinecraftBlock) {
+        self.containers.remove(&position);
+        if AIR.contains(&block.get_name().as_str()) {
+            self.blocks.remove(&position);
+        } else {
+            self.blocks.insert(position, block);
+        }
+    }
+
+    /// Fill a box with a block, corners included.
+    pub fn fill(&mut self, from: Xyz, to: Xyz, block: MinecraftBlock) {
+        for x in from.0.min(to.0)..=from.0.max(to.0) {
+            for y in from.1.min(to.1)..=from.1.max(to.1) {
+                for z in from.2.min(to.2)..=from.2.max(to.2) {
+                    self.set_block((x, y, z), block);
+                }
+            }
+        }
+    }
+
+    /// Put down a block that holds items, IE a chest with 27 slots.
+    pub fn set_container(&mut self, position: Xyz, block: MinecraftBlock, size: u16) {
+        self.set_block(position, block);
+        self.containers
+            .insert(position, GenericInventory::new(size));
+    }
+
+    /// The block at a position, `None` for air.
+    pub fn block(&self, position: Xyz) -> Option<MinecraftBlock> {
+        self.blocks.get(&position).copied()
+    }
+
+    pub fn container(&self, position: Xyz) -> Option<&GenericInventory> {
+        self.containers.get(&position)
+    }
+
+    pub fn container_mut(&mut self, position: Xyz) -> Option<&mut GenericInventory> {
+        self.containers.get_mut(&position)
+    }
+
+    /// Items lying on the ground at a position.
+    pub fn ground(&self, position: Xyz) -> &[GenericInventorySlot] {
+        self.ground.get(&position).map_or(&[], Vec::as_slice)
+    }
+
+    /// Add a turtle to the world. Replaces any turtle with the same ID.
+    pub fn add_turtle(&mut self, id: ComputerId, turtle: SimTurtle) {
+        self.turtles.insert(id, turtle);
+    }
+
+    pub fn turtle(&self, id: ComputerId) -> Option<&SimTurtle> {
+        self.turtles.get(&id)
+    }
+
+    pub fn turtle_mut(&mut self, id: ComputerId) -> Option<&mut SimTurtle> {
+        self.turtles.get_mut(&id)
+    }
+
+    /// Is there something solid here? Turtles count.
+    fn is_solid(&self, position: Xyz) -> bool {
+        let block = self
+            .block(position)
+            .is_some_and(|block| !PASSABLE.contains(&block.get_name().as_str()));
+        block
+            || self
+                .turtles
+                .values()
+                .any(|turtle| turtle.position == position)
+    }
+
+    /// Drop items on the ground.
+    fn spill(&mut self, position: Xyz, contents: GenericInventorySlot) {
+        if contents.count > 0 {
+            self.ground.entry(position).or_default().push(contents);
+        }
+    }
+
+    /// Run a turtle action, as if the turtle called it.
+    ///
+    /// Results are what the turtle function would have returned, and errors are the same strings
+    /// CC:Tweaked gives back.
+    pub fn run(&mut self, id: ComputerId, action: TurtleAction) -> Result<Value, String> {
+        let turtle = self
+            .turtles
+            .get(&id)
+            .ok_or(format!("No turtle with ID {id}"))?
+            .clone();
+        let facing = turtle.facing;
+        let result = match action {
+            TurtleAction::Forward => self.step(turtle, offset(facing, 1)),
+            TurtleAction::Back => self.step(turtle, offset(facing, -1)),
+            TurtleAction::Up => self.step(turtle, (0, 1, 0)),
+            TurtleAction::Down => self.step(turtle, (0, -1, 0)),
+            TurtleAction::TurnLeft | TurtleAction::TurnRight => {
+                let mut turtle = turtle;
+                turtle.facing = turn(turtle.facing, action == TurtleAction::TurnRight);
+                Ok((turtle, json!(true)))
+            }
+            TurtleAction::Dig(side) => self.dig(turtle, side),
+            TurtleAction::Place(side) => self.place(turtle, side),
+            TurtleAction::Detect(side) => {
+                let solid = self.is_solid(target(&turtle, side));
+                Ok((turtle, json!(solid)))
+            }
+            TurtleAction::Inspect(side) => {
+                let block = self
+                    .block(target(&turtle, side))
+                    .ok_or("No block to inspect")?;
+                let data = json!({
+                    "name": namespaced(&block.get_full_name()),
+                    "state": {},
+                    "tags": {},
+                });
+                Ok((turtle, data))
+            }
+            TurtleAction::Suck(side, count) => self.suck(turtle, side, count.unwrap_or(64)),
+            TurtleAction::Drop(side, count) => self.drop(turtle, side, count),
+            TurtleAction::Select(slot) => {
+                let mut turtle = turtle;
+                turtle.selected = slot;
+                Ok((turtle, json!(true)))
+            }
+            TurtleAction::GetSelectedSlot => Ok((turtle.clone(), json!(turtle.selected))),
+            TurtleAction::GetItemCount(slot) => {
+                let contents = turtle.inventory.get_slot(slot.unwrap_or(turtle.selected));
+                let count = contents.map_or(0, |contents| contents.count);
+                Ok((turtle, json!(count)))
+            }
+            TurtleAction::GetItemDetail(slot) => {
+                let detail = turtle
+                    .inventory
+                    .get_slot(slot.unwrap_or(turtle.selected))
+                    .map_or(Value::Null, |contents| {
+                        json!({
+                            "name": namespaced(&contents.item.get_full_name()),
+                            "count": contents.count,
+                        })
+                    });
+                Ok((turtle, detail))
+            }
+            TurtleAction::GetFuelLevel => Ok((turtle.clone(), json!(turtle.fuel))),
+            TurtleAction::GetFuelLimit => Ok((turtle.clone(), json!(turtle.fuel_limit))),
+            TurtleAction::Refuel(count) => refuel(turtle, count),
+        };
+
+        // Only keep changes to the turtle if the action worked.
+        let (turtle, value) = result?;
+        self.turtles.insert(id, turtle);
+        Ok(value)
+    }
+
+    fn step(&self, mut turtle: SimTurtle, by: Xyz) -> Result<(SimTurtle, Value), String> {
+        if turtle.fuel == 0 {
+            return Err("Out of fuel".to_string());
+        }
+        let next = add(turtle.position, by);
+        if next.1 >= MAX_Y {
+            return Err("Too high to move".to_string());
+        }
+        if next.1 < MIN_Y {
+            return Err("Too low to move".to_string());
+        }
+        if self.is_solid(next) {
+            return Err("Movement obstructed".to_string());
+        }
+        turtle.fuel -= 1;
+        turtle.position = next;
+        Ok((turtle, json!(true)))
+    }
+
+    fn dig(&mut self, mut turtle: SimTurtle, side: Side) -> Result<(SimTurtle, Value), String> {
+        let position = target(&turtle, side);
+        let block = self
+            .block(position)
+            .filter(|block| !PASSABLE.contains(&block.get_name().as_str()))
+            .ok_or("Nothing to dig here")?;
+        if UNBREAKABLE.contains(&block.get_name().as_str()) {
+            return Err("Cannot break unbreakable block".to_string());
+        }
+
+        self.blocks.remove(&position);
+        // Whatever was in it goes everywhere.
+        if let Some(container) = self.containers.remove(&position) {
+            for (_, contents) in container.list() {
+                self.spill(position, contents);
+            }
+        }
+        let drop = DROPS
+            .iter()
+            .find(|(from, _)| from == block.get_name())
+            .map_or(block.get_name().as_str(), |(_, to)| to);
+        if let Some(item) = MinecraftItem::from_string(drop) {
+            let left = turtle.store(item, 1);
+            self.spill(
+                turtle.position,
+                GenericInventorySlot {
+                    item,
+                    count: left as u8,
+                },
+            );
+        }
+        Ok((turtle, json!(true)))
+    }
+
+    fn place(&mut self, mut turtle: SimTurtle, side: Side) -> Result<(SimTurtle, Value), String> {
+        let contents = turtle
+            .inventory
+            .get_slot(turtle.selected)
+            .ok_or("No items to place")?;
+        let position = target(&turtle, side);
+        if self.is_solid(position) {
+            return Err("Cannot place block here".to_string());
+        }
+        let block = MinecraftBlock::from_string(contents.item.get_name().as_str())
+            .ok_or("Cannot place item here")?;
+        turtle.take(turtle.selected, 1);
+        self.set_block(position, block);
+        Ok((turtle, json!(true)))
+    }
+
+    fn suck(
+        &mut self,
+        mut turtle: SimTurtle,
+        side: Side,
+        count: u8,
+    ) -> Result<(SimTurtle, Value), String> {
+        let position = target(&turtle, side);
+        let taken = if let Some(container) = self.containers.get_mut(&position) {
+            let (slot, contents) = container
+                .list()
+                .first()
+                .copied()
+                .ok_or("No items to take")?;
+            let wanted = u32::from(count.min(contents.count));
+            let left = turtle.store(contents.item, wanted);
+            if left == wanted {
+                return Err("No space for items".to_string());
+            }
+            container.set_slot(
+                slot,
+                Some(GenericInventorySlot {
+                    item: contents.item,
+                    count: contents.count - (wanted - left) as u8,
+                }),
+            );
+            wanted - left
+        } else {
+            let ground = self
+                .ground
+                .get_mut(&position)
+                .filter(|ground| !ground.is_empty())
+                .ok_or("No items to take")?;
+            let contents = ground[0];
+            let wanted = u32::from(count.min(contents.count));
+            let left = turtle.store(contents.item, wanted);
+            if left == wanted {
+                return Err("No space for items".to_string());
+            }
+            let remaining = contents.count - (wanted - left) as u8;
+            if remaining == 0 {
+                ground.remove(0);
+            } else {
+                ground[0].count = remaining;
+            }
+            wanted - left
+        };
+        Ok((turtle, json!(taken > 0)))
+    }
+
+    fn drop(
+        &mut self,
+        mut turtle: SimTurtle,
+        side: Side,
+        count: Option<u8>,
+    ) -> Result<(SimTurtle, Value), String> {
+        let contents = turtle
+            .inventory
+            .get_slot(turtle.selected)
+            .ok_or("No items to drop")?;
+        let wanted = count.unwrap_or(contents.count).min(contents.count);
+        let position = target(&turtle, side);
+        let dropped = if let Some(container) = self.containers.get_mut(&position) {
+            let left = container.insert(contents.item, u32::from(wanted));
+            if left == u32::from(wanted) {
+                return Err("No space for items".to_string());
+            }
+            wanted - left as u8
+        } else {
+            self.spill(
+                position,
+                GenericInventorySlot {
+                    item: contents.item,
+                    count: wanted,
+                },
+            );
+            wanted
+        };
+        turtle.take(turtle.selected, dropped);
+        Ok((turtle, json!(true)))
+    }
+}
+
+fn refuel(mut turtle: SimTurtle, count: Option<u8>) -> Result<(SimTurtle, Value), String> {
+    let contents = turtle
+        .inventory
+        .get_slot(turtle.selected)
+        .ok_or("No items to combust")?;
+    let value = turtle_fuel_value(&contents.item)
+        .filter(|value| *value > 0)
+        .ok_or("Items not combustible")?;
+    // Don't burn more than it takes to fill up.
+    let space = turtle.fuel_limit.saturating_sub(turtle.fuel);
+    let burned =
+        u32::from(count.unwrap_or(contents.count).min(contents.count)).min(space.div_ceil(value));
+    turtle.take(turtle.selected, burned as u8);
+    turtle.fuel = (turtle.fuel + burned * value).min(turtle.fuel_limit);
+    Ok((turtle, json!(true)))
+}
+
+/// Mcdata names don't have the `minecraft:` on them, but CC:Tweaked's do.
+fn namespaced(name: &str) -> String {
+    if name.contains(':') {
+        name.to_string()
+    } else {
+        format!("minecraft:{name}")
+    }
+}
+
+fn add(position: Xyz, by: Xyz) -> Xyz {
+    (position.0 + by.0, position.1 + by.1, position.2 + by.2)
+}
+
+/// A step in a horizontal direction. North is -z, east is +x.
+fn offset(facing: MinecraftFacingDirection, distance: i64) -> Xyz {
+    match facing {
+        MinecraftFacingDirection::North => (0, 0, -distance),
+        MinecraftFacingDirection::South => (0, 0, distance),
+        MinecraftFacingDirection::East => (distance, 0, 0),
+        MinecraftFacingDirection::West => (-distance, 0, 0),
+        MinecraftFacingDirection::Up => (0, distance, 0),
+        MinecraftFacingDirection::Down => (0, -distance, 0),
+    }
+}
+
+fn turn(facing: MinecraftFacingDirection, right: bool) -> MinecraftFacingDirection {
+    use MinecraftFacingDirection::*;
+    let turned = match facing {
+        North => East,
+        East => South,
+        South => West,
+        West => North,
+        other => other,
+    };
+    if right {
+        turned
+    } else {
+        // Three rights make a left.
+        turn(turn(turned, true), true)
+    }
+}
+
+/// The block a turtle would act on.
+fn target(turtle: &SimTurtle, side: Side) -> Xyz {
+    let by = match side {
+        Side::Front => offset(turtle.facing, 1),
+        Side::Up => (0, 1, 0),
+        Side::Down => (0, -1, 0),
+    };
+    add(turtle.position, by)
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Walking, turning, and running out of fuel. No blocks involved, so no mcdata needed.
+fn sim_movement() {
+    let mut world = SimWorld::new();
+    let mut turtle = SimTurtle::new((0, MAX_Y - 2, 0), MinecraftFacingDirection::North);
+    turtle.fuel = 3;
+    world.add_turtle(1, turtle);
+    world.add_turtle(
+        2,
+        SimTurtle::new((0, MAX_Y - 2, 1), MinecraftFacingDirection::North),
+    );
+
+    assert_eq!(world.run(1, TurtleAction::Forward), Ok(json!(true)));
+    assert_eq!(world.turtle(1).unwrap().position, (0, MAX_Y - 2, -1));
+    assert_eq!(world.run(1, TurtleAction::TurnLeft), Ok(json!(true)));
+    assert_eq!(
+        world.turtle(1).unwrap().facing,
+        MinecraftFacingDirection::West
+    );
+    assert_eq!(world.run(1, TurtleAction::Up), Ok(json!(true)));
+    assert_eq!(
+        world.run(1, TurtleAction::Up),
+        Err("Too high to move".to_string())
+    );
+    assert_eq!(world.run(1, TurtleAction::Down), Ok(json!(true)));
+    assert_eq!(
+        world.run(1, TurtleAction::Forward),
+        Err("Out of fuel".to_string())
+    );
+    // Failed actions don't cost anything.
+    assert_eq!(world.run(1, TurtleAction::GetFuelLevel), Ok(json!(0)));
+
+    // Turtles get in each other's way.
+    world.turtle_mut(2).unwrap().fuel = 10;
+    assert_eq!(world.run(2, TurtleAction::Forward), Ok(json!(true)));
+    world.turtle_mut(1).unwrap().fuel = 10;
+    world.run(1, TurtleAction::TurnRight).unwrap();
+    assert_eq!(
+        world.run(1, TurtleAction::Back),
+        Err("Movement obstructed".to_string())
+    );
+    assert!(matches!(
+        TurtleAction::from_json(&json!({ "turtle": "select", "args": [17] })),
+        Err(error) if error == "bad argument #1 (slot out of range)"
+    ));
+}
+
+#[test]
+/// Digging, placing, and moving items in and out of a chest.
+fn sim_blocks_and_items() {
+    let stone = MinecraftBlock::from_string("stone").unwrap();
+    let bedrock = MinecraftBlock::from_string("bedrock").unwrap();
+    let chest = MinecraftBlock::from_string("chest").unwrap();
+    let cobblestone = MinecraftItem::from_string("cobblestone").unwrap();
+    let coal = MinecraftItem::from_string("coal").unwrap();
+
+    let mut world = SimWorld::new();
+    world.set_block((0, 0, -1), stone);
+    world.set_block((0, -1, 0), bedrock);
+    world.set_container((0, 1, 0), chest, 27);
+    world.container_mut((0, 1, 0)).unwrap().insert(coal, 10);
+    world.add_turtle(
+        1,
+        SimTurtle::new((0, 0, 0), MinecraftFacingDirection::North),
+    );
+
+    assert_eq!(
+        world.run(1, TurtleAction::Detect(Side::Front)),
+        Ok(json!(true))
+    );
+    assert_eq!(
+        world.run(1, TurtleAction::Inspect(Side::Front)).unwrap()["name"],
+        "minecraft:stone"
+    );
+    assert_eq!(
+        world.run(1, TurtleAction::Dig(Side::Down)),
+        Err("Cannot break unbreakable block".to_string())
+    );
+
+    // Dug items land in the selected slot.
+    world.run(1, TurtleAction::Select(3)).unwrap();
+    assert_eq!(
+        world.run(1, TurtleAction::Dig(Side::Front)),
+        Ok(json!(true))
+    );
+    assert_eq!(
+        world.run(1, TurtleAction::Dig(Side::Front)),
+        Err("Nothing to dig here".to_string())
+    );
+    let turtle = world.turtle(1).unwrap();
+    assert_eq!(turtle.inventory.get_slot(3).unwrap().item, cobblestone);
+
+    // Put it back, but it's cobble now.
+    assert_eq!(
+        world.run(1, TurtleAction::Place(Side::Front)),
+        Ok(json!(true))
+    );
+    assert_eq!(
+        world.block((0, 0, -1)),
+        MinecraftBlock::from_string("cobblestone")
+    );
+    assert_eq!(
+        world.run(1, TurtleAction::Place(Side::Front)),
+        Err("No items to place".to_string())
+    );
+
+    // Fuel from the chest.
+    assert_eq!(
+        world.run(1, TurtleAction::Suck(Side::Up, Some(4))),
+        Ok(json!(true))
+    );
+    assert_eq!(world.container((0, 1, 0)).unwrap().count(&coal), 6);
+    assert_eq!(world.run(1, TurtleAction::Refuel(Some(1))), Ok(json!(true)));
+    assert_eq!(world.run(1, TurtleAction::GetFuelLevel), Ok(json!(80)));
+    assert_eq!(
+        world.run(1, TurtleAction::Drop(Side::Up, None)),
+        Ok(json!(true))
+    );
+    assert_eq!(world.container((0, 1, 0)).unwrap().count(&coal), 9);
+    assert_eq!(
+        world.run(1, TurtleAction::Refuel(None)),
+        Err("No items to combust".to_string())
+    );
+}
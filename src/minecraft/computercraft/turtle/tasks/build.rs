@@ -0,0 +1,682 @@
+// Planning for the build task. See `build.md`
+//
+// Building is hard to order, turtles can easily wall themselves (or each other) in. Taking a build
+// apart is much easier though, you can only ever dig what you can reach. So we simulate turtles
+// deconstructing the finished build, then play that simulation backwards to get the placement order.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::minecraft::{
+    schematic::Schematic, types::MinecraftPosition, vanilla::block_state::BlockState,
+};
+
+type Xyz = (i64, i64, i64);
+
+/// The six directions a turtle can move or place in.
+const NEIGHBORS: [Xyz; 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+fn offset(a: Xyz, b: Xyz) -> Xyz {
+    (a.0 + b.0, a.1 + b.1, a.2 + b.2)
+}
+
+fn to_position((x, y, z): Xyz) -> MinecraftPosition {
+    MinecraftPosition {
+        x,
+        y,
+        z,
+        facing: None,
+    }
+}
+
+/// What a position in the build volume should be once the build is done.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Voxel<B> {
+    /// Empty, turtles can move through it.
+    Air,
+    /// A block we need to place.
+    ToChange(B),
+    /// Something we won't touch, but also can't move through.
+    NoChangeSolid,
+}
+
+/// The box we are building in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildVolume {
+    /// The minimum corner of the box.
+    pub origin: MinecraftPosition,
+    /// How big the box is on each axis.
+    pub size: (i64, i64, i64),
+    /// Free spaces just outside the box where turtles can come and go from.
+    pub entrances: Vec<MinecraftPosition>,
+}
+
+impl BuildVolume {
+    /// Is this position inside the box?
+    pub fn contains(&self, (x, y, z): Xyz) -> bool {
+        let o = &self.origin;
+        (o.x..o.x + self.size.0).contains(&x)
+            && (o.y..o.y + self.size.1).contains(&y)
+            && (o.z..o.z + self.size.2).contains(&z)
+    }
+}
+
+/// Settings for the planner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildConfig {
+    /// How much stepping into a `to_change` block adds to its depth. `k` in `build.md`
+    pub to_change_cost: u32,
+    /// How many blocks a turtle places in one trip. Turtles can hold 16 stacks, but mixed
+    /// materials rarely pack that nicely.
+    pub blocks_per_trip: usize,
+    /// How many turtles we can hand trips out to.
+    pub turtles: usize,
+    /// Seed for the simulation, so the same build always gets the same plan.
+    pub seed: u64,
+}
+
+impl Default for BuildConfig {
+    fn default() -> Self {
+        Self {
+            to_change_cost: 4,
+            blocks_per_trip: 64,
+            turtles: 1,
+            seed: 0,
+        }
+    }
+}
+
+/// Reasons we couldn't plan a build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildPlanError {
+    /// There's no way in.
+    NoEntrances,
+    /// An entrance is inside the volume, or isn't free.
+    BadEntrance(MinecraftPosition),
+    /// We need at least one turtle.
+    NoTurtles,
+    /// These blocks can't be reached without going through `no_change_solid`.
+    Unreachable(Vec<MinecraftPosition>),
+    /// A planned path goes through `no_change_solid`. This should never happen, but we check
+    /// anyways so a bad plan never makes it to a turtle.
+    ThroughSolid(MinecraftPosition),
+}
+
+/// A single block placement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement<B> {
+    /// Where the turtle stands. This is always next to the target.
+    pub stand: MinecraftPosition,
+    /// Where the block goes.
+    pub target: MinecraftPosition,
+    /// What to place.
+    pub block: B,
+}
+
+/// One trip for one turtle: walk in, place blocks while backing out, walk out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlacementTrip<B> {
+    /// Which turtle does this trip, `0..turtles`
+    pub turtle: usize,
+    /// The frame this trip may start on. Starting earlier could run into blocks that aren't
+    /// placed yet, or other turtles.
+    pub start: u64,
+    /// The frame this trip should be done by.
+    pub end: u64,
+    /// The path from an entrance to where the first placement happens, in order.
+    pub path_in: Vec<MinecraftPosition>,
+    /// The placements, in order. The turtle moves to `stand` before each one.
+    pub placements: Vec<Placement<B>>,
+    /// The path from the last `stand` back out to an entrance.
+    pub path_out: Vec<MinecraftPosition>,
+}
+
+/// The whole build, as trips for turtles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildSchedule<B> {
+    /// Every trip, sorted by start frame.
+    pub trips: Vec<PlacementTrip<B>>,
+    /// The frame the last trip is done.
+    pub frames: u64,
+}
+
+/// A trip from the deconstruction simulation.
+struct DeconstructTrip {
+    start: u64,
+    end: u64,
+    /// Entrance to the first standing position.
+    approach: Vec<Xyz>,
+    /// (stand, target) for every dig, in order.
+    digs: Vec<(Xyz, Xyz)>,
+}
+
+/// The block map and volume to build.
+#[derive(Debug, Clone)]
+pub struct BuildSite<B> {
+    pub volume: BuildVolume,
+    /// The finished build. Positions in the volume that aren't in here are treated as `air`.
+    pub voxels: HashMap<Xyz, Voxel<B>>,
+}
+
+impl BuildSite<BlockState> {
+    /// Make a build site from a schematic, placing its minimum corner at `origin`.
+    ///
+    /// Structure void is treated as `no_change_solid`, since we don't know what's there.
+    pub fn from_schematic(
+        schematic: &Schematic,
+        origin: MinecraftPosition,
+        entrances: Vec<MinecraftPosition>,
+    ) -> Self {
+        let mut voxels = HashMap::new();
+        for x in 0..schematic.size.0 {
+            for y in 0..schematic.size.1 {
+                for z in 0..schematic.size.2 {
+                    let voxel = match schematic.blocks.get(&(x, y, z)) {
+                        None => Voxel::NoChangeSolid,
+                        Some(block) => match block.block().get_name().as_str() {
+                            "air" | "cave_air" | "void_air" => Voxel::Air,
+                            _ => Voxel::ToChange(block.clone()),
+                        },
+                    };
+                    voxels.insert((origin.x + x, origin.y + y, origin.z + z), voxel);
+                }
+            }
+        }
+        Self {
+            volume: BuildVolume {
+                origin,
+                size: schematic.size,
+                entrances,
+            },
+            voxels,
+        }
+    }
+}
+
+impl<B: Clone> BuildSite<B> {
+    /// Plan the build.
+    pub fn plan(&self, config: &BuildConfig) -> Result<BuildSchedule<B>, BuildPlanError> {
+        if self.volume.entrances.is_empty() {
+            return Err(BuildPlanError::NoEntrances);
+        }
+        if config.turtles == 0 {
+            return Err(BuildPlanError::NoTurtles);
+        }
+        for entrance in &self.volume.entrances {
+            if self.volume.contains(entrance.xyz()) {
+                return Err(BuildPlanError::BadEntrance(*entrance));
+            }
+        }
+
+        let trips = self.simulate(config)?;
+        let schedule = self.reverse(trips, config);
+        self.validate(&schedule)?;
+        Ok(schedule)
+    }
+
+    // ==
+    // Simulation state
+    // ==
+
+    /// The voxel at a position in the simulated world.
+    fn state_at<'a>(&self, state: &'a HashMap<Xyz, Voxel<B>>, position: Xyz) -> &'a Voxel<B> {
+        state.get(&position).unwrap_or(&Voxel::Air)
+    }
+
+    /// Can a turtle stand here right now?
+    fn walkable(
+        &self,
+        state: &HashMap<Xyz, Voxel<B>>,
+        blocked: &HashMap<Xyz, u64>,
+        p: Xyz,
+    ) -> bool {
+        if blocked.contains_key(&p) {
+            return false;
+        }
+        if self.volume.contains(p) {
+            matches!(self.state_at(state, p), Voxel::Air)
+        } else {
+            self.volume.entrances.iter().any(|e| e.xyz() == p)
+        }
+    }
+
+    /// Score every position by how hard it is to get to from the entrances. See `build.md`
+    ///
+    /// Positions that can't be reached at all are left out.
+    pub fn count_depth(
+        &self,
+        state: &HashMap<Xyz, Voxel<B>>,
+        blocked: &HashMap<Xyz, u64>,
+        to_change_cost: u32,
+    ) -> HashMap<Xyz, u32> {
+        let mut depth: HashMap<Xyz, u32> = HashMap::new();
+        let mut queue: BinaryHeap<Reverse<(u32, Xyz)>> = BinaryHeap::new();
+        for entrance in &self.volume.entrances {
+            if !blocked.contains_key(&entrance.xyz()) {
+                depth.insert(entrance.xyz(), 0);
+                queue.push(Reverse((0, entrance.xyz())));
+            }
+        }
+
+        // Dijkstra, since costs aren't all the same.
+        while let Some(Reverse((score, here))) = queue.pop() {
+            if depth.get(&here).is_some_and(|best| *best < score) {
+                continue;
+            }
+            for direction in NEIGHBORS {
+                let next = offset(here, direction);
+                if !self.volume.contains(next) || blocked.contains_key(&next) {
+                    continue;
+                }
+                let cost = match self.state_at(state, next) {
+                    Voxel::Air => 1,
+                    Voxel::ToChange(_) => to_change_cost,
+                    // Infinity.
+                    Voxel::NoChangeSolid => continue,
+                };
+                let next_score = score.saturating_add(cost);
+                if depth.get(&next).is_none_or(|best| next_score < *best) {
+                    depth.insert(next, next_score);
+                    queue.push(Reverse((next_score, next)));
+                }
+            }
+        }
+        depth
+    }
+
+    /// Every `to_change` block that can be reached by only moving through air. See `build.md`
+    ///
+    /// Sorted, so the simulation is repeatable.
+    pub fn find_surface(
+        &self,
+        state: &HashMap<Xyz, Voxel<B>>,
+        blocked: &HashMap<Xyz, u64>,
+    ) -> Vec<Xyz> {
+        let reachable = self.reachable_air(state, blocked);
+        let mut surface: Vec<Xyz> = reachable
+            .keys()
+            .flat_map(|here| NEIGHBORS.iter().map(move |d| offset(*here, *d)))
+            .filter(|next| {
+                self.volume.contains(*next)
+                    && !blocked.contains_key(next)
+                    && matches!(self.state_at(state, *next), Voxel::ToChange(_))
+            })
+            .collect::<HashSet<Xyz>>()
+            .into_iter()
+            .collect();
+        surface.sort();
+        surface
+    }
+
+    /// Flood fill through air from the entrances. Each position maps to where we came from, so
+    /// paths can be rebuilt.
+    fn reachable_air(
+        &self,
+        state: &HashMap<Xyz, Voxel<B>>,
+        blocked: &HashMap<Xyz, u64>,
+    ) -> HashMap<Xyz, Option<Xyz>> {
+        let mut came_from: HashMap<Xyz, Option<Xyz>> = HashMap::new();
+        let mut queue: VecDeque<Xyz> = VecDeque::new();
+        for entrance in &self.volume.entrances {
+            if self.walkable(state, blocked, entrance.xyz()) {
+                came_from.insert(entrance.xyz(), None);
+                queue.push_back(entrance.xyz());
+            }
+        }
+        while let Some(here) = queue.pop_front() {
+            for direction in NEIGHBORS {
+                let next = offset(here, direction);
+                if !came_from.contains_key(&next) && self.walkable(state, blocked, next) {
+                    came_from.insert(next, Some(here));
+                    queue.push_back(next);
+                }
+            }
+        }
+        came_from
+    }
+
+    /// Shortest path through air from an entrance to somewhere next to `target`.
+    fn approach_path(
+        &self,
+        state: &HashMap<Xyz, Voxel<B>>,
+        blocked: &HashMap<Xyz, u64>,
+        target: Xyz,
+    ) -> Option<Vec<Xyz>> {
+        let came_from = self.reachable_air(state, blocked);
+        // Closest standing spot, by walking distance.
+        let mut best: Option<Vec<Xyz>> = None;
+        for direction in NEIGHBORS {
+            let stand = offset(target, direction);
+            if !came_from.contains_key(&stand) {
+                continue;
+            }
+            let mut path = vec![stand];
+            while let Some(Some(previous)) = came_from.get(path.last().expect("never empty")) {
+                path.push(*previous);
+            }
+            path.reverse();
+            if best.as_ref().is_none_or(|b| path.len() < b.len()) {
+                best = Some(path);
+            }
+        }
+        best
+    }
+
+    // ==
+    // Reverse deconstruction
+    // ==
+
+    /// Simulate turtles taking the finished build apart.
+    fn simulate(&self, config: &BuildConfig) -> Result<Vec<DeconstructTrip>, BuildPlanError> {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let mut state = self.voxels.clone();
+        // Positions turtles are currently using, and the frame they are free again.
+        let mut blocked: HashMap<Xyz, u64> = HashMap::new();
+        let mut active: Vec<u64> = vec![];
+        let mut trips: Vec<DeconstructTrip> = vec![];
+        let mut remaining = state
+            .values()
+            .filter(|v| matches!(v, Voxel::ToChange(_)))
+            .count();
+        let mut frame: u64 = 0;
+
+        while remaining > 0 {
+            frame += 1;
+            blocked.retain(|_, until| *until > frame);
+            active.retain(|end| *end > frame);
+
+            let surface = self.find_surface(&state, &blocked);
+            if surface.is_empty() {
+                if active.is_empty() {
+                    // Nobody is in the way, we just can't get there.
+                    let mut stuck: Vec<Xyz> = state
+                        .iter()
+                        .filter(|(_, v)| matches!(v, Voxel::ToChange(_)))
+                        .map(|(p, _)| *p)
+                        .collect();
+                    stuck.sort();
+                    return Err(BuildPlanError::Unreachable(
+                        stuck.into_iter().map(to_position).collect(),
+                    ));
+                }
+                continue;
+            }
+
+            // See note 3, the more turtles are out the less likely we send another.
+            if !rng.random_bool(1.0 / (1.0 + active.len() as f64)) {
+                continue;
+            }
+
+            let depth = self.count_depth(&state, &blocked, config.to_change_cost);
+            let location = surface[rng.random_range(0..surface.len())];
+            let approach = self
+                .approach_path(&state, &blocked, location)
+                .expect("Surface blocks are reachable through air");
+
+            // Vein mine, always going for the deepest neighbor.
+            let mut digs: Vec<(Xyz, Xyz)> = vec![];
+            let mut stand = *approach.last().expect("Paths always have an entrance");
+            let mut target = Some(location);
+            while let Some(dig) = target {
+                state.insert(dig, Voxel::Air);
+                remaining -= 1;
+                digs.push((stand, dig));
+                stand = dig;
+                if digs.len() >= config.blocks_per_trip {
+                    break;
+                }
+                target = NEIGHBORS
+                    .iter()
+                    .map(|d| offset(stand, *d))
+                    .filter(|next| {
+                        self.volume.contains(*next)
+                            && !blocked.contains_key(next)
+                            && matches!(self.state_at(&state, *next), Voxel::ToChange(_))
+                    })
+                    .max_by_key(|next| (depth.get(next).copied().unwrap_or(0), *next));
+            }
+
+            // One frame per position the turtle visits. Everything it touched is off limits
+            // until its done.
+            let end = frame + (approach.len() + digs.len()) as u64;
+            for position in approach.iter().chain(digs.iter().map(|(_, target)| target)) {
+                blocked.insert(*position, end);
+            }
+            active.push(end);
+            trips.push(DeconstructTrip {
+                start: frame,
+                end,
+                approach,
+                digs,
+            });
+        }
+        Ok(trips)
+    }
+
+    /// Play the simulation backwards, and hand the trips out to turtles.
+    fn reverse(&self, trips: Vec<DeconstructTrip>, config: &BuildConfig) -> BuildSchedule<B> {
+        let last = trips.iter().map(|t| t.end).max().unwrap_or(0);
+
+        // Reversed start times, the last trip to finish deconstructing is the first to build.
+        let mut reversed: Vec<(u64, DeconstructTrip)> =
+            trips.into_iter().map(|t| (last - t.end, t)).collect();
+        reversed.sort_by_key(|(start, _)| *start);
+
+        // If there are more trips at once than turtles, push things back. Everything after a
+        // delayed trip gets delayed by the same amount, so nothing starts before a trip it
+        // depends on has finished.
+        let mut turtle_free: Vec<u64> = vec![0; config.turtles];
+        let mut delay: u64 = 0;
+        let mut schedule = BuildSchedule {
+            trips: vec![],
+            frames: 0,
+        };
+
+        for (planned, trip) in reversed {
+            let (turtle, free) = turtle_free
+                .iter()
+                .copied()
+                .enumerate()
+                .min_by_key(|(index, free)| (*free, *index))
+                .expect("At least one turtle");
+            let start = (planned + delay).max(free);
+            delay += start - (planned + delay);
+            let end = start + (trip.end - trip.start);
+            turtle_free[turtle] = end;
+            schedule.frames = schedule.frames.max(end);
+
+            let path_in = trip
+                .approach
+                .iter()
+                .chain(trip.digs.iter().map(|(_, target)| target))
+                .copied()
+                .map(to_position)
+                .collect();
+            let placements = trip
+                .digs
+                .iter()
+                .rev()
+                .map(|(stand, target)| Placement {
+                    stand: to_position(*stand),
+                    target: to_position(*target),
+                    block: match self.voxels.get(target) {
+                        Some(Voxel::ToChange(block)) => block.clone(),
+                        _ => unreachable!("We only ever dig to_change blocks"),
+                    },
+                })
+                .collect();
+            let path_out = trip
+                .approach
+                .iter()
+                .rev()
+                .copied()
+                .map(to_position)
+                .collect();
+
+            schedule.trips.push(PlacementTrip {
+                turtle,
+                start,
+                end,
+                path_in,
+                placements,
+                path_out,
+            });
+        }
+        schedule
+    }
+
+    /// Make sure no turtle is ever asked to go through `no_change_solid`.
+    fn validate(&self, schedule: &BuildSchedule<B>) -> Result<(), BuildPlanError> {
+        for trip in &schedule.trips {
+            let stands = trip.placements.iter().map(|p| &p.stand);
+            for position in trip.path_in.iter().chain(stands).chain(&trip.path_out) {
+                if matches!(self.voxels.get(&position.xyz()), Some(Voxel::NoChangeSolid)) {
+                    return Err(BuildPlanError::ThroughSolid(*position));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+fn pos(x: i64, y: i64, z: i64) -> MinecraftPosition {
+    MinecraftPosition {
+        x,
+        y,
+        z,
+        facing: None,
+    }
+}
+
+#[cfg(test)]
+/// A solid box of numbered blocks, with a single entrance on the -x side.
+fn solid_box(size: i64) -> BuildSite<u32> {
+    let mut voxels = HashMap::new();
+    let mut id = 0;
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                voxels.insert((x, y, z), Voxel::ToChange(id));
+                id += 1;
+            }
+        }
+    }
+    BuildSite {
+        volume: BuildVolume {
+            origin: pos(0, 0, 0),
+            size: (size, size, size),
+            entrances: vec![pos(-1, 0, 0)],
+        },
+        voxels,
+    }
+}
+
+#[test]
+/// Play the schedule forwards, every move and placement should be possible.
+fn build_schedule_never_traps_itself() {
+    let site = solid_box(4);
+    let config = BuildConfig {
+        blocks_per_trip: 5,
+        ..Default::default()
+    };
+    let schedule = site.plan(&config).unwrap();
+
+    // With one turtle, trips happen one after another.
+    let mut world: HashMap<Xyz, u32> = HashMap::new();
+    for trip in &schedule.trips {
+        let mut here = trip.path_in[0];
+        assert!(!site.volume.contains(here.xyz()));
+        for step in trip.path_in.iter().skip(1) {
+            assert_eq!(here.manhattan_distance(step), 1);
+            assert!(!world.contains_key(&step.xyz()), "walked into a block");
+            here = *step;
+        }
+        for placement in &trip.placements {
+            assert!(here.manhattan_distance(&placement.stand) <= 1);
+            assert!(!world.contains_key(&placement.stand.xyz()));
+            assert_eq!(placement.stand.manhattan_distance(&placement.target), 1);
+            assert!(
+                world
+                    .insert(placement.target.xyz(), placement.block)
+                    .is_none()
+            );
+            here = placement.stand;
+        }
+        for step in &trip.path_out {
+            assert!(here.manhattan_distance(step) <= 1);
+            assert!(!world.contains_key(&step.xyz()), "walked into a block");
+            here = *step;
+        }
+    }
+    // Everything got built.
+    assert_eq!(world.len(), 64);
+    for (position, voxel) in &site.voxels {
+        assert_eq!(Voxel::ToChange(world[position]), *voxel);
+    }
+
+    // Same seed, same plan.
+    assert_eq!(site.plan(&config).unwrap(), schedule);
+}
+
+#[test]
+/// More turtles shouldn't double book anyone.
+fn build_schedule_multiple_turtles() {
+    let mut site = solid_box(4);
+    site.volume.entrances.push(pos(4, 3, 3));
+    let config = BuildConfig {
+        blocks_per_trip: 4,
+        turtles: 3,
+        seed: 7,
+        ..Default::default()
+    };
+    let schedule = site.plan(&config).unwrap();
+    for turtle in 0..3 {
+        let mut trips: Vec<&PlacementTrip<u32>> = schedule
+            .trips
+            .iter()
+            .filter(|t| t.turtle == turtle)
+            .collect();
+        trips.sort_by_key(|t| t.start);
+        for pair in trips.windows(2) {
+            assert!(pair[0].end <= pair[1].start);
+        }
+    }
+    let placed: usize = schedule.trips.iter().map(|t| t.placements.len()).sum();
+    assert_eq!(placed, 64);
+}
+
+#[test]
+/// Blocks walled off by `no_change_solid` can't be built, and we shouldn't try.
+fn build_refuses_sealed_blocks() {
+    let mut site = solid_box(3);
+    for voxel in site.voxels.values_mut() {
+        *voxel = Voxel::NoChangeSolid;
+    }
+    site.voxels.insert((1, 1, 1), Voxel::ToChange(0));
+    assert_eq!(
+        site.plan(&BuildConfig::default()),
+        Err(BuildPlanError::Unreachable(vec![pos(1, 1, 1)]))
+    );
+
+    site.volume.entrances.clear();
+    assert_eq!(
+        site.plan(&BuildConfig::default()),
+        Err(BuildPlanError::NoEntrances)
+    );
+}
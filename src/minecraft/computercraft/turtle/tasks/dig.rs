@@ -0,0 +1,426 @@
+// Planning for the dig task. See `dig.md`
+//
+// The turtle travels through the middle of every 3 layers of the volume, and uses `digUp()` and
+// `digDown()` to clear the layers above and below it, so a single pass clears three layers at once.
+// Within each layer the turtle zig-zags (serpentine) along the longest horizontal axis to keep turns
+// to a minimum.
+
+use crate::minecraft::{
+    computercraft::turtle::fuel::{FuelEstimate, FuelModel, path_cost},
+    types::MinecraftPosition,
+};
+
+/// How many items fit in a stack. Not every block stacks to 64, but the ones we dig usually do.
+const ITEMS_PER_STACK: u64 = 64;
+
+/// A volume to excavate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DigVolume {
+    /// Where the turtle starts, and returns to. This must be one of the corners of the volume.
+    pub start_point: MinecraftPosition,
+    /// One corner of the volume.
+    pub pos1: MinecraftPosition,
+    /// The opposite corner of the volume.
+    pub pos2: MinecraftPosition,
+}
+
+/// Reasons we couldn't plan a dig.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigPlanError {
+    /// The start point is not one of the eight corners of the volume.
+    StartNotACorner,
+    /// Asked to split a volume into zero pieces, or more pieces than it is wide.
+    BadSplit,
+}
+
+/// A single position the turtle stands in while digging, and what it digs around itself.
+///
+/// The turtle digs forwards into this position, moves into it, then digs up and down as needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DigStep {
+    /// Where the turtle is standing.
+    pub position: MinecraftPosition,
+    /// Should the turtle `digUp()` here?
+    pub dig_up: bool,
+    /// Should the turtle `digDown()` here?
+    pub dig_down: bool,
+}
+
+impl DigStep {
+    /// How many blocks are cleared at this step, at most.
+    pub fn blocks_cleared(&self) -> u64 {
+        1 + u64::from(self.dig_up) + u64::from(self.dig_down)
+    }
+}
+
+/// The full traversal of a volume.
+#[derive(Debug, Clone)]
+pub struct DigPlan {
+    /// Where the turtle starts and returns to.
+    pub start_point: MinecraftPosition,
+    /// Every step, in order.
+    pub steps: Vec<DigStep>,
+}
+
+/// A guess at how expensive a dig will be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DigEstimate {
+    /// The number of blocks in the volume. Air is counted as well, since we don't know where it is.
+    pub blocks: u64,
+    /// How many times the turtle will have to go back to the start point to empty its inventory.
+    pub inventory_trips: u32,
+    /// The fuel needed to walk the plan and walk back afterwards.
+    pub fuel: FuelEstimate,
+    /// The extra fuel needed for the inventory trips, there and back again.
+    pub inventory_trip_fuel: u32,
+}
+
+impl DigEstimate {
+    /// All of the fuel this dig is expected to use.
+    pub fn total_fuel(&self) -> u32 {
+        self.fuel.total().saturating_add(self.inventory_trip_fuel)
+    }
+}
+
+impl DigVolume {
+    /// Make a new dig volume. The start point must be one of the corners of the volume.
+    pub fn new(
+        start_point: MinecraftPosition,
+        pos1: MinecraftPosition,
+        pos2: MinecraftPosition,
+    ) -> Result<Self, DigPlanError> {
+        let volume = Self {
+            start_point,
+            pos1,
+            pos2,
+        };
+        let is_corner = |value: i64, a: i64, b: i64| value == a || value == b;
+        if !(is_corner(start_point.x, pos1.x, pos2.x)
+            && is_corner(start_point.y, pos1.y, pos2.y)
+            && is_corner(start_point.z, pos1.z, pos2.z))
+        {
+            return Err(DigPlanError::StartNotACorner);
+        }
+        Ok(volume)
+    }
+
+    /// How many blocks are in this volume.
+    pub fn block_count(&self) -> u64 {
+        let size = |a: i64, b: i64| a.abs_diff(b) + 1;
+        size(self.pos1.x, self.pos2.x)
+            * size(self.pos1.y, self.pos2.y)
+            * size(self.pos1.z, self.pos2.z)
+    }
+
+    /// Build the layer-by-layer serpentine traversal of this volume.
+    pub fn plan(&self) -> DigPlan {
+        // Every axis is walked starting from the start point, towards the opposite corner.
+        let xs = axis_from(self.start_point.x, self.pos1.x, self.pos2.x);
+        let ys = axis_from(self.start_point.y, self.pos1.y, self.pos2.y);
+        let zs = axis_from(self.start_point.z, self.pos1.z, self.pos2.z);
+
+        // Rows go along the longest axis, since turning costs time.
+        let x_rows = xs.len() >= zs.len();
+        let (along, across) = if x_rows { (&xs, &zs) } else { (&zs, &xs) };
+
+        // The serpentine for a single layer. IE for a 3x2 layer:
+        // > > >
+        // < < <
+        let mut layer: Vec<(i64, i64)> = Vec::with_capacity(along.len() * across.len());
+        for (row, cross) in across.iter().enumerate() {
+            let mut cells: Vec<(i64, i64)> = along
+                .iter()
+                .map(|value| {
+                    if x_rows {
+                        (*value, *cross)
+                    } else {
+                        (*cross, *value)
+                    }
+                })
+                .collect();
+            if row % 2 == 1 {
+                cells.reverse();
+            }
+            layer.extend(cells);
+        }
+
+        let mut steps: Vec<DigStep> = Vec::with_capacity(layer.len() * ys.len().div_ceil(3));
+        for (band_index, band) in ys.chunks(3).enumerate() {
+            // Stand in the middle of the band if we can. If the band is only two tall, stand on the
+            // layer closest to the start, since we're coming from that direction anyways.
+            let stand = if band.len() == 3 { band[1] } else { band[0] };
+            let dig_up = band.contains(&(stand + 1));
+            let dig_down = band.contains(&(stand - 1));
+
+            // Every other band walks the layer backwards, so we don't have to walk all the way
+            // back across the volume to start the next one.
+            let cells: Box<dyn Iterator<Item = &(i64, i64)>> = if band_index % 2 == 0 {
+                Box::new(layer.iter())
+            } else {
+                Box::new(layer.iter().rev())
+            };
+
+            for (cell_index, (x, z)) in cells.enumerate() {
+                let mut dig_up = dig_up;
+                let mut dig_down = dig_down;
+                // Coming from the last band, the first layer of this one is still solid, so
+                // dig into it instead of trying to move through it.
+                if band_index > 0 && cell_index == 0 && band[0] != stand {
+                    steps.push(DigStep {
+                        position: MinecraftPosition {
+                            x: *x,
+                            y: band[0],
+                            z: *z,
+                            facing: None,
+                        },
+                        dig_up: false,
+                        dig_down: false,
+                    });
+                    // Already dug it.
+                    if band[0] == stand + 1 {
+                        dig_up = false;
+                    } else {
+                        dig_down = false;
+                    }
+                }
+                steps.push(DigStep {
+                    position: MinecraftPosition {
+                        x: *x,
+                        y: stand,
+                        z: *z,
+                        facing: None,
+                    },
+                    dig_up,
+                    dig_down,
+                });
+            }
+        }
+
+        DigPlan {
+            start_point: self.start_point,
+            steps,
+        }
+    }
+
+    /// Split this volume into smaller volumes along its longest horizontal axis, so multiple
+    /// turtles can dig at once without bumping into each other.
+    ///
+    /// Each piece starts at the corner nearest to the original start point.
+    pub fn split(&self, pieces: usize) -> Result<Vec<DigVolume>, DigPlanError> {
+        let width_x = self.pos1.x.abs_diff(self.pos2.x) + 1;
+        let width_z = self.pos1.z.abs_diff(self.pos2.z) + 1;
+        let split_x = width_x >= width_z;
+        let width = if split_x { width_x } else { width_z };
+
+        if pieces == 0 || pieces as u64 > width {
+            return Err(DigPlanError::BadSplit);
+        }
+
+        let (low, high) = if split_x {
+            (self.pos1.x.min(self.pos2.x), self.pos1.x.max(self.pos2.x))
+        } else {
+            (self.pos1.z.min(self.pos2.z), self.pos1.z.max(self.pos2.z))
+        };
+
+        let mut volumes = Vec::with_capacity(pieces);
+        let base = width / pieces as u64;
+        let extra = width % pieces as u64;
+        let mut from = low;
+        for piece in 0..pieces as u64 {
+            // Spread the remainder over the first few pieces.
+            let size = base + u64::from(piece < extra);
+            let to = (from + size as i64 - 1).min(high);
+
+            let mut pos1 = self.pos1;
+            let mut pos2 = self.pos2;
+            let mut start = self.start_point;
+            // Start on whichever end of the slice is closer to the original start.
+            let start_on = if (self.start_point.x == low && split_x)
+                || (self.start_point.z == low && !split_x)
+            {
+                from
+            } else {
+                to
+            };
+            if split_x {
+                pos1.x = from;
+                pos2.x = to;
+                start.x = start_on;
+            } else {
+                pos1.z = from;
+                pos2.z = to;
+                start.z = start_on;
+            }
+            volumes.push(DigVolume::new(start, pos1, pos2)?);
+            from = to + 1;
+        }
+        Ok(volumes)
+    }
+}
+
+impl DigPlan {
+    /// Every position the turtle will be in, in order, starting and ending at the start point.
+    pub fn path(&self) -> Vec<MinecraftPosition> {
+        let mut path = Vec::with_capacity(self.steps.len() + 1);
+        path.push(self.start_point);
+        path.extend(self.steps.iter().map(|step| step.position));
+        path
+    }
+
+    /// Estimate the fuel and inventory trips this plan needs.
+    ///
+    /// `free_slots` is how many empty slots the turtle has to put dug items into.
+    pub fn estimate(&self, model: &FuelModel, free_slots: u8) -> DigEstimate {
+        let path = self.path();
+        let fuel = model.estimate(&path);
+        let capacity = u64::from(free_slots) * ITEMS_PER_STACK;
+
+        let mut blocks: u64 = 0;
+        let mut inventory_trips: u32 = 0;
+        let mut inventory_trip_fuel: u32 = 0;
+        for step in &self.steps {
+            blocks += step.blocks_cleared();
+            // Every time we fill up, go home and come back.
+            if capacity != 0 && blocks >= capacity * (u64::from(inventory_trips) + 1) {
+                inventory_trips += 1;
+                let distance = path_cost(&[step.position, self.start_point]);
+                inventory_trip_fuel = inventory_trip_fuel.saturating_add(distance * 2);
+            }
+        }
+
+        DigEstimate {
+            blocks,
+            inventory_trips,
+            fuel,
+            inventory_trip_fuel,
+        }
+    }
+}
+
+/// Every value from `start` to whichever of `a` or `b` is further from it, inclusive.
+fn axis_from(start: i64, a: i64, b: i64) -> Vec<i64> {
+    let end = if start == a { b } else { a };
+    if start <= end {
+        (start..=end).collect()
+    } else {
+        (end..=start).rev().collect()
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+fn pos(x: i64, y: i64, z: i64) -> MinecraftPosition {
+    MinecraftPosition {
+        x,
+        y,
+        z,
+        facing: None,
+    }
+}
+
+#[test]
+/// Every block in the volume should get dug exactly once.
+fn dig_plan_covers_volume() {
+    use std::collections::HashSet;
+
+    let volume = DigVolume::new(pos(4, 10, 2), pos(0, 3, 0), pos(4, 10, 2)).unwrap();
+    let plan = volume.plan();
+
+    let mut dug: HashSet<(i64, i64, i64)> = HashSet::new();
+    for step in &plan.steps {
+        let (x, y, z) = step.position.xyz();
+        assert!(dug.insert((x, y, z)), "Dug {x} {y} {z} twice!");
+        if step.dig_up {
+            assert!(dug.insert((x, y + 1, z)));
+        }
+        if step.dig_down {
+            assert!(dug.insert((x, y - 1, z)));
+        }
+    }
+    assert_eq!(dug.len() as u64, volume.block_count());
+
+    // Every step should be next to the last one, apart from moving down between bands.
+    for window in plan.steps.windows(2) {
+        let distance = window[0].position.manhattan_distance(&window[1].position);
+        assert!((1..=2).contains(&distance), "Skipped {distance} blocks");
+    }
+
+    // 8 layers means 3 bands, and the middle one needs a step to dig into it.
+    assert_eq!(plan.steps.len(), 5 * 3 * 3 + 1);
+}
+
+#[test]
+/// Moving between bands in solid ground should only ever go through blocks that have been dug.
+fn dig_plan_band_change_into_solid() {
+    use std::collections::HashSet;
+
+    let volume = DigVolume::new(pos(0, 6, 0), pos(0, 0, 0), pos(1, 6, 0)).unwrap();
+    let plan = volume.plan();
+
+    let mut solid: HashSet<(i64, i64, i64)> = HashSet::new();
+    for x in 0..=1 {
+        for y in 0..=6 {
+            solid.insert((x, y, 0));
+        }
+    }
+    // The turtle is already standing in the start point.
+    solid.remove(&plan.start_point.xyz());
+
+    let mut at = plan.start_point.xyz();
+    for step in &plan.steps {
+        let to = step.position.xyz();
+        // Steps only ever go along one axis, so walk it a block at a time. The last block gets
+        // dug by the step itself.
+        let toward = |from: i64, to: i64| from + (to - from).signum();
+        while at != to {
+            if solid.contains(&at) {
+                panic!("Walked through {at:?} on the way to {to:?}");
+            }
+            at = (toward(at.0, to.0), toward(at.1, to.1), toward(at.2, to.2));
+        }
+        // Dig forwards into the step, then up and down.
+        solid.remove(&to);
+        if step.dig_up {
+            solid.remove(&(to.0, to.1 + 1, to.2));
+        }
+        if step.dig_down {
+            solid.remove(&(to.0, to.1 - 1, to.2));
+        }
+    }
+    assert!(solid.is_empty(), "Never dug {solid:?}");
+}
+
+#[test]
+/// Estimate should count trips home when the inventory fills up.
+fn dig_estimate() {
+    let volume = DigVolume::new(pos(0, 0, 0), pos(0, 0, 0), pos(15, 2, 15)).unwrap();
+    let plan = volume.plan();
+    let model = FuelModel::new(1000, 20_000);
+    let estimate = plan.estimate(&model, 1);
+    assert_eq!(estimate.blocks, 16 * 16 * 3);
+    assert_eq!(estimate.inventory_trips, (16 * 16 * 3) / 64);
+    assert!(estimate.total_fuel() > estimate.fuel.total());
+
+    assert_eq!(
+        DigVolume::new(pos(1, 0, 0), pos(0, 0, 0), pos(2, 2, 2)),
+        Err(DigPlanError::StartNotACorner)
+    );
+}
+
+#[test]
+/// Splitting a volume should give pieces that cover the whole thing.
+fn dig_split() {
+    let volume = DigVolume::new(pos(9, 0, 0), pos(0, 0, 0), pos(9, 5, 3)).unwrap();
+    let pieces = volume.split(3).unwrap();
+    assert_eq!(pieces.len(), 3);
+    let total: u64 = pieces.iter().map(|piece| piece.block_count()).sum();
+    assert_eq!(total, volume.block_count());
+    // Pieces should start on the side closest to the original start.
+    assert_eq!(pieces[2].start_point.x, 9);
+
+    assert_eq!(volume.split(0), Err(DigPlanError::BadSplit));
+    assert_eq!(volume.split(11), Err(DigPlanError::BadSplit));
+}
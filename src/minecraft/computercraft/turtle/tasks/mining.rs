@@ -0,0 +1,514 @@
+// Planning for the mining task. See `mining.md`
+//
+// Mining is branch mining. The turtle digs a main trunk from its start position towards whichever
+// edge of the bounding box is furthest away, and every few blocks digs a branch out to either side.
+// When a scan turns up an ore that we want, we follow the vein until we run out of ore.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::minecraft::{
+    computercraft::turtle::fuel::FuelModel, types::MinecraftPosition,
+    vanilla::block_type::MinecraftBlock,
+};
+
+/// The default blocks that the mining task (and the dig task) are not allowed to break.
+///
+/// Mostly things that are valuable as-is, or are other people's stuff.
+pub static DEFAULT_DENY_LIST: &[&str] = &[
+    // Things that are more useful left alone.
+    "spawner",
+    "trial_spawner",
+    "vault",
+    "budding_amethyst",
+    "suspicious_sand",
+    "suspicious_gravel",
+    // Storage, somebody put stuff in these.
+    "chest",
+    "trapped_chest",
+    "barrel",
+    "ender_chest",
+    "shulker_box",
+    // Don't even try.
+    "bedrock",
+    "end_portal_frame",
+    "reinforced_deepslate",
+    // Don't eat your friends.
+    "turtle_normal",
+    "turtle_advanced",
+    "computer_normal",
+    "computer_advanced",
+    "disk_drive",
+    "wired_modem",
+    "wired_modem_full",
+    "cable",
+];
+
+/// A named set of blocks, such as an allow list or deny list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockList {
+    /// What this list is called, for logging and error messages.
+    pub name: String,
+    /// The names of the blocks in this list, without the `minecraft:` or `computercraft:` prefix.
+    blocks: HashSet<String>,
+}
+
+/// Reasons a mining task could not be planned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MiningPlanError {
+    /// A block list had a block in it that doesn't exist. Holds the list name and the bad block.
+    UnknownBlock(String, String),
+    /// The start position is not inside the bounding box.
+    StartOutsideBounds,
+    /// The y level to mine at is not inside the bounding box.
+    YLevelOutsideBounds,
+    /// Branch spacing has to be at least one, otherwise there is no wall between branches.
+    BadSpacing,
+}
+
+impl BlockList {
+    /// Make a block list from block names. Every block is checked against the Minecraft and
+    /// ComputerCraft data, and unknown names are rejected.
+    ///
+    /// Names may have their `minecraft:` or `computercraft:` prefix, it gets removed.
+    pub fn new<S: AsRef<str>>(
+        name: &str,
+        blocks: impl IntoIterator<Item = S>,
+    ) -> Result<Self, MiningPlanError> {
+        let mut list = Self {
+            name: name.to_string(),
+            blocks: HashSet::new(),
+        };
+        list.extend(blocks)?;
+        Ok(list)
+    }
+
+    /// The default deny list. See `DEFAULT_DENY_LIST`.
+    pub fn default_deny_list() -> Self {
+        Self::new("default deny list", DEFAULT_DENY_LIST.iter())
+            .expect("Default deny list should only contain real blocks.")
+    }
+
+    /// Add more blocks to this list. If any of the blocks are invalid, nothing is added.
+    pub fn extend<S: AsRef<str>>(
+        &mut self,
+        blocks: impl IntoIterator<Item = S>,
+    ) -> Result<(), MiningPlanError> {
+        let mut new_blocks: Vec<String> = vec![];
+        for block in blocks {
+            let stripped = strip_namespace(block.as_ref());
+            if MinecraftBlock::from_string(stripped).is_none() {
+                return Err(MiningPlanError::UnknownBlock(
+                    self.name.clone(),
+                    block.as_ref().to_string(),
+                ));
+            }
+            new_blocks.push(stripped.to_string());
+        }
+        self.blocks.extend(new_blocks);
+        Ok(())
+    }
+
+    /// Remove a block from this list. Returns true if it was in there.
+    pub fn remove(&mut self, block: &str) -> bool {
+        self.blocks.remove(strip_namespace(block))
+    }
+
+    /// Is this block in the list?
+    pub fn contains(&self, block: &MinecraftBlock) -> bool {
+        self.blocks.contains(block.get_name())
+    }
+
+    /// Is this block name in the list? Accepts names with or without the namespace.
+    pub fn contains_name(&self, name: &str) -> bool {
+        self.blocks.contains(strip_namespace(name))
+    }
+}
+
+/// Block names from the turtle (and users) come with their namespace, but our data doesn't have it.
+fn strip_namespace(name: &str) -> &str {
+    name.strip_prefix("minecraft:")
+        .or_else(|| name.strip_prefix("computercraft:"))
+        .unwrap_or(name)
+}
+
+/// Everything needed to plan a mining task. Mirrors `mining_task_data` in `mining.md`
+#[derive(Debug, Clone)]
+pub struct MiningTask {
+    /// Where the turtle starts, and comes back to.
+    pub start_position: MinecraftPosition,
+    /// The y level to mine at.
+    pub y_level: i64,
+    /// One corner of the bounding box.
+    pub pos1: MinecraftPosition,
+    /// The opposite corner of the bounding box.
+    pub pos2: MinecraftPosition,
+    /// The blocks we want.
+    pub allow: BlockList,
+    /// The blocks we never break, unless they are also in the allow list.
+    pub deny: BlockList,
+    /// How many blocks of wall to leave between branches. 2 is the usual, since you can see ores
+    /// on both sides of a branch.
+    pub branch_spacing: u32,
+}
+
+/// A planned mining run.
+#[derive(Debug, Clone)]
+pub struct MiningPlan {
+    /// Every position the turtle moves through, in order, starting at the start position.
+    ///
+    /// This includes the walk back out of each branch.
+    pub path: Vec<MinecraftPosition>,
+    /// Positions we wanted to dig through, but could not since they were on the deny list.
+    pub blocked: Vec<MinecraftPosition>,
+}
+
+impl MiningTask {
+    /// Make a mining task with the default deny list.
+    pub fn new(
+        start_position: MinecraftPosition,
+        y_level: i64,
+        pos1: MinecraftPosition,
+        pos2: MinecraftPosition,
+        allow: BlockList,
+    ) -> Result<Self, MiningPlanError> {
+        let task = Self {
+            start_position,
+            y_level,
+            pos1,
+            pos2,
+            allow,
+            deny: BlockList::default_deny_list(),
+            branch_spacing: 2,
+        };
+        task.validate()?;
+        Ok(task)
+    }
+
+    /// Check that the task makes any sense.
+    pub fn validate(&self) -> Result<(), MiningPlanError> {
+        if self.branch_spacing == 0 {
+            return Err(MiningPlanError::BadSpacing);
+        }
+        let start = self.start_position;
+        if !self.in_bounds(&MinecraftPosition {
+            y: self.pos1.y,
+            ..start
+        }) {
+            // The y level of the start is allowed to be outside, since we dig down into the box.
+            return Err(MiningPlanError::StartOutsideBounds);
+        }
+        let (low, high) = (self.pos1.y.min(self.pos2.y), self.pos1.y.max(self.pos2.y));
+        if self.y_level < low || self.y_level > high {
+            return Err(MiningPlanError::YLevelOutsideBounds);
+        }
+        Ok(())
+    }
+
+    /// Is this position inside the bounding box?
+    pub fn in_bounds(&self, position: &MinecraftPosition) -> bool {
+        let within = |value: i64, a: i64, b: i64| value >= a.min(b) && value <= a.max(b);
+        within(position.x, self.pos1.x, self.pos2.x)
+            && within(position.y, self.pos1.y, self.pos2.y)
+            && within(position.z, self.pos1.z, self.pos2.z)
+    }
+
+    /// Are we allowed to break this block?
+    ///
+    /// The allow list always wins over the deny list.
+    pub fn can_break(&self, block: &MinecraftBlock) -> bool {
+        self.allow.contains(block) || !self.deny.contains(block)
+    }
+
+    /// Do we want this block?
+    pub fn wants(&self, block: &MinecraftBlock) -> bool {
+        self.allow.contains(block)
+    }
+
+    /// Plan the branch mine.
+    ///
+    /// `known` is whatever we already know about the world. Positions it returns `None` for are
+    /// assumed to be breakable. Tunnels stop right before any block we are not allowed to break.
+    pub fn plan<F>(&self, known: F) -> MiningPlan
+    where
+        F: Fn(&MinecraftPosition) -> Option<MinecraftBlock>,
+    {
+        let mut path: Vec<MinecraftPosition> = vec![self.start_position];
+        let mut blocked: Vec<MinecraftPosition> = vec![];
+
+        let breakable = |position: &MinecraftPosition| {
+            known(position).is_none_or(|block| self.can_break(&block))
+        };
+
+        // Dig straight down (or up) to the y level first.
+        let mut here = self.start_position;
+        while here.y != self.y_level {
+            let next = MinecraftPosition {
+                y: here.y + (self.y_level - here.y).signum(),
+                ..here
+            };
+            if !breakable(&next) {
+                blocked.push(next);
+                return MiningPlan { path, blocked };
+            }
+            path.push(next);
+            here = next;
+        }
+
+        // Face whichever direction has the most room. See `mining.md`
+        let (low_x, high_x) = (self.pos1.x.min(self.pos2.x), self.pos1.x.max(self.pos2.x));
+        let (low_z, high_z) = (self.pos1.z.min(self.pos2.z), self.pos1.z.max(self.pos2.z));
+        let options = [
+            ((1, 0), high_x - here.x),
+            ((-1, 0), here.x - low_x),
+            ((0, 1), high_z - here.z),
+            ((0, -1), here.z - low_z),
+        ];
+        let ((trunk_x, trunk_z), trunk_length) = options
+            .into_iter()
+            .max_by_key(|(_, room)| *room)
+            .expect("There are four options.");
+
+        // Branches go out perpendicular to the trunk.
+        let (side_x, side_z) = (trunk_z, trunk_x);
+
+        // Walk out in a straight line until we either hit the wall or hit something we can't dig.
+        let tunnel = |from: MinecraftPosition,
+                      (dx, dz): (i64, i64),
+                      path: &mut Vec<MinecraftPosition>,
+                      blocked: &mut Vec<MinecraftPosition>|
+         -> MinecraftPosition {
+            let mut at = from;
+            loop {
+                let next = MinecraftPosition {
+                    x: at.x + dx,
+                    z: at.z + dz,
+                    ..at
+                };
+                if !self.in_bounds(&next) {
+                    return at;
+                }
+                if !breakable(&next) {
+                    blocked.push(next);
+                    return at;
+                }
+                path.push(next);
+                at = next;
+            }
+        };
+
+        let spacing = i64::from(self.branch_spacing) + 1;
+        for step in 0..=trunk_length {
+            if step > 0 {
+                let next = MinecraftPosition {
+                    x: here.x + trunk_x,
+                    z: here.z + trunk_z,
+                    ..here
+                };
+                if !breakable(&next) {
+                    blocked.push(next);
+                    break;
+                }
+                path.push(next);
+                here = next;
+            }
+
+            if step % spacing != 0 {
+                continue;
+            }
+
+            // Branch out on both sides, then walk back to the trunk.
+            for direction in [(side_x, side_z), (-side_x, -side_z)] {
+                let branch_start = path.len();
+                tunnel(here, direction, &mut path, &mut blocked);
+                let back: Vec<MinecraftPosition> = path[branch_start..]
+                    .iter()
+                    .rev()
+                    .skip(1)
+                    .copied()
+                    .chain(std::iter::once(here))
+                    .collect();
+                if path.len() > branch_start {
+                    path.extend(back);
+                }
+            }
+        }
+
+        MiningPlan { path, blocked }
+    }
+
+    /// Follow an ore vein, starting from an ore we just spotted.
+    ///
+    /// This works the same way the turtle does it, with a stack of positions to mine. `scan` is
+    /// what we would see at a position. Only blocks on the allow list inside of the bounding box are
+    /// followed. Diagonals are not checked, since the turtle can't see them either.
+    ///
+    /// Returns every ore position in the order they would be mined.
+    pub fn follow_vein<F>(&self, first: MinecraftPosition, scan: F) -> Vec<MinecraftPosition>
+    where
+        F: Fn(&MinecraftPosition) -> Option<MinecraftBlock>,
+    {
+        let mut mined: Vec<MinecraftPosition> = vec![];
+        let mut seen: HashSet<(i64, i64, i64)> = HashSet::new();
+        let mut stack: Vec<MinecraftPosition> = vec![first];
+        seen.insert(first.xyz());
+
+        while let Some(position) = stack.pop() {
+            // The block might not be there anymore, which is fine.
+            let Some(block) = scan(&position) else {
+                continue;
+            };
+            if !self.wants(&block) || !self.in_bounds(&position) {
+                continue;
+            }
+            mined.push(position);
+
+            for (dx, dy, dz) in [
+                (1, 0, 0),
+                (-1, 0, 0),
+                (0, 1, 0),
+                (0, -1, 0),
+                (0, 0, 1),
+                (0, 0, -1),
+            ] {
+                let neighbor = MinecraftPosition {
+                    x: position.x + dx,
+                    y: position.y + dy,
+                    z: position.z + dz,
+                    facing: None,
+                };
+                if seen.insert(neighbor.xyz()) {
+                    stack.push(neighbor);
+                }
+            }
+        }
+        mined
+    }
+}
+
+impl MiningPlan {
+    /// The index into the path where the fuel cutoff from `mining.md` would end the task, if it
+    /// ever does.
+    pub fn fuel_cutoff(&self, model: &FuelModel) -> Option<usize> {
+        // Walkback cost after every step, done incrementally, otherwise this is quadratic.
+        // The cost to get to each position in the walkback chain
+        let mut chain_costs: Vec<u32> = vec![];
+        let mut chain: Vec<MinecraftPosition> = vec![];
+        let mut seen: HashMap<(i64, i64, i64), usize> = HashMap::new();
+
+        for (index, position) in self.path.iter().enumerate() {
+            if let Some(&at) = seen.get(&position.xyz()) {
+                for pruned in chain.drain(at + 1..) {
+                    seen.remove(&pruned.xyz());
+                }
+                chain_costs.truncate(at + 1);
+            } else {
+                let cost = chain
+                    .last()
+                    .map(|last| chain_costs[chain.len() - 1] + last.manhattan_distance(position))
+                    .unwrap_or(0);
+                seen.insert(position.xyz(), chain.len());
+                chain.push(*position);
+                chain_costs.push(cost);
+            }
+
+            // Fuel used so far is just the steps we've taken.
+            let used = u32::try_from(index).unwrap_or(u32::MAX);
+            let remaining = FuelModel::new(model.fuel_level.saturating_sub(used), model.fuel_limit);
+            let walkback = *chain_costs.last().expect("Chain is never empty here.");
+            if remaining.mining_should_stop(walkback) {
+                return Some(index);
+            }
+        }
+        None
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+fn pos(x: i64, y: i64, z: i64) -> MinecraftPosition {
+    MinecraftPosition {
+        x,
+        y,
+        z,
+        facing: None,
+    }
+}
+
+#[test]
+/// The default deny list should be made of real blocks, and lists should reject fake ones.
+fn block_lists() {
+    let deny = BlockList::default_deny_list();
+    assert!(deny.contains(&MinecraftBlock::from_string("spawner").unwrap()));
+    assert!(deny.contains_name("computercraft:turtle_normal"));
+
+    let allow = BlockList::new("ores", ["minecraft:diamond_ore", "iron_ore"]).unwrap();
+    assert!(allow.contains_name("diamond_ore"));
+
+    assert_eq!(
+        BlockList::new("ores", ["diamond_ore", "diamond_ore_but_better"]),
+        Err(MiningPlanError::UnknownBlock(
+            "ores".to_string(),
+            "diamond_ore_but_better".to_string()
+        ))
+    );
+}
+
+#[test]
+/// Branch mine a box, and never break a spawner.
+fn branch_mining_plan() {
+    let allow = BlockList::new("ores", ["diamond_ore"]).unwrap();
+    let task = MiningTask::new(pos(0, 10, 0), 5, pos(0, 0, -4), pos(12, 10, 4), allow).unwrap();
+
+    let spawner = pos(6, 5, 2);
+    let plan = task.plan(|position| {
+        if *position == spawner {
+            MinecraftBlock::from_string("spawner")
+        } else {
+            None
+        }
+    });
+
+    // Went down first
+    assert_eq!(plan.path[5], pos(0, 5, 0));
+    // Trunk goes along +x since thats the furthest wall.
+    assert!(plan.path.contains(&pos(12, 5, 0)));
+    // Never stepped on the spawner
+    assert!(!plan.path.contains(&spawner));
+    assert_eq!(plan.blocked, vec![spawner]);
+    // Never left the box
+    assert!(plan.path.iter().all(|position| task.in_bounds(position)));
+
+    // Walk it with barely any fuel, we should stop early.
+    let model = FuelModel::new(20, 20_000);
+    assert!(plan.fuel_cutoff(&model).is_some());
+    let model = FuelModel::new(20_000, 20_000);
+    assert!(plan.fuel_cutoff(&model).is_none());
+}
+
+#[test]
+/// Follow a little L shaped vein.
+fn vein_following() {
+    let allow = BlockList::new("ores", ["iron_ore"]).unwrap();
+    let task =
+        MiningTask::new(pos(0, 0, 0), 0, pos(-10, -10, -10), pos(10, 10, 10), allow).unwrap();
+    let vein = [
+        pos(1, 0, 0),
+        pos(2, 0, 0),
+        pos(2, 1, 0),
+        pos(2, 2, 0),
+        pos(20, 0, 0),
+    ];
+    let mined = task.follow_vein(pos(1, 0, 0), |position| {
+        if vein.contains(position) {
+            MinecraftBlock::from_string("iron_ore")
+        } else {
+            MinecraftBlock::from_string("stone")
+        }
+    });
+    assert_eq!(mined.len(), 4);
+    // Out of the bounding box.
+    assert!(!mined.contains(&pos(20, 0, 0)));
+}
@@ -15,7 +15,11 @@ pub struct Turtle {
     /// What direction the Turtle is facing.
     facing: MinecraftFacingDirection,
     /// How much fuel the Turtle currently has.
-    fuel_level: u16,
+    ///
+    /// Advanced turtles can hold 100,000 fuel, so this cannot be a u16.
+    fuel_level: u32,
+    /// The most fuel this Turtle can hold. See the limits in `fuel.rs`
+    fuel_limit: u32,
     /// The inventory of the Turtle
     inventory: GenericInventory,
 }
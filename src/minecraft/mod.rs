@@ -1,4 +1,6 @@
 pub mod computercraft;
+pub mod nbt;
 pub mod peripherals;
+pub mod schematic;
 pub mod types;
 pub mod vanilla;
@@ -0,0 +1,703 @@
+// Named Binary Tag, Minecraft's file format for basically everything.
+// We need this to read structure files and schematics, and to write them back out.
+// Spec: https://minecraft.wiki/w/NBT_format
+
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+
+/// A single NBT value.
+///
+/// Compounds are kept in a BTreeMap so that writing the same data twice gives the same bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NbtTag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<NbtTag>),
+    Compound(BTreeMap<String, NbtTag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+/// Things that can go wrong while reading NBT.
+#[derive(Debug)]
+pub enum NbtError {
+    /// Ran out of bytes partway through a tag.
+    UnexpectedEnd,
+    /// Found a tag ID that doesn't exist.
+    UnknownTag(u8),
+    /// The root of the file was not a compound.
+    RootNotCompound,
+    /// A list or array said it had a negative length.
+    NegativeLength,
+    /// Decompression failed.
+    Io(std::io::Error),
+}
+
+impl From<std::io::Error> for NbtError {
+    fn from(value: std::io::Error) -> Self {
+        NbtError::Io(value)
+    }
+}
+
+impl NbtTag {
+    /// The ID of this tag type, as it appears in the binary format.
+    pub fn id(&self) -> u8 {
+        match self {
+            NbtTag::Byte(_) => 1,
+            NbtTag::Short(_) => 2,
+            NbtTag::Int(_) => 3,
+            NbtTag::Long(_) => 4,
+            NbtTag::Float(_) => 5,
+            NbtTag::Double(_) => 6,
+            NbtTag::ByteArray(_) => 7,
+            NbtTag::String(_) => 8,
+            NbtTag::List(_) => 9,
+            NbtTag::Compound(_) => 10,
+            NbtTag::IntArray(_) => 11,
+            NbtTag::LongArray(_) => 12,
+        }
+    }
+
+    // Casting helpers, so reading files isn't a wall of match statements.
+
+    /// Get a child of a compound by name.
+    pub fn get(&self, key: &str) -> Option<&NbtTag> {
+        match self {
+            NbtTag::Compound(map) => map.get(key),
+            _ => None,
+        }
+    }
+    /// Get this tag as a compound.
+    pub fn as_compound(&self) -> Option<&BTreeMap<String, NbtTag>> {
+        match self {
+            NbtTag::Compound(map) => Some(map),
+            _ => None,
+        }
+    }
+    /// Get this tag as a list.
+    pub fn as_list(&self) -> Option<&Vec<NbtTag>> {
+        match self {
+            NbtTag::List(list) => Some(list),
+            _ => None,
+        }
+    }
+    /// Get this tag as a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            NbtTag::String(string) => Some(string),
+            _ => None,
+        }
+    }
+    /// Get any integer type as an i64. Minecraft is not very consistent about which integer type
+    /// it uses for what, so this is usually what you want.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            NbtTag::Byte(value) => Some((*value).into()),
+            NbtTag::Short(value) => Some((*value).into()),
+            NbtTag::Int(value) => Some((*value).into()),
+            NbtTag::Long(value) => Some(*value),
+            _ => None,
+        }
+    }
+    /// Get any number as an f64.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            NbtTag::Float(value) => Some((*value).into()),
+            NbtTag::Double(value) => Some(*value),
+            other => other.as_i64().map(|value| value as f64),
+        }
+    }
+
+    /// Follow a path down into this tag, the same way `/data get` paths work.
+    /// IE `Items[0].id`, or `components."minecraft:custom_data".count`
+    ///
+    /// Returns None if any part of the path doesn't exist. Negative indexes count from the end.
+    pub fn path(&self, path: &str) -> Option<&NbtTag> {
+        let mut current = self;
+        let mut rest = path;
+        while !rest.is_empty() {
+            rest = rest.strip_prefix('.').unwrap_or(rest);
+            if let Some(inside) = rest.strip_prefix('[') {
+                let (index, after) = inside.split_once(']')?;
+                let index: i64 = index.trim().parse().ok()?;
+                let length = match current {
+                    NbtTag::List(list) => list.len(),
+                    NbtTag::ByteArray(array) => array.len(),
+                    NbtTag::IntArray(array) => array.len(),
+                    NbtTag::LongArray(array) => array.len(),
+                    _ => return None,
+                } as i64;
+                let index = if index < 0 { length + index } else { index };
+                // Arrays don't hold tags, so we can only go into lists.
+                current = current.as_list()?.get(usize::try_from(index).ok()?)?;
+                rest = after;
+            } else if let Some(inside) = rest.strip_prefix('"') {
+                let (key, after) = inside.split_once('"')?;
+                current = current.get(key)?;
+                rest = after;
+            } else {
+                let end = rest.find(['.', '[']).unwrap_or(rest.len());
+                current = current.get(&rest[..end])?;
+                rest = &rest[end..];
+            }
+        }
+        Some(current)
+    }
+}
+
+// ==
+// Reading
+// ==
+
+/// Read an NBT file. Handles both gzipped and uncompressed data.
+///
+/// Returns the name of the root tag (usually empty) and the root compound.
+pub fn read_nbt(bytes: &[u8]) -> Result<(String, NbtTag), NbtError> {
+    // Gzip magic number
+    let decompressed: Vec<u8>;
+    let data: &[u8] = if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut buffer = Vec::new();
+        GzDecoder::new(bytes).read_to_end(&mut buffer)?;
+        decompressed = buffer;
+        &decompressed
+    } else {
+        bytes
+    };
+
+    let mut reader = NbtReader { data, position: 0 };
+    let id = reader.byte()? as u8;
+    if id != 10 {
+        return Err(NbtError::RootNotCompound);
+    }
+    let name = reader.string()?;
+    let root = reader.payload(id)?;
+    Ok((name, root))
+}
+
+struct NbtReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl NbtReader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], NbtError> {
+        let end = self
+            .position
+            .checked_add(count)
+            .ok_or(NbtError::UnexpectedEnd)?;
+        let slice = self
+            .data
+            .get(self.position..end)
+            .ok_or(NbtError::UnexpectedEnd)?;
+        self.position = end;
+        Ok(slice)
+    }
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], NbtError> {
+        Ok(self.take(N)?.try_into().expect("Took exactly N bytes"))
+    }
+    fn byte(&mut self) -> Result<i8, NbtError> {
+        Ok(i8::from_be_bytes(self.array()?))
+    }
+    fn short(&mut self) -> Result<i16, NbtError> {
+        Ok(i16::from_be_bytes(self.array()?))
+    }
+    fn int(&mut self) -> Result<i32, NbtError> {
+        Ok(i32::from_be_bytes(self.array()?))
+    }
+    fn long(&mut self) -> Result<i64, NbtError> {
+        Ok(i64::from_be_bytes(self.array()?))
+    }
+    fn length(&mut self) -> Result<usize, NbtError> {
+        usize::try_from(self.int()?).map_err(|_| NbtError::NegativeLength)
+    }
+    fn string(&mut self) -> Result<String, NbtError> {
+        let length = u16::from_be_bytes(self.array()?) as usize;
+        // Minecraft uses "modified UTF-8", which only differs from normal UTF-8 for null bytes and
+        // emoji. We don't have either in block names, so lossy is fine.
+        Ok(String::from_utf8_lossy(self.take(length)?).into_owned())
+    }
+    fn payload(&mut self, id: u8) -> Result<NbtTag, NbtError> {
+        Ok(match id {
+            1 => NbtTag::Byte(self.byte()?),
+            2 => NbtTag::Short(self.short()?),
+            3 => NbtTag::Int(self.int()?),
+            4 => NbtTag::Long(self.long()?),
+            5 => NbtTag::Float(f32::from_be_bytes(self.array()?)),
+            6 => NbtTag::Double(f64::from_be_bytes(self.array()?)),
+            7 => {
+                let length = self.length()?;
+                NbtTag::ByteArray(self.take(length)?.iter().map(|b| *b as i8).collect())
+            }
+            8 => NbtTag::String(self.string()?),
+            9 => {
+                let inner = self.byte()? as u8;
+                let length = self.length()?;
+                let mut list = Vec::with_capacity(length.min(1 << 16));
+                for _ in 0..length {
+                    list.push(self.payload(inner)?);
+                }
+                NbtTag::List(list)
+            }
+            10 => {
+                let mut map = BTreeMap::new();
+                loop {
+                    let inner = self.byte()? as u8;
+                    if inner == 0 {
+                        break;
+                    }
+                    let name = self.string()?;
+                    map.insert(name, self.payload(inner)?);
+                }
+                NbtTag::Compound(map)
+            }
+            11 => {
+                let length = self.length()?;
+                let mut array = Vec::with_capacity(length.min(1 << 16));
+                for _ in 0..length {
+                    array.push(self.int()?);
+                }
+                NbtTag::IntArray(array)
+            }
+            12 => {
+                let length = self.length()?;
+                let mut array = Vec::with_capacity(length.min(1 << 16));
+                for _ in 0..length {
+                    array.push(self.long()?);
+                }
+                NbtTag::LongArray(array)
+            }
+            other => return Err(NbtError::UnknownTag(other)),
+        })
+    }
+}
+
+// ==
+// Writing
+// ==
+
+/// Write an NBT file, gzipped, since thats what Minecraft expects for structure files.
+pub fn write_nbt(name: &str, root: &NbtTag) -> Vec<u8> {
+    let mut raw: Vec<u8> = Vec::new();
+    raw.push(root.id());
+    write_string(&mut raw, name);
+    write_payload(&mut raw, root);
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(&raw)
+        .expect("Writing to a vec cannot fail.");
+    encoder.finish().expect("Writing to a vec cannot fail.")
+}
+
+fn write_string(out: &mut Vec<u8>, string: &str) {
+    // Strings longer than this can't be written. Block names are never this long.
+    let bytes = &string.as_bytes()[..string.len().min(u16::MAX as usize)];
+    out.extend((bytes.len() as u16).to_be_bytes());
+    out.extend(bytes);
+}
+
+fn write_payload(out: &mut Vec<u8>, tag: &NbtTag) {
+    match tag {
+        NbtTag::Byte(value) => out.extend(value.to_be_bytes()),
+        NbtTag::Short(value) => out.extend(value.to_be_bytes()),
+        NbtTag::Int(value) => out.extend(value.to_be_bytes()),
+        NbtTag::Long(value) => out.extend(value.to_be_bytes()),
+        NbtTag::Float(value) => out.extend(value.to_be_bytes()),
+        NbtTag::Double(value) => out.extend(value.to_be_bytes()),
+        NbtTag::ByteArray(array) => {
+            out.extend((array.len() as i32).to_be_bytes());
+            out.extend(array.iter().map(|b| *b as u8));
+        }
+        NbtTag::String(string) => write_string(out, string),
+        NbtTag::List(list) => {
+            // Empty lists are written as lists of End tags.
+            out.push(list.first().map(NbtTag::id).unwrap_or(0));
+            out.extend((list.len() as i32).to_be_bytes());
+            for item in list {
+                write_payload(out, item);
+            }
+        }
+        NbtTag::Compound(map) => {
+            for (name, value) in map {
+                out.push(value.id());
+                write_string(out, name);
+                write_payload(out, value);
+            }
+            out.push(0);
+        }
+        NbtTag::IntArray(array) => {
+            out.extend((array.len() as i32).to_be_bytes());
+            for value in array {
+                out.extend(value.to_be_bytes());
+            }
+        }
+        NbtTag::LongArray(array) => {
+            out.extend((array.len() as i32).to_be_bytes());
+            for value in array {
+                out.extend(value.to_be_bytes());
+            }
+        }
+    }
+}
+
+// ==
+// SNBT
+// ==
+
+// Stringified NBT, which is what commands print and take.
+// IE `{Fuel: 100, Items: [{Slot: 0b, id: "minecraft:coal", count: 5}], Pos: [I; 1, 2, 3]}`
+
+/// Things that can go wrong while reading SNBT. Positions are byte offsets into the string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnbtError {
+    /// Ran out of text partway through a tag.
+    UnexpectedEnd,
+    /// Found a character that can't go here.
+    Unexpected(usize, char),
+    /// A number that doesn't fit its type, or an array with the wrong thing in it.
+    BadValue(usize, String),
+    /// There was more text after the tag ended.
+    TrailingData(usize),
+}
+
+/// Read a single SNBT tag. Any amount of whitespace is fine around it.
+pub fn parse_snbt(text: &str) -> Result<NbtTag, SnbtError> {
+    let mut reader = SnbtReader { text, position: 0 };
+    let tag = reader.tag()?;
+    reader.skip_whitespace();
+    if reader.position != text.len() {
+        return Err(SnbtError::TrailingData(reader.position));
+    }
+    Ok(tag)
+}
+
+struct SnbtReader<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+/// Characters that can be in a key or value without quotes.
+fn is_unquoted(character: char) -> bool {
+    character.is_ascii_alphanumeric() || matches!(character, '_' | '-' | '.' | '+')
+}
+
+impl SnbtReader<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(character) = self.peek()
+            && character.is_whitespace()
+        {
+            self.position += character.len_utf8();
+        }
+    }
+
+    /// Skip whitespace, then take the next character.
+    fn next(&mut self) -> Result<char, SnbtError> {
+        self.skip_whitespace();
+        let character = self.peek().ok_or(SnbtError::UnexpectedEnd)?;
+        self.position += character.len_utf8();
+        Ok(character)
+    }
+
+    fn expect(&mut self, wanted: char) -> Result<(), SnbtError> {
+        match self.next()? {
+            character if character == wanted => Ok(()),
+            character => Err(SnbtError::Unexpected(
+                self.position - character.len_utf8(),
+                character,
+            )),
+        }
+    }
+
+    fn tag(&mut self) -> Result<NbtTag, SnbtError> {
+        self.skip_whitespace();
+        match self.peek().ok_or(SnbtError::UnexpectedEnd)? {
+            '{' => self.compound(),
+            '[' => self.list(),
+            '"' | '\'' => Ok(NbtTag::String(self.quoted()?)),
+            _ => {
+                let start = self.position;
+                let word = self.unquoted()?;
+                match word_to_tag(word) {
+                    Some(tag) => Ok(tag),
+                    // Words that start like numbers but aren't one are probably typos, not strings.
+                    None if word.starts_with(|c: char| c.is_ascii_digit() || c == '-') => {
+                        Err(SnbtError::BadValue(start, word.to_string()))
+                    }
+                    None => Ok(NbtTag::String(word.to_string())),
+                }
+            }
+        }
+    }
+
+    fn unquoted(&mut self) -> Result<&str, SnbtError> {
+        let start = self.position;
+        while let Some(character) = self.peek()
+            && is_unquoted(character)
+        {
+            self.position += 1;
+        }
+        if start == self.position {
+            let character = self.peek().ok_or(SnbtError::UnexpectedEnd)?;
+            return Err(SnbtError::Unexpected(start, character));
+        }
+        Ok(&self.text[start..self.position])
+    }
+
+    /// A string in either kind of quotes.
+    fn quoted(&mut self) -> Result<String, SnbtError> {
+        let quote = self.next()?;
+        let mut string = String::new();
+        loop {
+            let character = self.peek().ok_or(SnbtError::UnexpectedEnd)?;
+            self.position += character.len_utf8();
+            match character {
+                '\\' => {
+                    let escaped = self.peek().ok_or(SnbtError::UnexpectedEnd)?;
+                    self.position += escaped.len_utf8();
+                    string.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        other => other,
+                    });
+                }
+                character if character == quote => return Ok(string),
+                character => string.push(character),
+            }
+        }
+    }
+
+    fn compound(&mut self) -> Result<NbtTag, SnbtError> {
+        self.expect('{')?;
+        let mut map = BTreeMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(NbtTag::Compound(map));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = match self.peek() {
+                Some('"' | '\'') => self.quoted()?,
+                _ => self.unquoted()?.to_string(),
+            };
+            self.expect(':')?;
+            map.insert(key, self.tag()?);
+            match self.next()? {
+                ',' => continue,
+                '}' => return Ok(NbtTag::Compound(map)),
+                other => return Err(SnbtError::Unexpected(self.position - 1, other)),
+            }
+        }
+    }
+
+    /// Lists, and typed arrays like `[I; 1, 2, 3]`
+    fn list(&mut self) -> Result<NbtTag, SnbtError> {
+        self.expect('[')?;
+        self.skip_whitespace();
+        let rest = &self.text[self.position..];
+        let array_kind = match rest.as_bytes() {
+            [kind @ (b'B' | b'I' | b'L'), b';', ..] => Some(*kind),
+            _ => None,
+        };
+        if array_kind.is_some() {
+            self.position += 2;
+        }
+
+        let mut items = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.position += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                let start = self.position;
+                items.push((start, self.tag()?));
+                match self.next()? {
+                    ',' => continue,
+                    ']' => break,
+                    other => return Err(SnbtError::Unexpected(self.position - 1, other)),
+                }
+            }
+        }
+
+        // Arrays only take their own number type.
+        let bad = |(start, tag): (usize, NbtTag)| SnbtError::BadValue(start, format!("{tag:?}"));
+        Ok(match array_kind {
+            None => NbtTag::List(items.into_iter().map(|(_, tag)| tag).collect()),
+            Some(b'B') => NbtTag::ByteArray(
+                items
+                    .into_iter()
+                    .map(|item| match item.1 {
+                        NbtTag::Byte(value) => Ok(value),
+                        _ => Err(bad(item)),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            Some(b'I') => NbtTag::IntArray(
+                items
+                    .into_iter()
+                    .map(|item| match item.1 {
+                        NbtTag::Int(value) => Ok(value),
+                        _ => Err(bad(item)),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            Some(_) => NbtTag::LongArray(
+                items
+                    .into_iter()
+                    .map(|item| match item.1 {
+                        NbtTag::Long(value) => Ok(value),
+                        // Small longs are allowed to skip their suffix.
+                        NbtTag::Int(value) => Ok(value.into()),
+                        _ => Err(bad(item)),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+}
+
+/// Turn an unquoted word into a number or bool, if it is one.
+fn word_to_tag(word: &str) -> Option<NbtTag> {
+    match word {
+        "true" => return Some(NbtTag::Byte(1)),
+        "false" => return Some(NbtTag::Byte(0)),
+        _ => (),
+    }
+    let (number, suffix) = match word.char_indices().last()? {
+        (index, suffix) if suffix.is_ascii_alphabetic() => (&word[..index], Some(suffix)),
+        _ => (word, None),
+    };
+    Some(match suffix.map(|suffix| suffix.to_ascii_lowercase()) {
+        Some('b') => NbtTag::Byte(number.parse().ok()?),
+        Some('s') => NbtTag::Short(number.parse().ok()?),
+        Some('l') => NbtTag::Long(number.parse().ok()?),
+        Some('f') => NbtTag::Float(number.parse().ok()?),
+        Some('d') => NbtTag::Double(number.parse().ok()?),
+        Some(_) => return None,
+        // No suffix means an int, unless it has a decimal point.
+        None => match number.parse() {
+            Ok(value) => NbtTag::Int(value),
+            Err(_) if number.contains(['.', 'e', 'E']) => NbtTag::Double(number.parse().ok()?),
+            Err(_) => return None,
+        },
+    })
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Writing then reading should give back the same thing.
+fn nbt_round_trip() {
+    let mut inner = BTreeMap::new();
+    inner.insert("Name".to_string(), NbtTag::String("minecraft:stone".into()));
+    inner.insert("count".to_string(), NbtTag::Byte(-3));
+
+    let mut root = BTreeMap::new();
+    root.insert("DataVersion".to_string(), NbtTag::Int(3955));
+    root.insert(
+        "size".to_string(),
+        NbtTag::List(vec![NbtTag::Int(1), NbtTag::Int(2), NbtTag::Int(3)]),
+    );
+    root.insert(
+        "palette".to_string(),
+        NbtTag::List(vec![NbtTag::Compound(inner)]),
+    );
+    root.insert("empty".to_string(), NbtTag::List(vec![]));
+    root.insert(
+        "longs".to_string(),
+        NbtTag::LongArray(vec![i64::MIN, 0, i64::MAX]),
+    );
+    root.insert("bytes".to_string(), NbtTag::ByteArray(vec![-1, 0, 1]));
+    root.insert("float".to_string(), NbtTag::Float(2.5));
+    let root = NbtTag::Compound(root);
+
+    let bytes = write_nbt("test", &root);
+    let (name, read) = read_nbt(&bytes).unwrap();
+    assert_eq!(name, "test");
+    assert_eq!(read, root);
+    assert_eq!(read.get("DataVersion").and_then(NbtTag::as_i64), Some(3955));
+
+    // Truncated files should error, not panic.
+    let (_, raw) = bytes.split_at(bytes.len() / 2);
+    assert!(read_nbt(raw).is_err());
+}
+
+#[test]
+/// SNBT the way `/data get` prints it.
+fn snbt_parsing() {
+    let turtle = parse_snbt(
+        r#"{Fuel: 100, ComputerId: 7, On: 1b, Label: "Bob \"the\" turtle", Items: [{Slot: 0b, id: "minecraft:coal", count: 5, components: {"minecraft:custom_data": {nested: 'yes'}}}, {Slot: 15b, id: "minecraft:stone", count: 64}], Pos: [I; 1, -2, 3], Motion: [0.0d, 2.5f, -1.0E2], Empty: [], Big: [L; 5L, 6]}"#,
+    )
+    .unwrap();
+
+    assert_eq!(turtle.path("Fuel"), Some(&NbtTag::Int(100)));
+    assert_eq!(turtle.path("On"), Some(&NbtTag::Byte(1)));
+    assert_eq!(
+        turtle.path("Label").and_then(NbtTag::as_str),
+        Some("Bob \"the\" turtle")
+    );
+    assert_eq!(
+        turtle.path("Items[0].id").and_then(NbtTag::as_str),
+        Some("minecraft:coal")
+    );
+    assert_eq!(
+        turtle.path("Items[-1].Slot").and_then(NbtTag::as_i64),
+        Some(15)
+    );
+    assert_eq!(
+        turtle
+            .path(r#"Items[0].components."minecraft:custom_data".nested"#)
+            .and_then(NbtTag::as_str),
+        Some("yes")
+    );
+    assert_eq!(turtle.path("Pos"), Some(&NbtTag::IntArray(vec![1, -2, 3])));
+    assert_eq!(
+        turtle.path("Motion"),
+        Some(&NbtTag::List(vec![
+            NbtTag::Double(0.0),
+            NbtTag::Float(2.5),
+            NbtTag::Double(-100.0)
+        ]))
+    );
+    assert_eq!(turtle.path("Empty"), Some(&NbtTag::List(vec![])));
+    assert_eq!(turtle.path("Big"), Some(&NbtTag::LongArray(vec![5, 6])));
+    assert_eq!(turtle.path("Items[2]"), None);
+    assert_eq!(turtle.path("Fuel.nope"), None);
+
+    // Bare values are fine too, which is what you get from a `/data get` with a path.
+    assert_eq!(parse_snbt(" 3s "), Ok(NbtTag::Short(3)));
+    assert_eq!(
+        parse_snbt("minecraft:stone"),
+        Err(SnbtError::TrailingData(9))
+    );
+    assert_eq!(parse_snbt("stone"), Ok(NbtTag::String("stone".into())));
+
+    assert_eq!(parse_snbt("{a: 1"), Err(SnbtError::UnexpectedEnd));
+    assert_eq!(parse_snbt("{a 1}"), Err(SnbtError::Unexpected(3, '1')));
+    assert_eq!(
+        parse_snbt("300b"),
+        Err(SnbtError::BadValue(0, "300b".into()))
+    );
+    assert!(matches!(
+        parse_snbt("[B; 1b, 2]"),
+        Err(SnbtError::BadValue(8, _))
+    ));
+    assert_eq!(parse_snbt("{} {}"), Err(SnbtError::TrailingData(3)));
+}
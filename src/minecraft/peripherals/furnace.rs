@@ -0,0 +1,548 @@
+// Furnaces, and keeping a bunch of them busy.
+//
+// Through the generic inventory peripheral a furnace is just 3 slots: input, fuel, and output. Each
+// smelt takes 10 seconds, and fuel burns for a set amount of time (see vanilla/fuel.rs), so we can
+// predict exactly when a batch will be done without having to go check on it.
+
+use std::collections::VecDeque;
+
+use crate::minecraft::{
+    peripherals::inventory::{GenericInventorySlot, Inventory, MAX_STACK_SIZE},
+    types::MinecraftPosition,
+    vanilla::{
+        fuel::{TICKS_PER_SMELT, fuel_burn_ticks},
+        item_type::MinecraftItem,
+        smelting::smelting_output,
+    },
+};
+
+/// The slot the items to smelt go in.
+pub const INPUT_SLOT: u16 = 1;
+/// The slot fuel goes in.
+pub const FUEL_SLOT: u16 = 2;
+/// The slot smelted items come out of.
+pub const OUTPUT_SLOT: u16 = 3;
+
+/// `os.epoch("ingame")` counts in in-game milliseconds. A day is 24000 ticks, and 24 in-game
+/// hours, so every tick is 3.6 in-game seconds.
+pub const INGAME_MILLIS_PER_TICK: u64 = 3600;
+
+/// Convert `os.epoch("ingame")` into ticks.
+pub fn ticks_from_ingame_epoch(millis: u64) -> u64 {
+    millis / INGAME_MILLIS_PER_TICK
+}
+
+// ==
+// Furnace
+// ==
+
+/// A furnace, and what it is currently doing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Furnace {
+    slots: [Option<GenericInventorySlot>; 3],
+    /// How many more ticks the current piece of fuel will burn for.
+    pub burn_left: u32,
+    /// How far along the current smelt is, out of `TICKS_PER_SMELT`.
+    pub progress: u32,
+}
+
+impl Inventory for Furnace {
+    fn size(&self) -> u16 {
+        3
+    }
+
+    fn get_slot(&self, slot: u16) -> Option<GenericInventorySlot> {
+        self.slots
+            .get(usize::from(slot).checked_sub(1)?)
+            .copied()
+            .flatten()
+    }
+
+    fn set_slot(&mut self, slot: u16, contents: Option<GenericInventorySlot>) {
+        let Some(index) = usize::from(slot).checked_sub(1) else {
+            return;
+        };
+        if let Some(existing) = self.slots.get_mut(index) {
+            *existing = contents.filter(|contents| contents.count > 0);
+        }
+    }
+
+    fn can_insert(&self, slot: u16, item: &MinecraftItem) -> bool {
+        match slot {
+            INPUT_SLOT => smelting_output(item).is_some(),
+            FUEL_SLOT => fuel_burn_ticks(item).is_some(),
+            // Only the furnace can put things in the output.
+            _ => false,
+        }
+    }
+}
+
+impl Furnace {
+    /// An empty, cold furnace.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Can the output slot take another of this item?
+    fn output_has_room(&self, item: &MinecraftItem) -> bool {
+        match self.get_slot(OUTPUT_SLOT) {
+            None => true,
+            Some(output) => output.item == *item && output.count < MAX_STACK_SIZE,
+        }
+    }
+
+    /// Put a batch in, with its input and fuel in their own slots.
+    ///
+    /// `insert` would do, except it tops up any matching stack, so logs burned for charcoal would
+    /// all end up in the input. Nothing is loaded unless everything fits.
+    pub fn load(&mut self, batch: &SmeltBatch) -> Result<(), SmeltingError> {
+        let loads = [
+            (INPUT_SLOT, batch.input, batch.count),
+            (FUEL_SLOT, batch.fuel, batch.fuel_count),
+        ];
+        let mut filled = [None; 2];
+        for (index, (slot, item, count)) in loads.into_iter().enumerate() {
+            let existing = match self.get_slot(slot) {
+                None => 0,
+                Some(contents) if contents.item == item => contents.count,
+                Some(contents) => {
+                    return Err(SmeltingError::SlotTaken(
+                        slot,
+                        contents.item.get_name().clone(),
+                    ));
+                }
+            };
+            let total = u16::from(existing) + u16::from(count);
+            if !self.can_insert(slot, &item) || total > u16::from(self.slot_limit(slot)) {
+                return Err(SmeltingError::DoesntFit(slot, item.get_name().clone()));
+            }
+            filled[index] = Some(GenericInventorySlot {
+                item,
+                count: total as u8,
+            });
+        }
+        self.set_slot(INPUT_SLOT, filled[0]);
+        self.set_slot(FUEL_SLOT, filled[1]);
+        Ok(())
+    }
+
+    /// Run the furnace for some number of ticks. Returns how many items were smelted.
+    pub fn simulate(&mut self, mut ticks: u64) -> u32 {
+        let mut smelted = 0;
+        while ticks > 0 {
+            let Some(input) = self.get_slot(INPUT_SLOT) else {
+                break;
+            };
+            let Some(output) = smelting_output(&input.item) else {
+                break;
+            };
+            if !self.output_has_room(&output) {
+                break;
+            }
+
+            // Light another piece of fuel if we need to.
+            if self.burn_left == 0 {
+                let Some(fuel) = self.get_slot(FUEL_SLOT) else {
+                    break;
+                };
+                self.burn_left = fuel_burn_ticks(&fuel.item).unwrap_or(0);
+                self.remove(&fuel.item, 1);
+                if self.burn_left == 0 {
+                    break;
+                }
+            }
+
+            let step = u64::from(TICKS_PER_SMELT - self.progress)
+                .min(u64::from(self.burn_left))
+                .min(ticks);
+            self.progress += step as u32;
+            self.burn_left -= step as u32;
+            ticks -= step;
+
+            if self.progress == TICKS_PER_SMELT {
+                self.progress = 0;
+                self.remove(&input.item, 1);
+                let count = self.get_slot(OUTPUT_SLOT).map_or(0, |slot| slot.count);
+                self.set_slot(
+                    OUTPUT_SLOT,
+                    Some(GenericInventorySlot {
+                        item: output,
+                        count: count + 1,
+                    }),
+                );
+                smelted += 1;
+            }
+        }
+
+        // Fuel keeps burning even with nothing to smelt.
+        self.burn_left = self
+            .burn_left
+            .saturating_sub(u32::try_from(ticks).unwrap_or(u32::MAX));
+        smelted
+    }
+}
+
+// ==
+// Smelting jobs
+// ==
+
+/// Reasons we can't smelt something.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SmeltingError {
+    /// Nothing comes out of the furnace for this item.
+    NotSmeltable(String),
+    /// This item doesn't burn.
+    NotAFuel(String),
+    /// There is no furnace with this index.
+    NoSuchFurnace(usize),
+    /// Something else is already in this furnace slot.
+    SlotTaken(u16, String),
+    /// This many of the item won't go in this furnace slot.
+    DoesntFit(u16, String),
+}
+
+/// One furnace load of items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmeltBatch {
+    pub input: MinecraftItem,
+    pub count: u8,
+    pub fuel: MinecraftItem,
+    /// How much fuel to load, enough to smelt everything in the batch.
+    pub fuel_count: u8,
+}
+
+pub type JobId = u64;
+
+/// A batch that is in a furnace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmeltingJob {
+    pub id: JobId,
+    /// Which furnace it's in.
+    pub furnace: usize,
+    pub batch: SmeltBatch,
+    /// When the batch was loaded, in ticks.
+    pub started_at: u64,
+}
+
+impl SmeltingJob {
+    /// The tick that the last item comes out.
+    pub fn finishes_at(&self) -> u64 {
+        self.started_at + u64::from(self.batch.count) * u64::from(TICKS_PER_SMELT)
+    }
+
+    /// Is the batch done by this tick?
+    pub fn is_done(&self, now: u64) -> bool {
+        now >= self.finishes_at()
+    }
+}
+
+/// A turtle needs to go empty a furnace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pickup {
+    pub job: JobId,
+    pub furnace: usize,
+    /// Where the furnace is.
+    pub position: MinecraftPosition,
+    /// What will be waiting in the output.
+    pub item: MinecraftItem,
+    pub count: u8,
+    /// The tick it'll all be there.
+    pub ready_at: u64,
+}
+
+/// How much of a fuel it takes to smelt some number of items.
+pub fn fuel_needed(count: u32, fuel: &MinecraftItem) -> Option<u32> {
+    let burn = fuel_burn_ticks(fuel)?;
+    Some((count * TICKS_PER_SMELT).div_ceil(burn))
+}
+
+/// Split a smelting order into furnace sized batches.
+///
+/// Batches are limited by the output stack size, and by how much fuel fits in the fuel slot.
+pub fn plan_batches(
+    input: MinecraftItem,
+    count: u32,
+    fuel: MinecraftItem,
+) -> Result<Vec<SmeltBatch>, SmeltingError> {
+    if smelting_output(&input).is_none() {
+        return Err(SmeltingError::NotSmeltable(input.get_name().clone()));
+    }
+    let Some(burn) = fuel_burn_ticks(&fuel) else {
+        return Err(SmeltingError::NotAFuel(fuel.get_name().clone()));
+    };
+
+    // A full fuel slot can only smelt so much.
+    let per_fuel_slot = u32::from(MAX_STACK_SIZE) * burn / TICKS_PER_SMELT;
+    let per_batch = per_fuel_slot.min(u32::from(MAX_STACK_SIZE));
+    if per_batch == 0 {
+        return Err(SmeltingError::NotAFuel(fuel.get_name().clone()));
+    }
+
+    let mut batches = vec![];
+    let mut remaining = count;
+    while remaining > 0 {
+        let amount = remaining.min(per_batch);
+        remaining -= amount;
+        batches.push(SmeltBatch {
+            input,
+            count: amount as u8,
+            fuel,
+            fuel_count: fuel_needed(amount, &fuel).expect("Checked that this is a fuel") as u8,
+        });
+    }
+    Ok(batches)
+}
+
+/// A furnace we're managing.
+#[derive(Debug, Clone)]
+struct ManagedFurnace {
+    position: MinecraftPosition,
+    furnace: Furnace,
+    job: Option<SmeltingJob>,
+    /// The last tick we simulated up to.
+    updated_at: u64,
+}
+
+/// Keeps track of a group of furnaces, and what's cooking in them.
+#[derive(Debug, Clone, Default)]
+pub struct SmeltingManager {
+    furnaces: Vec<ManagedFurnace>,
+    queue: VecDeque<SmeltBatch>,
+    next_id: JobId,
+}
+
+impl SmeltingManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start managing a furnace. Returns its index.
+    pub fn add_furnace(&mut self, position: MinecraftPosition) -> usize {
+        self.furnaces.push(ManagedFurnace {
+            position,
+            furnace: Furnace::new(),
+            job: None,
+            updated_at: 0,
+        });
+        self.furnaces.len() - 1
+    }
+
+    /// Our model of a furnace.
+    pub fn furnace(&self, index: usize) -> Option<&Furnace> {
+        self.furnaces.get(index).map(|managed| &managed.furnace)
+    }
+
+    /// Queue up a smelting order. It gets split into batches that go into furnaces as they free up.
+    pub fn order(
+        &mut self,
+        input: MinecraftItem,
+        count: u32,
+        fuel: MinecraftItem,
+    ) -> Result<(), SmeltingError> {
+        self.queue.extend(plan_batches(input, count, fuel)?);
+        Ok(())
+    }
+
+    /// How many batches haven't been put in a furnace yet.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Catch every furnace up to the current tick, and load queued batches into idle furnaces.
+    ///
+    /// Batches that don't fit in a furnace stay queued for the next one.
+    ///
+    /// Returns the jobs that were started, so turtles can be sent to load them.
+    pub fn update(&mut self, now: u64) -> Vec<SmeltingJob> {
+        let mut started = vec![];
+        for (index, managed) in self.furnaces.iter_mut().enumerate() {
+            managed
+                .furnace
+                .simulate(now.saturating_sub(managed.updated_at));
+            managed.updated_at = now;
+
+            // Idle means no job, and nothing left to pick up.
+            if managed.job.is_some() || managed.furnace.get_slot(OUTPUT_SLOT).is_some() {
+                continue;
+            }
+            let Some(batch) = self.queue.pop_front() else {
+                continue;
+            };
+            if managed.furnace.load(&batch).is_err() {
+                self.queue.push_front(batch);
+                continue;
+            }
+            let job = SmeltingJob {
+                id: self.next_id,
+                furnace: index,
+                batch,
+                started_at: now,
+            };
+            self.next_id += 1;
+            managed.job = Some(job);
+            started.push(job);
+        }
+        started
+    }
+
+    /// Every batch a turtle will need to come collect, soonest first.
+    pub fn pickups(&self) -> Vec<Pickup> {
+        let mut pickups: Vec<Pickup> = self
+            .furnaces
+            .iter()
+            .filter_map(|managed| {
+                let job = managed.job?;
+                Some(Pickup {
+                    job: job.id,
+                    furnace: job.furnace,
+                    position: managed.position,
+                    item: smelting_output(&job.batch.input)?,
+                    count: job.batch.count,
+                    ready_at: job.finishes_at(),
+                })
+            })
+            .collect();
+        pickups.sort_by_key(|pickup| pickup.ready_at);
+        pickups
+    }
+
+    /// A turtle emptied the output of a furnace. Returns what it should have gotten.
+    ///
+    /// The job is finished once everything has been collected.
+    pub fn collect(
+        &mut self,
+        furnace: usize,
+        now: u64,
+    ) -> Result<Option<GenericInventorySlot>, SmeltingError> {
+        let managed = self
+            .furnaces
+            .get_mut(furnace)
+            .ok_or(SmeltingError::NoSuchFurnace(furnace))?;
+        managed
+            .furnace
+            .simulate(now.saturating_sub(managed.updated_at));
+        managed.updated_at = now;
+
+        let output = managed.furnace.get_slot(OUTPUT_SLOT);
+        managed.furnace.set_slot(OUTPUT_SLOT, None);
+        if managed.job.is_some_and(|job| job.is_done(now)) {
+            managed.job = None;
+        }
+        Ok(output)
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+fn item(name: &str) -> MinecraftItem {
+    MinecraftItem::from_string(name).unwrap()
+}
+
+#[test]
+/// Coal smelts 8 items, and each one takes 10 seconds.
+fn furnace_simulation() {
+    let mut furnace = Furnace::new();
+    assert_eq!(furnace.insert(item("cobblestone"), 10), 0);
+    // Coal can only go in the fuel slot, and stone can't go anywhere.
+    assert_eq!(furnace.insert(item("coal"), 1), 0);
+    assert_eq!(furnace.get_slot(FUEL_SLOT).unwrap().item, item("coal"));
+    assert_eq!(furnace.insert(item("stone"), 1), 1);
+
+    // Half way through the first item.
+    assert_eq!(furnace.simulate(100), 0);
+    assert_eq!(furnace.simulate(100), 1);
+    // Runs out of coal after 8.
+    assert_eq!(furnace.simulate(100_000), 7);
+    assert_eq!(furnace.count(&item("stone")), 8);
+    assert_eq!(furnace.count(&item("cobblestone")), 2);
+}
+
+#[test]
+/// Batches, fuel, and pickups.
+fn smelting_manager_schedules_pickups() {
+    assert_eq!(fuel_needed(7, &item("coal")), Some(1));
+    assert_eq!(fuel_needed(9, &item("coal")), Some(2));
+
+    // Sticks only burn for half an item, so a full fuel slot only does 32.
+    let batches = plan_batches(item("sand"), 40, item("stick")).unwrap();
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[0].count, 32);
+    assert_eq!(batches[0].fuel_count, 64);
+    assert!(plan_batches(item("stone_bricks"), 1, item("coal")).is_err());
+
+    let mut manager = SmeltingManager::new();
+    let furnace = manager.add_furnace(MinecraftPosition {
+        x: 0,
+        y: 64,
+        z: 0,
+        facing: None,
+    });
+    manager.order(item("raw_iron"), 70, item("coal")).unwrap();
+    assert_eq!(manager.queued(), 2);
+
+    let started = manager.update(1000);
+    assert_eq!(started.len(), 1);
+    let pickups = manager.pickups();
+    assert_eq!(pickups[0].item, item("iron_ingot"));
+    assert_eq!(pickups[0].ready_at, 1000 + 64 * 200);
+
+    // Nothing new starts while the furnace is busy.
+    assert!(manager.update(2000).is_empty());
+
+    // Collect it once its done, then the next batch can go in.
+    let collected = manager
+        .collect(furnace, pickups[0].ready_at)
+        .unwrap()
+        .unwrap();
+    assert_eq!(collected.count, 64);
+    assert_eq!(manager.update(pickups[0].ready_at).len(), 1);
+    assert_eq!(manager.queued(), 0);
+}
+
+#[test]
+/// Fuel goes in the fuel slot even when it could be smelted too.
+fn smelting_manager_smeltable_fuel() {
+    let mut manager = SmeltingManager::new();
+    let furnace = manager.add_furnace(MinecraftPosition {
+        x: 0,
+        y: 64,
+        z: 0,
+        facing: None,
+    });
+    // Logs burn for one and a half smelts.
+    manager.order(item("oak_log"), 10, item("oak_log")).unwrap();
+    assert_eq!(manager.update(0).len(), 1);
+    let loaded = manager.furnace(furnace).unwrap();
+    assert_eq!(loaded.get_slot(INPUT_SLOT).unwrap().count, 10);
+    assert_eq!(loaded.get_slot(FUEL_SLOT).unwrap().count, 7);
+
+    // Leftovers that don't match the batch keep it out.
+    let mut furnace = Furnace::new();
+    furnace.set_slot(
+        FUEL_SLOT,
+        Some(GenericInventorySlot {
+            item: item("coal"),
+            count: 1,
+        }),
+    );
+    let batch = plan_batches(item("oak_log"), 10, item("oak_log")).unwrap()[0];
+    assert_eq!(
+        furnace.load(&batch),
+        Err(SmeltingError::SlotTaken(FUEL_SLOT, "coal".to_string()))
+    );
+    assert_eq!(furnace.get_slot(INPUT_SLOT), None);
+    furnace.set_slot(
+        FUEL_SLOT,
+        Some(GenericInventorySlot {
+            item: item("oak_log"),
+            count: 60,
+        }),
+    );
+    assert_eq!(
+        furnace.load(&batch),
+        Err(SmeltingError::DoesntFit(FUEL_SLOT, "oak_log".to_string()))
+    );
+}
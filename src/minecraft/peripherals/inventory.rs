@@ -1,10 +1,122 @@
 // Generic inventories.
+//
+// This mirrors CC:Tweaked's generic inventory peripheral, so slots start at 1.
 
 use std::collections::HashMap;
 
 use crate::minecraft::vanilla::item_type::MinecraftItem;
 
-// TODO: if we implement the methods as a trait, then it would be easier to add new things.
+/// The most items that can be in a single slot.
+pub const MAX_STACK_SIZE: u8 = 64;
+
+/// Anything that holds items in slots.
+///
+/// Implementors only need to provide slot access, everything else is built on top of it.
+pub trait Inventory {
+    /// How many slots there are.
+    fn size(&self) -> u16;
+
+    /// What is in a slot, if anything.
+    fn get_slot(&self, slot: u16) -> Option<GenericInventorySlot>;
+
+    /// Replace the contents of a slot. This does not check `can_insert`, the caller should.
+    fn set_slot(&mut self, slot: u16, contents: Option<GenericInventorySlot>);
+
+    /// Can this item go in this slot at all? Furnaces only take fuel in the fuel slot, for example.
+    fn can_insert(&self, _slot: u16, _item: &MinecraftItem) -> bool {
+        true
+    }
+
+    /// The most items this slot can hold.
+    fn slot_limit(&self, _slot: u16) -> u8 {
+        MAX_STACK_SIZE
+    }
+
+    /// Every slot that has something in it.
+    fn list(&self) -> Vec<(u16, GenericInventorySlot)> {
+        (1..=self.size())
+            .filter_map(|slot| self.get_slot(slot).map(|contents| (slot, contents)))
+            .collect()
+    }
+
+    /// How many of an item are in the inventory in total.
+    fn count(&self, item: &MinecraftItem) -> u32 {
+        self.list()
+            .iter()
+            .filter(|(_, contents)| contents.item == *item)
+            .map(|(_, contents)| u32::from(contents.count))
+            .sum()
+    }
+
+    /// Put items in, topping up existing stacks before using empty slots.
+    ///
+    /// Returns how many items didn't fit.
+    fn insert(&mut self, item: MinecraftItem, mut count: u32) -> u32 {
+        // Existing stacks first, then empty slots.
+        for stacking in [true, false] {
+            for slot in 1..=self.size() {
+                if count == 0 {
+                    return 0;
+                }
+                if !self.can_insert(slot, &item) {
+                    continue;
+                }
+                let current = self.get_slot(slot);
+                let existing = match current {
+                    Some(contents) if stacking && contents.item == item => contents.count,
+                    None if !stacking => 0,
+                    _ => continue,
+                };
+                let space = u32::from(self.slot_limit(slot).saturating_sub(existing));
+                let moved = space.min(count);
+                if moved == 0 {
+                    continue;
+                }
+                count -= moved;
+                self.set_slot(
+                    slot,
+                    Some(GenericInventorySlot {
+                        item,
+                        count: existing + moved as u8,
+                    }),
+                );
+            }
+        }
+        count
+    }
+
+    /// Take items out, from the last slots first like a turtle would.
+    ///
+    /// Returns how many were actually removed.
+    fn remove(&mut self, item: &MinecraftItem, count: u32) -> u32 {
+        let mut removed = 0;
+        for slot in (1..=self.size()).rev() {
+            if removed == count {
+                break;
+            }
+            let Some(contents) = self.get_slot(slot) else {
+                continue;
+            };
+            if contents.item != *item {
+                continue;
+            }
+            let taken = (count - removed).min(u32::from(contents.count));
+            removed += taken;
+            let left = contents.count - taken as u8;
+            self.set_slot(
+                slot,
+                (left > 0).then_some(GenericInventorySlot {
+                    item: contents.item,
+                    count: left,
+                }),
+            );
+        }
+        removed
+    }
+}
+
+/// A plain inventory, like a chest or a turtle.
+#[derive(Debug, Clone)]
 pub struct GenericInventory {
     /// The size of the inventory, IE how many slots it has.
     size: u16,
@@ -12,9 +124,68 @@ pub struct GenericInventory {
     slots: HashMap<u16, GenericInventorySlot>,
 }
 
+/// The contents of a slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct GenericInventorySlot {
     // What item is in this slot.
-    item: Option<MinecraftItem>,
+    pub item: MinecraftItem,
     /// How many of that item are in the slot.
-    count: Option<u8>,
+    pub count: u8,
+}
+
+impl GenericInventory {
+    /// An empty inventory with this many slots.
+    pub fn new(size: u16) -> Self {
+        Self {
+            size,
+            slots: HashMap::new(),
+        }
+    }
+}
+
+impl Inventory for GenericInventory {
+    fn size(&self) -> u16 {
+        self.size
+    }
+
+    fn get_slot(&self, slot: u16) -> Option<GenericInventorySlot> {
+        self.slots.get(&slot).copied()
+    }
+
+    fn set_slot(&mut self, slot: u16, contents: Option<GenericInventorySlot>) {
+        if slot == 0 || slot > self.size {
+            return;
+        }
+        match contents {
+            Some(contents) if contents.count > 0 => {
+                self.slots.insert(slot, contents);
+            }
+            _ => {
+                self.slots.remove(&slot);
+            }
+        }
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Items should stack before spilling into new slots.
+fn inventory_insert_and_remove() {
+    let cobble = MinecraftItem::from_string("cobblestone").unwrap();
+    let sand = MinecraftItem::from_string("sand").unwrap();
+    let mut chest = GenericInventory::new(3);
+
+    assert_eq!(chest.insert(cobble, 70), 0);
+    assert_eq!(chest.insert(sand, 10), 0);
+    assert_eq!(chest.insert(cobble, 100), 100 - 58);
+    assert_eq!(chest.count(&cobble), 128);
+    assert_eq!(chest.list().len(), 3);
+
+    assert_eq!(chest.remove(&cobble, 65), 65);
+    assert_eq!(chest.count(&cobble), 63);
+    assert_eq!(chest.remove(&sand, 20), 10);
+    assert_eq!(chest.list().len(), 1);
 }
@@ -1 +1,2 @@
+pub mod furnace;
 pub mod inventory;
@@ -0,0 +1,860 @@
+// Schematics, a box of blocks that we want to build (or have scanned).
+// Builders design things in-game with structure blocks, or in WorldEdit / Litematica, so we read
+// all of those formats into one type.
+//
+// Formats:
+// - Vanilla structure files (.nbt) https://minecraft.wiki/w/Structure_file
+// - Sponge schematics (.schem) v2 and v3 https://github.com/SpongePowered/Schematic-Specification
+// - Litematica (.litematic)
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::minecraft::{
+    nbt::{NbtError, NbtTag, read_nbt, write_nbt},
+    types::MinecraftPosition,
+    vanilla::block_state::{BlockState, BlockStateError},
+};
+
+/// The data version we write into structure files, this is 1.21.1
+pub const STRUCTURE_DATA_VERSION: i32 = 3955;
+
+/// A box of blocks.
+///
+/// Positions are relative to the minimum corner of the box, so everything is in `0..size`.
+/// Positions without a block are "structure void", meaning whatever is in the world there should
+/// be left alone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schematic {
+    /// How big the box is on each axis.
+    pub size: (i64, i64, i64),
+    /// The blocks, keyed by relative position.
+    pub blocks: HashMap<(i64, i64, i64), BlockState>,
+}
+
+/// Things that can go wrong when loading a schematic.
+#[derive(Debug)]
+pub enum SchematicError {
+    /// The file wasn't valid NBT.
+    Nbt(NbtError),
+    /// We couldn't tell what kind of schematic this is.
+    UnknownFormat,
+    /// A required field was missing or the wrong type.
+    MissingField(&'static str),
+    /// A block, or one of its properties, isn't valid.
+    BlockState(BlockStateError),
+    /// A block referenced a palette entry that doesn't exist.
+    BadPaletteIndex(i64),
+    /// The box has no room for the blocks it says it has, IE a width of 0.
+    BadSize((i64, i64, i64)),
+}
+
+impl From<NbtError> for SchematicError {
+    fn from(value: NbtError) -> Self {
+        SchematicError::Nbt(value)
+    }
+}
+
+impl From<BlockStateError> for SchematicError {
+    fn from(value: BlockStateError) -> Self {
+        SchematicError::BlockState(value)
+    }
+}
+
+// ==
+// Block helpers
+// ==
+
+/// Read a palette entry, which is a compound of `Name` and optional `Properties`.
+fn from_palette_entry(entry: &NbtTag) -> Result<BlockState, SchematicError> {
+    let name = entry
+        .get("Name")
+        .and_then(NbtTag::as_str)
+        .ok_or(SchematicError::MissingField("Name"))?;
+    let mut state = BlockState::parse(name)?;
+    if let Some(props) = entry.get("Properties").and_then(NbtTag::as_compound) {
+        for (key, value) in props {
+            if let Some(value) = value.as_str() {
+                state.set(key, value)?;
+            }
+        }
+    }
+    Ok(state)
+}
+
+/// Turn a block state back into a palette entry.
+fn to_palette_entry(state: &BlockState) -> NbtTag {
+    let mut entry = BTreeMap::new();
+    entry.insert(
+        "Name".to_string(),
+        NbtTag::String(state.block().get_full_name().into_owned()),
+    );
+    if !state.properties().is_empty() {
+        let properties = state
+            .properties()
+            .iter()
+            .map(|(key, value)| (key.clone(), NbtTag::String(value.clone())))
+            .collect();
+        entry.insert("Properties".to_string(), NbtTag::Compound(properties));
+    }
+    NbtTag::Compound(entry)
+}
+
+/// Structure void isn't a real block, it just means "leave this alone".
+fn is_structure_void(name: &str) -> bool {
+    name == "minecraft:structure_void" || name == "structure_void"
+}
+
+// ==
+// Importing
+// ==
+
+impl Schematic {
+    /// Load a schematic, guessing the format from the contents.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SchematicError> {
+        let (_, root) = read_nbt(bytes)?;
+        if root.get("Regions").is_some() {
+            Self::from_litematic_tag(&root)
+        } else if root.get("Schematic").is_some() || root.get("Width").is_some() {
+            Self::from_sponge_tag(&root)
+        } else if root.get("blocks").is_some() {
+            Self::from_structure_tag(&root)
+        } else {
+            Err(SchematicError::UnknownFormat)
+        }
+    }
+
+    /// Load a vanilla structure block file.
+    pub fn from_structure_nbt(bytes: &[u8]) -> Result<Self, SchematicError> {
+        Self::from_structure_tag(&read_nbt(bytes)?.1)
+    }
+
+    /// Load a Sponge schematic, version 2 or 3.
+    pub fn from_sponge(bytes: &[u8]) -> Result<Self, SchematicError> {
+        Self::from_sponge_tag(&read_nbt(bytes)?.1)
+    }
+
+    /// Load a Litematica schematic. All regions are merged into one box.
+    pub fn from_litematic(bytes: &[u8]) -> Result<Self, SchematicError> {
+        Self::from_litematic_tag(&read_nbt(bytes)?.1)
+    }
+
+    fn from_structure_tag(root: &NbtTag) -> Result<Self, SchematicError> {
+        let size = read_int_list(root.get("size")).ok_or(SchematicError::MissingField("size"))?;
+
+        // Structures with random variants (shipwrecks and such) have `palettes` instead, we just
+        // use the first one.
+        let palette = root
+            .get("palette")
+            .and_then(NbtTag::as_list)
+            .or_else(|| {
+                root.get("palettes")
+                    .and_then(NbtTag::as_list)
+                    .and_then(|palettes| palettes.first())
+                    .and_then(NbtTag::as_list)
+            })
+            .ok_or(SchematicError::MissingField("palette"))?;
+        let palette = read_palette_list(palette)?;
+
+        let mut schematic = Schematic {
+            size,
+            blocks: HashMap::new(),
+        };
+        let blocks = root
+            .get("blocks")
+            .and_then(NbtTag::as_list)
+            .ok_or(SchematicError::MissingField("blocks"))?;
+        for entry in blocks {
+            let position =
+                read_int_list(entry.get("pos")).ok_or(SchematicError::MissingField("pos"))?;
+            let state = entry
+                .get("state")
+                .and_then(NbtTag::as_i64)
+                .ok_or(SchematicError::MissingField("state"))?;
+            schematic.insert_from_palette(position, &palette, state)?;
+        }
+        Ok(schematic)
+    }
+
+    fn from_sponge_tag(root: &NbtTag) -> Result<Self, SchematicError> {
+        // v3 wraps everything in a `Schematic` compound, v2 is the root itself.
+        let schem = root.get("Schematic").unwrap_or(root);
+        let dimension = |name: &'static str| {
+            schem
+                .get(name)
+                .and_then(NbtTag::as_i64)
+                // Sizes are stored as shorts, but are actually unsigned.
+                .map(|value| i64::from(value as u16))
+                .ok_or(SchematicError::MissingField(name))
+        };
+        let (width, height, length) = (
+            dimension("Width")?,
+            dimension("Height")?,
+            dimension("Length")?,
+        );
+        // We divide by these to find positions, so an empty box would crash.
+        if width == 0 || height == 0 || length == 0 {
+            return Err(SchematicError::BadSize((width, height, length)));
+        }
+
+        // v3 moved the block data into a `Blocks` compound.
+        let (palette, data) = match schem.get("Blocks") {
+            Some(blocks) => (blocks.get("Palette"), blocks.get("Data")),
+            None => (schem.get("Palette"), schem.get("BlockData")),
+        };
+        let palette = palette
+            .and_then(NbtTag::as_compound)
+            .ok_or(SchematicError::MissingField("Palette"))?;
+        let Some(NbtTag::ByteArray(data)) = data else {
+            return Err(SchematicError::MissingField("BlockData"));
+        };
+
+        // The palette maps block state strings to indexes, we want the other way around.
+        let mut states: HashMap<i64, Option<BlockState>> = HashMap::new();
+        for (state, index) in palette {
+            let index = index
+                .as_i64()
+                .ok_or(SchematicError::MissingField("Palette"))?;
+            let block = if is_structure_void(state.split('[').next().unwrap_or_default()) {
+                None
+            } else {
+                Some(BlockState::parse(state)?)
+            };
+            states.insert(index, block);
+        }
+
+        let mut schematic = Schematic {
+            size: (width, height, length),
+            blocks: HashMap::new(),
+        };
+
+        // Block data is a list of varints, in YZX order.
+        let mut bytes = data.iter().map(|b| *b as u8);
+        let mut index: i64 = 0;
+        while let Some(state) = read_varint(&mut bytes) {
+            let x = index % width;
+            let z = (index / width) % length;
+            let y = index / (width * length);
+            index += 1;
+            if y >= height {
+                break;
+            }
+            match states.get(&state) {
+                Some(Some(block)) => {
+                    schematic.blocks.insert((x, y, z), block.clone());
+                }
+                Some(None) => {}
+                None => return Err(SchematicError::BadPaletteIndex(state)),
+            }
+        }
+        Ok(schematic)
+    }
+
+    fn from_litematic_tag(root: &NbtTag) -> Result<Self, SchematicError> {
+        let regions = root
+            .get("Regions")
+            .and_then(NbtTag::as_compound)
+            .ok_or(SchematicError::MissingField("Regions"))?;
+
+        // Regions can be anywhere relative to each other, and can have negative sizes, so we put
+        // everything into absolute positions first, then shift it all to start at zero.
+        let mut absolute: HashMap<(i64, i64, i64), BlockState> = HashMap::new();
+        let mut min = (i64::MAX, i64::MAX, i64::MAX);
+        let mut max = (i64::MIN, i64::MIN, i64::MIN);
+
+        for region in regions.values() {
+            let position = read_xyz_compound(region.get("Position"))
+                .ok_or(SchematicError::MissingField("Position"))?;
+            let size = read_xyz_compound(region.get("Size"))
+                .ok_or(SchematicError::MissingField("Size"))?;
+            let palette = region
+                .get("BlockStatePalette")
+                .and_then(NbtTag::as_list)
+                .ok_or(SchematicError::MissingField("BlockStatePalette"))?;
+            let palette = read_palette_list(palette)?;
+            let Some(NbtTag::LongArray(states)) = region.get("BlockStates") else {
+                return Err(SchematicError::MissingField("BlockStates"));
+            };
+
+            // A negative size means the region extends backwards from its position.
+            let corner = |position: i64, size: i64| {
+                if size < 0 {
+                    position + size + 1
+                } else {
+                    position
+                }
+            };
+            let origin = (
+                corner(position.0, size.0),
+                corner(position.1, size.1),
+                corner(position.2, size.2),
+            );
+            let (sx, sy, sz) = (size.0.abs(), size.1.abs(), size.2.abs());
+            if sx == 0 || sy == 0 || sz == 0 {
+                continue;
+            }
+            min = (
+                min.0.min(origin.0),
+                min.1.min(origin.1),
+                min.2.min(origin.2),
+            );
+            max = (
+                max.0.max(origin.0 + sx),
+                max.1.max(origin.1 + sy),
+                max.2.max(origin.2 + sz),
+            );
+
+            // Litematica packs states as tightly as possible, values can span two longs.
+            let bits = (usize::BITS - (palette.len().max(1) - 1).leading_zeros()).max(2) as usize;
+            for y in 0..sy {
+                for z in 0..sz {
+                    for x in 0..sx {
+                        let index = ((y * sz + z) * sx + x) as usize;
+                        let state = unpack_bits(states, index, bits)
+                            .ok_or(SchematicError::MissingField("BlockStates"))?;
+                        let block = palette
+                            .get(state as usize)
+                            .ok_or(SchematicError::BadPaletteIndex(state as i64))?;
+                        if let Some(block) = block {
+                            absolute
+                                .insert((origin.0 + x, origin.1 + y, origin.2 + z), block.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        // No regions with any volume.
+        if min.0 == i64::MAX {
+            return Ok(Schematic::default());
+        }
+        Ok(Schematic {
+            size: (max.0 - min.0, max.1 - min.1, max.2 - min.2),
+            blocks: absolute
+                .into_iter()
+                .map(|((x, y, z), block)| ((x - min.0, y - min.1, z - min.2), block))
+                .collect(),
+        })
+    }
+
+    fn insert_from_palette(
+        &mut self,
+        position: (i64, i64, i64),
+        palette: &[Option<BlockState>],
+        state: i64,
+    ) -> Result<(), SchematicError> {
+        let block = usize::try_from(state)
+            .ok()
+            .and_then(|index| palette.get(index))
+            .ok_or(SchematicError::BadPaletteIndex(state))?;
+        if let Some(block) = block {
+            self.blocks.insert(position, block.clone());
+        }
+        Ok(())
+    }
+}
+
+/// Read a palette list, structure void becomes `None`.
+fn read_palette_list(list: &[NbtTag]) -> Result<Vec<Option<BlockState>>, SchematicError> {
+    list.iter()
+        .map(|entry| {
+            let name = entry
+                .get("Name")
+                .and_then(NbtTag::as_str)
+                .unwrap_or_default();
+            if is_structure_void(name) {
+                Ok(None)
+            } else {
+                from_palette_entry(entry).map(Some)
+            }
+        })
+        .collect()
+}
+
+/// Read a list of three ints, used for sizes and positions in structure files.
+fn read_int_list(tag: Option<&NbtTag>) -> Option<(i64, i64, i64)> {
+    let list = tag?.as_list()?;
+    match list.as_slice() {
+        [x, y, z] => Some((x.as_i64()?, y.as_i64()?, z.as_i64()?)),
+        _ => None,
+    }
+}
+
+/// Read a compound with `x`, `y`, and `z`, used by litematica.
+fn read_xyz_compound(tag: Option<&NbtTag>) -> Option<(i64, i64, i64)> {
+    let tag = tag?;
+    Some((
+        tag.get("x")?.as_i64()?,
+        tag.get("y")?.as_i64()?,
+        tag.get("z")?.as_i64()?,
+    ))
+}
+
+/// Read a single varint, as used in sponge block data.
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Option<i64> {
+    let mut value: i64 = 0;
+    for shift in (0..35).step_by(7) {
+        let byte = bytes.next()?;
+        value |= i64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    // Too long, just give up.
+    None
+}
+
+/// Pull the nth value of `bits` width out of a packed long array.
+fn unpack_bits(longs: &[i64], index: usize, bits: usize) -> Option<u64> {
+    let mask = (1u64 << bits) - 1;
+    let start = index * bits;
+    let (word, offset) = (start / 64, start % 64);
+    let mut value = (*longs.get(word)? as u64) >> offset;
+    if offset + bits > 64 {
+        value |= (*longs.get(word + 1)? as u64) << (64 - offset);
+    }
+    Some(value & mask)
+}
+
+// ==
+// Exporting
+// ==
+
+impl Schematic {
+    /// Capture a region of the world into a schematic.
+    ///
+    /// `lookup` tells us what is at a world position, returning `None` leaves that spot as
+    /// structure void.
+    pub fn from_region(
+        pos1: MinecraftPosition,
+        pos2: MinecraftPosition,
+        lookup: impl Fn(&MinecraftPosition) -> Option<BlockState>,
+    ) -> Self {
+        let min = (pos1.x.min(pos2.x), pos1.y.min(pos2.y), pos1.z.min(pos2.z));
+        let max = (pos1.x.max(pos2.x), pos1.y.max(pos2.y), pos1.z.max(pos2.z));
+        let mut blocks = HashMap::new();
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    let position = MinecraftPosition {
+                        x,
+                        y,
+                        z,
+                        facing: None,
+                    };
+                    if let Some(block) = lookup(&position) {
+                        blocks.insert((x - min.0, y - min.1, z - min.2), block);
+                    }
+                }
+            }
+        }
+        Schematic {
+            size: (max.0 - min.0 + 1, max.1 - min.1 + 1, max.2 - min.2 + 1),
+            blocks,
+        }
+    }
+
+    /// Save this schematic as a vanilla structure file, which can be loaded with a structure block.
+    pub fn to_structure_nbt(&self) -> Vec<u8> {
+        let mut palette: Vec<&BlockState> = Vec::new();
+        let mut blocks: Vec<NbtTag> = Vec::with_capacity(self.blocks.len());
+
+        // Sorted so the output is the same every time.
+        let mut positions: Vec<&(i64, i64, i64)> = self.blocks.keys().collect();
+        positions.sort();
+        for position in positions {
+            let block = &self.blocks[position];
+            let state = match palette.iter().position(|existing| *existing == block) {
+                Some(index) => index,
+                None => {
+                    palette.push(block);
+                    palette.len() - 1
+                }
+            };
+            let mut entry = BTreeMap::new();
+            entry.insert("pos".to_string(), int_list(*position));
+            entry.insert("state".to_string(), NbtTag::Int(state as i32));
+            blocks.push(NbtTag::Compound(entry));
+        }
+
+        let mut root = BTreeMap::new();
+        root.insert(
+            "DataVersion".to_string(),
+            NbtTag::Int(STRUCTURE_DATA_VERSION),
+        );
+        root.insert("size".to_string(), int_list(self.size));
+        root.insert(
+            "palette".to_string(),
+            NbtTag::List(palette.iter().map(|b| to_palette_entry(b)).collect()),
+        );
+        root.insert("blocks".to_string(), NbtTag::List(blocks));
+        root.insert("entities".to_string(), NbtTag::List(vec![]));
+        write_nbt("", &NbtTag::Compound(root))
+    }
+
+    /// Iterate over every block, with its position relative to the minimum corner.
+    pub fn iter(&self) -> impl Iterator<Item = (MinecraftPosition, &BlockState)> {
+        self.blocks.iter().map(|(&(x, y, z), block)| {
+            (
+                MinecraftPosition {
+                    x,
+                    y,
+                    z,
+                    facing: None,
+                },
+                block,
+            )
+        })
+    }
+}
+
+fn int_list((x, y, z): (i64, i64, i64)) -> NbtTag {
+    NbtTag::List(vec![
+        NbtTag::Int(x as i32),
+        NbtTag::Int(y as i32),
+        NbtTag::Int(z as i32),
+    ])
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Litematica values that span two longs.
+fn litematic_bit_unpacking() {
+    // 5 bit values, 0..=20 packed back to back.
+    let values: Vec<u64> = (0..=20).collect();
+    let mut longs = vec![0i64; 2];
+    for (index, value) in values.iter().enumerate() {
+        let start = index * 5;
+        let (word, offset) = (start / 64, start % 64);
+        longs[word] |= (value << offset) as i64;
+        if offset + 5 > 64 {
+            longs[word + 1] |= (value >> (64 - offset)) as i64;
+        }
+    }
+    for (index, value) in values.iter().enumerate() {
+        assert_eq!(unpack_bits(&longs, index, 5), Some(*value));
+    }
+    // Off the end
+    assert_eq!(unpack_bits(&longs, 30, 5), None);
+
+    let mut bytes = [0xac, 0x02, 0x05].into_iter();
+    assert_eq!(read_varint(&mut bytes), Some(300));
+    assert_eq!(read_varint(&mut bytes), Some(5));
+    assert_eq!(read_varint(&mut bytes), None);
+}
+
+#[test]
+/// Export a structure, then read it back in.
+fn structure_round_trip() {
+    let stone = BlockState::parse("minecraft:stone").unwrap();
+    let stairs = BlockState::parse("minecraft:oak_stairs[facing=north,half=bottom]").unwrap();
+    assert_eq!(stairs.get("facing"), Some("north"));
+
+    let corner = |x, y, z| MinecraftPosition {
+        x,
+        y,
+        z,
+        facing: None,
+    };
+    let schematic =
+        Schematic::from_region(corner(10, 64, 10), corner(12, 65, 11), |position| {
+            match (position.x, position.y) {
+                (10, _) => Some(stone.clone()),
+                (11, 64) => Some(stairs.clone()),
+                _ => None,
+            }
+        });
+    assert_eq!(schematic.size, (3, 2, 2));
+    assert_eq!(schematic.blocks.len(), 6);
+
+    let bytes = schematic.to_structure_nbt();
+    let loaded = Schematic::from_bytes(&bytes).unwrap();
+    assert_eq!(loaded, schematic);
+}
+
+#[cfg(test)]
+/// Some blocks to import, with a gap of structure void.
+fn test_schematic() -> Schematic {
+    let mut blocks = HashMap::new();
+    blocks.insert((0, 0, 0), BlockState::parse("minecraft:stone").unwrap());
+    blocks.insert((1, 0, 2), BlockState::parse("minecraft:dirt").unwrap());
+    blocks.insert(
+        (2, 1, 1),
+        BlockState::parse("minecraft:oak_stairs[facing=east,half=top]").unwrap(),
+    );
+    Schematic {
+        size: (3, 2, 3),
+        blocks,
+    }
+}
+
+#[cfg(test)]
+/// Where `test_schematic` has a block, and what it is. `None` is structure void.
+fn test_palette_index(
+    schematic: &Schematic,
+    palette: &[BlockState],
+    x: i64,
+    y: i64,
+    z: i64,
+) -> usize {
+    schematic.blocks.get(&(x, y, z)).map_or(0, |block| {
+        palette.iter().position(|b| b == block).unwrap() + 1
+    })
+}
+
+#[cfg(test)]
+/// Write a schematic as a Sponge schematic, the way WorldEdit does.
+fn to_sponge_tag(schematic: &Schematic, v3: bool) -> NbtTag {
+    let mut palette: Vec<BlockState> = schematic.blocks.values().cloned().collect();
+    palette.sort_by_key(BlockState::to_command_string);
+    palette.dedup();
+
+    let (width, height, length) = schematic.size;
+    let mut data = Vec::new();
+    for y in 0..height {
+        for z in 0..length {
+            for x in 0..width {
+                // Everything here is under 128, so each varint is one byte.
+                data.push(test_palette_index(schematic, &palette, x, y, z) as i8);
+            }
+        }
+    }
+    let mut palette_tag: BTreeMap<String, NbtTag> = palette
+        .iter()
+        .enumerate()
+        .map(|(index, block)| (block.to_command_string(), NbtTag::Int(index as i32 + 1)))
+        .collect();
+    palette_tag.insert("minecraft:structure_void".to_string(), NbtTag::Int(0));
+
+    let mut schem = BTreeMap::new();
+    schem.insert("Width".to_string(), NbtTag::Short(width as i16));
+    schem.insert("Height".to_string(), NbtTag::Short(height as i16));
+    schem.insert("Length".to_string(), NbtTag::Short(length as i16));
+    if v3 {
+        let mut blocks = BTreeMap::new();
+        blocks.insert("Palette".to_string(), NbtTag::Compound(palette_tag));
+        blocks.insert("Data".to_string(), NbtTag::ByteArray(data));
+        schem.insert("Version".to_string(), NbtTag::Int(3));
+        schem.insert("Blocks".to_string(), NbtTag::Compound(blocks));
+        let mut root = BTreeMap::new();
+        root.insert("Schematic".to_string(), NbtTag::Compound(schem));
+        NbtTag::Compound(root)
+    } else {
+        schem.insert("Version".to_string(), NbtTag::Int(2));
+        schem.insert("Palette".to_string(), NbtTag::Compound(palette_tag));
+        schem.insert("BlockData".to_string(), NbtTag::ByteArray(data));
+        NbtTag::Compound(schem)
+    }
+}
+
+#[cfg(test)]
+/// Write a schematic as a single region Litematica file, at some offset.
+fn to_litematic_tag(schematic: &Schematic, position: (i64, i64, i64)) -> NbtTag {
+    let mut palette: Vec<BlockState> = schematic.blocks.values().cloned().collect();
+    palette.sort_by_key(BlockState::to_command_string);
+    palette.dedup();
+
+    let (sx, sy, sz) = schematic.size;
+    let bits = (usize::BITS - palette.len().leading_zeros()).max(2) as usize;
+    let volume = (sx * sy * sz) as usize;
+    let mut longs = vec![0i64; (volume * bits).div_ceil(64)];
+    for y in 0..sy {
+        for z in 0..sz {
+            for x in 0..sx {
+                let index = ((y * sz + z) * sx + x) as usize;
+                let value = test_palette_index(schematic, &palette, x, y, z) as u64;
+                let (word, offset) = ((index * bits) / 64, (index * bits) % 64);
+                longs[word] |= (value << offset) as i64;
+                if offset + bits > 64 {
+                    longs[word + 1] |= (value >> (64 - offset)) as i64;
+                }
+            }
+        }
+    }
+
+    let xyz = |(x, y, z): (i64, i64, i64)| {
+        NbtTag::Compound(BTreeMap::from([
+            ("x".to_string(), NbtTag::Int(x as i32)),
+            ("y".to_string(), NbtTag::Int(y as i32)),
+            ("z".to_string(), NbtTag::Int(z as i32)),
+        ]))
+    };
+    let mut palette_tag = vec![NbtTag::Compound(BTreeMap::from([(
+        "Name".to_string(),
+        NbtTag::String("minecraft:structure_void".to_string()),
+    )]))];
+    palette_tag.extend(palette.iter().map(to_palette_entry));
+
+    let region = BTreeMap::from([
+        ("Position".to_string(), xyz(position)),
+        ("Size".to_string(), xyz(schematic.size)),
+        ("BlockStatePalette".to_string(), NbtTag::List(palette_tag)),
+        ("BlockStates".to_string(), NbtTag::LongArray(longs)),
+    ]);
+    NbtTag::Compound(BTreeMap::from([(
+        "Regions".to_string(),
+        NbtTag::Compound(BTreeMap::from([(
+            "Main".to_string(),
+            NbtTag::Compound(region),
+        )])),
+    )]))
+}
+
+#[test]
+/// Sponge v2 and v3 both come back out as the same schematic.
+fn sponge_round_trip() {
+    let schematic = test_schematic();
+    for v3 in [false, true] {
+        let bytes = write_nbt("", &to_sponge_tag(&schematic, v3));
+        assert_eq!(Schematic::from_bytes(&bytes).unwrap(), schematic);
+        assert_eq!(Schematic::from_sponge(&bytes).unwrap(), schematic);
+    }
+}
+
+#[test]
+/// Litematica regions are moved back to start at zero, even if they were drawn backwards.
+fn litematic_round_trip() {
+    let schematic = test_schematic();
+    let bytes = write_nbt("", &to_litematic_tag(&schematic, (100, -5, 7)));
+    assert_eq!(Schematic::from_bytes(&bytes).unwrap(), schematic);
+
+    // Same blocks, but the size is negative so the region goes backwards from its position.
+    let NbtTag::Compound(mut root) = to_litematic_tag(&schematic, (0, 0, 0)) else {
+        unreachable!()
+    };
+    let Some(NbtTag::Compound(regions)) = root.get_mut("Regions") else {
+        unreachable!()
+    };
+    let Some(NbtTag::Compound(region)) = regions.get_mut("Main") else {
+        unreachable!()
+    };
+    region.insert(
+        "Position".to_string(),
+        NbtTag::Compound(BTreeMap::from([
+            ("x".to_string(), NbtTag::Int(2)),
+            ("y".to_string(), NbtTag::Int(1)),
+            ("z".to_string(), NbtTag::Int(2)),
+        ])),
+    );
+    region.insert(
+        "Size".to_string(),
+        NbtTag::Compound(BTreeMap::from([
+            ("x".to_string(), NbtTag::Int(-3)),
+            ("y".to_string(), NbtTag::Int(-2)),
+            ("z".to_string(), NbtTag::Int(-3)),
+        ])),
+    );
+    let bytes = write_nbt("", &NbtTag::Compound(root));
+    assert_eq!(Schematic::from_litematic(&bytes).unwrap(), schematic);
+}
+
+#[test]
+/// Broken schematics are errors, not panics.
+fn malformed_schematics() {
+    let sponge = |fields: Vec<(&str, NbtTag)>| {
+        let root = fields
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect();
+        Schematic::from_sponge(&write_nbt("", &NbtTag::Compound(root)))
+    };
+    let size = |width, height, length| {
+        vec![
+            ("Width", NbtTag::Short(width)),
+            ("Height", NbtTag::Short(height)),
+            ("Length", NbtTag::Short(length)),
+            ("Palette", NbtTag::Compound(BTreeMap::new())),
+            ("BlockData", NbtTag::ByteArray(vec![0; 4])),
+        ]
+    };
+
+    // Empty boxes used to divide by zero.
+    assert!(matches!(
+        sponge(size(0, 2, 2)),
+        Err(SchematicError::BadSize((0, 2, 2)))
+    ));
+    assert!(matches!(
+        sponge(size(2, 2, 0)),
+        Err(SchematicError::BadSize((2, 2, 0)))
+    ));
+    assert!(matches!(
+        sponge(size(2, 0, 2)),
+        Err(SchematicError::BadSize((2, 0, 2)))
+    ));
+
+    // Data pointing past the palette.
+    assert!(matches!(
+        sponge(size(2, 1, 2)),
+        Err(SchematicError::BadPaletteIndex(0))
+    ));
+
+    let mut missing = size(2, 1, 2);
+    missing.retain(|(key, _)| *key != "Height");
+    assert!(matches!(
+        sponge(missing),
+        Err(SchematicError::MissingField("Height"))
+    ));
+    let mut missing = size(2, 1, 2);
+    missing.retain(|(key, _)| *key != "BlockData");
+    assert!(matches!(
+        sponge(missing),
+        Err(SchematicError::MissingField("BlockData"))
+    ));
+
+    // Litematica with a palette that's too small for its block states.
+    let region = BTreeMap::from([
+        (
+            "Position".to_string(),
+            NbtTag::Compound(BTreeMap::from([
+                ("x".to_string(), NbtTag::Int(0)),
+                ("y".to_string(), NbtTag::Int(0)),
+                ("z".to_string(), NbtTag::Int(0)),
+            ])),
+        ),
+        (
+            "Size".to_string(),
+            NbtTag::Compound(BTreeMap::from([
+                ("x".to_string(), NbtTag::Int(2)),
+                ("y".to_string(), NbtTag::Int(1)),
+                ("z".to_string(), NbtTag::Int(1)),
+            ])),
+        ),
+        ("BlockStatePalette".to_string(), NbtTag::List(vec![])),
+        ("BlockStates".to_string(), NbtTag::LongArray(vec![0b0100])),
+    ]);
+    let mut root = BTreeMap::new();
+    root.insert(
+        "Regions".to_string(),
+        NbtTag::Compound(BTreeMap::from([(
+            "Main".to_string(),
+            NbtTag::Compound(region.clone()),
+        )])),
+    );
+    let bytes = write_nbt("", &NbtTag::Compound(root));
+    assert!(matches!(
+        Schematic::from_litematic(&bytes),
+        Err(SchematicError::BadPaletteIndex(0))
+    ));
+
+    // Not enough block states for the size.
+    let mut short = region;
+    short.insert("BlockStates".to_string(), NbtTag::LongArray(vec![]));
+    let root = BTreeMap::from([(
+        "Regions".to_string(),
+        NbtTag::Compound(BTreeMap::from([(
+            "Main".to_string(),
+            NbtTag::Compound(short),
+        )])),
+    )]);
+    assert!(matches!(
+        Schematic::from_litematic(&write_nbt("", &NbtTag::Compound(root))),
+        Err(SchematicError::MissingField("BlockStates"))
+    ));
+
+    assert!(matches!(
+        Schematic::from_bytes(&write_nbt("", &NbtTag::Compound(BTreeMap::new()))),
+        Err(SchematicError::UnknownFormat)
+    ));
+}
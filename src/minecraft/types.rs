@@ -6,7 +6,7 @@
 
 use std::fmt::Display;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 /// The world position of something in Minecraft.
 ///
 /// This may contain a facing direction, but is not mandatory.
@@ -32,13 +32,28 @@ impl MinecraftPosition {
             facing: self.facing,
         }
     }
+    /// Taxicab distance between two positions, ignoring facing direction.
+    ///
+    /// This is also how much fuel a turtle needs to travel between the two positions if nothing
+    /// is in the way, since turning is free.
+    pub fn manhattan_distance(&self, other: &MinecraftPosition) -> u32 {
+        let distance =
+            self.x.abs_diff(other.x) + self.y.abs_diff(other.y) + self.z.abs_diff(other.z);
+        // Nobody is walking a turtle 4 billion blocks.
+        u32::try_from(distance).unwrap_or(u32::MAX)
+    }
+    /// Get the x, y, z of this position without the facing direction, for when we only care about
+    /// where something is, and not where it's looking.
+    pub fn xyz(&self) -> (i64, i64, i64) {
+        (self.x, self.y, self.z)
+    }
 }
 
 // ==
 // Minecraft Facing Direction
 // ==
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 /// The various directions that blocks can face.
 pub enum MinecraftFacingDirection {
     North,
@@ -0,0 +1,260 @@
+// Block states, the `[facing=north,half=bottom]` part of a block.
+// Without these, everything we place comes out facing whatever direction the game feels like.
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use serde_json::Value;
+
+use crate::minecraft::{
+    computercraft::lua_types::table::PairedLuaTable, types::MinecraftFacingDirection,
+    vanilla::block_type::MinecraftBlock,
+};
+
+/// A property a block can have, and every value it can take.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateProperty {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+impl StateProperty {
+    /// Make a property from a list of values.
+    pub fn new(name: &str, values: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            values: values.iter().map(|value| value.to_string()).collect(),
+        }
+    }
+
+    /// Make a true/false property.
+    pub fn boolean(name: &str) -> Self {
+        Self::new(name, &["true", "false"])
+    }
+}
+
+/// A block, and the values of its properties.
+///
+/// Properties that aren't set are left up to the game, which uses the block's default state.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BlockState {
+    block: MinecraftBlock,
+    properties: BTreeMap<String, String>,
+}
+
+/// Reasons a block state is invalid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockStateError {
+    /// The block isn't in the vanilla or modded data.
+    UnknownBlock(String),
+    /// The block doesn't have this property.
+    UnknownProperty { block: String, property: String },
+    /// The property can't have this value.
+    BadValue { property: String, value: String },
+    /// Couldn't parse the string or table at all.
+    Malformed(String),
+}
+
+impl From<MinecraftBlock> for BlockState {
+    fn from(block: MinecraftBlock) -> Self {
+        Self::new(block)
+    }
+}
+
+impl BlockState {
+    /// A block with no properties set.
+    pub fn new(block: MinecraftBlock) -> Self {
+        Self {
+            block,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    /// The block this is a state of.
+    pub fn block(&self) -> MinecraftBlock {
+        self.block
+    }
+
+    /// Every property that has been set.
+    pub fn properties(&self) -> &BTreeMap<String, String> {
+        &self.properties
+    }
+
+    /// Get the value of a property, if its set.
+    pub fn get(&self, property: &str) -> Option<&str> {
+        self.properties.get(property).map(String::as_str)
+    }
+
+    /// Set a property, checking that the block actually has it and that the value is allowed.
+    pub fn set(&mut self, property: &str, value: &str) -> Result<(), BlockStateError> {
+        let definitions = self.block.get_state_properties();
+        let Some(definition) = definitions.iter().find(|d| d.name == property) else {
+            return Err(BlockStateError::UnknownProperty {
+                block: self.block.get_full_name().into_owned(),
+                property: property.to_string(),
+            });
+        };
+        if !definition.values.iter().any(|allowed| allowed == value) {
+            return Err(BlockStateError::BadValue {
+                property: property.to_string(),
+                value: value.to_string(),
+            });
+        }
+        self.properties
+            .insert(property.to_string(), value.to_string());
+        Ok(())
+    }
+
+    /// Builder version of `set`.
+    pub fn with(mut self, property: &str, value: &str) -> Result<Self, BlockStateError> {
+        self.set(property, value)?;
+        Ok(self)
+    }
+
+    /// Set which way the block faces.
+    pub fn with_facing(self, facing: MinecraftFacingDirection) -> Result<Self, BlockStateError> {
+        self.with("facing", &facing.to_string())
+    }
+
+    /// Get which way the block faces, if it does.
+    pub fn facing(&self) -> Option<MinecraftFacingDirection> {
+        Some(match self.get("facing")? {
+            "north" => MinecraftFacingDirection::North,
+            "east" => MinecraftFacingDirection::East,
+            "south" => MinecraftFacingDirection::South,
+            "west" => MinecraftFacingDirection::West,
+            "up" => MinecraftFacingDirection::Up,
+            "down" => MinecraftFacingDirection::Down,
+            _ => return None,
+        })
+    }
+
+    /// The block as it would be written in a command. IE `minecraft:oak_stairs[facing=north]`
+    pub fn to_command_string(&self) -> String {
+        let name = self.block.get_full_name();
+        if self.properties.is_empty() {
+            return name.into_owned();
+        }
+        let properties: Vec<String> = self
+            .properties
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        format!("{name}[{}]", properties.join(","))
+    }
+
+    /// Parse a block the way commands write them. IE `minecraft:oak_stairs[facing=north]`
+    ///
+    /// The namespace is optional.
+    pub fn parse(string: &str) -> Result<Self, BlockStateError> {
+        let malformed = || BlockStateError::Malformed(string.to_string());
+        let (name, properties) = match string.split_once('[') {
+            Some((name, rest)) => (name, rest.strip_suffix(']').ok_or_else(malformed)?),
+            None => (string, ""),
+        };
+        let mut state = Self::new(block_from_name(name.trim())?);
+        for pair in properties.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let (key, value) = pair.split_once('=').ok_or_else(malformed)?;
+            state.set(key.trim(), value.trim())?;
+        }
+        Ok(state)
+    }
+
+    /// Read the table returned by `turtle.inspect()`, which looks like
+    /// `{ name = "minecraft:oak_stairs", state = { facing = "north", waterlogged = false }, tags = {...} }`
+    pub fn from_inspect(table: &PairedLuaTable) -> Result<Self, BlockStateError> {
+        let malformed = || BlockStateError::Malformed("inspect table".to_string());
+        let name = table
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(malformed)?;
+        let mut state = Self::new(block_from_name(name)?);
+
+        // Blocks without any properties still get an empty state table, but it might also be
+        // missing entirely.
+        let Some(raw_state) = table.get("state") else {
+            return Ok(state);
+        };
+        let properties: PairedLuaTable =
+            serde_json::from_value(raw_state.clone()).map_err(|_| malformed())?;
+        for pair in &properties.pairs {
+            let key = pair.key.as_str().ok_or_else(malformed)?;
+            // Lua gives us actual bools and numbers here, but commands only care about the text.
+            let value = match &pair.value {
+                Value::String(string) => string.clone(),
+                Value::Bool(boolean) => boolean.to_string(),
+                Value::Number(number) => number.to_string(),
+                _ => return Err(malformed()),
+            };
+            state.set(key, &value)?;
+        }
+        Ok(state)
+    }
+}
+
+impl Display for BlockState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_command_string())
+    }
+}
+
+/// Look up a block with or without its namespace.
+fn block_from_name(name: &str) -> Result<MinecraftBlock, BlockStateError> {
+    let stripped = name
+        .strip_prefix("minecraft:")
+        .or_else(|| name.strip_prefix("computercraft:"))
+        .unwrap_or(name);
+    MinecraftBlock::from_string(stripped)
+        .ok_or_else(|| BlockStateError::UnknownBlock(name.to_string()))
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Parse, validate, and print some states.
+fn block_state_round_trip() {
+    let stairs = BlockState::parse("minecraft:oak_stairs[half=bottom,facing=north]").unwrap();
+    assert_eq!(stairs.facing(), Some(MinecraftFacingDirection::North));
+    // Properties come out sorted.
+    assert_eq!(
+        stairs.to_command_string(),
+        "minecraft:oak_stairs[facing=north,half=bottom]"
+    );
+    assert_eq!(BlockState::parse(&stairs.to_string()).unwrap(), stairs);
+
+    // Stairs can't face up.
+    assert!(matches!(
+        stairs.clone().with_facing(MinecraftFacingDirection::Up),
+        Err(BlockStateError::BadValue { .. })
+    ));
+    // Stone doesn't have a facing.
+    assert!(matches!(
+        BlockState::parse("stone[facing=north]"),
+        Err(BlockStateError::UnknownProperty { .. })
+    ));
+    assert!(BlockState::parse("stone[facing").is_err());
+
+    // Modded blocks work too.
+    let turtle = BlockState::parse("computercraft:turtle_normal[facing=east]").unwrap();
+    assert_eq!(
+        turtle.to_command_string(),
+        "computercraft:turtle_normal[facing=east]"
+    );
+}
+
+#[test]
+/// Read a state out of an inspect result.
+fn block_state_from_inspect() {
+    let table: PairedLuaTable = serde_json::from_value(serde_json::json!({
+        "pairs": [
+            { "key": "name", "value": "minecraft:oak_log" },
+            { "key": "state", "value": { "pairs": [ { "key": "axis", "value": "y" } ] } },
+            { "key": "tags", "value": { "pairs": [] } },
+        ]
+    }))
+    .unwrap();
+    let log = BlockState::from_inspect(&table).unwrap();
+    assert_eq!(log.get("axis"), Some("y"));
+    assert_eq!(log.to_command_string(), "minecraft:oak_log[axis=y]");
+}
@@ -4,14 +4,30 @@ use std::borrow::Cow;
 use mcdata_rs::Block;
 
 use crate::minecraft::{
-    computercraft::modded_data::get_modded_data, vanilla::data_globals::get_mc_data,
+    computercraft::{modded_blocks::MODDED_BLOCK_STATES, modded_data::get_modded_data},
+    vanilla::{block_state::StateProperty, data_globals::get_mc_data},
 };
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub struct MinecraftBlock {
     block: &'static Block,
 }
 
+// Blocks are the same if their IDs are the same, no need to compare every field.
+impl PartialEq for MinecraftBlock {
+    fn eq(&self, other: &Self) -> bool {
+        self.block.id == other.block.id
+    }
+}
+
+impl Eq for MinecraftBlock {}
+
+impl std::hash::Hash for MinecraftBlock {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.block.id.hash(state);
+    }
+}
+
 impl MinecraftBlock {
     /// Get the name of this block as it would be used in commands. IE `minecraft:stone`
     pub fn get_full_name(&self) -> Cow<'static, str> {
@@ -31,6 +47,34 @@ impl MinecraftBlock {
     pub fn get_display_name(&self) -> &String {
         &self.block.display_name
     }
+    /// Every property this block's state can have, and their possible values.
+    pub fn get_state_properties(&self) -> Vec<StateProperty> {
+        if self.is_modded() {
+            // We don't fill in mcdata's state definitions for modded blocks, see modded_blocks.
+            return MODDED_BLOCK_STATES
+                .get(self.block.name.as_str())
+                .cloned()
+                .unwrap_or_default();
+        }
+        self.block
+            .states
+            .iter()
+            .map(|state| {
+                // Bools and ints don't always list their values, so we have to make them up.
+                let values = match (&state.values, state.state_type.as_str()) {
+                    (Some(values), _) => values.clone(),
+                    (None, "bool") => vec!["true".to_string(), "false".to_string()],
+                    (None, _) => (0..state.num_values.unwrap_or(0))
+                        .map(|value| value.to_string())
+                        .collect(),
+                };
+                StateProperty {
+                    name: state.name.clone(),
+                    values,
+                }
+            })
+            .collect()
+    }
     /// Check if this is a modded block.
     fn is_modded(&self) -> bool {
         self.block.id & 1u32 << 31 != 0
@@ -0,0 +1,128 @@
+// How much fuel things are worth.
+// Minecraft data does not ship burn times, so we keep our own table here. The server owns this table,
+// turtles never need to know it, since the server decides what they should be burning.
+
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+
+use crate::minecraft::vanilla::item_type::MinecraftItem;
+
+/// How many ticks it takes a furnace to smelt a single item.
+pub const TICKS_PER_SMELT: u32 = 200;
+
+/// Minecraft runs at 20 ticks a second, assuming the server isn't on fire.
+pub const TICKS_PER_SECOND: u32 = 20;
+
+// Burn times are stored in ticks, since thats what Minecraft uses internally, and everything else
+// (turtle fuel, furnace seconds, items smelted) can be derived from it.
+//
+// CC:Tweaked gives turtles one unit of fuel per second of furnace burn time, IE coal is 1600 ticks,
+// which is 80 seconds, which is 80 turtle fuel.
+//
+// Source: https://minecraft.wiki/w/Smelting#Fuel
+static FUEL_BURN_TICKS: Lazy<HashMap<&'static str, u32>> = Lazy::new(|| {
+    let mut m = HashMap::new();
+
+    // ===
+    // The good stuff
+    // ===
+
+    m.insert("lava_bucket", 20000);
+    m.insert("coal_block", 16000);
+    m.insert("dried_kelp_block", 4001); // yes, 4001.
+    m.insert("blaze_rod", 2400);
+    m.insert("coal", 1600);
+    m.insert("charcoal", 1600);
+
+    // ===
+    // Wood
+    // ===
+
+    // We only list the woods turtles are actually likely to get their hands on. Nether woods
+    // do not burn, so they are not in here at all.
+    for wood in [
+        "oak", "spruce", "birch", "jungle", "acacia", "dark_oak", "mangrove", "cherry",
+    ] {
+        // These are static strings, but the names are built at runtime, so we have to leak them.
+        // This only ever happens once.
+        for suffix in ["log", "wood", "planks"] {
+            m.insert(leak(format!("{wood}_{suffix}")), 300);
+            if suffix != "planks" {
+                m.insert(leak(format!("stripped_{wood}_{suffix}")), 300);
+            }
+        }
+        m.insert(leak(format!("{wood}_slab")), 150);
+        m.insert(leak(format!("{wood}_sapling")), 100);
+    }
+    // Mangroves have propagules instead of saplings.
+    m.remove("mangrove_sapling");
+    m.insert("mangrove_propagule", 100);
+
+    m.insert("bamboo_planks", 300);
+    m.insert("crafting_table", 300);
+    m.insert("chest", 300);
+    m.insert("stick", 100);
+    m.insert("bamboo", 50);
+
+    m
+});
+
+fn leak(string: String) -> &'static str {
+    Box::leak(string.into_boxed_str())
+}
+
+/// How many ticks an item burns for in a furnace. Returns `None` if this item is not a fuel.
+pub fn fuel_burn_ticks(item: &MinecraftItem) -> Option<u32> {
+    FUEL_BURN_TICKS.get(item.get_name().as_str()).copied()
+}
+
+/// How much turtle fuel a single one of these items is worth when passed to `turtle.refuel()`.
+///
+/// Returns `None` if the item cannot be used as fuel.
+pub fn turtle_fuel_value(item: &MinecraftItem) -> Option<u32> {
+    fuel_burn_ticks(item).map(|ticks| ticks / TICKS_PER_SECOND)
+}
+
+/// How many items a single one of these items can smelt in a furnace. This is fractional, since
+/// for example a stick only smelts half of an item.
+pub fn items_smelted_per_fuel(item: &MinecraftItem) -> Option<f64> {
+    fuel_burn_ticks(item).map(|ticks| f64::from(ticks) / f64::from(TICKS_PER_SMELT))
+}
+
+/// Every item name that can be burned, and how many ticks it burns for.
+pub fn all_fuels() -> impl Iterator<Item = (&'static str, u32)> {
+    FUEL_BURN_TICKS.iter().map(|(name, ticks)| (*name, *ticks))
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Every fuel in the table should be an actual item, otherwise we have a typo.
+fn fuel_table_items_exist() {
+    for (name, _) in all_fuels() {
+        assert!(
+            MinecraftItem::from_string(name).is_some(),
+            "Fuel `{name}` is not a real item!"
+        );
+    }
+}
+
+#[test]
+/// Make sure the conversions line up with what CC:Tweaked actually gives you.
+fn fuel_values_match_computercraft() {
+    let coal = MinecraftItem::from_string("coal").unwrap();
+    assert_eq!(turtle_fuel_value(&coal), Some(80));
+    assert_eq!(items_smelted_per_fuel(&coal), Some(8.0));
+
+    let lava = MinecraftItem::from_string("lava_bucket").unwrap();
+    assert_eq!(turtle_fuel_value(&lava), Some(1000));
+
+    let log = MinecraftItem::from_string("oak_log").unwrap();
+    assert_eq!(turtle_fuel_value(&log), Some(15));
+
+    let stone = MinecraftItem::from_string("stone").unwrap();
+    assert_eq!(turtle_fuel_value(&stone), None);
+}
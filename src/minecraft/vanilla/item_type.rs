@@ -8,7 +8,7 @@ use crate::minecraft::{
     computercraft::modded_data::get_modded_data, vanilla::data_globals::get_mc_data,
 };
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub struct MinecraftItem {
     // The inner mcdata item type. We make this wrapper MinecraftItem type so we
     // can implement new methods on it.
@@ -19,6 +19,21 @@ pub struct MinecraftItem {
     item: &'static Item,
 }
 
+// Items are the same if their IDs are the same, no need to compare every field.
+impl PartialEq for MinecraftItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.item.id == other.item.id
+    }
+}
+
+impl Eq for MinecraftItem {}
+
+impl std::hash::Hash for MinecraftItem {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.item.id.hash(state);
+    }
+}
+
 // On our implementations, we avoid using owned strings, since we don't want to constantly be cloning.
 // Apparently even if you don't update the string, cloning a String type is always a deep clone, which
 // is slow.
@@ -83,4 +98,8 @@ impl MinecraftItem {
                 .map(|item| Self { item })
         }
     }
+    /// Get a vanilla item by its numeric ID, which is how mcdata refers to items in recipes.
+    pub fn from_id(id: u32) -> Option<Self> {
+        get_mc_data().items_by_id.get(&id).map(|item| Self { item })
+    }
 }
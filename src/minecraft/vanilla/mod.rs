@@ -1,4 +1,8 @@
+pub mod block_state;
 pub mod block_type;
 pub mod data_globals;
+pub mod fuel;
 pub mod item_type;
 pub mod recipe;
+pub mod resolver;
+pub mod smelting;
@@ -1,6 +1,525 @@
 // The format for how items are crafted
+//
+// Vanilla recipes come from mcdata, which refers to everything by numeric item ID. CC:Tweaked
+// recipes aren't in there, so those are written out by hand at the bottom.
 
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::minecraft::vanilla::{data_globals::get_mc_data, item_type::MinecraftItem};
+
+/// How the ingredients of a recipe need to be laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CraftingShape {
+    /// Ingredients can go anywhere in the grid.
     Unshaped,
-    // todo
+    /// Ingredients must be in this pattern, but the pattern can be anywhere in the grid. We always
+    /// put it in the top left.
+    Shaped { width: u8, height: u8 },
+}
+
+/// Item tags we care about for crafting. Minecraft has a lot more than this, but most recipes
+/// only ever use a few.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ItemTag {
+    /// `minecraft:planks`
+    Planks,
+    /// `minecraft:logs`
+    Logs,
+    /// `minecraft:wool`
+    Wool,
+    /// `minecraft:coals`
+    Coals,
+    /// `minecraft:stone_crafting_materials`
+    StoneCraftingMaterials,
+    /// `c:glass_panes`
+    GlassPanes,
+    /// `c:chests/wooden`
+    WoodenChests,
+}
+
+impl ItemTag {
+    /// Does this item have this tag?
+    ///
+    /// We go by name, since mcdata doesn't ship tags.
+    pub fn matches(&self, item: &MinecraftItem) -> bool {
+        let name = item.get_name().as_str();
+        match self {
+            ItemTag::Planks => name.ends_with("_planks"),
+            ItemTag::Logs => {
+                // Stripped variants end with the same suffixes, so they're included.
+                ["_log", "_wood", "_stem", "_hyphae"]
+                    .iter()
+                    .any(|suffix| name.ends_with(suffix))
+            }
+            ItemTag::Wool => name.ends_with("_wool"),
+            ItemTag::Coals => name == "coal" || name == "charcoal",
+            ItemTag::StoneCraftingMaterials => {
+                matches!(name, "cobblestone" | "blackstone" | "cobbled_deepslate")
+            }
+            ItemTag::GlassPanes => name.ends_with("glass_pane"),
+            ItemTag::WoodenChests => name == "chest" || name == "trapped_chest",
+        }
+    }
+
+    /// The item we use when a recipe asks for this tag and we don't care which one we get.
+    pub fn default_item(&self) -> Option<MinecraftItem> {
+        MinecraftItem::from_string(match self {
+            ItemTag::Planks => "oak_planks",
+            ItemTag::Logs => "oak_log",
+            ItemTag::Wool => "white_wool",
+            ItemTag::Coals => "coal",
+            ItemTag::StoneCraftingMaterials => "cobblestone",
+            ItemTag::GlassPanes => "glass_pane",
+            ItemTag::WoodenChests => "chest",
+        })
+    }
+}
+
+/// Something that can go in a slot of a recipe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ingredient {
+    /// Exactly this item.
+    Item(MinecraftItem),
+    /// Any item with this tag.
+    Tag(ItemTag),
+    /// Any of these items.
+    AnyOf(Vec<MinecraftItem>),
+}
+
+impl Ingredient {
+    /// Can this item be used for this ingredient?
+    pub fn matches(&self, item: &MinecraftItem) -> bool {
+        match self {
+            Ingredient::Item(wanted) => wanted == item,
+            Ingredient::Tag(tag) => tag.matches(item),
+            Ingredient::AnyOf(items) => items.contains(item),
+        }
+    }
+
+    /// The item to use if we have no preference.
+    pub fn default_item(&self) -> Option<MinecraftItem> {
+        match self {
+            Ingredient::Item(item) => Some(*item),
+            Ingredient::Tag(tag) => tag.default_item(),
+            Ingredient::AnyOf(items) => items.first().copied(),
+        }
+    }
+}
+
+/// A single crafting recipe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipe {
+    /// What we get.
+    pub result: MinecraftItem,
+    /// How many we get per craft.
+    pub count: u32,
+    pub shape: CraftingShape,
+    /// For shaped recipes, the pattern in rows, `width * height` long. For unshaped recipes,
+    /// every ingredient, and never `None`.
+    pub ingredients: Vec<Option<Ingredient>>,
+}
+
+/// An item in the craft task's grid. This matches the `item` type in `craft.md`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CraftItem {
+    pub name: String,
+    pub count: u32,
+}
+
+impl Recipe {
+    /// Every ingredient in the recipe, skipping the gaps.
+    pub fn inputs(&self) -> impl Iterator<Item = &Ingredient> {
+        self.ingredients.iter().flatten()
+    }
+
+    /// Lay the recipe out in the 3x3 grid, top left to bottom right.
+    pub fn layout(&self) -> [Option<Ingredient>; 9] {
+        let mut grid: [Option<Ingredient>; 9] = Default::default();
+        match self.shape {
+            CraftingShape::Unshaped => {
+                for (slot, ingredient) in grid.iter_mut().zip(self.inputs()) {
+                    *slot = Some(ingredient.clone());
+                }
+            }
+            CraftingShape::Shaped { width, .. } => {
+                let width = width.max(1) as usize;
+                for (index, ingredient) in self.ingredients.iter().enumerate() {
+                    let (row, column) = (index / width, index % width);
+                    if let Some(slot) = grid.get_mut(row * 3 + column) {
+                        slot.clone_from(ingredient);
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    /// Build the grid the craft task expects, to craft this recipe `crafts` times.
+    ///
+    /// `pick` chooses the actual item for each ingredient, IE which planks to use. Returns `None`
+    /// if `pick` can't find an item for one of the ingredients.
+    pub fn craft_grid(
+        &self,
+        crafts: u32,
+        pick: impl Fn(&Ingredient) -> Option<MinecraftItem>,
+    ) -> Option<[Option<CraftItem>; 9]> {
+        let mut grid: [Option<CraftItem>; 9] = Default::default();
+        for (slot, ingredient) in grid.iter_mut().zip(self.layout()) {
+            if let Some(ingredient) = ingredient {
+                let item = pick(&ingredient)?;
+                *slot = Some(CraftItem {
+                    name: item.get_full_name().into_owned(),
+                    count: crafts,
+                });
+            }
+        }
+        Some(grid)
+    }
+
+    /// Read a recipe in the mcdata format.
+    ///
+    /// Shaped recipes have `inShape`, a list of rows of item IDs (or null), and unshaped recipes
+    /// have `ingredients`, a flat list of item IDs.
+    pub fn from_mcdata(value: &Value) -> Option<Self> {
+        let result = value.get("result")?;
+        let (result_id, count) = match result {
+            Value::Number(id) => (id.as_u64()?, 1),
+            other => (
+                other.get("id")?.as_u64()?,
+                other.get("count").and_then(Value::as_u64).unwrap_or(1),
+            ),
+        };
+        let result = MinecraftItem::from_id(u32::try_from(result_id).ok()?)?;
+
+        // Ingredients are either an ID, null, or an object with an ID in it.
+        let ingredient = |value: &Value| -> Option<Option<Ingredient>> {
+            let id = match value {
+                Value::Null => return Some(None),
+                Value::Number(id) => id.as_u64()?,
+                other => other.get("id")?.as_u64()?,
+            };
+            Some(Some(Ingredient::Item(MinecraftItem::from_id(
+                u32::try_from(id).ok()?,
+            )?)))
+        };
+
+        if let Some(rows) = value.get("inShape").and_then(Value::as_array) {
+            let rows: Vec<&Vec<Value>> = rows.iter().filter_map(Value::as_array).collect();
+            let width = rows.iter().map(|row| row.len()).max()?;
+            let mut ingredients = Vec::with_capacity(width * rows.len());
+            for row in &rows {
+                for column in 0..width {
+                    ingredients.push(match row.get(column) {
+                        Some(value) => ingredient(value)?,
+                        None => None,
+                    });
+                }
+            }
+            return Some(Self {
+                result,
+                count: count as u32,
+                shape: CraftingShape::Shaped {
+                    width: width as u8,
+                    height: rows.len() as u8,
+                },
+                ingredients,
+            });
+        }
+
+        let ingredients = value
+            .get("ingredients")?
+            .as_array()?
+            .iter()
+            .map(ingredient)
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            result,
+            count: count as u32,
+            shape: CraftingShape::Unshaped,
+            ingredients,
+        })
+    }
+}
+
+// ==
+// Recipe database
+// ==
+
+/// Every recipe we know about, by the name of what it makes.
+#[derive(Debug, Default)]
+pub struct RecipeDatabase {
+    recipes: HashMap<String, Vec<Recipe>>,
+}
+
+static RECIPES: Lazy<RecipeDatabase> = Lazy::new(|| {
+    let mut database = RecipeDatabase::default();
+    // Hand written ones first, so they win over the mcdata versions.
+    for recipe in hand_written_recipes() {
+        database.add(recipe);
+    }
+    if let Some(recipes) = get_mc_data().recipes.as_ref() {
+        database.add_mcdata(recipes);
+    }
+    database
+});
+
+/// Get the global recipe database.
+pub fn get_recipes() -> &'static RecipeDatabase {
+    &RECIPES
+}
+
+impl RecipeDatabase {
+    /// Add a recipe. Earlier recipes for the same item are preferred.
+    pub fn add(&mut self, recipe: Recipe) {
+        self.recipes
+            .entry(recipe.result.get_name().clone())
+            .or_default()
+            .push(recipe);
+    }
+
+    /// Add every recipe from mcdata's `recipes.json`, which is a map of result ID to a list of
+    /// recipes. Recipes we can't read are skipped.
+    pub fn add_mcdata(&mut self, recipes: &Value) {
+        let Some(map) = recipes.as_object() else {
+            return;
+        };
+        for list in map.values() {
+            for recipe in list.as_array().into_iter().flatten() {
+                if let Some(recipe) = Recipe::from_mcdata(recipe) {
+                    self.add(recipe);
+                }
+            }
+        }
+    }
+
+    /// Every recipe that makes this item.
+    pub fn recipes_for(&self, item: &MinecraftItem) -> &[Recipe] {
+        self.recipes
+            .get(item.get_name())
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The recipe we'd rather use to make this item.
+    pub fn preferred(&self, item: &MinecraftItem) -> Option<&Recipe> {
+        self.recipes_for(item).first()
+    }
+}
+
+// ==
+// Hand written recipes
+// ==
+
+/// Shorthand for writing shaped recipes. Each character in the pattern maps to an ingredient, and
+/// spaces are empty.
+fn shaped(result: &str, count: u32, pattern: &[&str], key: &[(char, Ingredient)]) -> Recipe {
+    let width = pattern.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut ingredients = vec![];
+    for row in pattern {
+        let mut characters = row.chars();
+        for _ in 0..width {
+            ingredients.push(characters.next().and_then(|character| {
+                key.iter()
+                    .find(|(k, _)| *k == character)
+                    .map(|(_, ingredient)| ingredient.clone())
+            }));
+        }
+    }
+    Recipe {
+        result: item(result),
+        count,
+        shape: CraftingShape::Shaped {
+            width: width as u8,
+            height: pattern.len() as u8,
+        },
+        ingredients,
+    }
+}
+
+fn item(name: &str) -> MinecraftItem {
+    MinecraftItem::from_string(name).unwrap_or_else(|| panic!("{name} is not an item!"))
+}
+
+fn exact(name: &str) -> Ingredient {
+    Ingredient::Item(item(name))
+}
+
+/// Recipes that mcdata either doesn't have, or has with a single item where the game actually
+/// takes a tag.
+fn hand_written_recipes() -> Vec<Recipe> {
+    vec![
+        // Vanilla, but with tags.
+        shaped(
+            "chest",
+            1,
+            &["###", "# #", "###"],
+            &[('#', Ingredient::Tag(ItemTag::Planks))],
+        ),
+        shaped(
+            "crafting_table",
+            1,
+            &["##", "##"],
+            &[('#', Ingredient::Tag(ItemTag::Planks))],
+        ),
+        shaped(
+            "stick",
+            4,
+            &["#", "#"],
+            &[('#', Ingredient::Tag(ItemTag::Planks))],
+        ),
+        shaped(
+            "furnace",
+            1,
+            &["###", "# #", "###"],
+            &[('#', Ingredient::Tag(ItemTag::StoneCraftingMaterials))],
+        ),
+        shaped(
+            "torch",
+            4,
+            &["C", "S"],
+            &[
+                ('C', Ingredient::Tag(ItemTag::Coals)),
+                ('S', exact("stick")),
+            ],
+        ),
+        // CC:Tweaked
+        shaped(
+            "computer_normal",
+            1,
+            &["###", "#R#", "#G#"],
+            &[
+                ('#', exact("stone")),
+                ('R', exact("redstone")),
+                ('G', Ingredient::Tag(ItemTag::GlassPanes)),
+            ],
+        ),
+        shaped(
+            "computer_advanced",
+            1,
+            &["###", "#R#", "#G#"],
+            &[
+                ('#', exact("gold_ingot")),
+                ('R', exact("redstone")),
+                ('G', Ingredient::Tag(ItemTag::GlassPanes)),
+            ],
+        ),
+        shaped(
+            "turtle_normal",
+            1,
+            &["###", "#C#", "#I#"],
+            &[
+                ('#', exact("iron_ingot")),
+                ('C', exact("computer_normal")),
+                ('I', Ingredient::Tag(ItemTag::WoodenChests)),
+            ],
+        ),
+        shaped(
+            "turtle_advanced",
+            1,
+            &["###", "#C#", "#I#"],
+            &[
+                ('#', exact("gold_ingot")),
+                ('C', exact("computer_advanced")),
+                ('I', Ingredient::Tag(ItemTag::WoodenChests)),
+            ],
+        ),
+        shaped(
+            "disk_drive",
+            1,
+            &["###", "#R#", "#R#"],
+            &[('#', exact("stone")), ('R', exact("redstone"))],
+        ),
+        // Floppies can also be dyed, but we don't care what color they are.
+        Recipe {
+            result: item("disk"),
+            count: 1,
+            shape: CraftingShape::Unshaped,
+            ingredients: vec![Some(exact("redstone")), Some(exact("paper"))],
+        },
+    ]
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// The turtle recipe from `turtle.md` should come out in the right slots.
+fn turtle_recipe_grid() {
+    let turtle = item("turtle_normal");
+    let recipe = get_recipes().preferred(&turtle).unwrap();
+    let grid = recipe
+        .craft_grid(2, |ingredient| ingredient.default_item())
+        .unwrap();
+
+    let names: Vec<Option<&str>> = grid
+        .iter()
+        .map(|slot| slot.as_ref().map(|slot| slot.name.as_str()))
+        .collect();
+    assert_eq!(names[4], Some("computercraft:computer_normal"));
+    assert_eq!(names[7], Some("chest"));
+    assert_eq!(
+        names
+            .iter()
+            .filter(|name| **name == Some("iron_ingot"))
+            .count(),
+        7
+    );
+    assert!(grid.iter().flatten().all(|slot| slot.count == 2));
+}
+
+#[test]
+/// Small recipes go in the top left, and tags accept any of their items.
+fn recipe_layouts() {
+    let sticks = get_recipes().preferred(&item("stick")).unwrap();
+    let layout = sticks.layout();
+    assert!(layout[0].is_some() && layout[3].is_some());
+    assert_eq!(layout.iter().flatten().count(), 2);
+    assert!(layout[0].as_ref().unwrap().matches(&item("birch_planks")));
+    assert!(!layout[0].as_ref().unwrap().matches(&item("oak_log")));
+
+    // Unshaped recipes fill from the start.
+    let disk = get_recipes().preferred(&item("disk")).unwrap();
+    assert_eq!(disk.layout().iter().flatten().count(), 2);
+
+    // Nobody has stone, so we can't make a grid.
+    let drive = get_recipes().preferred(&item("disk_drive")).unwrap();
+    assert!(
+        drive
+            .craft_grid(1, |ingredient| match ingredient {
+                Ingredient::Item(item) if item.get_name() == "stone" => None,
+                other => other.default_item(),
+            })
+            .is_none()
+    );
+}
+
+#[test]
+/// Read the mcdata recipe format.
+fn mcdata_recipe_format() {
+    let stone = item("stone");
+    let furnace = item("furnace");
+    let json = serde_json::json!({
+        "inShape": [[item_id(&stone), item_id(&stone)], [null, item_id(&stone)]],
+        "result": { "count": 1, "id": item_id(&furnace) }
+    });
+    let recipe = Recipe::from_mcdata(&json).unwrap();
+    assert_eq!(
+        recipe.shape,
+        CraftingShape::Shaped {
+            width: 2,
+            height: 2
+        }
+    );
+    assert_eq!(recipe.ingredients[2], None);
+    assert_eq!(recipe.layout()[4], Some(Ingredient::Item(stone)));
+}
+
+#[cfg(test)]
+fn item_id(item: &MinecraftItem) -> u32 {
+    get_mc_data().items_by_name[item.get_name()].id
 }
@@ -0,0 +1,394 @@
+// Work out everything that goes into making an item.
+//
+// `turtle.md` walks through this by hand for a turtle. This does the same thing recursively: use
+// what we already have, then smelt, then craft, and if all else fails, go gather it.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::minecraft::vanilla::{
+    fuel::{TICKS_PER_SECOND, TICKS_PER_SMELT, fuel_burn_ticks},
+    item_type::MinecraftItem,
+    recipe::{Ingredient, Recipe, RecipeDatabase},
+    smelting::{SECONDS_PER_SMELT, smelting_input},
+};
+
+// ==
+// Item ledger
+// ==
+
+/// A count of items we have on hand, across every inventory we care about.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ItemLedger {
+    counts: HashMap<MinecraftItem, u32>,
+}
+
+impl ItemLedger {
+    /// An empty ledger.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many of this item we have.
+    pub fn count(&self, item: &MinecraftItem) -> u32 {
+        self.counts.get(item).copied().unwrap_or(0)
+    }
+
+    /// Add some items.
+    pub fn add(&mut self, item: MinecraftItem, count: u32) {
+        if count > 0 {
+            *self.counts.entry(item).or_default() += count;
+        }
+    }
+
+    /// Take up to `count` of an item. Returns how many we actually got.
+    pub fn take(&mut self, item: &MinecraftItem, count: u32) -> u32 {
+        let Some(have) = self.counts.get_mut(item) else {
+            return 0;
+        };
+        let taken = count.min(*have);
+        *have -= taken;
+        if *have == 0 {
+            self.counts.remove(item);
+        }
+        taken
+    }
+
+    /// Find an item we have that fits an ingredient. If we have several, we use whichever we have
+    /// the most of.
+    pub fn find_matching(&self, ingredient: &Ingredient) -> Option<MinecraftItem> {
+        self.counts
+            .iter()
+            .filter(|(item, _)| ingredient.matches(item))
+            .max_by_key(|(item, count)| (**count, item.get_name()))
+            .map(|(item, _)| *item)
+    }
+
+    /// Every item and how many we have.
+    pub fn iter(&self) -> impl Iterator<Item = (&MinecraftItem, &u32)> {
+        self.counts.iter()
+    }
+}
+
+// ==
+// Resolution
+// ==
+
+/// How a step gets its items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepKind {
+    /// We already have all of them.
+    InStock,
+    /// Craft them with this recipe, `crafts` times.
+    Craft { recipe: Recipe, crafts: u32 },
+    /// Smelt them from this item.
+    Smelt { input: MinecraftItem },
+    /// Nothing makes this, go out and get it.
+    Gather,
+}
+
+/// A node in the resolution tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveStep {
+    pub item: MinecraftItem,
+    /// How many we need in total.
+    pub count: u32,
+    /// How many of those are already in the ledger.
+    pub from_stock: u32,
+    pub kind: StepKind,
+    /// The steps that make the inputs for this one.
+    pub children: Vec<ResolveStep>,
+}
+
+/// Everything needed to make an item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    pub root: ResolveStep,
+    /// Items we need to go gather.
+    pub raw_materials: HashMap<MinecraftItem, u32>,
+    /// How long the furnaces need to burn for, in seconds.
+    pub fuel_seconds: u32,
+    /// Extra items made along the way that nothing used. IE 16 glass panes when we needed one.
+    pub leftovers: HashMap<MinecraftItem, u32>,
+    /// What's left of the ledger once everything is made, so resolutions can be chained.
+    pub remaining: ItemLedger,
+}
+
+impl Resolution {
+    /// How many of a fuel item it takes to do all of the smelting.
+    pub fn fuel_items(&self, fuel: &MinecraftItem) -> Option<u32> {
+        let ticks = fuel_burn_ticks(fuel)?;
+        Some((self.fuel_seconds * TICKS_PER_SECOND).div_ceil(ticks))
+    }
+
+    /// How many smelts happen in total.
+    pub fn smelts(&self) -> u32 {
+        self.fuel_seconds * TICKS_PER_SECOND / TICKS_PER_SMELT
+    }
+}
+
+/// Figure out how to get `count` of `item`, given what's in the ledger.
+///
+/// The ledger isn't touched, we work on a copy.
+pub fn resolve(
+    item: MinecraftItem,
+    count: u32,
+    ledger: &ItemLedger,
+    recipes: &RecipeDatabase,
+) -> Resolution {
+    let mut resolver = Resolver {
+        recipes,
+        ledger: ledger.clone(),
+        raw_materials: HashMap::new(),
+        fuel_seconds: 0,
+        visiting: HashSet::new(),
+    };
+    let root = resolver
+        .resolve(item, count)
+        .expect("Loops back to the top level item are dropped by the top level item.");
+
+    // Anything we have more of now than we started with is a by-product.
+    let leftovers = resolver
+        .ledger
+        .iter()
+        .filter_map(|(item, now)| {
+            let extra = now.saturating_sub(ledger.count(item));
+            (extra > 0).then_some((*item, extra))
+        })
+        .collect();
+
+    Resolution {
+        root,
+        raw_materials: resolver.raw_materials,
+        fuel_seconds: resolver.fuel_seconds,
+        leftovers,
+        remaining: resolver.ledger,
+    }
+}
+
+struct Resolver<'a> {
+    recipes: &'a RecipeDatabase,
+    ledger: ItemLedger,
+    raw_materials: HashMap<MinecraftItem, u32>,
+    fuel_seconds: u32,
+    /// Items we're in the middle of resolving, so we don't go in circles. IE iron ingots from iron
+    /// blocks from iron ingots.
+    visiting: HashSet<MinecraftItem>,
+}
+
+/// A recipe led back to an item further up the tree, which one it was.
+///
+/// Anything in between can't be made without already having that item, so it isn't worth going
+/// out to gather either. IE redstone blocks, when we're trying to make redstone.
+#[derive(Debug)]
+struct LoopsBackTo(MinecraftItem);
+
+/// Everything that a resolver changes, so we can undo a recipe that didn't work out.
+type Snapshot = (ItemLedger, HashMap<MinecraftItem, u32>, u32);
+
+impl Resolver<'_> {
+    fn snapshot(&self) -> Snapshot {
+        (
+            self.ledger.clone(),
+            self.raw_materials.clone(),
+            self.fuel_seconds,
+        )
+    }
+
+    fn restore(&mut self, (ledger, raw_materials, fuel_seconds): Snapshot) {
+        self.ledger = ledger;
+        self.raw_materials = raw_materials;
+        self.fuel_seconds = fuel_seconds;
+    }
+
+    /// Fails if the only ways to make this item need something further up the tree.
+    fn resolve(&mut self, item: MinecraftItem, count: u32) -> Result<ResolveStep, LoopsBackTo> {
+        let from_stock = self.ledger.take(&item, count);
+        let needed = count - from_stock;
+        let mut step = ResolveStep {
+            item,
+            count,
+            from_stock,
+            kind: StepKind::InStock,
+            children: vec![],
+        };
+        if needed == 0 {
+            return Ok(step);
+        }
+        if !self.visiting.insert(item) {
+            return Err(LoopsBackTo(item));
+        }
+
+        // Loops back to this item just mean that way doesn't work, but loops further up mean
+        // this item can't be made at all.
+        let mut blocked = None;
+        let mut check = |result: LoopsBackTo| {
+            if result.0 != item {
+                blocked = Some(result);
+            }
+        };
+
+        // Smelting first, since the crafting recipes for things like ingots just go in circles.
+        if let Some(input) = smelting_input(&item) {
+            let snapshot = self.snapshot();
+            match self.resolve(input, needed) {
+                Ok(child) => {
+                    self.fuel_seconds += needed * SECONDS_PER_SMELT;
+                    step.kind = StepKind::Smelt { input };
+                    step.children = vec![child];
+                    self.visiting.remove(&item);
+                    return Ok(step);
+                }
+                Err(loops) => check(loops),
+            }
+            self.restore(snapshot);
+        }
+
+        for recipe in self.recipes.recipes_for(&item) {
+            let snapshot = self.snapshot();
+            match self.craft(recipe, needed) {
+                Ok((crafts, children)) => {
+                    step.kind = StepKind::Craft {
+                        recipe: recipe.clone(),
+                        crafts,
+                    };
+                    step.children = children;
+                    self.visiting.remove(&item);
+                    return Ok(step);
+                }
+                Err(loops) => check(loops),
+            }
+            self.restore(snapshot);
+        }
+        self.visiting.remove(&item);
+
+        // IE a redstone block while making redstone. Gathering one would be cheating.
+        if let Some(loops) = blocked {
+            return Err(loops);
+        }
+
+        // Nothing makes it, go get it.
+        *self.raw_materials.entry(item).or_default() += needed;
+        step.kind = StepKind::Gather;
+        Ok(step)
+    }
+
+    /// Resolve every input of a recipe. Fails if any input loops back up the tree.
+    fn craft(
+        &mut self,
+        recipe: &Recipe,
+        needed: u32,
+    ) -> Result<(u32, Vec<ResolveStep>), LoopsBackTo> {
+        let crafts = needed.div_ceil(recipe.count.max(1));
+
+        // Group up the ingredients, every slot takes one item per craft.
+        let mut grouped: Vec<(&Ingredient, u32)> = vec![];
+        for ingredient in recipe.inputs() {
+            match grouped
+                .iter_mut()
+                .find(|(existing, _)| *existing == ingredient)
+            {
+                Some((_, amount)) => *amount += crafts,
+                None => grouped.push((ingredient, crafts)),
+            }
+        }
+
+        let mut children = vec![];
+        for (ingredient, amount) in grouped {
+            // Use whatever we have on hand for tags, otherwise the default.
+            let Some(item) = self
+                .ledger
+                .find_matching(ingredient)
+                .or_else(|| ingredient.default_item())
+            else {
+                // Nothing fits at all, so this recipe is as good as a loop back to what it makes.
+                return Err(LoopsBackTo(recipe.result));
+            };
+            children.push(self.resolve(item, amount)?);
+        }
+
+        // Anything extra goes back in the ledger for later steps.
+        let extra = crafts * recipe.count - needed;
+        self.ledger.add(recipe.result, extra);
+        Ok((crafts, children))
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+use crate::minecraft::vanilla::recipe::get_recipes;
+
+#[cfg(test)]
+fn item(name: &str) -> MinecraftItem {
+    MinecraftItem::from_string(name).unwrap()
+}
+
+#[test]
+/// The turtle breakdown from `turtle.md`
+fn resolve_turtle_from_nothing() {
+    let resolution = resolve(item("turtle_normal"), 1, &ItemLedger::new(), get_recipes());
+
+    let raw = &resolution.raw_materials;
+    assert_eq!(raw.get(&item("raw_iron")), Some(&7));
+    assert_eq!(raw.get(&item("oak_log")), Some(&2));
+    assert_eq!(raw.get(&item("cobblestone")), Some(&7));
+    assert_eq!(raw.get(&item("redstone")), Some(&1));
+    assert_eq!(raw.get(&item("sand")), Some(&6));
+
+    // 7 iron, 7 stone, 6 glass.
+    assert_eq!(resolution.fuel_seconds, 200);
+    assert_eq!(resolution.smelts(), 20);
+    assert_eq!(resolution.fuel_items(&item("coal")), Some(3));
+
+    // One glass pane out of a craft of 16.
+    assert_eq!(resolution.leftovers.get(&item("glass_pane")), Some(&15));
+    assert!(matches!(resolution.root.kind, StepKind::Craft { .. }));
+
+    // Storage blocks only come from the thing we're trying to make, so they should never be gathered.
+    let blocks: Vec<_> = raw
+        .keys()
+        .map(MinecraftItem::get_name)
+        .filter(|name| name.ends_with("_block"))
+        .collect();
+    assert!(blocks.is_empty(), "Gathering storage blocks: {blocks:?}");
+}
+
+#[test]
+/// Redstone can be made from a redstone block, which is made from redstone. That's a loop, so the
+/// redstone should be gathered, not the block.
+fn resolve_skips_storage_blocks() {
+    let resolution = resolve(item("redstone"), 3, &ItemLedger::new(), get_recipes());
+    assert_eq!(resolution.raw_materials.get(&item("redstone")), Some(&3));
+    assert_eq!(resolution.raw_materials.len(), 1);
+    assert_eq!(resolution.root.kind, StepKind::Gather);
+
+    // Unless we've got one lying around.
+    let mut ledger = ItemLedger::new();
+    ledger.add(item("redstone_block"), 1);
+    let resolution = resolve(item("redstone"), 3, &ledger, get_recipes());
+    assert!(resolution.raw_materials.is_empty());
+    assert_eq!(resolution.leftovers.get(&item("redstone")), Some(&6));
+}
+
+#[test]
+/// Things in the ledger get used before anything is made.
+fn resolve_uses_ledger() {
+    let mut ledger = ItemLedger::new();
+    ledger.add(item("iron_ingot"), 5);
+    ledger.add(item("chest"), 1);
+    ledger.add(item("birch_planks"), 3);
+
+    let resolution = resolve(item("turtle_normal"), 1, &ledger, get_recipes());
+    let raw = &resolution.raw_materials;
+    assert_eq!(raw.get(&item("raw_iron")), Some(&2));
+    assert_eq!(raw.get(&item("oak_log")), None);
+    // The ledger we passed in didn't change.
+    assert_eq!(ledger.count(&item("iron_ingot")), 5);
+
+    // Sticks should use the birch planks we have.
+    let sticks = resolve(item("stick"), 4, &ledger, get_recipes());
+    assert!(sticks.raw_materials.is_empty());
+    assert_eq!(sticks.root.children[0].item, item("birch_planks"));
+}
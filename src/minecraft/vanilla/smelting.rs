@@ -0,0 +1,59 @@
+// Furnace recipes.
+// mcdata doesn't have these at all, so we only list the ones we actually need. Every smelt takes a
+// single input and makes a single output.
+
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+
+use crate::minecraft::vanilla::{
+    fuel::{TICKS_PER_SECOND, TICKS_PER_SMELT},
+    item_type::MinecraftItem,
+};
+
+/// How long a single smelt takes, in seconds.
+pub const SECONDS_PER_SMELT: u32 = TICKS_PER_SMELT / TICKS_PER_SECOND;
+
+/// Output item name -> input item name.
+static SMELTING: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
+    let mut m = HashMap::new();
+    m.insert("iron_ingot", "raw_iron");
+    m.insert("gold_ingot", "raw_gold");
+    m.insert("copper_ingot", "raw_copper");
+    m.insert("stone", "cobblestone");
+    m.insert("smooth_stone", "stone");
+    m.insert("deepslate", "cobbled_deepslate");
+    m.insert("glass", "sand");
+    m.insert("brick", "clay_ball");
+    m.insert("charcoal", "oak_log");
+    m.insert("dried_kelp", "kelp");
+    m
+});
+
+/// What we need to put in a furnace to get this item out, if it can be smelted at all.
+pub fn smelting_input(output: &MinecraftItem) -> Option<MinecraftItem> {
+    SMELTING
+        .get(output.get_name().as_str())
+        .and_then(|input| MinecraftItem::from_string(*input))
+}
+
+/// What comes out of the furnace when this item is smelted.
+pub fn smelting_output(input: &MinecraftItem) -> Option<MinecraftItem> {
+    SMELTING
+        .iter()
+        .find(|(_, smelted)| **smelted == input.get_name().as_str())
+        .and_then(|(output, _)| MinecraftItem::from_string(*output))
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Every smelting recipe should use real items.
+fn smelting_items_exist() {
+    for (output, input) in SMELTING.iter() {
+        assert!(MinecraftItem::from_string(*output).is_some(), "{output}");
+        assert!(MinecraftItem::from_string(*input).is_some(), "{input}");
+    }
+}
@@ -0,0 +1,759 @@
+// Talking to a Minecraft server over RCON.
+// The control server uses this for admin work, like keeping chunks loaded around active work sites,
+// or finding a turtle that's gone quiet. The test harness uses it to drive the test server.
+//
+// RCON packets are `length, id, type, body, 0, 0`, with little endian i32s for the numbers. Minecraft
+// splits long responses over multiple packets without saying how many, so after every command we
+// send a junk packet too. Minecraft answers packets in order, so once the junk packet's answer comes
+// back, we know we've got the whole response.
+
+use std::{
+    sync::atomic::{AtomicI32, Ordering},
+    time::Duration,
+};
+
+use log::{debug, warn};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    sync::Mutex,
+    time::{Instant, sleep, sleep_until, timeout},
+};
+
+use crate::minecraft::{
+    nbt::{NbtTag, parse_snbt},
+    types::MinecraftPosition,
+    vanilla::block_type::MinecraftBlock,
+};
+
+/// Minecraft won't take commands longer than this.
+pub const MAX_COMMAND_LENGTH: usize = 1446;
+
+/// Nothing Minecraft sends is anywhere near this big, so something has gone wrong if a packet says it is.
+const MAX_PACKET_LENGTH: usize = 1 << 16;
+
+const RESPONSE: i32 = 0;
+const COMMAND: i32 = 2;
+const LOGIN: i32 = 3;
+
+#[derive(Debug)]
+pub enum RconError {
+    Io(std::io::Error),
+    /// The server didn't take our password.
+    BadPassword,
+    /// The server took too long to answer.
+    Timeout,
+    /// Couldn't connect, even after retrying. Holds the last reason.
+    Unreachable(std::io::Error),
+    /// The command is longer than `MAX_COMMAND_LENGTH`.
+    TooLong(usize),
+    /// The server sent something that isn't RCON.
+    Protocol(String),
+    /// The command doesn't give back the kind of response that was asked for. Holds what it did give.
+    WrongResponse(RconResponse),
+}
+
+impl From<std::io::Error> for RconError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+// ==
+// Packets
+// ==
+
+/// A single RCON packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Packet {
+    id: i32,
+    kind: i32,
+    body: String,
+}
+
+async fn write_packet<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    packet: &Packet,
+) -> Result<(), RconError> {
+    let body = packet.body.as_bytes();
+    let length = i32::try_from(body.len() + 10).map_err(|_| RconError::TooLong(body.len()))?;
+    let mut bytes = Vec::with_capacity(body.len() + 14);
+    bytes.extend_from_slice(&length.to_le_bytes());
+    bytes.extend_from_slice(&packet.id.to_le_bytes());
+    bytes.extend_from_slice(&packet.kind.to_le_bytes());
+    bytes.extend_from_slice(body);
+    bytes.extend_from_slice(&[0, 0]);
+    writer.write_all(&bytes).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn read_packet<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Packet, RconError> {
+    let length = reader.read_i32_le().await?;
+    let length = usize::try_from(length)
+        .ok()
+        .filter(|length| (10..=MAX_PACKET_LENGTH).contains(length))
+        .ok_or_else(|| RconError::Protocol(format!("Packet length of {length}")))?;
+    let mut bytes = vec![0; length];
+    reader.read_exact(&mut bytes).await?;
+
+    let id = i32::from_le_bytes(bytes[0..4].try_into().expect("Four bytes"));
+    let kind = i32::from_le_bytes(bytes[4..8].try_into().expect("Four bytes"));
+    // Drop the two nulls on the end.
+    let body = String::from_utf8_lossy(&bytes[8..length - 2]).into_owned();
+    Ok(Packet { id, kind, body })
+}
+
+// ==
+// Client
+// ==
+
+/// How patient and polite the client is.
+#[derive(Debug, Clone, Copy)]
+pub struct RconSettings {
+    /// The least time between sending two commands, so we don't flood the server.
+    pub min_interval: Duration,
+    /// How many times to try connecting before giving up.
+    pub connect_attempts: u32,
+    /// How long to wait after the first failed connection. Doubles after every failure.
+    pub first_backoff: Duration,
+    /// The longest to ever wait between connection attempts.
+    pub max_backoff: Duration,
+    /// How long to wait for the server to answer anything.
+    pub timeout: Duration,
+}
+
+impl Default for RconSettings {
+    fn default() -> Self {
+        Self {
+            min_interval: Duration::from_millis(5),
+            connect_attempts: 5,
+            first_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(10),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// A connection to a server's RCON.
+///
+/// Commands are sent one at a time, no matter how many tasks share the client. If the connection
+/// drops, the command that was running fails (since we can't know if it ran), and the next command
+/// reconnects.
+pub struct RconClient {
+    address: String,
+    password: String,
+    settings: RconSettings,
+    state: Mutex<ClientState>,
+    next_id: AtomicI32,
+}
+
+struct ClientState {
+    stream: Option<TcpStream>,
+    last_sent: Option<Instant>,
+}
+
+impl RconClient {
+    /// Make a client. This doesn't connect until the first command, or `connect` is called.
+    ///
+    /// The address includes the port, IE `localhost:25575`.
+    pub fn new(address: impl Into<String>, password: impl Into<String>) -> Self {
+        Self::with_settings(address, password, RconSettings::default())
+    }
+
+    pub fn with_settings(
+        address: impl Into<String>,
+        password: impl Into<String>,
+        settings: RconSettings,
+    ) -> Self {
+        Self {
+            address: address.into(),
+            password: password.into(),
+            settings,
+            state: Mutex::new(ClientState {
+                stream: None,
+                last_sent: None,
+            }),
+            next_id: AtomicI32::new(1),
+        }
+    }
+
+    /// Connect now, instead of waiting for the first command. Does nothing if already connected.
+    pub async fn connect(&self) -> Result<(), RconError> {
+        let mut state = self.state.lock().await;
+        if state.stream.is_none() {
+            state.stream = Some(self.connect_with_backoff().await?);
+        }
+        Ok(())
+    }
+
+    /// Run a command, and get back whatever the server said. The leading `/` is optional.
+    pub async fn command(&self, command: &str) -> Result<String, RconError> {
+        let command = command.strip_prefix('/').unwrap_or(command);
+        if command.len() > MAX_COMMAND_LENGTH {
+            return Err(RconError::TooLong(command.len()));
+        }
+
+        let mut state = self.state.lock().await;
+        let mut stream = match state.stream.take() {
+            Some(stream) => stream,
+            None => self.connect_with_backoff().await?,
+        };
+
+        if let Some(last_sent) = state.last_sent {
+            sleep_until(last_sent + self.settings.min_interval).await;
+        }
+        state.last_sent = Some(Instant::now());
+
+        debug!("[Rcon]: {command}");
+        let response = timeout(self.settings.timeout, self.exchange(&mut stream, command))
+            .await
+            .unwrap_or(Err(RconError::Timeout))?;
+        // Only put the stream back if everything went fine, otherwise the next command reconnects.
+        state.stream = Some(stream);
+        Ok(response)
+    }
+
+    /// Run a typed command.
+    pub async fn run(&self, command: &RconCommand) -> Result<RconResponse, RconError> {
+        let response = self.command(&command.to_command_string()).await?;
+        Ok(command.read_response(&response))
+    }
+
+    /// Run a pass or fail command, and get whether it passed.
+    pub async fn run_bool(&self, command: &RconCommand) -> Result<bool, RconError> {
+        match self.run(command).await? {
+            RconResponse::Success(passed) => Ok(passed),
+            other => Err(RconError::WrongResponse(other)),
+        }
+    }
+
+    /// Run a data command, and get its data.
+    pub async fn run_data(&self, command: &RconCommand) -> Result<Option<NbtTag>, RconError> {
+        match self.run(command).await? {
+            RconResponse::Data(data) => Ok(data),
+            other => Err(RconError::WrongResponse(other)),
+        }
+    }
+
+    /// Send a command and read the whole response.
+    async fn exchange(&self, stream: &mut TcpStream, command: &str) -> Result<String, RconError> {
+        let id = self.next_id();
+        let end_id = self.next_id();
+        let request = Packet {
+            id,
+            kind: COMMAND,
+            body: command.to_string(),
+        };
+        // Minecraft answers anything it doesn't understand with an error, which tells us the command is done.
+        let end = Packet {
+            id: end_id,
+            kind: RESPONSE,
+            body: String::new(),
+        };
+        write_packet(stream, &request).await?;
+        write_packet(stream, &end).await?;
+
+        let mut response = String::new();
+        loop {
+            let packet = read_packet(stream).await?;
+            match packet.id {
+                packet_id if packet_id == id => response += &packet.body,
+                packet_id if packet_id == end_id => return Ok(response),
+                other => {
+                    warn!("Got an RCON packet for {other} while waiting for {id}, ignoring it.")
+                }
+            }
+        }
+    }
+
+    /// Keep trying to connect, waiting longer each time.
+    async fn connect_with_backoff(&self) -> Result<TcpStream, RconError> {
+        let mut backoff = self.settings.first_backoff;
+        let mut attempt = 1;
+        loop {
+            let error = match self.try_connect().await {
+                Ok(stream) => return Ok(stream),
+                // Trying again won't fix the password.
+                Err(RconError::BadPassword) => return Err(RconError::BadPassword),
+                Err(RconError::Io(error)) => error,
+                Err(RconError::Timeout) => std::io::ErrorKind::TimedOut.into(),
+                Err(other) => return Err(other),
+            };
+            if attempt >= self.settings.connect_attempts {
+                return Err(RconError::Unreachable(error));
+            }
+            warn!(
+                "Couldn't reach RCON at {} ({error}), trying again in {backoff:?}...",
+                self.address
+            );
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(self.settings.max_backoff);
+            attempt += 1;
+        }
+    }
+
+    /// Connect and log in.
+    async fn try_connect(&self) -> Result<TcpStream, RconError> {
+        let login = async {
+            let mut stream = TcpStream::connect(&self.address).await?;
+            let id = self.next_id();
+            let packet = Packet {
+                id,
+                kind: LOGIN,
+                body: self.password.clone(),
+            };
+            write_packet(&mut stream, &packet).await?;
+            loop {
+                let answer = read_packet(&mut stream).await?;
+                // Some servers send an empty response before the real answer.
+                if answer.kind != COMMAND {
+                    continue;
+                }
+                return match answer.id {
+                    -1 => Err(RconError::BadPassword),
+                    answer_id if answer_id == id => Ok(stream),
+                    other => Err(RconError::Protocol(format!(
+                        "Login answer for {other} instead of {id}"
+                    ))),
+                };
+            }
+        };
+        timeout(self.settings.timeout, login)
+            .await
+            .unwrap_or(Err(RconError::Timeout))
+    }
+
+    /// Request IDs are never -1, since that means a bad password.
+    fn next_id(&self) -> i32 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        if id < 0 {
+            // Wrapped around, which would take a while.
+            self.next_id.store(1, Ordering::Relaxed);
+            return self.next_id();
+        }
+        id
+    }
+}
+
+// ==
+// Commands
+// ==
+
+/// Commands the control server needs, so we aren't building strings everywhere.
+#[derive(Debug, Clone)]
+pub enum RconCommand {
+    /// Is the chunk holding this position loaded?
+    ///
+    /// Returns a pass or fail.
+    IsLoaded(MinecraftPosition),
+
+    /// Keep every chunk between two positions loaded, even with no players around. Only x and z matter.
+    ///
+    /// Returns a pass or fail, which fails if they were all already force loaded.
+    ForceloadAdd(MinecraftPosition, MinecraftPosition),
+
+    /// Stop force loading every chunk between two positions. Only x and z matter.
+    ///
+    /// Returns a pass or fail, which fails if none of them were force loaded.
+    ForceloadRemove(MinecraftPosition, MinecraftPosition),
+
+    /// Is the chunk holding this position force loaded?
+    ///
+    /// Returns a pass or fail.
+    ForceloadQuery(MinecraftPosition),
+
+    /// Is this block at this position? Handy for checking if a turtle is where we think it is.
+    ///
+    /// Returns a pass or fail.
+    TestForBlock(MinecraftPosition, MinecraftBlock),
+
+    /// Place a block.
+    ///
+    /// Returns a pass or fail.
+    SetBlock(MinecraftPosition, MinecraftBlock),
+
+    /// Get data from a block entity, IE a turtle's `ComputerId`. An empty path gets all of it.
+    ///
+    /// Returns the NBT Data, or None if there's no block entity, or nothing at that path.
+    GetBlockData(MinecraftPosition, String),
+
+    /// Anything else.
+    ///
+    /// Returns the Text the server sent back.
+    Raw(String),
+}
+
+/// What a command gave back. See each command for which one it returns.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RconResponse {
+    Success(bool),
+    Data(Option<NbtTag>),
+    Text(String),
+}
+
+impl RconCommand {
+    /// The command to send.
+    pub fn to_command_string(&self) -> String {
+        match self {
+            RconCommand::IsLoaded(position) => {
+                format!("execute if loaded {}", position.as_command_string())
+            }
+            RconCommand::ForceloadAdd(from, to) => {
+                format!("forceload add {} {}", column(from), column(to))
+            }
+            RconCommand::ForceloadRemove(from, to) => {
+                format!("forceload remove {} {}", column(from), column(to))
+            }
+            RconCommand::ForceloadQuery(position) => {
+                format!("forceload query {}", column(position))
+            }
+            RconCommand::TestForBlock(position, block) => format!(
+                "execute if block {} {}",
+                position.as_command_string(),
+                block.get_full_name()
+            ),
+            RconCommand::SetBlock(position, block) => format!(
+                "setblock {} {}",
+                position.as_command_string(),
+                block.get_full_name()
+            ),
+            RconCommand::GetBlockData(position, path) => {
+                format!("data get block {} {path}", position.as_command_string())
+                    .trim_end()
+                    .to_string()
+            }
+            RconCommand::Raw(command) => command.clone(),
+        }
+    }
+
+    /// Make sense of what the server sent back.
+    pub fn read_response(&self, response: &str) -> RconResponse {
+        match self {
+            RconCommand::IsLoaded(_) | RconCommand::TestForBlock(..) => {
+                RconResponse::Success(response.starts_with("Test passed"))
+            }
+            RconCommand::ForceloadAdd(..) => RconResponse::Success(response.starts_with("Marked")),
+            RconCommand::ForceloadRemove(..) => {
+                RconResponse::Success(response.starts_with("Unmarked"))
+            }
+            RconCommand::ForceloadQuery(_) => {
+                RconResponse::Success(response.contains("is marked for force loading"))
+            }
+            RconCommand::SetBlock(..) => {
+                RconResponse::Success(response.contains("Changed the block"))
+            }
+            RconCommand::GetBlockData(..) => RconResponse::Data(command_data(response)),
+            RconCommand::Raw(_) => RconResponse::Text(response.to_string()),
+        }
+    }
+}
+
+/// Just the x and z of a position, for commands that work on columns.
+fn column(position: &MinecraftPosition) -> String {
+    format!("{} {}", position.x, position.z)
+}
+
+/// Pull the NBT out of a `data get` result. The text before it is translated, so rather than look
+/// for the english, we take whatever comes after the first `: ` that parses.
+pub fn command_data(result: &str) -> Option<NbtTag> {
+    result
+        .match_indices(": ")
+        .find_map(|(index, _)| parse_snbt(&result[index + 2..]).ok())
+}
+
+// ===
+// Tests
+// ===
+
+/// A pretend Minecraft RCON server. Answers `echo <text>` with the text, `long` with more than fits in a
+/// packet, `hang` with nothing, and anything else with the canned answer for it, if there is one.
+#[cfg(test)]
+struct FakeServer {
+    address: String,
+    /// Every command that came in.
+    commands: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+#[cfg(test)]
+impl FakeServer {
+    /// Start a server. Connections get dropped after `drop_after` commands, if set.
+    async fn start(
+        password: &'static str,
+        drop_after: Option<usize>,
+        answers: &'static [(&'static str, &'static str)],
+    ) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let commands = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let seen = commands.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let seen = seen.clone();
+                tokio::spawn(async move {
+                    let mut handled = 0;
+                    while let Ok(packet) = read_packet(&mut stream).await {
+                        let reply = |id, body: &str| Packet {
+                            id,
+                            kind: RESPONSE,
+                            body: body.to_string(),
+                        };
+                        match packet.kind {
+                            LOGIN => {
+                                let id = if packet.body == password {
+                                    packet.id
+                                } else {
+                                    -1
+                                };
+                                let answer = Packet {
+                                    id,
+                                    kind: COMMAND,
+                                    body: String::new(),
+                                };
+                                write_packet(&mut stream, &answer).await.unwrap();
+                            }
+                            COMMAND => {
+                                seen.lock().unwrap().push(packet.body.clone());
+                                let body = match packet.body.as_str() {
+                                    "long" => "a".repeat(5000),
+                                    "hang" => {
+                                        sleep(Duration::from_secs(60)).await;
+                                        String::new()
+                                    }
+                                    text => match text.strip_prefix("echo ") {
+                                        Some(echo) => echo.to_string(),
+                                        None => answers
+                                            .iter()
+                                            .find(|(command, _)| *command == text)
+                                            .map_or("Unknown command", |(_, answer)| answer)
+                                            .to_string(),
+                                    },
+                                };
+                                // Split up like Minecraft does.
+                                let chunks: Vec<&str> = if body.is_empty() {
+                                    vec![""]
+                                } else {
+                                    body.as_bytes()
+                                        .chunks(4096)
+                                        .map(|chunk| std::str::from_utf8(chunk).unwrap())
+                                        .collect()
+                                };
+                                for chunk in chunks {
+                                    write_packet(&mut stream, &reply(packet.id, chunk))
+                                        .await
+                                        .unwrap();
+                                }
+                                handled += 1;
+                                if drop_after == Some(handled) {
+                                    // Eat the end packet first, so the response still finishes.
+                                    let end = read_packet(&mut stream).await.unwrap();
+                                    write_packet(&mut stream, &reply(end.id, "Unknown request 0"))
+                                        .await
+                                        .unwrap();
+                                    return;
+                                }
+                            }
+                            other => {
+                                let body = format!("Unknown request {other:x}");
+                                write_packet(&mut stream, &reply(packet.id, &body))
+                                    .await
+                                    .unwrap();
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        Self { address, commands }
+    }
+
+    fn commands(&self) -> Vec<String> {
+        self.commands.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+/// Quick settings, so tests that wait don't take forever.
+fn test_settings() -> RconSettings {
+    RconSettings {
+        min_interval: Duration::ZERO,
+        connect_attempts: 3,
+        first_backoff: Duration::from_millis(20),
+        max_backoff: Duration::from_millis(30),
+        timeout: Duration::from_millis(500),
+    }
+}
+
+#[tokio::test]
+/// Logging in, sending commands, and putting split up responses back together.
+async fn rcon_commands() {
+    let server = FakeServer::start("1235", None, &[]).await;
+    let client = RconClient::with_settings(&server.address, "1235", test_settings());
+    client.connect().await.unwrap();
+    assert_eq!(client.command("/echo hello").await.unwrap(), "hello");
+    assert_eq!(client.command("long").await.unwrap(), "a".repeat(5000));
+    assert_eq!(client.command("echo").await.unwrap(), "Unknown command");
+    assert!(matches!(
+        client.command(&"a".repeat(MAX_COMMAND_LENGTH + 1)).await,
+        Err(RconError::TooLong(_))
+    ));
+    assert_eq!(server.commands(), ["echo hello", "long", "echo"]);
+
+    // A hanging command times out, and the client gets back on its feet afterwards.
+    assert!(matches!(
+        client.command("hang").await,
+        Err(RconError::Timeout)
+    ));
+    assert_eq!(client.command("echo back").await.unwrap(), "back");
+
+    let wrong = RconClient::with_settings(&server.address, "1234", test_settings());
+    assert!(matches!(wrong.connect().await, Err(RconError::BadPassword)));
+}
+
+#[tokio::test]
+/// Lots of tasks sharing a client get their own answers, and don't go faster than the rate limit.
+async fn rcon_sharing() {
+    let server = FakeServer::start("1235", None, &[]).await;
+    let settings = RconSettings {
+        min_interval: Duration::from_millis(10),
+        ..test_settings()
+    };
+    let client = std::sync::Arc::new(RconClient::with_settings(&server.address, "1235", settings));
+
+    let started = Instant::now();
+    let tasks: Vec<_> = (0..10)
+        .map(|index| {
+            let client = client.clone();
+            tokio::spawn(async move {
+                let answer = client.command(&format!("echo {index}")).await.unwrap();
+                assert_eq!(answer, index.to_string());
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    assert!(started.elapsed() >= Duration::from_millis(90));
+    assert_eq!(server.commands().len(), 10);
+}
+
+#[tokio::test]
+/// Dropped connections come back on the next command, and dead servers are given up on.
+async fn rcon_reconnecting() {
+    let server = FakeServer::start("1235", Some(1), &[]).await;
+    let client = RconClient::with_settings(&server.address, "1235", test_settings());
+    assert_eq!(client.command("echo one").await.unwrap(), "one");
+    // The server hung up, so this one can't get an answer.
+    assert!(client.command("echo two").await.is_err());
+    assert_eq!(client.command("echo three").await.unwrap(), "three");
+
+    // Nothing listening here anymore.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    drop(listener);
+    let client = RconClient::with_settings(address, "1235", test_settings());
+    let started = Instant::now();
+    assert!(matches!(
+        client.command("echo hi").await,
+        Err(RconError::Unreachable(_))
+    ));
+    // Waited 20ms, then 30ms.
+    assert!(started.elapsed() >= Duration::from_millis(50));
+}
+
+#[tokio::test]
+/// Typed commands build the right strings and understand the answers.
+async fn rcon_typed_commands() {
+    static ANSWERS: &[(&str, &str)] = &[
+        ("execute if loaded 16 -60 -3", "Test passed"),
+        (
+            "forceload add 0 0 31 -17",
+            "Marked 4 chunks in minecraft:overworld from [0, -2] to [1, 0] to be force loaded",
+        ),
+        (
+            "forceload remove 0 0 31 -17",
+            "No chunks were removed from force loading",
+        ),
+        (
+            "forceload query 16 -3",
+            "Chunk at [1, -1] in minecraft:overworld is not marked for force loading",
+        ),
+        (
+            "data get block 16 -60 -3 Fuel",
+            "16, -60, -3 has the following block data: 80",
+        ),
+        (
+            "data get block 16 -60 -3",
+            "The target block is not a block entity",
+        ),
+    ];
+    let server = FakeServer::start("1235", None, ANSWERS).await;
+    let client = RconClient::with_settings(&server.address, "1235", test_settings());
+
+    let here = MinecraftPosition {
+        x: 16,
+        y: -60,
+        z: -3,
+        facing: None,
+    };
+    let origin = MinecraftPosition {
+        x: 0,
+        y: 100,
+        z: 0,
+        facing: None,
+    };
+    let corner = MinecraftPosition {
+        x: 31,
+        y: 0,
+        z: -17,
+        facing: None,
+    };
+    let run_bool = async |command| client.run_bool(&command).await.unwrap();
+    let run_data = async |command| client.run_data(&command).await.unwrap();
+    assert!(run_bool(RconCommand::IsLoaded(here)).await);
+    assert!(run_bool(RconCommand::ForceloadAdd(origin, corner)).await);
+    assert!(!run_bool(RconCommand::ForceloadRemove(origin, corner)).await);
+    assert!(!run_bool(RconCommand::ForceloadQuery(here)).await);
+    assert_eq!(
+        run_data(RconCommand::GetBlockData(here, "Fuel".to_string())).await,
+        Some(NbtTag::Int(80))
+    );
+    assert_eq!(
+        run_data(RconCommand::GetBlockData(here, String::new())).await,
+        None
+    );
+    assert_eq!(
+        client
+            .run(&RconCommand::Raw("echo raw".to_string()))
+            .await
+            .unwrap(),
+        RconResponse::Text("raw".to_string())
+    );
+
+    // Asking for the wrong kind of response is an error, not a panic.
+    assert!(matches!(
+        client.run_data(&RconCommand::IsLoaded(here)).await,
+        Err(RconError::WrongResponse(RconResponse::Success(true)))
+    ));
+    assert!(matches!(
+        client
+            .run_bool(&RconCommand::Raw("echo raw".to_string()))
+            .await,
+        Err(RconError::WrongResponse(RconResponse::Text(_)))
+    ));
+}
+
+#[test]
+/// NBT comes out of `data get` results no matter what language the server is in.
+fn read_command_data() {
+    let data = command_data(
+        r#"-2, -60, 10 has the following block data: {Items: [{Slot: 0b, id: "minecraft:coal", count: 5}], Fuel: 10}"#,
+    )
+    .unwrap();
+    assert_eq!(data.path("Fuel").and_then(NbtTag::as_i64), Some(10));
+    assert_eq!(
+        command_data("-2, -60, 10 a les données de bloc suivantes : 5b"),
+        Some(NbtTag::Byte(5))
+    );
+    assert_eq!(command_data("Found no elements matching Items"), None);
+}
@@ -0,0 +1,367 @@
+// Watching what the Minecraft server prints.
+// Lines look like `[14:03:21] [Server thread/INFO] [minecraft/DedicatedServer]: Done (3.245s)! ...`, where
+// vanilla leaves out the logger part. Anything that doesn't start with a `[` belongs to the line
+// before it, which is how stack traces show up.
+//
+// Most lines don't matter. The ones that do get turned into events, which are sent to everyone
+// subscribed to the watcher, so tests can check nothing went wrong and the control server can react
+// to what's going on in the world.
+
+use std::time::Duration;
+
+use log::debug;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    sync::broadcast,
+    task::JoinHandle,
+    time::timeout,
+};
+
+/// How many events a subscriber can fall behind before it starts missing them.
+const EVENT_BUFFER: usize = 256;
+
+/// Warnings and errors wait for their stack trace, but if nothing comes after them for this long,
+/// they probably don't have one.
+const TRACE_WAIT: Duration = Duration::from_millis(250);
+
+/// Something happened on the server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerEvent {
+    /// The server finished starting, and how long it took in seconds, if it said.
+    Ready(Option<f64>),
+    PlayerJoined(String),
+    PlayerLeft(String),
+    /// A chunk failed to load or save.
+    ChunkError(LogEntry),
+    /// CC:Tweaked had a problem with a computer. Holds the computer's ID, if we could find one.
+    ComputerError(Option<u16>, LogEntry),
+    /// Anything else that errored, or threw an exception.
+    Exception(LogEntry),
+    /// The log ended, so the server is gone.
+    Stopped,
+}
+
+/// How serious a log line is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+/// A single thing the server logged, along with its stack trace if it had one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub thread: String,
+    pub level: LogLevel,
+    /// Which logger this came from, IE `minecraft/DedicatedServer`. Vanilla doesn't say.
+    pub logger: Option<String>,
+    pub message: String,
+    /// Every line after this one that belonged to it.
+    pub stack_trace: Vec<String>,
+}
+
+impl LogEntry {
+    /// Read the start of a log line, or None if this isn't the start of one.
+    fn parse(line: &str) -> Option<Self> {
+        // [time] [thread/LEVEL] [logger]: message
+        let (_time, rest) = line.strip_prefix('[')?.split_once("] [")?;
+        let (thread_level, rest) = rest.split_once(']')?;
+        let (thread, level) = thread_level.rsplit_once('/')?;
+        let level = match level {
+            "TRACE" => LogLevel::Trace,
+            "DEBUG" => LogLevel::Debug,
+            "INFO" => LogLevel::Info,
+            "WARN" => LogLevel::Warn,
+            "ERROR" => LogLevel::Error,
+            "FATAL" => LogLevel::Fatal,
+            _ => return None,
+        };
+        let (logger, message) = match rest.strip_prefix(": ") {
+            Some(message) => (None, message),
+            None => {
+                let (logger, message) = rest.strip_prefix(" [")?.split_once("]: ")?;
+                (Some(logger.to_string()), message)
+            }
+        };
+        Some(Self {
+            thread: thread.to_string(),
+            level,
+            logger,
+            message: message.to_string(),
+            stack_trace: vec![],
+        })
+    }
+
+    /// Is this from CC:Tweaked?
+    fn is_computercraft(&self) -> bool {
+        // CC's loggers are all under `dan200.computercraft`, which sometimes gets shortened.
+        self.logger.as_ref().is_some_and(|logger| {
+            logger.to_lowercase().contains("computercraft")
+                || logger.starts_with("da.co")
+                || logger.contains("Computer")
+        })
+    }
+}
+
+/// Turns log lines into events.
+#[derive(Default)]
+pub struct LogParser {
+    /// A warning or error waiting to see if a stack trace comes after it.
+    pending: Option<LogEntry>,
+}
+
+impl LogParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the next line. Might finish the line before it, so this can return more than one event.
+    pub fn push(&mut self, line: &str) -> Vec<ServerEvent> {
+        let Some(entry) = LogEntry::parse(line) else {
+            // Part of the line before, if we care about it.
+            if let Some(pending) = &mut self.pending {
+                pending.stack_trace.push(line.to_string());
+            }
+            return vec![];
+        };
+
+        let mut events: Vec<ServerEvent> = self.finish().into_iter().collect();
+        if entry.level >= LogLevel::Warn {
+            self.pending = Some(entry);
+        } else if let Some(event) = classify(entry) {
+            events.push(event);
+        }
+        events
+    }
+
+    /// Finish off the last line, since nothing else is coming after it.
+    pub fn finish(&mut self) -> Option<ServerEvent> {
+        self.pending.take().and_then(classify)
+    }
+}
+
+/// Work out what a log entry means, if anything.
+fn classify(entry: LogEntry) -> Option<ServerEvent> {
+    let message = entry.message.as_str();
+    if entry.level < LogLevel::Warn {
+        if let Some(time) = message.strip_prefix("Done (")
+            && message.contains("For help, type")
+        {
+            let seconds = time
+                .split_once("s)")
+                .and_then(|(time, _)| time.parse().ok());
+            return Some(ServerEvent::Ready(seconds));
+        }
+        // Chat starts with `<name>`, so players can't fake these.
+        if let Some(name) = message.strip_suffix(" joined the game")
+            && !name.contains(' ')
+        {
+            return Some(ServerEvent::PlayerJoined(name.to_string()));
+        }
+        if let Some(name) = message.strip_suffix(" left the game")
+            && !name.contains(' ')
+        {
+            return Some(ServerEvent::PlayerLeft(name.to_string()));
+        }
+        // Only warnings and up wait for a stack trace, so anything else is just chatter.
+        return None;
+    }
+
+    if entry.is_computercraft() {
+        return Some(ServerEvent::ComputerError(computer_id(message), entry));
+    }
+    if message.to_lowercase().contains("chunk") {
+        return Some(ServerEvent::ChunkError(entry));
+    }
+    if entry.level >= LogLevel::Error || !entry.stack_trace.is_empty() {
+        return Some(ServerEvent::Exception(entry));
+    }
+    // Just a warning.
+    None
+}
+
+/// Find which computer a message is about, IE `Error running computer #12` or `Computer 12 is...`.
+fn computer_id(message: &str) -> Option<u16> {
+    let lower = message.to_lowercase();
+    lower.match_indices("computer").find_map(|(index, word)| {
+        let after = lower[index + word.len()..].trim_start_matches([' ', '#']);
+        let after = after.strip_prefix("id").unwrap_or(after).trim_start();
+        let digits: String = after.chars().take_while(char::is_ascii_digit).collect();
+        digits.parse().ok()
+    })
+}
+
+// ==
+// Watching
+// ==
+
+/// Reads a server's log in the background, and hands out events to whoever wants them.
+pub struct LogWatcher {
+    sender: broadcast::Sender<ServerEvent>,
+}
+
+impl Default for LogWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogWatcher {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        Self { sender }
+    }
+
+    /// Get every event from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Start reading a log, usually the server's stdout. Sends `Stopped` once it ends.
+    ///
+    /// Subscribe before calling this, or you might miss the first few events.
+    pub fn watch<R: AsyncRead + Unpin + Send + 'static>(&self, log: R) -> JoinHandle<()> {
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(log).lines();
+            let mut parser = LogParser::new();
+            // Nobody listening is fine, so the send results get ignored.
+            loop {
+                match timeout(TRACE_WAIT, lines.next_line()).await {
+                    Ok(Ok(Some(line))) => {
+                        debug!("[Server]: {line}");
+                        for event in parser.push(&line) {
+                            let _ = sender.send(event);
+                        }
+                    }
+                    Ok(_) => break,
+                    // Quiet for a bit, so whatever was waiting for a stack trace isn't getting one.
+                    Err(_) => {
+                        if let Some(event) = parser.finish() {
+                            let _ = sender.send(event);
+                        }
+                    }
+                }
+            }
+            if let Some(event) = parser.finish() {
+                let _ = sender.send(event);
+            }
+            let _ = sender.send(ServerEvent::Stopped);
+        })
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+/// A log with a bit of everything in it.
+const EXAMPLE_LOG: &str = r#"[04:12:30] [main/INFO] [ne.ne.fm.lo.FMLLoader/]: Loading mods
+[04:12:31] [Server thread/INFO] [minecraft/DedicatedServer]: Starting minecraft server version 1.21.1
+[04:12:33] [Server thread/WARN] [minecraft/DedicatedServer]: Nothing to worry about
+[04:12:35] [Server thread/INFO] [minecraft/DedicatedServer]: Done (3.245s)! For help, type "help"
+[04:12:40] [Server thread/INFO]: Doc joined the game
+[04:12:41] [Server thread/INFO] [minecraft/MinecraftServer]: <Doc> Steve joined the game
+[04:12:45] [Computer-Runner-1/ERROR] [dan200.computercraft.core.computer.ComputerExecutor/]: Error running task on computer #12
+java.lang.IllegalStateException: Oops
+	at dan200.computercraft.core.computer.ComputerExecutor.work(ComputerExecutor.java:100)
+	... 3 more
+[04:12:46] [Server thread/ERROR] [minecraft/ChunkSerializer]: Couldn't load chunk [3, -2]
+[04:12:47] [Server thread/ERROR] [ne.ne.ev.EventBus/EVENTBUS]: Exception caught during firing event
+java.lang.NullPointerException: null
+	at com.example.BadMod.onTick(BadMod.java:42)
+Caused by: java.lang.RuntimeException: deeper
+[04:12:50] [Server thread/INFO]: Doc left the game
+[04:12:51] [Server thread/WARN] [da.co.sh.co.ServerComputer/]: Computer 7 is taking too long"#;
+
+#[test]
+/// Every sort of event gets pulled out, with stack traces attached to the right line.
+fn parse_log() {
+    let mut parser = LogParser::new();
+    let mut events: Vec<ServerEvent> = EXAMPLE_LOG
+        .lines()
+        .flat_map(|line| parser.push(line))
+        .collect();
+    events.extend(parser.finish());
+
+    assert_eq!(events.len(), 7, "{events:#?}");
+    assert_eq!(events[0], ServerEvent::Ready(Some(3.245)));
+    assert_eq!(events[1], ServerEvent::PlayerJoined("Doc".to_string()));
+
+    let ServerEvent::ComputerError(Some(12), entry) = &events[2] else {
+        panic!("Expected a computer error, got {:?}", events[2]);
+    };
+    assert_eq!(entry.level, LogLevel::Error);
+    assert_eq!(entry.thread, "Computer-Runner-1");
+    assert_eq!(entry.stack_trace.len(), 3);
+    assert_eq!(
+        entry.stack_trace[0],
+        "java.lang.IllegalStateException: Oops"
+    );
+
+    let ServerEvent::ChunkError(entry) = &events[3] else {
+        panic!("Expected a chunk error, got {:?}", events[3]);
+    };
+    assert_eq!(entry.message, "Couldn't load chunk [3, -2]");
+    assert!(entry.stack_trace.is_empty());
+
+    let ServerEvent::Exception(entry) = &events[4] else {
+        panic!("Expected an exception, got {:?}", events[4]);
+    };
+    assert_eq!(entry.logger.as_deref(), Some("ne.ne.ev.EventBus/EVENTBUS"));
+    assert_eq!(
+        entry.stack_trace.last().map(String::as_str),
+        Some("Caused by: java.lang.RuntimeException: deeper")
+    );
+
+    assert_eq!(events[5], ServerEvent::PlayerLeft("Doc".to_string()));
+    assert!(matches!(&events[6], ServerEvent::ComputerError(Some(7), _)));
+}
+
+#[test]
+/// Finding computer IDs in the ways CC:Tweaked writes them.
+fn find_computer_ids() {
+    assert_eq!(computer_id("Error running computer #12"), Some(12));
+    assert_eq!(computer_id("Computer 7 is taking too long"), Some(7));
+    assert_eq!(
+        computer_id("Terminating computer ID 300 due to timeout"),
+        Some(300)
+    );
+    assert_eq!(computer_id("Error in computer thread"), None);
+}
+
+#[tokio::test]
+/// The watcher sends events as lines come in, doesn't sit on errors forever, and says when the log ends.
+async fn watch_log() {
+    let (mut writer, reader) = tokio::io::duplex(1024);
+    let watcher = LogWatcher::new();
+    let mut events = watcher.subscribe();
+    let task = watcher.watch(reader);
+
+    use tokio::io::AsyncWriteExt;
+    writer
+        .write_all(b"[04:12:35] [Server thread/INFO]: Done (1.5s)! For help, type \"help\"\n")
+        .await
+        .unwrap();
+    assert_eq!(events.recv().await.unwrap(), ServerEvent::Ready(Some(1.5)));
+
+    // Nothing comes after this, so it should still show up once the watcher stops waiting for a trace.
+    writer
+        .write_all(b"[04:12:36] [Server thread/ERROR] [minecraft/ChunkMap]: Failed to save chunk\n")
+        .await
+        .unwrap();
+    let event = tokio::time::timeout(TRACE_WAIT * 4, events.recv())
+        .await
+        .expect("Should stop waiting for a trace.")
+        .unwrap();
+    assert!(matches!(event, ServerEvent::ChunkError(_)));
+
+    drop(writer);
+    assert_eq!(events.recv().await.unwrap(), ServerEvent::Stopped);
+    task.await.unwrap();
+}
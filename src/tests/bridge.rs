@@ -1,50 +1,40 @@
 // Bridge between Minecraft and this repo. Since for testing we need to start a server.
 
-use std::{
-    fs::{self, OpenOptions},
-    io::Write,
-    num::NonZero,
-    path::{Path, PathBuf},
-    process::Stdio,
-    sync::OnceLock,
-};
+use std::{fs, num::NonZero, path::PathBuf, process::Stdio, sync::OnceLock};
 
+use crate::tests::bridge_config::BRIDGE_CONFIG;
+use crate::tests::provision::{
+    ArtifactStore, MODS, NEOFORGE_INSTALLER, ServerCheck, check_server, install_mods,
+    record_install, repair_server,
+};
 use crate::tests::test_harness::test_enviroment::MINECRAFT_TESTING_ENV;
 
-use rcon::{AsyncStdStream, Connection};
+use crate::rcon::{RconClient, RconSettings};
+use crate::server_log::{LogWatcher, ServerEvent};
 
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
+use tokio::sync::broadcast::{self, error::RecvError};
 use tokio::time::{Duration, timeout};
 
 use log::{debug, error, info, warn};
 
 use crate::minecraft::vanilla::data_globals::CURRENT_MINECRAFT_VERSION;
 
-// Urls for the installers and such.
-static MINECRAFT_RCON_PORT: &str = "25575";
-// nobody will ever guess 1235
-static MINECRAFT_RCON_PASSWORD: &str = "1235";
-static JVM_ARGS: &str = "-XX:+UseZGC -XX:+ZGenerational";
-static NEOFORGE_INSTALLER_URL: &str = "https://maven.neoforged.net/releases/net/neoforged/neoforge/21.1.218/neoforge-21.1.218-installer.jar";
-static MOD_URLS: &[&str] = &[
-    "https://cdn.modrinth.com/data/gu7yAYhd/versions/hAW75xeY/cc-tweaked-1.21.1-forge-1.117.0.jar",
-    "https://cdn.modrinth.com/data/uXXizFIs/versions/CnpoQxCx/ferritecore-7.0.2-neoforge.jar",
-    "https://cdn.modrinth.com/data/gvQqBUqZ/versions/G5SDYehn/lithium-neoforge-0.15.1%2Bmc1.21.1.jar",
-    "https://cdn.modrinth.com/data/nmDcB62a/versions/8Be8uJW6/modernfix-neoforge-5.25.1%2Bmc1.21.1.jar",
-    "https://cdn.modrinth.com/data/l6YH9Als/versions/v5qtqRQi/spark-1.10.124-neoforge.jar",
-];
+// Ports, passwords and such are in `bridge_config.rs`, the installer and mods are in `provision.rs`.
 // TODO: Use `spark` for profiling tests for performance testing?
 
-// we are assuming you run `cargo test` while in `/meshpit`
-static SERVER_DIRECTORY: &str = "./test_server";
+/// Comes before the JVM args we add to `user_jvm_args.txt`, so we can find and replace them later.
+static JVM_ARGS_MARKER: &str = "# Added by meshpit, changes below this line get replaced.";
 
 /// Information to keep track of where mc tests are done.
 // #[derive(Debug)]
 pub struct MinecraftEnvironment {
     process: Option<tokio::process::Child>, // into sandwich
-    rcon_connection: Option<rcon::Connection<AsyncStdStream>>,
+    rcon_connection: Option<RconClient>,
     server_dir: PathBuf,
+    /// Everything interesting the server logs.
+    log: LogWatcher,
 }
 
 // TODO: This doesn't seem to run if tests fail.
@@ -54,8 +44,12 @@ pub struct MinecraftEnvironment {
 fn post_test_shutdown() {
     info!("Running post-test cleanup...");
 
-    // TODO: Add a flag to keep the server running and to delay tests starting initially to allow player to join.
-    // std::thread::sleep(Duration::from_secs(300));
+    if BRIDGE_CONFIG.keep_alive {
+        info!(
+            "Keep-alive is on, leaving the server running. Use `/stop` in game when you're done."
+        );
+        return;
+    }
 
     // function is async so we need another thread.
     let handle = std::thread::spawn(|| {
@@ -84,22 +78,59 @@ impl MinecraftEnvironment {
         };
 
         info!("Starting test server... ({CURRENT_MINECRAFT_VERSION})");
-        // Check if the server directory already exists
-        let server_dir = Path::new(SERVER_DIRECTORY).to_path_buf();
+        let store = match ArtifactStore::from_env() {
+            Ok(store) => store,
+            Err(error) => {
+                error!("Unable to set up the artifact cache! {error:#?}");
+                panic!()
+            }
+        };
+
+        // Make sure the server is there, and still what we want.
+        let server_dir = BRIDGE_CONFIG.server_dir.clone();
         info!("Checking for server in {}...", server_dir.to_string_lossy());
-        if server_dir.exists() && server_dir.is_dir() {
-            // Should already be set up.
-            info!("Server directory exists, assuming its already set up...");
-        } else {
-            // run setup first.
-            info!("Server not present, doing initial server setup...");
-            MinecraftEnvironment::setup(&server_dir).await;
-            info!("Done with first time server setup.");
+        match check_server(&server_dir, &NEOFORGE_INSTALLER, MODS) {
+            ServerCheck::Ready => info!("Server is ready."),
+            ServerCheck::Repair { broken, extra } => {
+                info!("Server needs some mods fixed...");
+                if let Err(error) = repair_server(&server_dir, &store, &broken, &extra).await {
+                    error!("Failed to repair the server! {error:#?}");
+                    panic!()
+                }
+            }
+            ServerCheck::Reinstall(reason) => {
+                info!("{reason}, doing server setup...");
+                // Get everything before touching the old server, so a bad download doesn't leave
+                // us with nothing.
+                info!("Getting NeoForge... This might take a second (if you have bad internet)");
+                let neoforge = match store.fetch(&NEOFORGE_INSTALLER).await {
+                    Ok(bytes) => bytes,
+                    Err(error) => {
+                        error!("Unable to get NeoForge! {error}");
+                        panic!()
+                    }
+                };
+                for artifact in MODS {
+                    if let Err(error) = store.fetch(artifact).await {
+                        error!("Unable to get mods! {error}");
+                        panic!()
+                    }
+                }
+                if server_dir.exists()
+                    && let Err(error) = fs::remove_dir_all(&server_dir)
+                {
+                    error!("Unable to remove the old server! {error:#?}");
+                    panic!()
+                }
+                MinecraftEnvironment::setup(&server_dir, &store, &neoforge).await;
+                info!("Done with server setup.");
+            }
         }
 
         let mut server = MinecraftEnvironment {
             process: None,
             rcon_connection: None,
+            log: LogWatcher::new(),
             server_dir,
         };
 
@@ -107,6 +138,10 @@ impl MinecraftEnvironment {
         info!("Cleaning up old worlds...");
         server.cleanup();
 
+        // The config might have changed since setup.
+        info!("Applying bridge config...");
+        server.apply_config();
+
         // Launch that mf
         info!("Starting server...");
         server.start_and_wait().await;
@@ -121,6 +156,10 @@ impl MinecraftEnvironment {
         // attach rcon
         server.attach_rcon().await;
 
+        if BRIDGE_CONFIG.keep_alive {
+            server.wait_for_player().await;
+        }
+
         server
     }
     /// Check if server is still running
@@ -131,8 +170,13 @@ impl MinecraftEnvironment {
     pub fn get_server_folder(&self) -> &PathBuf {
         &self.server_dir
     }
+    /// Get everything the server logs that we care about from now on.
+    pub fn events(&self) -> broadcast::Receiver<ServerEvent> {
+        self.log.subscribe()
+    }
     /// Set up the server environment via downloading, installing, and configuring.
-    async fn setup(server_dir: &PathBuf) {
+    /// Install a fresh server with the NeoForge installer's bytes. Its mods should already be cached.
+    async fn setup(server_dir: &PathBuf, store: &ArtifactStore, neoforge: &[u8]) {
         // make the dir
         if let Err(error) = std::fs::create_dir(server_dir) {
             error!("Unable to setup server directory! Do we have permission?");
@@ -147,17 +191,8 @@ impl MinecraftEnvironment {
             panic!()
         };
 
-        // download neoforge
-        info!("Downloading NeoForge... This might take a second (if you have bad internet)");
         let jar_path = server_dir.join("installer.jar");
-        let neoforge = reqwest::get(NEOFORGE_INSTALLER_URL)
-            .await
-            .expect("Should be able to get it")
-            .bytes()
-            .await
-            .expect("Should be able to cast to bytes.");
-
-        fs::write(&jar_path, &neoforge).expect("Should be able to write");
+        fs::write(&jar_path, neoforge).expect("Should be able to write");
 
         // Now run the neoforge installer.
         let status = Command::new("java")
@@ -187,28 +222,14 @@ impl MinecraftEnvironment {
         info!("Finished installing NeoForge!");
 
         // Time to stuff some mods in there
-        info!("Downloading required mods...");
-        let mod_folder: PathBuf = server_dir.join("mods");
-
-        // make the mod folder in-case it doesnt exist yet.
-        let _ = std::fs::create_dir(&mod_folder); // if this fails we'll die anyways.
-
-        for url in MOD_URLS {
-            let mod_filename = url
-                .split('/')
-                .next_back()
-                .expect("There should be a file name at the end.");
-            info!("Downloading `{mod_filename}`...");
-            let download = reqwest::get(*url)
-                .await
-                .expect("Should be able to get it")
-                .bytes()
-                .await
-                .expect("Should be able to cast to bytes.");
-            fs::write(mod_folder.join(mod_filename), download).expect("Should be able to write");
+        info!("Getting required mods...");
+        if let Err(error) = install_mods(server_dir, store, &MODS.iter().collect::<Vec<_>>()).await
+        {
+            error!("Unable to get mods! {error:#?}");
+            panic!()
         }
 
-        info!("Finished downloading mods!");
+        info!("Finished getting mods!");
 
         info!("Accepting EULA...");
         let eula_path = server_dir.join("eula.txt");
@@ -219,12 +240,12 @@ impl MinecraftEnvironment {
 
         // We're going to move the server dir into the following struct, so we'll set up the rest of the relative paths here
         let config_dir = server_dir.join("config");
-        let java_args_file = server_dir.join("user_jvm_args.txt");
         let server_properties_file = server_dir.join("server.properties");
 
         let mut server = MinecraftEnvironment {
             process: None,
             rcon_connection: None,
+            log: LogWatcher::new(),
             server_dir: server_dir.to_path_buf(),
         };
 
@@ -269,40 +290,13 @@ impl MinecraftEnvironment {
         fs::write(&computercraft_toml, config.to_string())
             .expect("Should be able to write back the edited toml.");
 
-        // Now set up the JVM args.
-        // We don't wanna force people to use Java 25 or anything, so we will use generic flags.
-        // TODO: Add to the documentation that when running a server standalone with this, you should really use the following flags on java 25:
-        // -XX:+UseZGC -XX:+UseCompactObjectHeaders <- java 25
+        // The JVM args and RCON get set every time the server starts, see `apply_config`.
 
-        info!("Setting java args...");
-        let mut args_editor = OpenOptions::new()
-            .append(true)
-            .create(false)
-            .open(java_args_file)
-            .expect("The args file should be there.");
-        let formatted_string = format!("\n{JVM_ARGS}");
-        args_editor
-            .write_all(formatted_string.as_bytes())
-            .expect("Should be able to update file.");
-        info!("Done!");
-
-        // Enable rcon
         info!("Setting up server.properties...");
         let mut properties_text =
             fs::read_to_string(&server_properties_file).expect("Should exist.");
 
         // Since its plaintext, editing this is a bit more annoying.
-        // Enable RCON
-        properties_text = properties_text.replace("enable-rcon=false", "enable-rcon=true");
-        properties_text = properties_text.replace(
-            "rcon.port=25575",
-            &format!("rcon.port={MINECRAFT_RCON_PORT}"),
-        );
-        properties_text = properties_text.replace(
-            "rcon.password=",
-            &format!("rcon.password={MINECRAFT_RCON_PASSWORD}"),
-        );
-
         // motd bc why not
         properties_text = properties_text.replace("motd=A Minecraft Server", "motd=Meshpit Test");
 
@@ -330,6 +324,10 @@ impl MinecraftEnvironment {
         // replace the old config
         fs::write(server_properties_file, properties_text).expect("Should be able to replace it.");
 
+        // Written last, so a setup that dies halfway gets redone next time.
+        record_install(server_dir, &NEOFORGE_INSTALLER, Some(neoforge))
+            .expect("Should be able to record the install.");
+
         info!("Done!");
 
         // All done!
@@ -351,6 +349,59 @@ impl MinecraftEnvironment {
         }
     }
 
+    /// Put the RCON settings and JVM args from the config into the server's files.
+    ///
+    /// Done on every start instead of during setup, so changing the config doesn't need a reinstall.
+    fn apply_config(&self) {
+        let properties_file = self.server_dir.join("server.properties");
+        let properties_text = fs::read_to_string(&properties_file).expect("Should exist.");
+        let properties_text = set_properties(
+            &properties_text,
+            &[
+                ("enable-rcon", "true"),
+                ("rcon.port", &BRIDGE_CONFIG.rcon_port.to_string()),
+                ("rcon.password", &BRIDGE_CONFIG.rcon_password),
+            ],
+        );
+        fs::write(properties_file, properties_text).expect("Should be able to replace it.");
+
+        // We don't wanna force people to use Java 25 or anything, so the default flags are generic.
+        // TODO: Add to the documentation that when running a server standalone with this, you should really use the following flags on java 25:
+        // -XX:+UseZGC -XX:+UseCompactObjectHeaders <- java 25
+        let args_file = self.server_dir.join("user_jvm_args.txt");
+        let args_text = fs::read_to_string(&args_file).expect("The args file should be there.");
+        let args_text = set_jvm_args(&args_text, &BRIDGE_CONFIG.jvm_args);
+        fs::write(args_file, args_text).expect("Should be able to update file.");
+    }
+
+    /// Hold off on running tests until someone joins to watch them.
+    async fn wait_for_player(&mut self) {
+        info!(
+            "Keep-alive is on, waiting for a player to join before running tests... (rcon at {})",
+            BRIDGE_CONFIG.rcon_target()
+        );
+        // Subscribe first, so nobody can sneak in between checking and waiting.
+        let mut events = self.log.subscribe();
+        let list = self.send_rcon("list").await.expect("Rcon should be up.");
+        if players_online(&list).is_some_and(|players| players > 0) {
+            info!("Someone's already here, starting tests!");
+            return;
+        }
+        loop {
+            match events.recv().await {
+                Ok(ServerEvent::PlayerJoined(name)) => {
+                    info!("{name} joined, starting tests!");
+                    return;
+                }
+                Ok(ServerEvent::Stopped) | Err(RecvError::Closed) => {
+                    error!("Server stopped while waiting for a player!");
+                    panic!()
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+            }
+        }
+    }
+
     /// Starts the Minecraft server and blocks until it finishes starting.
     async fn start_and_wait(&mut self) {
         #[cfg(windows)]
@@ -366,19 +417,24 @@ impl MinecraftEnvironment {
             .spawn()
             .expect("Failed to run server start script");
         self.process = Some(child);
-        self.scan_output(")! For help, type \"help\"").await;
+        self.watch_until_ready().await;
         assert!(self.process.is_some())
     }
 
     /// Set up RCON for the server
     async fn attach_rcon(&mut self) {
-        let build = <Connection<AsyncStdStream>>::builder();
-        let connection = build
-            .enable_minecraft_quirks(true)
-            .connect(
-                format!("localhost:{MINECRAFT_RCON_PORT}"), //TODO: make the address configurable
-                MINECRAFT_RCON_PASSWORD,
-            )
+        let settings = RconSettings {
+            // Tests send a lot of commands, and the server is right here.
+            min_interval: Duration::ZERO,
+            ..RconSettings::default()
+        };
+        let connection = RconClient::with_settings(
+            BRIDGE_CONFIG.rcon_target(),
+            &BRIDGE_CONFIG.rcon_password,
+            settings,
+        );
+        connection
+            .connect()
             .await
             .expect("Should be able to open rcon.");
         self.rcon_connection = Some(connection)
@@ -388,9 +444,9 @@ impl MinecraftEnvironment {
     ///
     /// Returns `None` if rcon is not open.
     pub async fn send_rcon(&mut self, command: &str) -> Option<String> {
-        if let Some(ref mut connection) = self.rcon_connection {
+        if let Some(connection) = &self.rcon_connection {
             let response = connection
-                .cmd(command)
+                .command(command)
                 .await
                 .expect("rcon should not fail.");
 
@@ -434,34 +490,112 @@ impl MinecraftEnvironment {
         }
     }
 
-    /// Looks for output from the minecraft server process, and gives up after 30 sec
-    async fn scan_output(&mut self, return_on: &str) {
-        #[allow(clippy::collapsible_if)] // maybe clean this up later but i like how this looks -Doc
-        if let Some(child) = &mut self.process {
-            if let Some(stdout) = child.stdout.take() {
-                let mut reader = BufReader::new(stdout).lines();
-
-                // max of 30 second timeout
-                let res = timeout(Duration::from_secs(30), async {
-                    while let Ok(Some(line)) = reader.next_line().await {
-                        debug!("[Server]: {}", line);
-                        if line.contains(return_on) {
-                            return Ok(());
-                        }
-                    }
-                    Err("Server closed unexpectedly")
-                })
-                .await;
-
-                if res.is_err() {
-                    error!("Timed out waiting for: `{}`!", return_on);
-                    panic!()
-                }
-            }
-        } else {
+    /// Start watching the server's log, and wait for it to say it's ready. Gives up after 30 sec.
+    async fn watch_until_ready(&mut self) {
+        let Some(stdout) = self.process.as_mut().and_then(|child| child.stdout.take()) else {
             // no server to scan output from?
             error!("Tried to scan output when there was no server to scan!");
             panic!()
+        };
+        let mut events = self.log.subscribe();
+        self.log.watch(stdout);
+
+        // max of 30 second timeout
+        let result = timeout(Duration::from_secs(30), async {
+            loop {
+                match events.recv().await {
+                    Ok(ServerEvent::Ready(_)) => return Ok(()),
+                    Ok(ServerEvent::Stopped) | Err(RecvError::Closed) => {
+                        return Err("Server closed unexpectedly");
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                }
+            }
+        })
+        .await;
+
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(reason)) => {
+                error!("{reason} while starting!");
+                panic!()
+            }
+            Err(_) => {
+                error!("Timed out waiting for the server to start!");
+                panic!()
+            }
         }
     }
 }
+
+/// Set keys in a `server.properties` file, adding any that aren't there yet.
+fn set_properties(text: &str, properties: &[(&str, &str)]) -> String {
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    for (key, value) in properties {
+        let new_line = format!("{key}={value}");
+        match lines
+            .iter_mut()
+            .find(|line| line.split_once('=').is_some_and(|(name, _)| name == *key))
+        {
+            Some(line) => *line = new_line,
+            None => lines.push(new_line),
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Replace the JVM args we added last time, if any, with new ones.
+fn set_jvm_args(text: &str, args: &str) -> String {
+    let (before, _) = text.split_once(JVM_ARGS_MARKER).unwrap_or((text, ""));
+    format!("{}\n{JVM_ARGS_MARKER}\n{args}\n", before.trim_end())
+}
+
+/// How many players are online, from the response to `/list`.
+fn players_online(list: &str) -> Option<u32> {
+    // "There are 1 of a max of 20 players online: Doc"
+    list.split_whitespace().find_map(|word| word.parse().ok())
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Editing the server's files keeps everything we don't touch, and can be done over and over.
+fn server_file_edits() {
+    let properties =
+        "#Minecraft server properties\nenable-rcon=false\nrcon.password=\nmotd=Meshpit Test\n";
+    let edited = set_properties(
+        properties,
+        &[
+            ("enable-rcon", "true"),
+            ("rcon.password", "1235"),
+            ("rcon.port", "25600"),
+        ],
+    );
+    assert_eq!(
+        edited,
+        "#Minecraft server properties\nenable-rcon=true\nrcon.password=1235\nmotd=Meshpit Test\nrcon.port=25600\n"
+    );
+    assert_eq!(set_properties(&edited, &[("rcon.port", "25600")]), edited);
+
+    let args = "# Xmx and Xms set the maximum and minimum RAM usage\n# -Xmx4G\n";
+    let once = set_jvm_args(args, "-XX:+UseZGC");
+    assert!(once.starts_with(args) && once.ends_with("-XX:+UseZGC\n"));
+    let twice = set_jvm_args(&once, "-XX:+UseG1GC");
+    assert_eq!(twice, once.replace("-XX:+UseZGC", "-XX:+UseG1GC"));
+}
+
+#[test]
+/// Reading the player count out of `/list`.
+fn player_count() {
+    assert_eq!(
+        players_online("There are 0 of a max of 20 players online: "),
+        Some(0)
+    );
+    assert_eq!(
+        players_online("There are 2 of a max of 20 players online: Doc, Steve"),
+        Some(2)
+    );
+    assert_eq!(players_online("Unknown command"), None);
+}
@@ -0,0 +1,241 @@
+// Settings for the test server bridge.
+// Everything has a default that works for a normal `cargo test`, so none of this needs setting. Settings are
+// read from `./meshpit_bridge.toml` (or wherever `MESHPIT_BRIDGE_CONFIG` points) if it exists, and then
+// environment variables override the file. The variable for a setting is its name in capitals with
+// `MESHPIT_` in front, IE `rcon_port` is `MESHPIT_RCON_PORT`.
+//
+// ```toml
+// server_dir = "./test_server"
+// rcon_address = "localhost"
+// rcon_port = 25575
+// rcon_password = "1235"
+// jvm_args = "-XX:+UseZGC -XX:+ZGenerational"
+// websocket_bind = "localhost:4816"
+// websocket_url = "localhost:4816/meshpit"
+// keep_alive = false
+// ```
+
+use std::path::PathBuf;
+
+use once_cell::sync::Lazy;
+use toml_edit::{DocumentMut, Item, Value};
+
+use crate::minecraft::computercraft::turtle::lua::DEFAULT_SERVER_URL;
+
+/// The config every test uses. Loaded the first time it's needed, and a bad config stops the tests.
+pub(super) static BRIDGE_CONFIG: Lazy<BridgeConfig> =
+    Lazy::new(|| BridgeConfig::load().unwrap_or_else(|error| panic!("Bad bridge config! {error}")));
+
+/// How to run and talk to the test server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct BridgeConfig {
+    /// Where the server lives. Relative paths are from wherever you run `cargo test`.
+    pub server_dir: PathBuf,
+    /// Where to connect to RCON, without the port.
+    pub rcon_address: String,
+    pub rcon_port: u16,
+    // nobody will ever guess 1235
+    pub rcon_password: String,
+    /// Extra arguments for java when running the server.
+    pub jvm_args: String,
+    /// Where the test websocket listens.
+    pub websocket_bind: String,
+    /// Where test computers connect to the websocket, without the scheme. Not always the same as
+    /// `websocket_bind`, IE when the server runs in a container.
+    pub websocket_url: String,
+    /// Wait for a player to join before running any tests, and leave the server running once they're done.
+    ///
+    /// Handy for watching tests happen.
+    pub keep_alive: bool,
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            // we are assuming you run `cargo test` while in `/meshpit`
+            server_dir: PathBuf::from("./test_server"),
+            rcon_address: "localhost".to_string(),
+            rcon_port: 25575,
+            rcon_password: "1235".to_string(),
+            jvm_args: "-XX:+UseZGC -XX:+ZGenerational".to_string(),
+            websocket_bind: "localhost:4816".to_string(),
+            websocket_url: DEFAULT_SERVER_URL.to_string(),
+            keep_alive: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(super) enum ConfigError {
+    Io(std::io::Error),
+    Toml(toml_edit::TomlError),
+    /// A setting in the file that we don't know about. Probably a typo.
+    Unknown(String),
+    /// A setting with a value that doesn't make sense for it.
+    BadValue(String, String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Toml(error) => write!(f, "{error}"),
+            Self::Unknown(key) => write!(f, "`{key}` isn't a setting"),
+            Self::BadValue(key, value) => write!(f, "`{value}` isn't a valid {key}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<toml_edit::TomlError> for ConfigError {
+    fn from(error: toml_edit::TomlError) -> Self {
+        Self::Toml(error)
+    }
+}
+
+/// Every setting there is.
+const KEYS: [&str; 8] = [
+    "server_dir",
+    "rcon_address",
+    "rcon_port",
+    "rcon_password",
+    "jvm_args",
+    "websocket_bind",
+    "websocket_url",
+    "keep_alive",
+];
+
+impl BridgeConfig {
+    /// Load the config from the file and environment, see the top of this file.
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = std::env::var("MESHPIT_BRIDGE_CONFIG")
+            .map_or_else(|_| PathBuf::from("./meshpit_bridge.toml"), PathBuf::from);
+        let file = match std::fs::read_to_string(&path) {
+            Ok(text) => Some(text),
+            // Only complain about a missing file if someone asked for it.
+            Err(error)
+                if error.kind() == std::io::ErrorKind::NotFound
+                    && std::env::var("MESHPIT_BRIDGE_CONFIG").is_err() =>
+            {
+                None
+            }
+            Err(error) => return Err(error.into()),
+        };
+        Self::from_sources(file.as_deref(), |name| std::env::var(name).ok())
+    }
+
+    /// Build a config from the text of a config file, and a way to look up environment variables.
+    fn from_sources(
+        file: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let document: DocumentMut = file.unwrap_or_default().parse()?;
+        if let Some((key, _)) = document.iter().find(|(key, _)| !KEYS.contains(key)) {
+            return Err(ConfigError::Unknown(key.to_string()));
+        }
+
+        let mut config = Self::default();
+        for key in KEYS {
+            let variable = format!("MESHPIT_{}", key.to_uppercase());
+            let Some(text) = env(&variable).or_else(|| document.get(key).and_then(file_value))
+            else {
+                continue;
+            };
+            let bad = || ConfigError::BadValue(key.to_string(), text.clone());
+            match key {
+                "server_dir" => config.server_dir = PathBuf::from(&text),
+                "rcon_address" => config.rcon_address = text,
+                "rcon_port" => config.rcon_port = text.parse().map_err(|_| bad())?,
+                "rcon_password" => config.rcon_password = text,
+                "jvm_args" => config.jvm_args = text,
+                "websocket_bind" => config.websocket_bind = text,
+                "websocket_url" => config.websocket_url = text,
+                "keep_alive" => {
+                    config.keep_alive = match text.to_lowercase().as_str() {
+                        "true" | "1" | "yes" => true,
+                        "false" | "0" | "no" => false,
+                        _ => return Err(bad()),
+                    }
+                }
+                _ => unreachable!("Every key is handled."),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Where to connect to RCON, with the port.
+    pub fn rcon_target(&self) -> String {
+        format!("{}:{}", self.rcon_address, self.rcon_port)
+    }
+}
+
+/// Settings from the file, as text so they're handled the same as environment variables.
+fn file_value(item: &Item) -> Option<String> {
+    match item.as_value()? {
+        Value::String(text) => Some(text.value().clone()),
+        Value::Integer(number) => Some(number.value().to_string()),
+        Value::Boolean(boolean) => Some(boolean.value().to_string()),
+        // Anything else is never valid, so let it fail.
+        other => Some(other.to_string()),
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Defaults, the file, and the environment all stack up in the right order.
+fn config_sources() {
+    let no_env = |_: &str| None;
+    assert_eq!(
+        BridgeConfig::from_sources(None, no_env).unwrap(),
+        BridgeConfig::default()
+    );
+
+    let file = r#"
+        rcon_port = 25600
+        rcon_password = "hunter2"
+        keep_alive = true
+    "#;
+    let config = BridgeConfig::from_sources(Some(file), no_env).unwrap();
+    assert_eq!(config.rcon_port, 25600);
+    assert_eq!(config.rcon_password, "hunter2");
+    assert!(config.keep_alive);
+    assert_eq!(config.rcon_target(), "localhost:25600");
+
+    // The environment wins.
+    let env = |name: &str| match name {
+        "MESHPIT_RCON_PORT" => Some("25601".to_string()),
+        "MESHPIT_KEEP_ALIVE" => Some("no".to_string()),
+        "MESHPIT_WEBSOCKET_URL" => Some("10.0.0.2:5000/meshpit".to_string()),
+        _ => None,
+    };
+    let config = BridgeConfig::from_sources(Some(file), env).unwrap();
+    assert_eq!(config.rcon_port, 25601);
+    assert_eq!(config.rcon_password, "hunter2");
+    assert!(!config.keep_alive);
+    assert_eq!(config.websocket_url, "10.0.0.2:5000/meshpit");
+    assert_eq!(config.websocket_bind, "localhost:4816");
+
+    // Mistakes
+    assert!(matches!(
+        BridgeConfig::from_sources(Some("rcon_prot = 1"), no_env),
+        Err(ConfigError::Unknown(key)) if key == "rcon_prot"
+    ));
+    assert!(matches!(
+        BridgeConfig::from_sources(Some("rcon_port = 70000"), no_env),
+        Err(ConfigError::BadValue(key, _)) if key == "rcon_port"
+    ));
+    assert!(matches!(
+        BridgeConfig::from_sources(None, |_| Some("maybe".to_string())),
+        Err(ConfigError::BadValue(..))
+    ));
+}
@@ -1,4 +1,6 @@
 mod bridge;
+mod bridge_config;
 mod connection;
 pub mod prelude;
+mod provision;
 mod test_harness;
@@ -0,0 +1,490 @@
+// Getting the files the test server needs, and making sure they're still right.
+// Everything the server needs is an artifact. Artifacts are looked for in a local cache first, and
+// only downloaded if they aren't there. Downloads get saved into the cache, so after one run with
+// internet, the cache folder (or a tarball of it) can be copied to machines without internet.
+//
+// Set `MESHPIT_ARTIFACT_CACHE` to use a different cache folder than `./test_artifacts`, and set
+// `MESHPIT_ARTIFACT_TARBALL` to a `.tar` or `.tar.gz` of artifacts to fill the cache from.
+//
+// Every artifact is pinned to a hash, and anything that doesn't match is refused. After installing,
+// we write down which installer we used. Every run checks the server folder against the pins,
+// replacing broken or outdated mods, and reinstalling from scratch if anything bigger changed.
+
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use flate2::read::GzDecoder;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// A file the test server needs.
+#[derive(Debug)]
+pub(super) struct Artifact {
+    pub file_name: &'static str,
+    pub url: &'static str,
+    /// The SHA-256 of the file, in lowercase hex. Anything that doesn't match is refused.
+    pub sha256: &'static str,
+}
+
+/// Stand-in for hashes nobody has filled in yet. Never matches anything, so fetching the artifact
+/// fails with a `HashMismatch` that says what the hash should be.
+// TODO: Replace these with the hashes from those errors. They were written without internet.
+const NOT_PINNED_YET: &str = "not pinned yet";
+
+pub(super) static NEOFORGE_INSTALLER: Artifact = Artifact {
+    file_name: "neoforge-21.1.218-installer.jar",
+    url: "https://maven.neoforged.net/releases/net/neoforged/neoforge/21.1.218/neoforge-21.1.218-installer.jar",
+    sha256: NOT_PINNED_YET,
+};
+pub(super) static MODS: &[Artifact] = &[
+    Artifact {
+        file_name: "cc-tweaked-1.21.1-forge-1.117.0.jar",
+        url: "https://cdn.modrinth.com/data/gu7yAYhd/versions/hAW75xeY/cc-tweaked-1.21.1-forge-1.117.0.jar",
+        sha256: NOT_PINNED_YET,
+    },
+    Artifact {
+        file_name: "ferritecore-7.0.2-neoforge.jar",
+        url: "https://cdn.modrinth.com/data/uXXizFIs/versions/CnpoQxCx/ferritecore-7.0.2-neoforge.jar",
+        sha256: NOT_PINNED_YET,
+    },
+    Artifact {
+        file_name: "lithium-neoforge-0.15.1+mc1.21.1.jar",
+        url: "https://cdn.modrinth.com/data/gvQqBUqZ/versions/G5SDYehn/lithium-neoforge-0.15.1%2Bmc1.21.1.jar",
+        sha256: NOT_PINNED_YET,
+    },
+    Artifact {
+        file_name: "modernfix-neoforge-5.25.1+mc1.21.1.jar",
+        url: "https://cdn.modrinth.com/data/nmDcB62a/versions/8Be8uJW6/modernfix-neoforge-5.25.1%2Bmc1.21.1.jar",
+        sha256: NOT_PINNED_YET,
+    },
+    Artifact {
+        file_name: "spark-1.10.124-neoforge.jar",
+        url: "https://cdn.modrinth.com/data/l6YH9Als/versions/v5qtqRQi/spark-1.10.124-neoforge.jar",
+        sha256: NOT_PINNED_YET,
+    },
+];
+
+/// Where we write down what got installed, inside the server folder.
+const STATE_FILE: &str = "meshpit_provision.json";
+
+#[derive(Debug)]
+pub(super) enum ProvisionError {
+    Io(std::io::Error),
+    /// Couldn't download an artifact, and it wasn't in the cache either.
+    Download(&'static str, String),
+    /// An artifact didn't match its pinned hash.
+    HashMismatch {
+        file_name: &'static str,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl std::fmt::Display for ProvisionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Download(file_name, error) => {
+                write!(f, "Couldn't download `{file_name}`: {error}")
+            }
+            Self::HashMismatch {
+                file_name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "`{file_name}` should have the sha256 {expected}, but it's {actual}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProvisionError {}
+
+impl From<std::io::Error> for ProvisionError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// The lowercase hex SHA-256 of some bytes.
+pub(super) fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+// ==
+// Cache
+// ==
+
+/// Where artifacts come from.
+pub(super) struct ArtifactStore {
+    cache: PathBuf,
+}
+
+impl ArtifactStore {
+    /// Use a cache folder. It's made if it doesn't exist.
+    pub fn new(cache: impl Into<PathBuf>) -> Result<Self, ProvisionError> {
+        let cache = cache.into();
+        fs::create_dir_all(&cache)?;
+        Ok(Self { cache })
+    }
+
+    /// Set up the store from the environment variables, see the top of this file.
+    pub fn from_env() -> Result<Self, ProvisionError> {
+        let cache = std::env::var("MESHPIT_ARTIFACT_CACHE")
+            .map_or_else(|_| PathBuf::from("./test_artifacts"), PathBuf::from);
+        let store = Self::new(cache)?;
+        if let Ok(tarball) = std::env::var("MESHPIT_ARTIFACT_TARBALL") {
+            info!("Unpacking artifacts from {tarball}...");
+            store.unpack(Path::new(&tarball))?;
+        }
+        Ok(store)
+    }
+
+    /// Put every file in a tarball into the cache. Folders inside the tarball are ignored, only the
+    /// file names matter.
+    pub fn unpack(&self, tarball: &Path) -> Result<(), ProvisionError> {
+        let file = File::open(tarball)?;
+        let reader: Box<dyn Read> = if tarball.extension().is_some_and(|ext| ext == "gz") {
+            Box::new(GzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path()?.into_owned();
+            let Some(name) = path.file_name() else {
+                continue;
+            };
+            entry.unpack(self.cache.join(name))?;
+        }
+        Ok(())
+    }
+
+    /// Get an artifact, from the cache if we can, or by downloading it.
+    ///
+    /// Cached files that don't match their pin are thrown out and downloaded again.
+    pub async fn fetch(&self, artifact: &'static Artifact) -> Result<Vec<u8>, ProvisionError> {
+        let cached = self.cache.join(artifact.file_name);
+        if let Ok(bytes) = fs::read(&cached) {
+            match check_pin(artifact, &bytes) {
+                Ok(()) => return Ok(bytes),
+                Err(error) => warn!(
+                    "Cached {} is bad, downloading again. {error}",
+                    artifact.file_name
+                ),
+            }
+        }
+
+        info!("Downloading `{}`...", artifact.file_name);
+        let download = async {
+            reqwest::get(artifact.url)
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await
+        };
+        let bytes = download
+            .await
+            .map_err(|error| ProvisionError::Download(artifact.file_name, error.to_string()))?
+            .to_vec();
+        check_pin(artifact, &bytes)?;
+        fs::write(&cached, &bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Make sure some bytes match an artifact's pin.
+fn check_pin(artifact: &'static Artifact, bytes: &[u8]) -> Result<(), ProvisionError> {
+    let actual = sha256_hex(bytes);
+    if actual != artifact.sha256 {
+        return Err(ProvisionError::HashMismatch {
+            file_name: artifact.file_name,
+            expected: artifact.sha256.to_string(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
+// ==
+// Checking the server
+// ==
+
+/// What we installed last time.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ProvisionState {
+    /// The installer's file name, and its hash.
+    installer: (String, String),
+}
+
+/// What needs doing before the server can run.
+#[derive(Debug)]
+pub(super) enum ServerCheck {
+    /// Everything matches.
+    Ready,
+    /// The server is fine, but some mods need replacing or removing.
+    Repair {
+        broken: Vec<&'static Artifact>,
+        extra: Vec<PathBuf>,
+    },
+    /// Start over, for this reason.
+    Reinstall(String),
+}
+
+/// Look over a server folder to see if it still matches what we want.
+pub(super) fn check_server(
+    server_dir: &Path,
+    installer: &'static Artifact,
+    mods: &'static [Artifact],
+) -> ServerCheck {
+    let Some(state) = fs::read_to_string(server_dir.join(STATE_FILE))
+        .ok()
+        .and_then(|text| serde_json::from_str::<ProvisionState>(&text).ok())
+    else {
+        // Also what happens if setup crashed halfway, since the state is written last.
+        return ServerCheck::Reinstall("No record of a finished install".to_string());
+    };
+
+    let (installer_name, installer_hash) = &state.installer;
+    if installer_name != installer.file_name {
+        return ServerCheck::Reinstall(format!(
+            "NeoForge changed from {installer_name} to {}",
+            installer.file_name
+        ));
+    }
+    if installer.sha256 != installer_hash {
+        return ServerCheck::Reinstall("NeoForge installer doesn't match its pin".to_string());
+    }
+    // The installer makes this, and we need it to run.
+    if !server_dir.join("user_jvm_args.txt").exists() {
+        return ServerCheck::Reinstall("NeoForge install is missing files".to_string());
+    }
+
+    let mod_folder = server_dir.join("mods");
+    let broken: Vec<&'static Artifact> = mods
+        .iter()
+        .filter(|artifact| {
+            let Ok(bytes) = fs::read(mod_folder.join(artifact.file_name)) else {
+                return true;
+            };
+            sha256_hex(&bytes) != artifact.sha256
+        })
+        .collect();
+
+    // Mods we don't want anymore, like old versions.
+    let extra: Vec<PathBuf> = fs::read_dir(&mod_folder)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && !mods.iter().any(|artifact| {
+                    path.file_name()
+                        .is_some_and(|name| name == artifact.file_name)
+                })
+        })
+        .collect();
+
+    if broken.is_empty() && extra.is_empty() {
+        ServerCheck::Ready
+    } else {
+        ServerCheck::Repair { broken, extra }
+    }
+}
+
+/// Put mods in the mod folder, replacing whatever is there.
+pub(super) async fn install_mods(
+    server_dir: &Path,
+    store: &ArtifactStore,
+    mods: &[&'static Artifact],
+) -> Result<(), ProvisionError> {
+    let mod_folder = server_dir.join("mods");
+    fs::create_dir_all(&mod_folder)?;
+    for artifact in mods {
+        let bytes = store.fetch(artifact).await?;
+        fs::write(mod_folder.join(artifact.file_name), bytes)?;
+    }
+    Ok(())
+}
+
+/// Fix up a server that `check_server` said needs repairs.
+pub(super) async fn repair_server(
+    server_dir: &Path,
+    store: &ArtifactStore,
+    broken: &[&'static Artifact],
+    extra: &[PathBuf],
+) -> Result<(), ProvisionError> {
+    for path in extra {
+        info!("Removing unwanted mod {}", path.display());
+        fs::remove_file(path)?;
+    }
+    for artifact in broken {
+        info!("Replacing mod `{}`", artifact.file_name);
+    }
+    install_mods(server_dir, store, broken).await?;
+    record_install(server_dir, &NEOFORGE_INSTALLER, None)
+}
+
+/// Write down what's installed, so the next run can check it. Must be the last step of setup.
+///
+/// Pass the installer's bytes if it was just installed, otherwise the old record is kept.
+pub(super) fn record_install(
+    server_dir: &Path,
+    installer: &'static Artifact,
+    installer_bytes: Option<&[u8]>,
+) -> Result<(), ProvisionError> {
+    let old: ProvisionState = fs::read_to_string(server_dir.join(STATE_FILE))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default();
+    let installer = match installer_bytes {
+        Some(bytes) => (installer.file_name.to_string(), sha256_hex(bytes)),
+        None => old.installer,
+    };
+
+    let state = ProvisionState { installer };
+    let json = serde_json::to_string_pretty(&state).expect("State is always valid json.");
+    fs::write(server_dir.join(STATE_FILE), json)?;
+    Ok(())
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+/// A fresh empty folder in the temp directory.
+fn scratch_folder(name: &str) -> PathBuf {
+    let folder = std::env::temp_dir().join(format!("meshpit-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&folder);
+    fs::create_dir_all(&folder).unwrap();
+    folder
+}
+
+#[test]
+/// Known answer, so we know the hex comes out right.
+fn sha256_known_answer() {
+    assert_eq!(
+        sha256_hex(b"abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
+
+#[tokio::test]
+/// Cached artifacts get used without downloading, and ones that don't match their pin are refused.
+async fn artifacts_from_cache() {
+    static PINNED: Artifact = Artifact {
+        file_name: "abc.jar",
+        // Nothing should ever try to download this.
+        url: "http://localhost:1/abc.jar",
+        sha256: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+    };
+    let folder = scratch_folder("cache");
+
+    // Fill the cache from a tarball, with a folder in the way to make sure only names matter.
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(3);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "artifacts/abc.jar", &b"abc"[..])
+        .unwrap();
+    let tarball = folder.join("artifacts.tar");
+    fs::write(&tarball, builder.into_inner().unwrap()).unwrap();
+
+    let store = ArtifactStore::new(folder.join("cache")).unwrap();
+    store.unpack(&tarball).unwrap();
+    assert_eq!(store.fetch(&PINNED).await.unwrap(), b"abc");
+
+    // Corrupt it. It should try to download again, which can't work.
+    fs::write(folder.join("cache/abc.jar"), b"abd").unwrap();
+    assert!(matches!(
+        store.fetch(&PINNED).await,
+        Err(ProvisionError::Download("abc.jar", _))
+    ));
+
+    fs::remove_dir_all(folder).unwrap();
+}
+
+#[test]
+/// Broken, missing, and leftover mods are all noticed, and bigger changes mean reinstalling.
+fn server_checks() {
+    // sha256 of `installer`
+    const INSTALLER_HASH: &str = "9c0d294c05fc1d88d698034609bb81c0c69196327594e4c69d2915c80fd9850c";
+    static INSTALLER: Artifact = Artifact {
+        file_name: "installer.jar",
+        url: "",
+        sha256: INSTALLER_HASH,
+    };
+    static OTHER_INSTALLER: Artifact = Artifact {
+        file_name: "newer-installer.jar",
+        url: "",
+        sha256: INSTALLER_HASH,
+    };
+    static TEST_MODS: &[Artifact] = &[
+        Artifact {
+            file_name: "a.jar",
+            url: "",
+            // sha256 of `anything`
+            sha256: "ee0874170b7f6f32b8c2ac9573c428d35b575270a66b757c2c0185d2bd09718d",
+        },
+        Artifact {
+            file_name: "b.jar",
+            url: "",
+            sha256: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        },
+    ];
+    let server = scratch_folder("server");
+
+    assert!(matches!(
+        check_server(&server, &INSTALLER, TEST_MODS),
+        ServerCheck::Reinstall(_)
+    ));
+
+    fs::write(server.join("user_jvm_args.txt"), "").unwrap();
+    fs::create_dir(server.join("mods")).unwrap();
+    fs::write(server.join("mods/a.jar"), "anything").unwrap();
+    fs::write(server.join("mods/b.jar"), "abc").unwrap();
+    record_install(&server, &INSTALLER, Some(b"installer")).unwrap();
+    assert!(matches!(
+        check_server(&server, &INSTALLER, TEST_MODS),
+        ServerCheck::Ready
+    ));
+
+    fs::write(server.join("mods/a.jar"), "something else").unwrap();
+    fs::remove_file(server.join("mods/b.jar")).unwrap();
+    fs::write(server.join("mods/old.jar"), "").unwrap();
+    let ServerCheck::Repair { broken, extra } = check_server(&server, &INSTALLER, TEST_MODS) else {
+        panic!("Should need repairs");
+    };
+    let broken: Vec<&str> = broken.iter().map(|artifact| artifact.file_name).collect();
+    assert_eq!(broken, ["a.jar", "b.jar"]);
+    assert_eq!(extra, [server.join("mods/old.jar")]);
+
+    assert!(matches!(
+        check_server(&server, &OTHER_INSTALLER, TEST_MODS),
+        ServerCheck::Reinstall(_)
+    ));
+
+    // An installer that isn't the pinned one.
+    record_install(&server, &INSTALLER, Some(b"not the installer")).unwrap();
+    assert!(matches!(
+        check_server(&server, &INSTALLER, TEST_MODS),
+        ServerCheck::Reinstall(_)
+    ));
+
+    fs::remove_dir_all(server).unwrap();
+}
@@ -0,0 +1,6 @@
+-- Dig whatever is in front of us, and tell the test we did.
+local networking = require("networking")
+local walkback = require("walkback")
+
+walkback.dig()
+networking.sendToControl("dug")
@@ -0,0 +1,42 @@
+-- Put in front of test startup files, so the test harness can see what the computer printed after
+-- the test is over. Everything written to the terminal also gets appended to /terminal.log
+do
+    local log = fs.open("/terminal.log", "a")
+    local native = term.current()
+    local capture = {}
+    -- Copy everything over, so this works anywhere a normal terminal does.
+    for name, func in pairs(native) do
+        capture[name] = func
+    end
+
+    local function record(text)
+        log.write(text)
+        log.flush()
+    end
+
+    local _, lastY = native.getCursorPos()
+    capture.write = function(text)
+        record(tostring(text))
+        native.write(text)
+    end
+    capture.blit = function(text, textColour, backgroundColour)
+        record(text)
+        native.blit(text, textColour, backgroundColour)
+    end
+    -- print() moves down a line with setCursorPos, or scrolls if it's already at the bottom.
+    capture.setCursorPos = function(x, y)
+        if y ~= lastY then
+            record("\n")
+        end
+        lastY = y
+        native.setCursorPos(x, y)
+    end
+    capture.scroll = function(lines)
+        if lines > 0 then
+            record("\n")
+        end
+        native.scroll(lines)
+    end
+
+    term.redirect(capture)
+end
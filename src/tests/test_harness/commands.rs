@@ -1,7 +1,15 @@
 // Stuff related to running commands.
 
+use log::warn;
+
 use crate::{
-    minecraft::{types::MinecraftPosition, vanilla::block_type::MinecraftBlock},
+    minecraft::{
+        nbt::NbtTag,
+        peripherals::inventory::GenericInventorySlot,
+        types::MinecraftPosition,
+        vanilla::{block_state::BlockState, block_type::MinecraftBlock, item_type::MinecraftItem},
+    },
+    rcon::command_data,
     tests::test_harness::test_enviroment::{MINECRAFT_TESTING_ENV, MinecraftTestHandle},
 };
 
@@ -10,10 +18,12 @@ use crate::{
 /// All positions input into this command are interpreted as offsets from 0,0,0.
 #[derive(Clone)]
 pub enum TestCommand {
-    /// Place a block.
+    /// Place a block, with its block state.
+    ///
+    /// If the position has a facing direction and the state doesn't, the position's facing is used.
     ///
     /// Returns a pass or fail.
-    SetBlock(MinecraftPosition, MinecraftBlock),
+    SetBlock(MinecraftPosition, BlockState),
 
     /// Fill some blocks.
     ///
@@ -29,13 +39,73 @@ pub enum TestCommand {
     /// For example, you can get the fuel level of a turtle with "Fuel". Do note that
     /// an empty input string will return all blockdata.
     ///
-    /// Returns Data, or None if no data was found, or no block was at that position.
+    /// Returns the NBT Data, or None if no data was found, or no block was at that position.
     GetBlockData(MinecraftPosition, String),
 
-    /// Run a raw command. You should not do this.
-    #[doc(hidden)]
-    #[deprecated(note = "I'm too lazy to refactor and make this private. Don't use.")]
-    RawCommand(String),
+    /// Put items in a slot of a container, or empty it with None. Slots start at 0, like they do
+    /// in commands.
+    ///
+    /// Returns a pass or fail.
+    SetSlot(MinecraftPosition, u8, Option<GenericInventorySlot>),
+
+    /// Put a floppy with this disk ID into a disk drive. `SetSlot` can't, since the ID is an item
+    /// component. See `write_bootstrap_disk` for putting files on it first.
+    ///
+    /// Returns a pass or fail.
+    InsertFloppy(MinecraftPosition, u16),
+
+    /// Read everything in a container.
+    ///
+    /// Returns Items, or None if there isn't a container there.
+    GetItems(MinecraftPosition),
+
+    /// Set the fuel level of a turtle.
+    ///
+    /// Returns a pass or fail. Setting the fuel to what it already was is a pass.
+    SetFuel(MinecraftPosition, u64),
+
+    /// Kick a computer on by its position. This is the only way to turn on a freshly placed computer,
+    /// since it doesn't have an ID until it's been on once.
+    ///
+    /// Returns a pass or fail.
+    StartComputer(MinecraftPosition),
+
+    /// Turn a computer on (true) or off (false) by its ID.
+    ///
+    /// Returns a pass or fail. Does nothing if it's already in that state, which still passes.
+    SetComputerPower(u16, bool),
+
+    /// Summon an entity in the middle of a block. IE `zombie` or `minecraft:item_frame`.
+    ///
+    /// Returns a pass or fail.
+    Summon(MinecraftPosition, String),
+
+    /// Remove every entity (besides players) within a box, optionally only of one type.
+    ///
+    /// Returns the Count of entities removed.
+    KillEntities(MinecraftPosition, MinecraftPosition, Option<String>),
+
+    // The rest of these change the whole server, not just this test! Tests run in parallel, so
+    // anything using these should make sure it won't break other tests.
+    /// Set the time of day in ticks.
+    ///
+    /// Returns a pass or fail.
+    SetTime(u32),
+
+    /// Set a gamerule. IE `("doDaylightCycle", "false")`
+    ///
+    /// Returns a pass or fail.
+    SetGameRule(String, String),
+
+    /// Freeze (true) or unfreeze (false) the game's ticking.
+    ///
+    /// Returns a pass or fail.
+    FreezeTicks(bool),
+
+    /// Step the game forwards some ticks while frozen.
+    ///
+    /// Returns a pass or fail, which fails if the game isn't frozen.
+    StepTicks(u32),
 }
 
 impl TestCommand {
@@ -45,19 +115,28 @@ impl TestCommand {
         let mut env = MINECRAFT_TESTING_ENV.lock().await;
         let corner = handle.corner();
         match self {
-            TestCommand::SetBlock(minecraft_position, minecraft_block) => {
+            TestCommand::SetBlock(minecraft_position, block_state) => {
                 let position = corner.with_offset(*minecraft_position).as_command_string();
-                let block_string = minecraft_block.get_full_name();
 
                 // Set the facing if needed.
-                let face = if let Some(direction) = minecraft_position.facing {
-                    format!("[facing={direction}]")
-                } else {
-                    "".to_string()
-                };
+                let mut block_state = block_state.clone();
+                if let Some(direction) = minecraft_position.facing
+                    && block_state.get("facing").is_none()
+                {
+                    block_state = match block_state.clone().with_facing(direction) {
+                        Ok(facing) => facing,
+                        Err(error) => {
+                            return TestCommandResult::Failed(format!(
+                                "{} can't face {direction}: {error:?}",
+                                block_state.block().get_name()
+                            ));
+                        }
+                    };
+                }
+                let block_string = block_state.to_command_string();
 
                 let result = env
-                    .run_command(format!("setblock {position} {block_string}{face}"))
+                    .run_command(format!("setblock {position} {block_string}"))
                     .await;
                 // If we placed the block, we will get this text.
                 TestCommandResult::Success(result.contains("Changed the block"))
@@ -123,27 +202,130 @@ impl TestCommand {
                 let position = corner.with_offset(*minecraft_position).as_command_string();
                 let command_string = format!("/data get block {position} {path}");
                 let result: String = env.run_command(command_string).await;
-
-                // Could not get data?
-                if !result.contains("has the following block data:") {
-                    return TestCommandResult::Data(None);
-                }
-
-                // Remove the prefix.
-                match result.split("has the following block data:").last() {
-                    Some(string) => TestCommandResult::Data(Some(string.trim().to_string())),
-                    None => TestCommandResult::Data(None),
-                }
+                TestCommandResult::Data(command_data(&result))
+            }
+            TestCommand::SetSlot(minecraft_position, slot, contents) => {
+                let position = corner.with_offset(*minecraft_position).as_command_string();
+                let with = match contents {
+                    Some(contents) => {
+                        format!("{} {}", contents.item.get_full_name(), contents.count)
+                    }
+                    None => "minecraft:air".to_string(),
+                };
+                let result = env
+                    .run_command(format!(
+                        "item replace block {position} container.{slot} with {with}"
+                    ))
+                    .await;
+                TestCommandResult::Success(result.contains("Replaced a slot"))
             }
-            #[allow(deprecated)] // Yeah i know.
-            TestCommand::RawCommand(command) => {
-                // This is not very safe. but im not writing an interface for tons of commands.
-                let result = env.run_command(command.to_string()).await;
-                if result.is_empty() {
-                    TestCommandResult::Data(None)
+            TestCommand::InsertFloppy(minecraft_position, disk_id) => {
+                let position = corner.with_offset(*minecraft_position).as_command_string();
+                let result = env
+                    .run_command(format!(
+                        "item replace block {position} container.0 with computercraft:disk[computercraft:disk_id={disk_id}] 1"
+                    ))
+                    .await;
+                TestCommandResult::Success(result.contains("Replaced a slot"))
+            }
+            TestCommand::GetItems(minecraft_position) => {
+                let position = corner.with_offset(*minecraft_position).as_command_string();
+                // We get everything instead of just the items, since empty containers don't have an
+                // items list at all, and that's an error.
+                let result = env.run_command(format!("data get block {position}")).await;
+                let items = command_data(&result).and_then(|data| match data.get("Items") {
+                    Some(items) => read_items(items),
+                    None => Some(vec![]),
+                });
+                TestCommandResult::Items(items)
+            }
+            TestCommand::SetFuel(minecraft_position, fuel) => {
+                let position = corner.with_offset(*minecraft_position).as_command_string();
+                let result = env
+                    .run_command(format!(
+                        "data modify block {position} Fuel set value {fuel}"
+                    ))
+                    .await;
+                // If the fuel value is what we want to set it to, thats fine as well.
+                TestCommandResult::Success(
+                    result.contains("Modified block data of") || result.contains("Nothing changed"),
+                )
+            }
+            TestCommand::StartComputer(minecraft_position) => {
+                let position = corner.with_offset(*minecraft_position).as_command_string();
+                let result = env
+                    .run_command(format!("data modify block {position} On set value 1b"))
+                    .await;
+                TestCommandResult::Success(result.contains("Modified block data of"))
+            }
+            TestCommand::SetComputerPower(id, on) => {
+                let (command, expected) = if *on {
+                    ("turn-on", "Turned on")
                 } else {
-                    TestCommandResult::Data(Some(result))
-                }
+                    ("shutdown", "Shutdown")
+                };
+                let result = env
+                    .run_command(format!("computercraft {command} {id}"))
+                    .await;
+                TestCommandResult::Success(result.contains(expected))
+            }
+            TestCommand::Summon(minecraft_position, entity) => {
+                let position = corner.with_offset(*minecraft_position);
+                // Entities go at the corner of the block unless we center them.
+                let result = env
+                    .run_command(format!(
+                        "summon {entity} {}.5 {} {}.5",
+                        position.x, position.y, position.z
+                    ))
+                    .await;
+                TestCommandResult::Success(result.contains("Summoned new"))
+            }
+            TestCommand::KillEntities(pos1, pos2, entity) => {
+                let start = corner.with_offset(*pos1);
+                let end = corner.with_offset(*pos2);
+                // dx/dy/dz grow the box from the starting corner, so we start at the lowest one.
+                let (x, y, z) = (start.x.min(end.x), start.y.min(end.y), start.z.min(end.z));
+                let (dx, dy, dz) = (
+                    (start.x - end.x).abs(),
+                    (start.y - end.y).abs(),
+                    (start.z - end.z).abs(),
+                );
+                let kind = entity.as_deref().unwrap_or("!player");
+                let result = env
+                    .run_command(format!(
+                        "kill @e[type={kind},x={x},y={y},z={z},dx={dx},dy={dy},dz={dz}]"
+                    ))
+                    .await;
+                // "Killed 3 entities", or "Killed Zombie" if there was only one.
+                let count = match result.strip_prefix("Killed ") {
+                    Some(rest) => rest
+                        .strip_suffix(" entities")
+                        .and_then(|count| count.parse().ok())
+                        .unwrap_or(1),
+                    None => 0,
+                };
+                TestCommandResult::Count(count)
+            }
+            TestCommand::SetTime(time) => {
+                let result = env.run_command(format!("time set {time}")).await;
+                TestCommandResult::Success(result.contains("Set the time"))
+            }
+            TestCommand::SetGameRule(rule, value) => {
+                let result = env.run_command(format!("gamerule {rule} {value}")).await;
+                TestCommandResult::Success(result.contains("is now set to"))
+            }
+            TestCommand::FreezeTicks(freeze) => {
+                let (command, expected) = if *freeze {
+                    ("freeze", "The game is frozen")
+                } else {
+                    ("unfreeze", "The game is running")
+                };
+                let result = env.run_command(format!("tick {command}")).await;
+                TestCommandResult::Success(result.contains(expected))
+            }
+            TestCommand::StepTicks(ticks) => {
+                let result = env.run_command(format!("tick step {ticks}")).await;
+                TestCommandResult::Success(result.contains("Stepping"))
             }
         }
     }
@@ -153,23 +335,81 @@ pub enum TestCommandResult {
     /// A pass/fail.
     Success(bool),
     /// Maybe some data.
-    Data(Option<String>),
+    Data(Option<NbtTag>),
+    /// Whatever is in a container, by slot. Slots start at 0.
+    Items(Option<Vec<(u8, GenericInventorySlot)>>),
+    /// How many things a command affected.
+    Count(u32),
+    /// The command couldn't even be sent, and why. IE a block that can't face the way it was asked to.
+    Failed(String),
 }
 
 // we impl some casting methods to make tests less verbose
 impl TestCommandResult {
     /// Extract the success bool. Panics if this is not a success variant.
+    ///
+    /// Commands that `Failed` count as false, since that's what the test cares about.
     pub fn success(self) -> bool {
         match self {
             TestCommandResult::Success(result) => result,
+            TestCommandResult::Failed(reason) => {
+                warn!("Test command failed: {reason}");
+                false
+            }
             _ => panic!("Not a success variant!"),
         }
     }
     /// Extract the Data option. Panics if this is not a Data variant.
-    pub fn data(self) -> Option<String> {
+    pub fn data(self) -> Option<NbtTag> {
         match self {
             TestCommandResult::Data(data) => data,
+            TestCommandResult::Failed(reason) => panic!("Test command failed: {reason}"),
             _ => panic!("Not a data variant!"),
         }
     }
+    /// Extract the Items option. Panics if this is not an items variant.
+    pub fn items(self) -> Option<Vec<(u8, GenericInventorySlot)>> {
+        match self {
+            TestCommandResult::Items(items) => items,
+            TestCommandResult::Failed(reason) => panic!("Test command failed: {reason}"),
+            _ => panic!("Not an items variant!"),
+        }
+    }
+    /// Extract the Count. Panics if this is not a count variant.
+    pub fn count(self) -> u32 {
+        match self {
+            TestCommandResult::Count(count) => count,
+            TestCommandResult::Failed(reason) => panic!("Test command failed: {reason}"),
+            _ => panic!("Not a count variant!"),
+        }
+    }
+}
+
+// ==
+// Reading data
+// ==
+
+/// Read an Items list, which looks like
+/// `[{Slot: 0b, id: "minecraft:coal", count: 5}, {Slot: 3b, id: "minecraft:stone", count: 1, components: {...}}]`
+///
+/// Returns None if anything in there doesn't make sense.
+fn read_items(items: &NbtTag) -> Option<Vec<(u8, GenericInventorySlot)>> {
+    items
+        .as_list()?
+        .iter()
+        .map(|item| {
+            let slot = u8::try_from(item.get("Slot")?.as_i64()?).ok()?;
+            let id = item.get("id")?.as_str()?;
+            let name = id.split_once(':').map_or(id, |(_, name)| name);
+            let item_type = MinecraftItem::from_string(name)?;
+            let count = u8::try_from(item.get("count")?.as_i64()?).ok()?;
+            Some((
+                slot,
+                GenericInventorySlot {
+                    item: item_type,
+                    count,
+                },
+            ))
+        })
+        .collect()
 }
@@ -3,9 +3,12 @@
 // Annoyingly, computers do not turn on and off instantly, and can take a weirdly long time. So we control a delay here.
 pub(super) const COMPUTER_STATE_CHANGE_TIME: Duration = Duration::from_millis(1000);
 
-use std::{path::PathBuf, time::Duration};
+use std::time::Duration;
 
-use crate::tests::prelude::*;
+use crate::{
+    minecraft::computercraft::turtle::lua::{LuaAsset, get_asset},
+    tests::prelude::*,
+};
 
 // Do not derive on this. We do not want to be able to make copies of computers like this.
 pub struct ComputerSetup {
@@ -36,28 +39,25 @@ pub struct MeshpitLibraries {
     pub panic: Option<bool>,
     /// Helpers.
     pub helpers: Option<bool>,
+    /// Over the air updates. Needs networking to report back.
+    pub update: Option<bool>,
 }
 
 impl MeshpitLibraries {
-    /// Get a Vec containing all of the paths to the libraries.
-    pub fn to_files(self) -> Vec<PathBuf> {
-        let mut paths: Vec<PathBuf> = vec![];
-
-        let lua_folder = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-            .join("src/minecraft/computercraft/turtle/lua");
-        if self.networking.unwrap_or(false) {
-            paths.push(lua_folder.join("networking.lua"));
-        };
-        if self.walkback.unwrap_or(false) {
-            paths.push(lua_folder.join("walkback.lua"));
-        };
-        if self.panic.unwrap_or(false) {
-            paths.push(lua_folder.join("panic.lua"));
-        };
-        if self.helpers.unwrap_or(false) {
-            paths.push(lua_folder.join("helpers.lua"));
-        };
-        paths
+    /// Get all of the libraries to put on the computer.
+    pub fn to_assets(self) -> Vec<&'static LuaAsset> {
+        let wanted = [
+            (self.networking, "networking.lua"),
+            (self.walkback, "walkback.lua"),
+            (self.panic, "panic.lua"),
+            (self.helpers, "helpers.lua"),
+            (self.update, "update.lua"),
+        ];
+        wanted
+            .into_iter()
+            .filter(|(enabled, _)| enabled.unwrap_or(false))
+            .map(|(_, name)| get_asset(name).expect("Library should be embedded"))
+            .collect()
     }
     pub fn new() -> Self {
         Self {
@@ -65,6 +65,7 @@ impl MeshpitLibraries {
             walkback: None,
             panic: None,
             helpers: None,
+            update: None,
         }
     }
 }
@@ -104,6 +105,8 @@ pub enum ComputerConfigs {
     Empty,
     /// Adds a `startup.lua` file to this computer with the contents of the incoming string. This does
     /// not include any of the standard libraries that meshpit uses. Use StartupIncludingLibraries instead.
+    ///
+    /// The terminal gets recorded for the test report, which adds a little to the start of the first line.
     Startup(String),
     /// Adds a `startup.lua` file to this computer, additionally including some meshpit libraries.
     StartupIncludingLibraries(String, MeshpitLibraries),
@@ -131,17 +134,11 @@ pub struct TestComputer {
 impl TestComputer {
     /// Turn on the computer. Does nothing if computer is already on.
     pub async fn turn_on(&self, handle: &mut MinecraftTestHandle) {
-        // /computercraft turn-on 49
-        #[allow(deprecated)] // we use the raw command here. yes that sucks.
-        let command = TestCommand::RawCommand(format!("computercraft turn-on {}", self.id));
-        let result = handle
-            .command(command)
-            .await
-            .data()
-            .expect("This should print");
+        let command = TestCommand::SetComputerPower(self.id, true);
         assert!(
-            result.contains("Turned on"),
-            "Missing turned on message! {result}"
+            handle.command(command).await.success(),
+            "Failed to turn on computer {}!",
+            self.id
         );
 
         // This takes a moment, so we must wait.
@@ -155,17 +152,11 @@ impl TestComputer {
 
     /// Turn off the computer. Does nothing if computer is already off.
     pub async fn turn_off(&self, handle: &mut MinecraftTestHandle) {
-        // /computercraft shutdown 49
-        #[allow(deprecated)] // we use the raw command here. yes that sucks.
-        let command = TestCommand::RawCommand(format!("computercraft shutdown {}", self.id));
-        let result = handle
-            .command(command)
-            .await
-            .data()
-            .expect("This should print");
+        let command = TestCommand::SetComputerPower(self.id, false);
         assert!(
-            result.contains("Shutdown"),
-            "Missing shutdown message! {result}"
+            handle.command(command).await.success(),
+            "Failed to shut down computer {}!",
+            self.id
         );
         std::thread::sleep(COMPUTER_STATE_CHANGE_TIME);
     }
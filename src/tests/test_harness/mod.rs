@@ -1,6 +1,9 @@
 pub(super) mod commands;
 pub(super) mod computer_builder;
+mod plots;
+mod report;
 mod sanity_tests;
+pub(super) mod scenario;
 pub(super) mod test_enviroment;
 pub(super) mod test_websocket;
 pub(super) mod types;
@@ -0,0 +1,130 @@
+// Handing out space in the test world.
+// Every test gets its own plot so tests can run in parallel without stepping on each other. Plots are
+// packed as close to 0,0 as they fit, and plots that get freed are handed out again, so the test
+// world doesn't keep growing the longer tests run.
+
+/// How many blocks of space to leave between plots.
+///
+/// `TestForBlock` uses the block just outside of the plot's corner, so this must be at least 1.
+const PLOT_GAP: i64 = 4;
+
+/// Plots won't go past this on the x axis, so they stay in rows we can look at.
+const ROW_LENGTH: i64 = 100;
+
+/// The footprint of a plot on the floor. Sizes are in blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct Plot {
+    pub x: i64,
+    pub z: i64,
+    pub size_x: i64,
+    pub size_z: i64,
+}
+
+impl Plot {
+    /// Do these plots overlap, or at least come closer than the gap?
+    fn too_close(&self, other: &Plot) -> bool {
+        self.x < other.x + other.size_x + PLOT_GAP
+            && other.x < self.x + self.size_x + PLOT_GAP
+            && self.z < other.z + other.size_z + PLOT_GAP
+            && other.z < self.z + self.size_z + PLOT_GAP
+    }
+}
+
+/// Keeps track of which plots are in use.
+///
+/// Plots all share the same floor height, so this only has to pack rectangles. Vertical space isn't
+/// packed at all, every plot gets the whole column above its footprint. How tall a test is only
+/// matters when clearing it.
+#[derive(Default)]
+pub(super) struct PlotAllocator {
+    used: Vec<Plot>,
+}
+
+impl PlotAllocator {
+    /// Find room for a new plot.
+    pub fn allocate(&mut self, size_x: i64, size_z: i64) -> Plot {
+        // Bottom-left packing. New plots can only go at the origin, or right next to (or below) an
+        // existing plot, so those are the only spots worth trying.
+        let mut spots = vec![(0, 0)];
+        for plot in &self.used {
+            let below = plot.z + plot.size_z + PLOT_GAP;
+            spots.push((plot.x + plot.size_x + PLOT_GAP, plot.z));
+            spots.push((plot.x, below));
+            spots.push((0, below));
+        }
+        // Fill rows from the top, left to right.
+        spots.sort_by_key(|(x, z)| (*z, *x));
+
+        let plot = spots
+            .into_iter()
+            .map(|(x, z)| Plot {
+                x,
+                z,
+                size_x,
+                size_z,
+            })
+            // Plots wider than a row still fit at the start of one.
+            .filter(|plot| plot.x == 0 || plot.x + plot.size_x <= ROW_LENGTH)
+            .find(|plot| !self.used.iter().any(|used| used.too_close(plot)))
+            .expect("Below every other plot is always free.");
+        self.used.push(plot);
+        plot
+    }
+
+    /// Give a plot back so it can be used again. The plot must already be cleared.
+    pub fn free(&mut self, plot: Plot) {
+        self.used.retain(|used| *used != plot);
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Plots never touch, stay in their rows, and get reused once freed.
+fn plot_packing() {
+    let mut plots = PlotAllocator::default();
+    let first = plots.allocate(5, 5);
+    assert_eq!((first.x, first.z), (0, 0));
+
+    let mut taken = vec![first];
+    for size in [3, 10, 1, 7, 30, 20, 3, 3, 150, 2] {
+        taken.push(plots.allocate(size, size / 2 + 1));
+    }
+    for (index, plot) in taken.iter().enumerate() {
+        assert!(plot.x == 0 || plot.x + plot.size_x <= ROW_LENGTH);
+        for other in &taken[index + 1..] {
+            assert!(!plot.too_close(other), "{plot:?} is too close to {other:?}");
+        }
+    }
+    // Things that fit on the first row should go there.
+    assert_eq!(
+        taken[1],
+        Plot {
+            x: 9,
+            z: 0,
+            size_x: 3,
+            size_z: 2
+        }
+    );
+
+    // Freeing a plot lets something else use that space.
+    plots.free(taken[1]);
+    let reused = plots.allocate(3, 2);
+    assert_eq!(reused, taken[1]);
+
+    // Freeing everything brings us back to the start.
+    for plot in taken.into_iter().chain([reused]) {
+        plots.free(plot);
+    }
+    assert_eq!(
+        plots.allocate(50, 50),
+        Plot {
+            x: 0,
+            z: 0,
+            size_x: 50,
+            size_z: 50
+        }
+    );
+}
@@ -0,0 +1,200 @@
+// Writing down how tests went.
+// Plot floors only tell you anything if you join the world. Every time a test finishes, we rewrite
+// `report.json` and `junit.xml` with every test that has finished so far, so the report is still
+// useful if the run gets killed halfway through.
+//
+// Reports go in `./test_reports`, or wherever `MESHPIT_REPORT_DIR` points.
+
+use std::{path::PathBuf, sync::Mutex};
+
+use dashmap::DashMap;
+use log::warn;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::minecraft::computercraft::deploy::packet_data;
+
+/// Goes in front of test startup files to record the terminal, see the script for how.
+pub(super) const TERMINAL_CAPTURE: &str = include_str!("../startup_scripts/terminal_capture.lua");
+/// Where the capture script is put on computers.
+pub(super) const TERMINAL_CAPTURE_NAME: &str = "terminal_capture.lua";
+/// Where the terminal log ends up on computers.
+pub(super) const TERMINAL_LOG_NAME: &str = "terminal.log";
+
+/// What happened to a single computer during a test.
+#[derive(Debug, Clone, Serialize)]
+pub struct ComputerReport {
+    pub id: u16,
+    /// Everything the computer printed, if it had a startup file to capture it with.
+    pub terminal: Option<String>,
+    /// The data of every panic packet this computer sent.
+    pub panics: Vec<String>,
+    /// Errors computercraft logged about this computer, with their stack traces.
+    pub errors: Vec<String>,
+}
+
+/// How a single test went.
+#[derive(Debug, Clone, Serialize)]
+pub struct TestReport {
+    /// The full path of the test, IE `tests::test_harness::sanity_tests::basic_block_test`
+    pub name: String,
+    pub passed: bool,
+    /// Why the test failed, if it did.
+    pub failure: Option<String>,
+    /// The world position of the plot's corner.
+    pub corner: [i64; 3],
+    /// How big the plot is, in blocks.
+    pub size: [u16; 3],
+    pub seconds: f64,
+    pub computers: Vec<ComputerReport>,
+}
+
+static REPORTS: Mutex<Vec<TestReport>> = Mutex::new(Vec::new());
+
+/// Panics we've received, by computer, waiting for their test to finish.
+static PANICS: Lazy<DashMap<u16, Vec<String>>> = Lazy::new(DashMap::new);
+
+/// Hold onto a panic packet until the test that owns the computer finishes.
+pub(super) fn record_panic(id: u16, packet: &str) {
+    let data = packet_data(packet).map_or_else(|| packet.to_string(), |data| data.to_string());
+    PANICS.entry(id).or_default().push(data);
+}
+
+/// Get every panic a computer has sent so far.
+pub(super) fn take_panics(id: u16) -> Vec<String> {
+    PANICS
+        .remove(&id)
+        .map(|(_, panics)| panics)
+        .unwrap_or_default()
+}
+
+/// Add a finished test, and write out the reports again.
+pub(super) fn finish(report: TestReport) {
+    let mut reports = REPORTS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    reports.push(report);
+
+    let directory = std::env::var("MESHPIT_REPORT_DIR")
+        .map_or_else(|_| PathBuf::from("./test_reports"), PathBuf::from);
+    let json = serde_json::to_string_pretty(&*reports).expect("Reports are always valid json.");
+    let result = std::fs::create_dir_all(&directory)
+        .and_then(|()| std::fs::write(directory.join("report.json"), json))
+        .and_then(|()| std::fs::write(directory.join("junit.xml"), junit(&reports)));
+    if let Err(error) = result {
+        // Not worth failing a test over.
+        warn!(
+            "Couldn't write test report to {}! {error}",
+            directory.display()
+        );
+    }
+}
+
+/// Turn the reports into JUnit XML, which is what most CI systems want.
+fn junit(reports: &[TestReport]) -> String {
+    let failures = reports.iter().filter(|report| !report.passed).count();
+    let seconds: f64 = reports.iter().map(|report| report.seconds).sum();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml += &format!(
+        "<testsuites tests=\"{}\" failures=\"{failures}\" time=\"{seconds:.3}\">\n",
+        reports.len()
+    );
+    xml += &format!(
+        "  <testsuite name=\"meshpit\" tests=\"{}\" failures=\"{failures}\" time=\"{seconds:.3}\">\n",
+        reports.len()
+    );
+    for report in reports {
+        let (class, name) = report.name.rsplit_once("::").unwrap_or(("", &report.name));
+        xml += &format!(
+            "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\">\n",
+            escape(class),
+            escape(name),
+            report.seconds
+        );
+        if let Some(failure) = &report.failure {
+            xml += &format!("      <failure message=\"{}\"/>\n", escape(failure));
+        }
+
+        // Everything else goes in the output, since JUnit has nowhere better to put it.
+        let [x, y, z] = report.corner;
+        let [size_x, size_y, size_z] = report.size;
+        let mut output = format!("Plot at {x} {y} {z}, {size_x}x{size_y}x{size_z}\n");
+        for computer in &report.computers {
+            output += &format!("\n== Computer {} ==\n", computer.id);
+            if let Some(terminal) = &computer.terminal {
+                output += terminal;
+                output += "\n";
+            }
+            for panic in &computer.panics {
+                output += &format!("PANIC: {panic}\n");
+            }
+            for error in &computer.errors {
+                output += &format!("SERVER ERROR: {error}\n");
+            }
+        }
+        xml += &format!("      <system-out>{}</system-out>\n", escape(&output));
+        xml += "    </testcase>\n";
+    }
+    xml += "  </testsuite>\n</testsuites>\n";
+    xml
+}
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .map(|character| match character {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&apos;".to_string(),
+            // XML 1.0 can't hold most control characters at all, even escaped.
+            character if character.is_control() && !matches!(character, '\n' | '\t') => {
+                "\u{FFFD}".to_string()
+            }
+            character => character.to_string(),
+        })
+        .collect()
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Failures, terminals and panics all end up in the XML, escaped.
+fn junit_output() {
+    let reports = [
+        TestReport {
+            name: "tests::sanity::passes".to_string(),
+            passed: true,
+            failure: None,
+            corner: [0, -60, 0],
+            size: [3, 4, 3],
+            seconds: 1.5,
+            computers: vec![],
+        },
+        TestReport {
+            name: "tests::sanity::fails".to_string(),
+            passed: false,
+            failure: Some("Expected <fire>".to_string()),
+            corner: [7, -60, 0],
+            size: [3, 4, 3],
+            seconds: 2.25,
+            computers: vec![ComputerReport {
+                id: 12,
+                terminal: Some("hello & goodbye\u{1}".to_string()),
+                panics: vec!["{\"stack_trace\":\"oops\"}".to_string()],
+                errors: vec!["Error running task on computer #12".to_string()],
+            }],
+        },
+    ];
+    let xml = junit(&reports);
+    assert!(xml.contains("<testsuites tests=\"2\" failures=\"1\" time=\"3.750\">"));
+    assert!(xml.contains("<testcase classname=\"tests::sanity\" name=\"fails\" time=\"2.250\">"));
+    assert!(xml.contains("<failure message=\"Expected &lt;fire&gt;\"/>"));
+    assert!(xml.contains("Plot at 7 -60 0, 3x4x3"));
+    assert!(xml.contains("== Computer 12 ==\nhello &amp; goodbye\u{FFFD}\n"));
+    assert!(xml.contains("PANIC: {&quot;stack_trace&quot;:&quot;oops&quot;}"));
+    assert!(xml.contains("SERVER ERROR: Error running task on computer #12"));
+    assert_eq!(xml.matches("<failure").count(), 1);
+}
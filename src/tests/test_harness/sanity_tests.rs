@@ -2,7 +2,10 @@
 
 use std::time::Duration;
 
-use crate::tests::prelude::*;
+use crate::{
+    minecraft::{peripherals::inventory::GenericInventorySlot, vanilla::item_type::MinecraftItem},
+    tests::prelude::*,
+};
 
 #[tokio::test]
 #[ignore] // Only a sanity test. Doesn't always need to be ran.
@@ -10,6 +13,7 @@ use crate::tests::prelude::*;
 async fn basic_block_test() {
     let area = TestArea {
         size_x: 5,
+        size_y: 4,
         size_z: 5,
     };
 
@@ -38,7 +42,7 @@ async fn basic_block_test() {
             z: 2,
             facing: None,
         },
-        MinecraftBlock::from_string("netherrack").unwrap(),
+        MinecraftBlock::from_string("netherrack").unwrap().into(),
     );
 
     // fire
@@ -49,7 +53,7 @@ async fn basic_block_test() {
             z: 2,
             facing: None,
         },
-        MinecraftBlock::from_string("fire").unwrap(),
+        MinecraftBlock::from_string("fire").unwrap().into(),
     );
 
     // torch1
@@ -60,7 +64,9 @@ async fn basic_block_test() {
             z: 2,
             facing: None,
         },
-        MinecraftBlock::from_string("redstone_torch").unwrap(),
+        MinecraftBlock::from_string("redstone_torch")
+            .unwrap()
+            .into(),
     );
     // torch2
     let torch2 = TestCommand::SetBlock(
@@ -70,7 +76,9 @@ async fn basic_block_test() {
             z: 1,
             facing: None,
         },
-        MinecraftBlock::from_string("redstone_torch").unwrap(),
+        MinecraftBlock::from_string("redstone_torch")
+            .unwrap()
+            .into(),
     );
     // torch3
     let torch3 = TestCommand::SetBlock(
@@ -80,7 +88,9 @@ async fn basic_block_test() {
             z: 2,
             facing: None,
         },
-        MinecraftBlock::from_string("redstone_torch").unwrap(),
+        MinecraftBlock::from_string("redstone_torch")
+            .unwrap()
+            .into(),
     );
     // torch4
     let torch4 = TestCommand::SetBlock(
@@ -90,7 +100,9 @@ async fn basic_block_test() {
             z: 3,
             facing: None,
         },
-        MinecraftBlock::from_string("redstone_torch").unwrap(),
+        MinecraftBlock::from_string("redstone_torch")
+            .unwrap()
+            .into(),
     );
 
     let build_commands: Vec<TestCommand> = vec![base, rack, fire, torch1, torch2, torch3, torch4];
@@ -125,6 +137,7 @@ async fn basic_block_test() {
 async fn place_every_block() {
     let area = TestArea {
         size_x: 5,
+        size_y: 4,
         size_z: 5,
     };
 
@@ -178,7 +191,7 @@ async fn place_every_block() {
         if !test
             .command(TestCommand::SetBlock(
                 block_pos,
-                MinecraftBlock::from_string(block).unwrap(),
+                MinecraftBlock::from_string(block).unwrap().into(),
             ))
             .await
             .success()
@@ -199,6 +212,7 @@ async fn place_every_block() {
 async fn basic_computer_test() {
     let area = TestArea {
         size_x: 3,
+        size_y: 4,
         size_z: 3,
     };
 
@@ -227,27 +241,34 @@ async fn basic_computer_test() {
 
     // Computer should be off before we start
     let is_this_thing_on = test.command(is_on.clone()).await.data().unwrap();
-    assert!(
-        is_this_thing_on.contains("0b"),
-        "Computer was already on! Got: {is_this_thing_on}"
+    assert_eq!(
+        is_this_thing_on.as_i64(),
+        Some(0),
+        "Computer was already on! Got: {is_this_thing_on:?}"
     );
 
     // Turn the computer on
     c.turn_on(&mut test).await;
     let result = test.command(is_on.clone()).await.data().unwrap();
-    assert!(
-        result.contains("1b"),
-        "Computer didn't turn on! data: {result}"
+    assert_eq!(
+        result.as_i64(),
+        Some(1),
+        "Computer didn't turn on! data: {result:?}"
     );
 
     // turn it back off
     c.turn_off(&mut test).await;
     let result = test.command(is_on.clone()).await.data().unwrap();
-    assert!(
-        result.contains("0b"),
-        "Computer didn't turn off! data: {result}"
+    assert_eq!(
+        result.as_i64(),
+        Some(0),
+        "Computer didn't turn off! data: {result:?}"
     );
 
+    // Switching it on and off shouldn't upset computercraft.
+    let errors = test.computer_errors();
+    assert!(errors.is_empty(), "Computercraft errors: {errors:#?}");
+
     // if we made it here, the test has passed.
     test.stop(true).await;
 }
@@ -257,6 +278,7 @@ async fn basic_computer_test() {
 async fn turtle_fuel_test() {
     let area = TestArea {
         size_x: 3,
+        size_y: 4,
         size_z: 3,
     };
 
@@ -276,15 +298,15 @@ async fn turtle_fuel_test() {
         );
         test.build_computer(&turtle_pos, turtle_setup).await;
         // make sure it got the correct amount of fuel
-        let found: u64 = match TestCommand::GetBlockData(turtle_pos, "Fuel".to_string())
+        let found = TestCommand::GetBlockData(turtle_pos, "Fuel".to_string())
             .invoke(&mut test)
             .await
             .data()
-            .unwrap_or(1234.to_string())
-            .parse::<u64>()
-        {
-            Ok(ok) => ok,
-            Err(_) => {
+            .and_then(|fuel| fuel.as_i64())
+            .and_then(|fuel| u64::try_from(fuel).ok());
+        let found = match found {
+            Some(found) => found,
+            None => {
                 test.stop(false).await;
                 panic!("Failed to get fuel data of turtle {turtle_number}!")
             }
@@ -308,6 +330,7 @@ async fn turtle_fuel_test() {
 async fn test_startup() {
     let area = TestArea {
         size_x: 3,
+        size_y: 4,
         size_z: 3,
     };
 
@@ -333,10 +356,13 @@ async fn test_startup() {
     // Put a piston and sand on top of the computer, since we cant check the data on it... lol.
     position.y += 1;
     position.facing = Some(MinecraftFacingDirection::Up);
-    if !TestCommand::SetBlock(position, MinecraftBlock::from_string("piston").unwrap())
-        .invoke(&mut test)
-        .await
-        .success()
+    if !TestCommand::SetBlock(
+        position,
+        MinecraftBlock::from_string("piston").unwrap().into(),
+    )
+    .invoke(&mut test)
+    .await
+    .success()
     {
         test.stop(false).await;
         panic!("Failed to place piston!")
@@ -345,7 +371,7 @@ async fn test_startup() {
     position.y += 1;
     position.facing = None;
     let sand = MinecraftBlock::from_string("sand").unwrap();
-    if !TestCommand::SetBlock(position, sand)
+    if !TestCommand::SetBlock(position, sand.into())
         .invoke(&mut test)
         .await
         .success()
@@ -373,3 +399,103 @@ async fn test_startup() {
 
     test.stop(true).await;
 }
+
+#[tokio::test]
+#[ignore] // Only a sanity test. Doesn't always need to be ran.
+/// Fill a chest, read it back, then summon and clean up some entities.
+async fn container_and_entity_test() {
+    let area = TestArea {
+        size_x: 3,
+        size_y: 4,
+        size_z: 3,
+    };
+    let mut test = MinecraftTestHandle::new(area).await;
+
+    let chest = MinecraftPosition {
+        x: 1,
+        y: 1,
+        z: 1,
+        facing: None,
+    };
+    assert!(
+        test.command(TestCommand::SetBlock(
+            chest,
+            MinecraftBlock::from_string("chest").unwrap().into()
+        ))
+        .await
+        .success()
+    );
+    assert_eq!(
+        test.command(TestCommand::GetItems(chest)).await.items(),
+        Some(vec![])
+    );
+
+    let coal = GenericInventorySlot {
+        item: MinecraftItem::from_string("coal").unwrap(),
+        count: 5,
+    };
+    assert!(
+        test.command(TestCommand::SetSlot(chest, 3, Some(coal)))
+            .await
+            .success()
+    );
+    assert_eq!(
+        test.command(TestCommand::GetItems(chest)).await.items(),
+        Some(vec![(3, coal)])
+    );
+
+    // Stand some pigs on top of the chest, then get rid of them.
+    let above = MinecraftPosition { y: 2, ..chest };
+    for _ in 0..2 {
+        assert!(
+            test.command(TestCommand::Summon(above, "pig".to_string()))
+                .await
+                .success()
+        );
+    }
+    let count = test
+        .command(TestCommand::KillEntities(
+            above,
+            above,
+            Some("pig".to_string()),
+        ))
+        .await
+        .count();
+    assert_eq!(count, 2);
+
+    test.stop(true).await;
+}
+
+#[tokio::test]
+#[ignore] // Freezes the whole server, so this will break any other test running alongside it.
+/// Freeze the game, step it, and put everything back.
+async fn world_control_test() {
+    let area = TestArea {
+        size_x: 1,
+        size_y: 1,
+        size_z: 1,
+    };
+    let mut test = MinecraftTestHandle::new(area).await;
+
+    // Stepping only works while frozen.
+    assert!(!test.command(TestCommand::StepTicks(1)).await.success());
+    assert!(test.command(TestCommand::FreezeTicks(true)).await.success());
+    assert!(test.command(TestCommand::StepTicks(20)).await.success());
+    assert!(
+        test.command(TestCommand::FreezeTicks(false))
+            .await
+            .success()
+    );
+
+    assert!(test.command(TestCommand::SetTime(6000)).await.success());
+    assert!(
+        test.command(TestCommand::SetGameRule(
+            "doDaylightCycle".to_string(),
+            "false".to_string()
+        ))
+        .await
+        .success()
+    );
+
+    test.stop(true).await;
+}
@@ -0,0 +1,743 @@
+// Declarative test scenarios.
+// Spelling out every `TestCommand` by hand gets old fast, and isn't something you can hand to
+// someone who doesn't write rust. Scenarios are toml files that describe the plot, the computers
+// on it, and what the plot should look like once they're done.
+//
+// See `tests/scenarios/spawner.toml` for every option a scenario can use.
+//
+// All positions are `[x, y, z]` offsets inside the test area, same as normal test commands.
+
+use std::{path::Path, time::Duration};
+
+use toml_edit::{DocumentMut, Item, Table};
+
+use crate::{
+    minecraft::vanilla::block_state::{BlockState, BlockStateError},
+    tests::prelude::*,
+};
+
+/// Palette characters that mean "don't touch this block".
+const SKIP_CHARACTERS: [char; 2] = ['.', ' '];
+
+/// How long to wait for computers to report back if the scenario doesn't say.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A fully parsed scenario, ready to run.
+#[derive(Clone)]
+pub struct Scenario {
+    pub area: TestArea,
+    /// Every block to place, in file order. Blocks first, then layers.
+    pub setup: Vec<TestCommand>,
+    pub computers: Vec<ScenarioComputer>,
+    /// How long to wait after the computers are done before checking the plot.
+    pub settle: Duration,
+    /// How long to wait for each computer's expected message.
+    pub timeout: Duration,
+    /// Which block should be where at the end.
+    pub expect: Vec<(MinecraftPosition, MinecraftBlock)>,
+}
+
+/// A computer to build for the scenario.
+#[derive(Clone)]
+pub struct ScenarioComputer {
+    /// Includes the facing direction if one was set.
+    pub position: MinecraftPosition,
+    pub kind: ComputerKind,
+    pub libraries: MeshpitLibraries,
+    /// Contents of `startup.lua`. Computers without one just sit there.
+    pub startup: String,
+    /// If set, the scenario waits for this computer to send a message containing this text.
+    pub expect_message: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    Toml(toml_edit::TomlError),
+    /// A key that has to be there isn't. Holds where it was expected, like `computers[1].at`.
+    Missing(String),
+    /// A key we don't know about. Probably a typo.
+    Unknown(String),
+    /// A key is there, but holds the wrong sort of value.
+    WrongType(String),
+    /// A block (or its state) that we can't place.
+    BadBlock(String, BlockStateError),
+    /// Something is outside of the test area.
+    OutOfArea(String),
+    /// Anything else that's wrong with a value, with an explanation.
+    Invalid(String),
+}
+
+impl std::fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Toml(error) => write!(f, "{error}"),
+            Self::Missing(key) => write!(f, "{key} is missing"),
+            Self::Unknown(key) => write!(f, "{key} isn't a scenario option"),
+            Self::WrongType(key) => write!(f, "{key} is the wrong type"),
+            Self::BadBlock(key, error) => write!(f, "{key} isn't a block we can place: {error:?}"),
+            Self::OutOfArea(reason) | Self::Invalid(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<std::io::Error> for ScenarioError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<toml_edit::TomlError> for ScenarioError {
+    fn from(error: toml_edit::TomlError) -> Self {
+        Self::Toml(error)
+    }
+}
+
+// ==
+// Parsing
+// ==
+
+impl Scenario {
+    /// Read a scenario file.
+    ///
+    /// `startup_file` paths are relative to the scenario file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        Self::parse_from(&text, path.parent())
+    }
+
+    /// Parse a scenario. `startup_file` isn't allowed here, since we don't know where to look.
+    pub fn parse(text: &str) -> Result<Self, ScenarioError> {
+        Self::parse_from(text, None)
+    }
+
+    fn parse_from(text: &str, directory: Option<&Path>) -> Result<Self, ScenarioError> {
+        let document: DocumentMut = text.parse()?;
+        let root = document.as_table();
+        known_keys(
+            root,
+            &[
+                "area",
+                "blocks",
+                "palette",
+                "layers",
+                "computers",
+                "run",
+                "expect",
+            ],
+            "",
+        )?;
+
+        let area_table = table(root, "area", "area")?;
+        known_keys(area_table, &["size_x", "size_y", "size_z"], "area")?;
+        let area = TestArea {
+            size_x: size(area_table, "size_x", "area")?,
+            size_y: size(area_table, "size_y", "area")?,
+            size_z: size(area_table, "size_z", "area")?,
+        };
+
+        let mut setup = vec![];
+
+        // Single blocks and fills
+        for (index, block) in tables(root, "blocks")?.iter().enumerate() {
+            let context = format!("blocks[{index}]");
+            known_keys(block, &["at", "to", "block"], &context)?;
+            let start = position(block, "at", &context, area)?;
+            let state = block_state(block, &context)?;
+            match block.get("to") {
+                Some(_) => {
+                    let end = position(block, "to", &context, area)?;
+                    if !state.properties().is_empty() {
+                        return Err(ScenarioError::Invalid(format!(
+                            "{context} fills can't have block states"
+                        )));
+                    }
+                    setup.push(TestCommand::Fill(start, end, state.block()));
+                }
+                None => setup.push(TestCommand::SetBlock(start, state)),
+            }
+        }
+
+        // Palette grids
+        let mut palette = vec![];
+        if let Some(item) = root.get("palette") {
+            let entries = item
+                .as_table()
+                .ok_or_else(|| ScenarioError::WrongType("palette".to_string()))?;
+            for (key, value) in entries.iter() {
+                let context = format!("palette.{key}");
+                let mut characters = key.chars();
+                let (Some(character), None) = (characters.next(), characters.next()) else {
+                    return Err(ScenarioError::Invalid(format!(
+                        "{context} keys must be a single character"
+                    )));
+                };
+                if SKIP_CHARACTERS.contains(&character) {
+                    return Err(ScenarioError::Invalid(format!(
+                        "{context} '{character}' always means empty"
+                    )));
+                }
+                // Palette entries are just `character = "block"`, anything fancier is a mistake.
+                let name = value
+                    .as_str()
+                    .ok_or_else(|| ScenarioError::WrongType(context.clone()))?;
+                let state = BlockState::parse(name)
+                    .map_err(|error| ScenarioError::BadBlock(context, error))?;
+                palette.push((character, state));
+            }
+        }
+
+        for (index, layer) in tables(root, "layers")?.iter().enumerate() {
+            let context = format!("layers[{index}]");
+            known_keys(layer, &["y", "x", "z", "rows"], &context)?;
+            let y = integer(layer, "y", &context)?;
+            let x_offset = optional_integer(layer, "x", &context)?.unwrap_or(0);
+            let z_offset = optional_integer(layer, "z", &context)?.unwrap_or(0);
+            let rows = layer
+                .get("rows")
+                .ok_or_else(|| ScenarioError::Missing(format!("{context}.rows")))?
+                .as_array()
+                .ok_or_else(|| ScenarioError::WrongType(format!("{context}.rows")))?;
+
+            // Rows go south, characters go east.
+            for (z, row) in rows.iter().enumerate() {
+                let row = row
+                    .as_str()
+                    .ok_or_else(|| ScenarioError::WrongType(format!("{context}.rows[{z}]")))?;
+                for (x, character) in row.chars().enumerate() {
+                    if SKIP_CHARACTERS.contains(&character) {
+                        continue;
+                    }
+                    let (_, state) = palette
+                        .iter()
+                        .find(|(key, _)| *key == character)
+                        .ok_or_else(|| {
+                            ScenarioError::Invalid(format!(
+                                "{context}.rows[{z}] uses '{character}', which isn't in the palette"
+                            ))
+                        })?;
+                    let at = MinecraftPosition {
+                        x: x_offset + x as i64,
+                        y,
+                        z: z_offset + z as i64,
+                        facing: None,
+                    };
+                    check_area(at, area, &format!("{context}.rows[{z}][{x}]"))?;
+                    setup.push(TestCommand::SetBlock(at, state.clone()));
+                }
+            }
+        }
+
+        let mut computers = vec![];
+        for (index, computer) in tables(root, "computers")?.iter().enumerate() {
+            let context = format!("computers[{index}]");
+            known_keys(
+                computer,
+                &[
+                    "at",
+                    "facing",
+                    "kind",
+                    "fuel",
+                    "libraries",
+                    "startup",
+                    "startup_file",
+                    "expect_message",
+                ],
+                &context,
+            )?;
+            let mut position = position(computer, "at", &context, area)?;
+            if let Some(item) = computer.get("facing") {
+                position.facing = Some(facing(item, &format!("{context}.facing"))?);
+            }
+
+            let kind = match optional_string(computer, "kind", &context)?.unwrap_or("turtle") {
+                "turtle" => ComputerKind::Turtle(
+                    optional_integer(computer, "fuel", &context)?
+                        .map(|fuel| {
+                            u64::try_from(fuel).map_err(|_| {
+                                ScenarioError::Invalid(format!("{context}.fuel can't be negative"))
+                            })
+                        })
+                        .transpose()?,
+                ),
+                "computer" => {
+                    if computer.contains_key("fuel") {
+                        return Err(ScenarioError::Invalid(format!(
+                            "{context} computers don't take fuel"
+                        )));
+                    }
+                    ComputerKind::Basic
+                }
+                other => {
+                    return Err(ScenarioError::Invalid(format!(
+                        "{context}.kind must be \"turtle\" or \"computer\", not \"{other}\""
+                    )));
+                }
+            };
+
+            let mut libraries = MeshpitLibraries::new();
+            if let Some(item) = computer.get("libraries") {
+                let names = item
+                    .as_array()
+                    .ok_or_else(|| ScenarioError::WrongType(format!("{context}.libraries")))?;
+                for name in names.iter() {
+                    let name = name
+                        .as_str()
+                        .ok_or_else(|| ScenarioError::WrongType(format!("{context}.libraries")))?;
+                    let slot = match name.trim_end_matches(".lua") {
+                        "networking" => &mut libraries.networking,
+                        "walkback" => &mut libraries.walkback,
+                        "panic" => &mut libraries.panic,
+                        "helpers" => &mut libraries.helpers,
+                        "update" => &mut libraries.update,
+                        _ => {
+                            return Err(ScenarioError::Invalid(format!(
+                                "{context}.libraries has unknown library \"{name}\""
+                            )));
+                        }
+                    };
+                    *slot = Some(true);
+                }
+            }
+
+            let inline = optional_string(computer, "startup", &context)?;
+            let file = optional_string(computer, "startup_file", &context)?;
+            let startup = match (inline, file) {
+                (Some(_), Some(_)) => {
+                    return Err(ScenarioError::Invalid(format!(
+                        "{context} can't have both startup and startup_file"
+                    )));
+                }
+                (Some(startup), None) => startup.to_string(),
+                (None, Some(file)) => {
+                    let directory = directory.ok_or_else(|| {
+                        ScenarioError::Invalid(format!(
+                            "{context}.startup_file needs the scenario to be loaded from a file"
+                        ))
+                    })?;
+                    std::fs::read_to_string(directory.join(file))?
+                }
+                (None, None) => String::new(),
+            };
+
+            let expect_message =
+                optional_string(computer, "expect_message", &context)?.map(str::to_string);
+
+            computers.push(ScenarioComputer {
+                position,
+                kind,
+                libraries,
+                startup,
+                expect_message,
+            });
+        }
+
+        let mut settle = Duration::ZERO;
+        let mut timeout = DEFAULT_TIMEOUT;
+        if let Some(item) = root.get("run") {
+            let run = item
+                .as_table()
+                .ok_or_else(|| ScenarioError::WrongType("run".to_string()))?;
+            known_keys(run, &["wait_seconds", "timeout_seconds"], "run")?;
+            if let Some(seconds) = optional_seconds(run, "wait_seconds", "run")? {
+                settle = seconds;
+            }
+            if let Some(seconds) = optional_seconds(run, "timeout_seconds", "run")? {
+                timeout = seconds;
+            }
+        }
+
+        let mut expect = vec![];
+        for (index, expectation) in tables(root, "expect")?.iter().enumerate() {
+            let context = format!("expect[{index}]");
+            known_keys(expectation, &["at", "block"], &context)?;
+            let at = position(expectation, "at", &context, area)?;
+            let state = block_state(expectation, &context)?;
+            // The test command can only check the block itself.
+            if !state.properties().is_empty() {
+                return Err(ScenarioError::Invalid(format!(
+                    "{context} can only check the block, not its state"
+                )));
+            }
+            expect.push((at, state.block()));
+        }
+
+        Ok(Self {
+            area,
+            setup,
+            computers,
+            settle,
+            timeout,
+            expect,
+        })
+    }
+}
+
+// Little helpers for pulling values out of tables, with errors that say where things went wrong.
+
+/// Complain about the first key that isn't one of `keys`.
+fn known_keys(table: &Table, keys: &[&str], context: &str) -> Result<(), ScenarioError> {
+    match table.iter().find(|(key, _)| !keys.contains(key)) {
+        Some((key, _)) if context.is_empty() => Err(ScenarioError::Unknown(key.to_string())),
+        Some((key, _)) => Err(ScenarioError::Unknown(format!("{context}.{key}"))),
+        None => Ok(()),
+    }
+}
+
+fn table<'a>(parent: &'a Table, key: &str, context: &str) -> Result<&'a Table, ScenarioError> {
+    parent
+        .get(key)
+        .ok_or_else(|| ScenarioError::Missing(context.to_string()))?
+        .as_table()
+        .ok_or_else(|| ScenarioError::WrongType(context.to_string()))
+}
+
+/// `[[key]]` arrays of tables. Missing is the same as empty.
+fn tables<'a>(parent: &'a Table, key: &str) -> Result<Vec<&'a Table>, ScenarioError> {
+    match parent.get(key) {
+        None => Ok(vec![]),
+        Some(Item::ArrayOfTables(array)) => Ok(array.iter().collect()),
+        Some(_) => Err(ScenarioError::WrongType(key.to_string())),
+    }
+}
+
+fn optional_integer(table: &Table, key: &str, context: &str) -> Result<Option<i64>, ScenarioError> {
+    table
+        .get(key)
+        .map(|item| {
+            item.as_integer()
+                .ok_or_else(|| ScenarioError::WrongType(format!("{context}.{key}")))
+        })
+        .transpose()
+}
+
+fn integer(table: &Table, key: &str, context: &str) -> Result<i64, ScenarioError> {
+    optional_integer(table, key, context)?
+        .ok_or_else(|| ScenarioError::Missing(format!("{context}.{key}")))
+}
+
+fn optional_string<'a>(
+    table: &'a Table,
+    key: &str,
+    context: &str,
+) -> Result<Option<&'a str>, ScenarioError> {
+    table
+        .get(key)
+        .map(|item| {
+            item.as_str()
+                .ok_or_else(|| ScenarioError::WrongType(format!("{context}.{key}")))
+        })
+        .transpose()
+}
+
+/// Seconds can be written as either integers or floats.
+fn optional_seconds(
+    table: &Table,
+    key: &str,
+    context: &str,
+) -> Result<Option<Duration>, ScenarioError> {
+    let Some(item) = table.get(key) else {
+        return Ok(None);
+    };
+    let seconds = item
+        .as_float()
+        .or_else(|| item.as_integer().map(|seconds| seconds as f64))
+        .ok_or_else(|| ScenarioError::WrongType(format!("{context}.{key}")))?;
+    Duration::try_from_secs_f64(seconds)
+        .map(Some)
+        .map_err(|_| ScenarioError::Invalid(format!("{context}.{key} must be positive")))
+}
+
+fn size(table: &Table, key: &str, context: &str) -> Result<u16, ScenarioError> {
+    let value = integer(table, key, context)?;
+    match u16::try_from(value) {
+        Ok(value) if value > 0 => Ok(value),
+        _ => Err(ScenarioError::Invalid(format!(
+            "{context}.{key} must be between 1 and {}",
+            u16::MAX
+        ))),
+    }
+}
+
+/// The `block` key of a table.
+fn block_state(table: &Table, context: &str) -> Result<BlockState, ScenarioError> {
+    let name = optional_string(table, "block", context)?
+        .ok_or_else(|| ScenarioError::Missing(format!("{context}.block")))?;
+    BlockState::parse(name)
+        .map_err(|error| ScenarioError::BadBlock(format!("{context}.block"), error))
+}
+
+/// An `[x, y, z]` position, which has to be inside the test area.
+fn position(
+    table: &Table,
+    key: &str,
+    context: &str,
+    area: TestArea,
+) -> Result<MinecraftPosition, ScenarioError> {
+    let context = format!("{context}.{key}");
+    let wrong_type = || ScenarioError::WrongType(context.clone());
+    let values = table
+        .get(key)
+        .ok_or_else(|| ScenarioError::Missing(context.clone()))?
+        .as_array()
+        .ok_or_else(wrong_type)?;
+    let coordinates: Vec<i64> = values
+        .iter()
+        .map(|value| value.as_integer().ok_or_else(wrong_type))
+        .collect::<Result<_, _>>()?;
+    let [x, y, z] = coordinates[..] else {
+        return Err(wrong_type());
+    };
+    let position = MinecraftPosition {
+        x,
+        y,
+        z,
+        facing: None,
+    };
+    check_area(position, area, &context)?;
+    Ok(position)
+}
+
+fn check_area(
+    position: MinecraftPosition,
+    area: TestArea,
+    context: &str,
+) -> Result<(), ScenarioError> {
+    let inside = (0..i64::from(area.size_x)).contains(&position.x)
+        && (0..i64::from(area.size_z)).contains(&position.z)
+        && (0..=i64::from(area.size_y)).contains(&position.y);
+    if inside {
+        Ok(())
+    } else {
+        Err(ScenarioError::OutOfArea(format!(
+            "{context} ({}) is outside of the {}x{}x{} area",
+            position.as_command_string(),
+            area.size_x,
+            area.size_y,
+            area.size_z
+        )))
+    }
+}
+
+fn facing(item: &Item, context: &str) -> Result<MinecraftFacingDirection, ScenarioError> {
+    let name = item
+        .as_str()
+        .ok_or_else(|| ScenarioError::WrongType(context.to_string()))?;
+    Ok(match name {
+        "north" => MinecraftFacingDirection::North,
+        "east" => MinecraftFacingDirection::East,
+        "south" => MinecraftFacingDirection::South,
+        "west" => MinecraftFacingDirection::West,
+        "up" => MinecraftFacingDirection::Up,
+        "down" => MinecraftFacingDirection::Down,
+        _ => {
+            return Err(ScenarioError::Invalid(format!(
+                "{context} \"{name}\" isn't a direction"
+            )));
+        }
+    })
+}
+
+// ==
+// Running
+// ==
+
+impl MinecraftTestHandle {
+    /// Build a scenario on a fresh plot, run its computers, and check the result.
+    ///
+    /// Returns a description of everything that went wrong, so an empty list means it passed. The
+    /// plot is marked passed or failed either way.
+    pub async fn run_scenario(scenario: &Scenario) -> Vec<String> {
+        let mut test = MinecraftTestHandle::new(scenario.area).await;
+        let mut failures = vec![];
+
+        for (index, command) in scenario.setup.iter().enumerate() {
+            if !test.command(command.clone()).await.success() {
+                failures.push(format!("Setup command {index} failed"));
+            }
+        }
+
+        // Sockets have to be registered before the computers boot, or we'd miss their first message.
+        let mut running = vec![];
+        for computer in &scenario.computers {
+            let setup = ComputerSetup::new(
+                computer.kind,
+                ComputerConfigs::StartupIncludingLibraries(
+                    computer.startup.clone(),
+                    computer.libraries,
+                ),
+            );
+            let built = test.build_computer(&computer.position, setup).await;
+            let socket = match computer.expect_message {
+                Some(_) => Some(TestWebsocket::new(built.id()).await),
+                None => None,
+            };
+            running.push((built, socket, computer));
+        }
+
+        for (computer, _, _) in &running {
+            computer.turn_on(&mut test).await;
+        }
+
+        for (_, socket, computer) in &mut running {
+            let (Some(socket), Some(expected)) = (socket, &computer.expect_message) else {
+                continue;
+            };
+            let heard = tokio::time::timeout(scenario.timeout, async {
+                while let Some(message) = socket.receiver.recv().await {
+                    if message.contains(expected.as_str()) {
+                        return true;
+                    }
+                }
+                false
+            })
+            .await;
+            if heard != Ok(true) {
+                failures.push(format!(
+                    "Computer at {} never sent \"{expected}\"",
+                    computer.position.as_command_string()
+                ));
+            }
+        }
+
+        tokio::time::sleep(scenario.settle).await;
+
+        for (position, block) in &scenario.expect {
+            if !test
+                .command(TestCommand::TestForBlock(*position, *block))
+                .await
+                .success()
+            {
+                failures.push(format!(
+                    "Expected {} at {}",
+                    block.get_name(),
+                    position.as_command_string()
+                ));
+            }
+        }
+
+        for (computer, _, _) in &running {
+            computer.turn_off(&mut test).await;
+        }
+
+        test.stop(failures.is_empty()).await;
+        failures
+    }
+}
+
+// ===
+// Tests
+// ===
+
+#[test]
+/// Every section of the example scenario comes out the way it's written.
+fn parse_example_scenario() {
+    let scenario = Scenario::load(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/tests/scenarios/spawner.toml"
+    ))
+    .unwrap();
+    assert_eq!(
+        (
+            scenario.area.size_x,
+            scenario.area.size_y,
+            scenario.area.size_z
+        ),
+        (5, 4, 5)
+    );
+
+    // The fill and the dirt, then netherrack + 4 torches + fire from the layers.
+    assert_eq!(scenario.setup.len(), 2 + 5 + 1);
+    assert!(matches!(scenario.setup[0], TestCommand::Fill(..)));
+
+    assert_eq!(scenario.computers.len(), 1);
+    let turtle = &scenario.computers[0];
+    assert!(matches!(turtle.kind, ComputerKind::Turtle(Some(100))));
+    assert_eq!(
+        turtle.position.facing,
+        Some(MinecraftFacingDirection::South)
+    );
+    assert_eq!(turtle.libraries.networking, Some(true));
+    assert!(turtle.startup.contains("walkback.dig()"));
+    assert_eq!(turtle.libraries.walkback, Some(true));
+
+    assert_eq!(scenario.settle, Duration::from_secs(1));
+    assert_eq!(scenario.expect.len(), 2);
+}
+
+#[test]
+/// Broken scenarios say where they're broken.
+fn scenario_errors() {
+    let error = |text: &str| Scenario::parse(text).err().unwrap();
+
+    assert!(
+        matches!(error("[run]\nwait_seconds = 1"), ScenarioError::Missing(key) if key == "area")
+    );
+    assert!(matches!(
+        error("[area]\nsize_x = 0\nsize_y = 4\nsize_z = 4"),
+        ScenarioError::Invalid(_)
+    ));
+    assert!(matches!(
+        error("[area]\nsize_x = 4\nsize_y = 4\nsize_z = 4\n[[computers]]\nat = [1, 1]"),
+        ScenarioError::WrongType(key) if key == "computers[0].at"
+    ));
+    assert!(matches!(
+        error("[area]\nsize_x = 4\nsize_y = 4\nsize_z = 4\n[[computers]]\nat = [4, 1, 0]"),
+        ScenarioError::OutOfArea(_)
+    ));
+    assert!(matches!(
+        error(
+            "[area]\nsize_x = 4\nsize_y = 4\nsize_z = 4\n[[computers]]\nat = [0, 1, 0]\nkind = \"pocket\""
+        ),
+        ScenarioError::Invalid(_)
+    ));
+    assert!(matches!(
+        error(
+            "[area]\nsize_x = 4\nsize_y = 4\nsize_z = 4\n[[computers]]\nat = [0, 1, 0]\nstartup_file = \"a.lua\""
+        ),
+        ScenarioError::Invalid(_)
+    ));
+    assert!(matches!(
+        error("[area]\nsize_x = 4\nsize_y = 4\nsize_z = 4\n[palette]\nab = \"stone\""),
+        ScenarioError::Invalid(_)
+    ));
+
+    // Typos
+    let area = "[area]\nsize_x = 4\nsize_y = 4\nsize_z = 4\n";
+    assert!(matches!(
+        error(&format!("{area}[[computer]]\nat = [0, 1, 0]")),
+        ScenarioError::Unknown(key) if key == "computer"
+    ));
+    assert!(matches!(
+        error(&format!("{area}[[computers]]\nat = [0, 1, 0]\nexpect_mesage = \"hi\"")),
+        ScenarioError::Unknown(key) if key == "computers[0].expect_mesage"
+    ));
+    assert!(matches!(
+        error(&format!("{area}[run]\nwait = 1")),
+        ScenarioError::Unknown(key) if key == "run.wait"
+    ));
+    assert!(matches!(
+        error("[area]\nsize_x = 4\nsize_y = 4\nsize_z = 4\nsize = 4"),
+        ScenarioError::Unknown(key) if key == "area.size"
+    ));
+    assert!(matches!(
+        error(&format!("{area}[palette]\nS = {{ block = \"stone\" }}")),
+        ScenarioError::WrongType(key) if key == "palette.S"
+    ));
+}
+
+#[tokio::test]
+/// Run the example scenario for real.
+async fn spawner_scenario() {
+    let scenario = Scenario::load(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/tests/scenarios/spawner.toml"
+    ))
+    .unwrap_or_else(|error| panic!("Bad scenario! {error}"));
+    let failures = MinecraftTestHandle::run_scenario(&scenario).await;
+    assert!(failures.is_empty(), "{failures:#?}");
+}
@@ -1,22 +1,46 @@
 // Testing galore
 
-use crate::tests::{prelude::*, test_harness::computer_builder::COMPUTER_STATE_CHANGE_TIME};
-use std::{cmp::max, sync::Arc};
-
+use crate::tests::{
+    bridge_config::BRIDGE_CONFIG,
+    prelude::*,
+    test_harness::{
+        computer_builder::COMPUTER_STATE_CHANGE_TIME,
+        plots::{Plot, PlotAllocator},
+        report::{
+            self, ComputerReport, TERMINAL_CAPTURE, TERMINAL_CAPTURE_NAME, TERMINAL_LOG_NAME,
+            TestReport,
+        },
+    },
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+use log::error;
 use once_cell::sync::Lazy;
-use tokio::sync::Mutex;
+use tokio::sync::{
+    Mutex,
+    broadcast::{self, error::TryRecvError},
+};
 
+use crate::server_log::{LogEntry, LogLevel, ServerEvent};
 use crate::tests::bridge::MinecraftEnvironment;
 
 pub struct MinecraftTestEnvironment {
     /// The Minecraft environment we're running in.
     pub environment: MinecraftEnvironment,
-    /// Where the current offset of the test setup plots are
-    next_plot_corner: MinecraftPosition,
-    /// The highest z value we've seen, as to not crash into old tests.
-    highest_z: i64,
+    /// Which plots are currently in use.
+    plots: PlotAllocator,
 }
 
+/// The y level of every plot's floor.
+const PLOT_FLOOR_Y: i64 = -60;
+
+/// The most blocks a single fill command can change.
+const FILL_LIMIT: i64 = 32768;
+
 // this is the minecraft instance that all of the tests will use, so we have to make it once and then hold it here, otherwise
 // we would have to re-start the server for every test, and that would be stupid as hell.
 // TODO: I think i can refactor this into that ctor startup block that sets up logging for tests.
@@ -44,14 +68,7 @@ impl MinecraftTestEnvironment {
         // Load and lock that global, its ours!
         Self {
             environment,
-            // This does not need a facing direction.
-            next_plot_corner: MinecraftPosition {
-                x: 0,
-                y: -60,
-                z: 0,
-                facing: None,
-            },
-            highest_z: 0,
+            plots: PlotAllocator::default(),
         }
     }
 }
@@ -69,6 +86,34 @@ pub struct MinecraftTestHandle {
     ///
     /// This cannot be modified once the test has started, since we cannot move our testing location.
     corner: MinecraftPosition,
+
+    /// The plot we were given, so we can give it back.
+    plot: Plot,
+
+    /// The IDs of every computer built in this test, so we can clean them up.
+    computers: Vec<u16>,
+
+    /// Which test this is, taken from the name of the thread it runs on.
+    name: String,
+
+    /// When the test started, for the report.
+    started: Instant,
+
+    /// Where computer folders are, so we can still read terminal logs when the test panics.
+    computer_root: PathBuf,
+
+    /// The test server's folder.
+    server_folder: PathBuf,
+
+    /// Whether the test was stopped properly. Tests that panic never stop, so they get reported when
+    /// the handle is dropped instead.
+    stopped: bool,
+
+    /// Everything the server has logged since the test started.
+    events: broadcast::Receiver<ServerEvent>,
+
+    /// Computercraft errors about our computers that have been pulled out of `events` so far.
+    computer_errors: Vec<(u16, LogEntry)>,
 }
 
 impl MinecraftTestHandle {
@@ -76,17 +121,25 @@ impl MinecraftTestHandle {
     ///
     /// Requires a test area, since we need to know how much space this test will use.
     pub async fn new(area: TestArea) -> Self {
-        // Account for the area starting at "0" thus being 1 block bigger than expected.
-        let area = TestArea {
-            size_x: area.size_x - 1,
-            size_z: area.size_x - 1,
-        };
-
         // Get a copy of the environment
         let mut env = MINECRAFT_TESTING_ENV.lock().await;
 
         // Find our plot position
-        let corner = env.get_test_position(area);
+        let plot = env.plots.allocate(area.size_x.into(), area.size_z.into());
+        // This does not need a facing direction.
+        let corner = MinecraftPosition {
+            x: plot.x,
+            y: PLOT_FLOOR_Y,
+            z: plot.z,
+            facing: None,
+        };
+
+        // Account for the area starting at "0" thus being 1 block bigger than expected.
+        let area = TestArea {
+            size_x: area.size_x - 1,
+            size_y: area.size_y,
+            size_z: area.size_z - 1,
+        };
 
         // Force load the plot
         let c1 = corner;
@@ -109,7 +162,23 @@ impl MinecraftTestHandle {
         )
         .await;
 
-        Self { area, corner }
+        Self {
+            area,
+            corner,
+            plot,
+            computers: vec![],
+            // Tests run on a thread named after them.
+            name: std::thread::current()
+                .name()
+                .unwrap_or("unknown test")
+                .to_string(),
+            started: Instant::now(),
+            computer_root: env.computers_folder(),
+            server_folder: env.environment.get_server_folder().clone(),
+            stopped: false,
+            events: env.environment.events(),
+            computer_errors: vec![],
+        }
     }
 
     /// Run a test command.
@@ -117,18 +186,96 @@ impl MinecraftTestHandle {
         command.invoke(self).await
     }
 
+    /// Every computercraft error about this test's computers since the test started, and which
+    /// computer it was about.
+    ///
+    /// Only errors that say which computer they're about can be matched to a test, so this won't
+    /// catch everything. If the event channel lagged, every computer gets an error saying so.
+    pub fn computer_errors(&mut self) -> Vec<(u16, LogEntry)> {
+        loop {
+            match self.events.try_recv() {
+                Ok(ServerEvent::ComputerError(Some(id), entry)) if self.computers.contains(&id) => {
+                    self.computer_errors.push((id, entry));
+                }
+                // Any of the skipped events could have been ours, so blame every computer.
+                Err(TryRecvError::Lagged(skipped)) => {
+                    for id in &self.computers {
+                        let entry = LogEntry {
+                            thread: "meshpit".to_string(),
+                            level: LogLevel::Error,
+                            logger: None,
+                            message: format!("Missed {skipped} server events, errors may be lost."),
+                            stack_trace: vec![],
+                        };
+                        self.computer_errors.push((*id, entry));
+                    }
+                }
+                Ok(_) => {}
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            }
+        }
+        self.computer_errors.clone()
+    }
+
     /// Get the area of the test
     pub fn area(&self) -> TestArea {
         self.area
     }
 
+    /// Get the test server's folder, IE for writing floppies with `write_bootstrap_disk`.
+    pub fn server_folder(&self) -> &Path {
+        &self.server_folder
+    }
+
+    /// The url computers reach the test websocket at, for `bootstrap_script` and friends.
+    pub fn websocket_url(&self) -> &'static str {
+        &BRIDGE_CONFIG.websocket_url
+    }
+
     /// Get the corner position of the test
     pub fn corner(&self) -> MinecraftPosition {
         self.corner
     }
 
     /// Finish the test and clean up. Requires a pass or fail status to update the plot floor.
-    pub async fn stop(self, passed: bool) {
+    ///
+    /// Computers built by the test are always turned off. Passing plots are then emptied and handed
+    /// out again, but failed plots are left alone (and never reused) so you can go look at what
+    /// went wrong.
+    pub async fn stop(mut self, passed: bool) {
+        self.stopped = true;
+        let computers = self.computers.clone();
+        for id in &computers {
+            // Might already be off, or even gone. We don't care either way.
+            let _ = self
+                .command(TestCommand::SetComputerPower(*id, false))
+                .await;
+        }
+        // Before cleaning up, since that deletes the terminal logs.
+        self.report(
+            passed,
+            (!passed).then(|| "Test stopped as failed".to_string()),
+        );
+
+        if passed {
+            // Whatever got dropped or summoned.
+            let top = MinecraftPosition {
+                x: self.area.size_x.into(),
+                y: self.area.size_y.into(),
+                z: self.area.size_z.into(),
+                facing: None,
+            };
+            let origin = MinecraftPosition {
+                x: 0,
+                y: 0,
+                z: 0,
+                facing: None,
+            };
+            let _ = self
+                .command(TestCommand::KillEntities(origin, top, None))
+                .await;
+        }
+
         let mut env = MINECRAFT_TESTING_ENV.lock().await;
         // Update floor
         let block = if passed {
@@ -139,8 +286,61 @@ impl MinecraftTestHandle {
 
         env.update_floor(self.corner, self.area, block).await;
         // We do not stop force-loading the chunks, since another test could be contained within it.
-        // If we had something else to clean here, we would. but we dont.
-        // TODO: Turn off all the computers within this chunk if possible? Maybe replace their blocks if needed.
+        if !passed {
+            return;
+        }
+
+        let cleared = env.clear_plot(self.corner, self.area).await;
+        for id in &computers {
+            // Gone already is fine.
+            let _ = std::fs::remove_dir_all(env.computer_folder(*id));
+        }
+        match cleared {
+            Ok(()) => env.plots.free(self.plot),
+            // The plot stays taken, so nothing else gets built on top of the mess. The test
+            // itself still passed, so we don't fail it over this.
+            Err(feedback) => error!("Couldn't clear the plot! {feedback}"),
+        }
+    }
+
+    /// Write down how this test went.
+    fn report(&mut self, passed: bool, failure: Option<String>) {
+        let errors = self.computer_errors();
+        let computers = self
+            .computers
+            .iter()
+            .map(|id| ComputerReport {
+                id: *id,
+                terminal: std::fs::read_to_string(
+                    self.computer_root
+                        .join(id.to_string())
+                        .join(TERMINAL_LOG_NAME),
+                )
+                .ok(),
+                panics: report::take_panics(*id),
+                errors: errors
+                    .iter()
+                    .filter(|(error_id, _)| error_id == id)
+                    .map(|(_, error)| {
+                        std::iter::once(error.message.as_str())
+                            .chain(error.stack_trace.iter().map(String::as_str))
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    })
+                    .collect(),
+            })
+            .collect();
+        // The area is stored one smaller than it really is, see `new`.
+        let size = [self.area.size_x + 1, self.area.size_y, self.area.size_z + 1];
+        report::finish(TestReport {
+            name: self.name.clone(),
+            passed,
+            failure,
+            corner: [self.corner.x, self.corner.y, self.corner.z],
+            size,
+            seconds: self.started.elapsed().as_secs_f64(),
+            computers,
+        });
     }
 
     /// Create and initialize a computer within this test.
@@ -166,29 +366,18 @@ impl MinecraftTestHandle {
         // of raw commands.
         let block: MinecraftBlock = setup.kind.into();
         assert!(
-            TestCommand::SetBlock(*position, block)
+            TestCommand::SetBlock(*position, block.into())
                 .invoke(self)
                 .await
                 .success()
         );
 
-        // Don't forget to offset the position into the test we're in.
-        let position_string = position.with_offset(self.corner()).as_command_string();
-
-        // Turn on the computer. This is our only raw command.
-        // /data modify block -2 -60 10 On set value 1b
-        #[allow(deprecated)]
-        // used to kickstart computers. We do not have a test command for setting data right now.
-        let turn_on = TestCommand::RawCommand(format!(
-            "/data modify block {position_string} On set value 1b"
-        ));
+        // Turn on the computer.
         assert!(
-            turn_on
+            TestCommand::StartComputer(*position)
                 .invoke(self)
                 .await
-                .data()
-                .unwrap()
-                .contains("Modified block data of")
+                .success()
         );
 
         // wait for that to turn on, it can take a bit.
@@ -200,31 +389,27 @@ impl MinecraftTestHandle {
             .invoke(self)
             .await
             .data()
-            .unwrap()
-            .parse()
-            .unwrap();
+            .and_then(|id| id.as_i64())
+            .and_then(|id| id.try_into().ok())
+            .expect("Computer should have an ID once it's been on.");
 
         // Now that we have the computers ID, we will turn it back off, then do whatever setup we need to do after this.
         // Since all we need is the ID to construct the final computer type, we'll do that now and use methods on it.
         let new_computer: TestComputer = TestComputer { id };
+        self.computers.push(id);
 
         new_computer.turn_off(self).await;
 
         // If this is a turtle and needs fuel, set it.
         if let ComputerKind::Turtle(amount) = setup.kind {
             let fuel = amount.unwrap_or(0);
-            #[allow(deprecated)] // yes another raw command. TODO:!
-            let refuel = TestCommand::RawCommand(format!(
-                "/data modify block {position_string} Fuel set value {fuel}"
-            ))
-            .invoke(self)
-            .await
-            .data()
-            .unwrap();
-            // If the fuel value is what we want to set it to, thats fine as well.
-            let assertion =
-                refuel.contains("Modified block data of") || refuel.contains("Nothing changed.");
-            assert!(assertion, "Failed to set fuel! Got: {refuel}");
+            assert!(
+                TestCommand::SetFuel(*position, fuel)
+                    .invoke(self)
+                    .await
+                    .success(),
+                "Failed to set fuel!"
+            );
         }
 
         // Update files if needed.
@@ -233,24 +418,19 @@ impl MinecraftTestHandle {
             ComputerConfigs::Empty => { /* nothing to do */ }
             // ComputerConfigs::Websocket(port) => todo!(),
             ComputerConfigs::Startup(startup) => {
-                add_file_to_computer(new_computer.id, startup, "startup.lua")
+                add_startup_to_computer(new_computer.id, startup)
                     .await
                     .expect("Unable to write startup lua file.");
             }
             ComputerConfigs::StartupIncludingLibraries(startup, libraries) => {
-                add_file_to_computer(new_computer.id, startup, "startup.lua")
+                add_startup_to_computer(new_computer.id, startup)
                     .await
                     .expect("Unable to write startup lua file.");
                 // Loop over he libraries and add them
-                for path in libraries.to_files() {
-                    let file_contents =
-                        std::fs::read_to_string(&path).expect("Unable to read lua file!");
-                    let file_name = path
-                        .file_name()
-                        .expect("Should have a file name")
-                        .to_str()
-                        .expect("Should be valid.");
-                    add_file_to_computer(new_computer.id, file_contents, file_name)
+                for asset in libraries.to_assets() {
+                    // Point networking at our websocket.
+                    let contents = asset.with_server_url(&BRIDGE_CONFIG.websocket_url);
+                    add_file_to_computer(new_computer.id, contents, asset.name)
                         .await
                         .expect("Unable to write a lua file to the computer!");
                 }
@@ -262,6 +442,14 @@ impl MinecraftTestHandle {
     }
 }
 
+/// Create the startup file on a computer, with terminal capturing tacked onto the front.
+async fn add_startup_to_computer(id: u16, startup: String) -> Result<(), std::io::Error> {
+    add_file_to_computer(id, TERMINAL_CAPTURE, TERMINAL_CAPTURE_NAME).await?;
+    // Same line as the first line of the startup, so line numbers in errors stay right.
+    let startup = format!("dofile(\"/{TERMINAL_CAPTURE_NAME}\"); {startup}");
+    add_file_to_computer(id, startup, "startup.lua").await
+}
+
 /// Create a file on a computer.
 ///
 /// Make sure to include the file extension if needed.
@@ -274,14 +462,8 @@ async fn add_file_to_computer<S: ToString + AsRef<[u8]>, P: AsRef<std::path::Pat
 ) -> Result<(), std::io::Error> {
     // Open the path for the computer folders.
     // Use a block here so we dont keep the env locked.
-    let computer_directory = {
-        let env = MINECRAFT_TESTING_ENV.lock().await;
-        env.environment
-            .get_server_folder()
-            .join("world/computercraft/computer")
-    };
+    let this_computer_dir = MINECRAFT_TESTING_ENV.lock().await.computer_folder(id);
     // We will re-create the folder structure just in case...
-    let this_computer_dir = computer_directory.join(id.to_string());
     std::fs::create_dir_all(&this_computer_dir)?;
 
     // Write the file
@@ -289,7 +471,28 @@ async fn add_file_to_computer<S: ToString + AsRef<[u8]>, P: AsRef<std::path::Pat
     Ok(())
 }
 
+// Tests that panic never get to stop, but we still want to know about them.
+impl Drop for MinecraftTestHandle {
+    fn drop(&mut self) {
+        if !self.stopped {
+            self.report(false, Some("Panicked before stopping".to_string()));
+        }
+    }
+}
+
 impl MinecraftTestEnvironment {
+    /// Where computercraft keeps every computer's files.
+    fn computers_folder(&self) -> PathBuf {
+        self.environment
+            .get_server_folder()
+            .join("world/computercraft/computer")
+    }
+
+    /// Where the files for a computer are kept.
+    fn computer_folder(&self, id: u16) -> PathBuf {
+        self.computers_folder().join(id.to_string())
+    }
+
     /// Runs a minecraft command. Does not need a preceding slash.
     pub(super) async fn run_command(&mut self, command: String) -> String {
         self.environment
@@ -327,46 +530,101 @@ impl MinecraftTestEnvironment {
         }
     }
 
-    /// Get the next open test position
-    fn get_test_position(&mut self, area: TestArea) -> MinecraftPosition {
-        // we just give the current position, then we update our offsets afterwards
-        // to pre-prepare for the next test
-        let give_me = self.next_plot_corner;
-
-        // Update the z gap if needed
-        self.highest_z = max(self.highest_z, area.size_z.into());
-
-        let test_gap: i64 = 4;
-
-        // Set the corner of where the next plot will be
-        // If the last test was over 100 blocks away from 0,0 on the x axis, we will
-        // move to a new row of tests. since we wanna look at em :D
-
-        let mut next_x: i64 = give_me.x;
-        let mut next_z: i64 = give_me.z;
-
-        if next_x >= 100 {
-            // Shift down by the highest width seen, plus the gap
-            next_z += self.highest_z + test_gap;
-
-            // Reset the x position, and shift back since we always add the offset
-            // regardless if we moved to a new row.
-            next_x = 0 - test_gap - area.size_x as i64;
+    /// Replace everything above the floor of a test with air.
+    ///
+    /// Returns the feedback of the first fill that didn't work.
+    async fn clear_plot(
+        &mut self,
+        corner: MinecraftPosition,
+        area: TestArea,
+    ) -> Result<(), String> {
+        for (start, end) in clear_boxes(area) {
+            let p1 = corner.with_offset(start).as_command_string();
+            let p2 = corner.with_offset(end).as_command_string();
+            let feedback = self
+                .run_command(format!("fill {p1} {p2} minecraft:air"))
+                .await;
+            // Nothing to fill means it was already clear, which is fine.
+            if !feedback.contains("Successfully filled")
+                && !feedback.contains("No blocks were filled")
+            {
+                return Err(feedback);
+            }
+        }
+        Ok(())
+    }
+}
 
-            // Reset the largest z we've seen
-            self.highest_z = 0
+/// Split the space above a plot's floor into boxes that each fit in one fill.
+///
+/// Boxes are a few layers at a time, and if a single layer is already too big, that gets split
+/// along x and z too.
+fn clear_boxes(area: TestArea) -> Vec<(MinecraftPosition, MinecraftPosition)> {
+    let (size_x, size_y, size_z) = (
+        i64::from(area.size_x) + 1,
+        i64::from(area.size_y),
+        i64::from(area.size_z) + 1,
+    );
+    let step_z = size_z.min(FILL_LIMIT);
+    let step_x = size_x.min(FILL_LIMIT / step_z).max(1);
+    let step_y = size_y.min(FILL_LIMIT / (step_x * step_z)).max(1);
+
+    let corner = |x, y, z| MinecraftPosition {
+        x,
+        y,
+        z,
+        facing: None,
+    };
+    let mut boxes = vec![];
+    for y in (1..=size_y).step_by(step_y as usize) {
+        for x in (0..size_x).step_by(step_x as usize) {
+            for z in (0..size_z).step_by(step_z as usize) {
+                boxes.push((
+                    corner(x, y, z),
+                    corner(
+                        (x + step_x).min(size_x) - 1,
+                        (y + step_y - 1).min(size_y),
+                        (z + step_z).min(size_z) - 1,
+                    ),
+                ));
+            }
         }
+    }
+    boxes
+}
 
-        // Increment to the next plot
-        next_x += area.size_x as i64 + test_gap;
-        let next: MinecraftPosition = MinecraftPosition {
-            x: next_x,
-            y: give_me.y, // the y position never changes.
-            z: next_z,
-            facing: None,
-        };
-        self.next_plot_corner = next;
+// ===
+// Tests
+// ===
 
-        give_me
+#[test]
+/// Clearing covers the whole plot exactly once, without any fill being too big.
+fn clear_boxes_fit() {
+    let volume = |(start, end): &(MinecraftPosition, MinecraftPosition)| {
+        (end.x - start.x + 1) * (end.y - start.y + 1) * (end.z - start.z + 1)
+    };
+    let areas = [
+        (3, 3, 3),
+        (100, 20, 100),
+        // A single layer bigger than one fill.
+        (300, 2, 300),
+        // A single row bigger than one fill.
+        (40000, 1, 1),
+    ];
+    for (size_x, size_y, size_z) in areas {
+        let area = TestArea {
+            size_x,
+            size_y,
+            size_z,
+        };
+        let boxes = clear_boxes(area);
+        assert!(boxes.iter().all(|fill| volume(fill) <= FILL_LIMIT));
+        let total: i64 = boxes.iter().map(volume).sum();
+        assert_eq!(
+            total,
+            (i64::from(size_x) + 1) * i64::from(size_y) * (i64::from(size_z) + 1)
+        );
+        // The floor is left alone.
+        assert!(boxes.iter().all(|(start, _)| start.y >= 1));
     }
 }
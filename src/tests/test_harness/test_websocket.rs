@@ -3,7 +3,10 @@
 // TODO: This implementation might just end up being what we do for the actual server, and
 // thus will need to be moved out of here.
 
-use std::sync::{Arc, OnceLock};
+use std::{
+    path::Path,
+    sync::{Arc, OnceLock},
+};
 
 use dashmap::DashMap;
 use futures_util::{SinkExt, StreamExt};
@@ -14,6 +17,12 @@ use tokio::{
 };
 use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
 
+use crate::{
+    minecraft::computercraft::deploy::is_panic,
+    tests::{bridge_config::BRIDGE_CONFIG, test_harness::report},
+    trace::{Direction, TraceRecorder},
+};
+
 // We force move the websocket to another thread, otherwise it would close between tests.
 static WEBSOCKET_RUNNING: OnceCell<()> = OnceCell::const_new();
 static GLOBAL_RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
@@ -62,10 +71,27 @@ fn get_registry() -> &'static Registry {
     REGISTRY.get_or_init(|| Arc::new(DashMap::new()))
 }
 
+/// Set `MESHPIT_TRACE` to a file path to record every frame the test server sees, see `trace.rs`.
+static RECORDER: OnceLock<Option<TraceRecorder>> = OnceLock::new();
+
+fn get_recorder() -> Option<&'static TraceRecorder> {
+    RECORDER
+        .get_or_init(|| {
+            let path = std::env::var("MESHPIT_TRACE").ok()?;
+            match TraceRecorder::create(Path::new(&path)) {
+                Ok(recorder) => Some(recorder),
+                Err(error) => {
+                    warn!("Couldn't start recording a trace to {path}! {error}");
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
 /// Run the websocket.
 async fn run_test_websocket_server() {
-    // This is currently hardcoded.
-    let address = "localhost:4816";
+    let address = &BRIDGE_CONFIG.websocket_bind;
     let listener = TcpListener::bind(address)
         .await
         .expect("Failed to bind for websocket!");
@@ -132,6 +158,12 @@ async fn run_test_websocket_server() {
             let incoming = tokio::spawn(async move {
                 while let Some(Ok(message)) = websocket_receiver.next().await {
                     if let Ok(text) = message.into_text() {
+                        if let Some(recorder) = get_recorder() {
+                            recorder.record(Some(id), Direction::Inbound, &text);
+                        }
+                        if is_panic(&text) {
+                            report::record_panic(id, &text);
+                        }
                         // TODO: Replace this with a better websocket health check because this wastes packets
                         if text
                             .as_str()
@@ -158,6 +190,9 @@ async fn run_test_websocket_server() {
             // Server -> Computer
             let outgoing = tokio::spawn(async move {
                 while let Some(message) = broker.from_test.recv().await {
+                    if let Some(recorder) = get_recorder() {
+                        recorder.record(Some(id), Direction::Outbound, &message);
+                    }
                     // Close the socket if the computer doesn't accept the message.
                     if websocket_sender.send(message.into()).await.is_err() {
                         break;
@@ -5,6 +5,9 @@
 pub struct TestArea {
     /// How many blocks long (east direction) this test needs
     pub size_x: u16,
+    /// How many blocks tall (above the floor) this test needs. Everything up to here is cleared
+    /// when the test passes.
+    pub size_y: u16,
     /// How many blocks wide (south direction) this test needs
     pub size_z: u16,
 }
@@ -0,0 +1,431 @@
+// Recording and replaying websocket sessions.
+// Reproducing a turtle bug usually means re-running a multi-minute in-game test. Instead, we can
+// record every frame that goes over the websocket, then feed it back in later without minecraft.
+//
+// Traces are json lines, one frame per line, so they can be appended to as they're recorded and
+// are still readable if the test crashes halfway.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{Message, client::IntoClientRequest, http::HeaderValue},
+};
+
+use crate::minecraft::computercraft::deploy::ComputerId;
+
+/// Which way a frame went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Computer to server.
+    Inbound,
+    /// Server to computer.
+    Outbound,
+}
+
+/// A single websocket frame.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceFrame {
+    /// Milliseconds since the recording started.
+    pub millis: u64,
+    /// Which computer this was, if we know.
+    pub computer: Option<ComputerId>,
+    pub direction: Direction,
+    pub text: String,
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    Io(std::io::Error),
+    /// A line that isn't a frame, and which line it was, starting at 1.
+    BadLine(usize, serde_json::Error),
+}
+
+impl From<std::io::Error> for TraceError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+// ==
+// Recording
+// ==
+
+struct RecorderInner {
+    started: Instant,
+    writer: Box<dyn Write + Send>,
+}
+
+/// Writes frames out as they happen. Cheap to clone, every clone writes to the same trace.
+#[derive(Clone)]
+pub struct TraceRecorder {
+    inner: Arc<Mutex<RecorderInner>>,
+}
+
+impl TraceRecorder {
+    /// Start recording into a file, replacing it if it exists.
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        Ok(Self::from_writer(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Start recording into anything.
+    pub fn from_writer(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(RecorderInner {
+                started: Instant::now(),
+                writer: Box::new(writer),
+            })),
+        }
+    }
+
+    /// Record a frame.
+    ///
+    /// Failing to write is logged and otherwise ignored, a broken trace shouldn't take the
+    /// server down with it.
+    pub fn record(&self, computer: Option<ComputerId>, direction: Direction, text: &str) {
+        let mut inner = self.inner.lock().expect("Trace recorder poisoned");
+        let frame = TraceFrame {
+            millis: inner.started.elapsed().as_millis() as u64,
+            computer,
+            direction,
+            text: text.to_string(),
+        };
+        let line = serde_json::to_string(&frame).expect("Frames always serialize");
+        // Flush every frame, otherwise a crash loses the end of the trace, which is the bit we
+        // actually want.
+        if let Err(error) = writeln!(inner.writer, "{line}").and_then(|_| inner.writer.flush()) {
+            log::warn!("Failed to write trace frame! {error}");
+        }
+    }
+}
+
+/// Read a trace back in from a string.
+pub fn parse_trace(trace: &str) -> Result<Vec<TraceFrame>, TraceError> {
+    trace
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|error| TraceError::BadLine(index + 1, error))
+        })
+        .collect()
+}
+
+/// Read a trace file.
+pub fn read_trace(path: &Path) -> Result<Vec<TraceFrame>, TraceError> {
+    let mut frames = vec![];
+    for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        frames.push(
+            serde_json::from_str(&line).map_err(|error| TraceError::BadLine(index + 1, error))?,
+        );
+    }
+    Ok(frames)
+}
+
+// ==
+// Replay
+// ==
+
+/// Something the computer sent, and everything the server sent back before the computer sent
+/// anything else.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exchange {
+    /// `None` for whatever the server sent before the computer said anything.
+    pub inbound: Option<String>,
+    pub outbound: Vec<String>,
+}
+
+/// Split one computer's frames into exchanges.
+pub fn exchanges(frames: &[TraceFrame], computer: ComputerId) -> Vec<Exchange> {
+    let mut exchanges = vec![Exchange {
+        inbound: None,
+        outbound: vec![],
+    }];
+    for frame in frames
+        .iter()
+        .filter(|frame| frame.computer == Some(computer))
+    {
+        match frame.direction {
+            Direction::Inbound => exchanges.push(Exchange {
+                inbound: Some(frame.text.clone()),
+                outbound: vec![],
+            }),
+            Direction::Outbound => exchanges
+                .last_mut()
+                .expect("Always at least one")
+                .outbound
+                .push(frame.text.clone()),
+        }
+    }
+    exchanges
+}
+
+/// Every computer in a trace, in the order they first showed up.
+pub fn computers(frames: &[TraceFrame]) -> Vec<ComputerId> {
+    let mut computers = vec![];
+    for computer in frames.iter().filter_map(|frame| frame.computer) {
+        if !computers.contains(&computer) {
+            computers.push(computer);
+        }
+    }
+    computers
+}
+
+/// A reply that didn't match the recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Which exchange this was in, see `exchanges`.
+    pub exchange: usize,
+    /// `None` if the server sent something extra.
+    pub expected: Option<String>,
+    /// `None` if the server didn't send something it used to.
+    pub actual: Option<String>,
+}
+
+/// How a replay went.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// How many frames the computer sent.
+    pub sent: usize,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl ReplayReport {
+    /// Did the server do exactly what it did when the trace was recorded?
+    pub fn is_clean(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    fn compare(&mut self, exchange: usize, expected: &[String], actual: &[String]) {
+        for index in 0..expected.len().max(actual.len()) {
+            let expected = expected.get(index).cloned();
+            let actual = actual.get(index).cloned();
+            if expected != actual {
+                self.mismatches.push(Mismatch {
+                    exchange,
+                    expected,
+                    actual,
+                });
+            }
+        }
+    }
+}
+
+/// Feed a computer's side of a trace into some server logic, and check it answers the same way.
+///
+/// The handler gets called once with `None` first, for anything the server sends unprompted, then
+/// once for each frame the computer sent. It returns whatever the server would send back.
+pub fn replay_into<F>(frames: &[TraceFrame], computer: ComputerId, mut handler: F) -> ReplayReport
+where
+    F: FnMut(Option<&str>) -> Vec<String>,
+{
+    let mut report = ReplayReport::default();
+    for (index, exchange) in exchanges(frames, computer).iter().enumerate() {
+        if exchange.inbound.is_some() {
+            report.sent += 1;
+        }
+        let actual = handler(exchange.inbound.as_deref());
+        report.compare(index, &exchange.outbound, &actual);
+    }
+    report
+}
+
+/// Play a computer's side of a trace against a running server, as if the computer had connected.
+///
+/// After each frame we wait for as many replies as were recorded, giving up on each after
+/// `patience`. Extra replies that show up late get counted against the next exchange.
+pub async fn replay_against(
+    frames: &[TraceFrame],
+    computer: ComputerId,
+    url: &str,
+    patience: Duration,
+) -> Result<ReplayReport, tokio_tungstenite::tungstenite::Error> {
+    let mut request = url.into_client_request()?;
+    request
+        .headers_mut()
+        .insert("Computer-ID", HeaderValue::from(computer));
+    let (socket, _) = connect_async(request).await?;
+    let (mut sender, mut receiver) = socket.split();
+
+    let mut report = ReplayReport::default();
+    for (index, exchange) in exchanges(frames, computer).iter().enumerate() {
+        if let Some(inbound) = &exchange.inbound {
+            sender.send(Message::Text(inbound.clone().into())).await?;
+            report.sent += 1;
+        }
+        let mut actual = vec![];
+        while actual.len() < exchange.outbound.len() {
+            match tokio::time::timeout(patience, receiver.next()).await {
+                Ok(Some(Ok(Message::Text(text)))) => actual.push(text.to_string()),
+                Ok(Some(Ok(_))) => continue,
+                // Closed, broken, or just too slow.
+                _ => break,
+            }
+        }
+        report.compare(index, &exchange.outbound, &actual);
+    }
+    Ok(report)
+}
+
+// ===
+// Tests
+// ===
+
+#[cfg(test)]
+fn frame(millis: u64, direction: Direction, text: &str) -> TraceFrame {
+    TraceFrame {
+        millis,
+        computer: Some(3),
+        direction,
+        text: text.to_string(),
+    }
+}
+
+#[test]
+/// Traces survive being written out and read back in.
+fn trace_file_round_trip() {
+    let path = std::env::temp_dir().join(format!("meshpit_trace_{}.jsonl", std::process::id()));
+    let recorder = TraceRecorder::create(&path).unwrap();
+    recorder.record(Some(3), Direction::Outbound, "go");
+    recorder
+        .clone()
+        .record(Some(3), Direction::Inbound, "went\n\"there\"");
+    recorder.record(None, Direction::Inbound, "who?");
+
+    let frames = read_trace(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[1].text, "went\n\"there\"");
+    assert_eq!(frames[1].direction, Direction::Inbound);
+    assert_eq!(frames[2].computer, None);
+    assert!(frames[0].millis <= frames[2].millis);
+    assert_eq!(computers(&frames), vec![3]);
+
+    assert!(matches!(
+        parse_trace("{\"nope\": 1}"),
+        Err(TraceError::BadLine(1, _))
+    ));
+}
+
+#[test]
+/// Replaying into server logic points out where it answers differently.
+fn trace_replay_into_handler() {
+    let frames = vec![
+        frame(0, Direction::Outbound, "hello"),
+        frame(1, Direction::Inbound, "ping"),
+        frame(2, Direction::Outbound, "pong"),
+        frame(3, Direction::Inbound, "ping"),
+        frame(4, Direction::Outbound, "pong"),
+        frame(5, Direction::Outbound, "bye"),
+    ];
+    assert_eq!(exchanges(&frames, 3).len(), 3);
+
+    let same = replay_into(&frames, 3, |inbound| match inbound {
+        None => vec!["hello".to_string()],
+        Some(_) => vec!["pong".to_string()],
+    });
+    // The last bye is missing.
+    assert_eq!(same.sent, 2);
+    assert_eq!(
+        same.mismatches,
+        vec![Mismatch {
+            exchange: 2,
+            expected: Some("bye".to_string()),
+            actual: None,
+        }]
+    );
+
+    let mut pings = 0;
+    let fixed = replay_into(&frames, 3, |inbound| {
+        let Some(_) = inbound else {
+            return vec!["hello".to_string()];
+        };
+        pings += 1;
+        let mut replies = vec!["pong".to_string()];
+        if pings == 2 {
+            replies.push("bye".to_string());
+        }
+        replies
+    });
+    assert!(fixed.is_clean());
+}
+
+#[tokio::test]
+/// Record a live session through `CCWebsocket`, then replay the computer's side against it again.
+async fn trace_record_and_replay_live() {
+    use tokio::net::TcpListener;
+
+    use crate::websocket::CCWebsocket;
+
+    /// A tiny server that answers everything with how long it was.
+    async fn serve(listener: TcpListener, recorder: Option<TraceRecorder>) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (socket, mut incoming) = match recorder {
+            Some(recorder) => CCWebsocket::new_recorded(stream, recorder).await,
+            None => CCWebsocket::new(stream).await,
+        };
+        socket.send("welcome".to_string()).unwrap();
+        while let Some(message) = incoming.recv().await {
+            socket.send(message.len().to_string()).unwrap();
+        }
+    }
+
+    let recording = Arc::new(Mutex::new(Vec::new()));
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let recorder = TraceRecorder::from_writer(Shared(recording.clone()));
+
+    // Record a session by hand.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}/meshpit", listener.local_addr().unwrap());
+    tokio::spawn(serve(listener, Some(recorder)));
+    let mut request = url.as_str().into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("Computer-ID", HeaderValue::from(9u16));
+    let (mut socket, _) = connect_async(request).await.unwrap();
+    assert_eq!(
+        socket.next().await.unwrap().unwrap().into_text().unwrap(),
+        "welcome"
+    );
+    for message in ["a", "abc"] {
+        socket.send(Message::Text(message.into())).await.unwrap();
+        socket.next().await.unwrap().unwrap();
+    }
+    drop(socket);
+
+    let frames =
+        parse_trace(&String::from_utf8(recording.lock().unwrap().clone()).unwrap()).unwrap();
+    assert_eq!(frames.len(), 5);
+    assert_eq!(computers(&frames), vec![9]);
+
+    // And play it back against a fresh server.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}/meshpit", listener.local_addr().unwrap());
+    tokio::spawn(serve(listener, None));
+    let report = replay_against(&frames, 9, &url, Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(report.sent, 2);
+    assert!(report.is_clean(), "{report:?}");
+}
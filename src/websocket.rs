@@ -2,7 +2,21 @@
 
 use futures_util::{SinkExt, StreamExt};
 use tokio::{net::TcpStream, sync::mpsc};
-use tokio_tungstenite::{accept_async, tungstenite::Message};
+use tokio_tungstenite::{
+    accept_hdr_async,
+    tungstenite::{
+        Message,
+        handshake::server::{Request, Response},
+    },
+};
+
+use crate::{
+    minecraft::computercraft::{
+        bootstrap::LibraryBundle,
+        deploy::{ComputerId, update_message},
+    },
+    trace::{Direction, TraceRecorder},
+};
 
 pub struct CCWebsocket {
     // Messages put into this channel are sent into Minecraft.
@@ -13,8 +27,34 @@ pub struct CCWebsocket {
 impl CCWebsocket {
     /// Make a new websocket connection.
     pub async fn new(stream: TcpStream) -> (Self, mpsc::UnboundedReceiver<String>) {
+        Self::start(stream, None).await
+    }
+
+    /// Make a new websocket connection, and record every frame that goes over it.
+    pub async fn new_recorded(
+        stream: TcpStream,
+        recorder: TraceRecorder,
+    ) -> (Self, mpsc::UnboundedReceiver<String>) {
+        Self::start(stream, Some(recorder)).await
+    }
+
+    async fn start(
+        stream: TcpStream,
+        recorder: Option<TraceRecorder>,
+    ) -> (Self, mpsc::UnboundedReceiver<String>) {
+        // Grab the computer ID on the way past, if it sent one. Only used for traces right now.
+        let mut computer_id: Option<ComputerId> = None;
+        #[allow(clippy::result_large_err)] // tungstenite picks the error type, not us.
+        let callback = |request: &Request, response: Response| {
+            computer_id = request
+                .headers()
+                .get("Computer-ID")
+                .and_then(|id| id.to_str().ok())
+                .and_then(|id| id.parse().ok());
+            Ok(response)
+        };
         // TODO: error handling
-        let websocket_stream = accept_async(stream)
+        let websocket_stream = accept_hdr_async(stream, callback)
             .await
             .expect("Failed to accept websocket!");
 
@@ -30,8 +70,12 @@ impl CCWebsocket {
         // set up threads to send the contents of the channels out the websocket, and vice-versa
 
         // Outgoing
+        let outgoing_recorder = recorder.clone();
         tokio::spawn(async move {
             while let Some(outgoing) = outgoing_rx.recv().await {
+                if let Some(recorder) = &outgoing_recorder {
+                    recorder.record(computer_id, Direction::Outbound, &outgoing);
+                }
                 // TODO: detect failures in sending
                 websocket_sender
                     .send(Message::Text(outgoing.into()))
@@ -45,6 +89,9 @@ impl CCWebsocket {
             while let Some(incoming) = websocket_receiver.next().await {
                 // TODO: Error handling
                 let text = incoming.unwrap().into_text().unwrap();
+                if let Some(recorder) = &recorder {
+                    recorder.record(computer_id, Direction::Inbound, &text);
+                }
                 incoming_tx.send(text.to_string()).unwrap()
             }
         });
@@ -59,4 +106,8 @@ impl CCWebsocket {
         self.outgoing_tx.send(string).unwrap();
         Ok(())
     }
+    /// Push a library bundle to the computer on the other end. It'll install it and reboot.
+    pub fn push_update(&self, bundle: &LibraryBundle) {
+        let _ = self.send(update_message(bundle));
+    }
 }